@@ -0,0 +1,36 @@
+# Rebuilds oui.tsv.gz from the IEEE MA-L, MA-M and MA-S public listings.
+param(
+    [string]$Output = (Join-Path $PSScriptRoot 'oui.tsv.gz')
+)
+
+$ErrorActionPreference = 'Stop'
+$sources = @(
+    @{ Code = 'L'; Url = 'https://standards-oui.ieee.org/oui/oui.csv' },
+    @{ Code = 'M'; Url = 'https://standards-oui.ieee.org/oui28/mam.csv' },
+    @{ Code = 'S'; Url = 'https://standards-oui.ieee.org/oui36/oui36.csv' }
+)
+
+$rows = [System.Collections.Generic.List[string]]::new()
+foreach ($source in $sources) {
+    Write-Host "Downloading $($source.Url)"
+    $content = (Invoke-WebRequest -Uri $source.Url -UseBasicParsing -UserAgent 'ConfUtils OUI updater').Content
+    foreach ($row in ($content | ConvertFrom-Csv)) {
+        $prefix = ([string]$row.Assignment).Trim().ToUpperInvariant()
+        $name = (([string]$row.'Organization Name') -replace '\s+', ' ').Trim()
+        if ($prefix -match '^[0-9A-F]+$' -and $name) {
+            $rows.Add("$($source.Code)`t$prefix`t$name")
+        }
+    }
+}
+
+$text = "# registry`tprefix`torganization`n" + (($rows | Sort-Object -Unique) -join "`n") + "`n"
+$bytes = [System.Text.Encoding]::UTF8.GetBytes($text)
+$file = [System.IO.File]::Create($Output)
+try {
+    $gzip = [System.IO.Compression.GZipStream]::new($file, [System.IO.Compression.CompressionLevel]::Optimal)
+    $gzip.Write($bytes, 0, $bytes.Length)
+    $gzip.Dispose()
+} finally {
+    $file.Dispose()
+}
+Write-Host "Wrote $($rows.Count) assignments to $Output"
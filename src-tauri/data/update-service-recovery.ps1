@@ -0,0 +1,26 @@
+# Rebuilds service_recovery_defaults.json from the recovery settings of a clean Windows install.
+param(
+    [string]$Output = (Join-Path $PSScriptRoot 'service_recovery_defaults.json')
+)
+
+$ErrorActionPreference = 'Stop'
+$names = @{ 0 = 'none'; 1 = 'restart'; 2 = 'reboot' }
+$services = [ordered]@{}
+foreach ($key in (Get-ChildItem 'HKLM:\SYSTEM\CurrentControlSet\Services' | Sort-Object PSChildName)) {
+    $blob = $key.GetValue('FailureActions')
+    if (-not $blob -or $blob.Length -lt 20) { continue }
+    $count = [BitConverter]::ToUInt32($blob, 12)
+    $actions = @()
+    for ($i = 0; $i -lt $count -and 20 + $i * 8 + 8 -le $blob.Length; $i++) {
+        $type = [BitConverter]::ToUInt32($blob, 20 + $i * 8)
+        $delay = [BitConverter]::ToUInt32($blob, 24 + $i * 8)
+        if (-not $names.ContainsKey([int]$type)) { $actions = @(); break }
+        $actions += "$($names[[int]$type])/$delay"
+    }
+    if ($actions.Count -gt 0) {
+        $services[$key.PSChildName] = [ordered]@{ reset = [BitConverter]::ToUInt32($blob, 0); actions = $actions }
+    }
+}
+
+[ordered]@{ version = 1; services = $services } | ConvertTo-Json -Depth 4 | Set-Content -Path $Output -Encoding UTF8
+Write-Host "Wrote recovery defaults for $($services.Count) services to $Output"
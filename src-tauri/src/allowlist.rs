@@ -0,0 +1,245 @@
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+use crate::hosts;
+use crate::util;
+
+const MAX_ENTRIES: usize = 1000;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AllowEntry {
+    pub pattern: String,
+    #[serde(default)]
+    pub note: Option<String>,
+    #[serde(default)]
+    pub added_unix: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Allowlist {
+    pub entries: Vec<AllowEntry>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Suppressed {
+    pub domain: String,
+    pub pattern: String,
+}
+
+fn allowlist_path() -> PathBuf {
+    util::data_dir().join("allowlist.json")
+}
+
+pub fn normalize_pattern(raw: &str) -> Result<String, String> {
+    let pattern = raw.trim().trim_end_matches('.').to_lowercase();
+    if pattern.is_empty() || pattern == "*" {
+        return Err("Allowlist entry cannot be empty or match everything".to_string());
+    }
+    // Wildcards may stand for any run of characters; the remaining text must
+    // still look like a hostname so typos do not silently allow nothing.
+    let literal = pattern.replace("*.", "").replace('*', "x");
+    if !hosts::is_valid_hostname(&literal) {
+        return Err(format!("Invalid allowlist entry: {}", raw.trim()));
+    }
+    Ok(pattern)
+}
+
+fn glob_match(pattern: &[u8], text: &[u8]) -> bool {
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+    while t < text.len() {
+        if p < pattern.len() && pattern[p] == b'*' {
+            backtrack = Some((p, t));
+            p += 1;
+        } else if p < pattern.len() && pattern[p] == text[t] {
+            p += 1;
+            t += 1;
+        } else if let Some((star, matched)) = backtrack {
+            p = star + 1;
+            t = matched + 1;
+            backtrack = Some((star, matched + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == b'*')
+}
+
+pub fn pattern_matches(pattern: &str, domain: &str) -> bool {
+    let domain = domain.trim().trim_end_matches('.').to_lowercase();
+    // "*.example.com" also covers the apex domain itself.
+    if let Some(apex) = pattern.strip_prefix("*.") {
+        if domain == apex {
+            return true;
+        }
+    }
+    glob_match(pattern.as_bytes(), domain.as_bytes())
+}
+
+impl Allowlist {
+    pub fn matching(&self, domain: &str) -> Option<&AllowEntry> {
+        self.entries
+            .iter()
+            .find(|e| pattern_matches(&e.pattern, domain))
+    }
+
+    pub fn filter<S: AsRef<str>>(&self, domains: &[S]) -> (Vec<String>, Vec<Suppressed>) {
+        let mut kept = Vec::new();
+        let mut suppressed = Vec::new();
+        for domain in domains {
+            let domain = domain.as_ref();
+            match self.matching(domain) {
+                Some(entry) => suppressed.push(Suppressed {
+                    domain: domain.to_string(),
+                    pattern: entry.pattern.clone(),
+                }),
+                None => kept.push(domain.to_string()),
+            }
+        }
+        (kept, suppressed)
+    }
+}
+
+// A damaged file is reported instead of treated as empty, so the next edit
+// does not overwrite the user's entries.
+fn parse(raw: &str) -> Result<Allowlist, String> {
+    serde_json::from_str(raw).map_err(|e| format!("Allowlist file is corrupt: {}", e))
+}
+
+pub fn load() -> Result<Allowlist, String> {
+    match std::fs::read_to_string(allowlist_path()) {
+        Ok(raw) => parse(&raw),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Allowlist::default()),
+        Err(e) => Err(format!("Failed to read allowlist: {}", e)),
+    }
+}
+
+fn save(list: &Allowlist) -> Result<(), String> {
+    let path = allowlist_path();
+    if let Some(parent) = path.parent() {
+        let _ = std::fs::create_dir_all(parent);
+    }
+    let raw = serde_json::to_string_pretty(list)
+        .map_err(|e| format!("Failed to serialize allowlist: {}", e))?;
+    std::fs::write(path, raw).map_err(|e| format!("Failed to save allowlist: {}", e))
+}
+
+pub fn add(pattern: &str, note: Option<String>) -> Result<AllowEntry, String> {
+    let pattern = normalize_pattern(pattern)?;
+    let mut list = load()?;
+    if let Some(existing) = list.entries.iter().find(|e| e.pattern == pattern) {
+        return Ok(existing.clone());
+    }
+    if list.entries.len() >= MAX_ENTRIES {
+        return Err(format!("Allowlist is full (max {} entries)", MAX_ENTRIES));
+    }
+    let entry = AllowEntry {
+        pattern,
+        note: note
+            .map(|n| n.trim().chars().take(200).collect::<String>())
+            .filter(|n| !n.is_empty()),
+        added_unix: util::now_unix(),
+    };
+    list.entries.push(entry.clone());
+    save(&list)?;
+    Ok(entry)
+}
+
+pub fn remove(pattern: &str) -> Result<bool, String> {
+    let pattern = pattern.trim().trim_end_matches('.').to_lowercase();
+    let mut list = load()?;
+    let before = list.entries.len();
+    list.entries.retain(|e| e.pattern != pattern);
+    if list.entries.len() == before {
+        return Ok(false);
+    }
+    save(&list)?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn list(patterns: &[&str]) -> Allowlist {
+        Allowlist {
+            entries: patterns
+                .iter()
+                .map(|p| AllowEntry {
+                    pattern: normalize_pattern(p).unwrap(),
+                    note: None,
+                    added_unix: 0,
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn normalize_pattern_rejects_empty_and_invalid_entries() {
+        assert_eq!(normalize_pattern(" Example.COM. ").unwrap(), "example.com");
+        assert_eq!(normalize_pattern("*.Example.com").unwrap(), "*.example.com");
+        for raw in ["", "  ", "*", "exa mple.com", "-bad.example.com"] {
+            assert!(normalize_pattern(raw).is_err(), "{:?}", raw);
+        }
+    }
+
+    #[test]
+    fn exact_patterns_ignore_case_and_trailing_dot() {
+        assert!(pattern_matches("example.com", "example.com"));
+        assert!(pattern_matches("example.com", "EXAMPLE.com."));
+        assert!(!pattern_matches("example.com", "www.example.com"));
+        assert!(!pattern_matches("example.com", "example.com.evil.net"));
+        assert!(!pattern_matches("example.com", "notexample.com"));
+    }
+
+    #[test]
+    fn wildcard_patterns_cover_subdomains_and_the_apex() {
+        assert!(pattern_matches("*.example.com", "www.example.com"));
+        assert!(pattern_matches("*.example.com", "a.b.example.com"));
+        assert!(pattern_matches("*.example.com", "Example.Com."));
+        assert!(!pattern_matches("*.example.com", "badexample.com"));
+        assert!(!pattern_matches("*.example.com", "example.com.evil.net"));
+
+        assert!(pattern_matches("ad*.example.com", "ads.example.com"));
+        assert!(pattern_matches("ad*.example.com", "ad.example.com"));
+        assert!(pattern_matches("cdn-*.example.net", "cdn-01.example.net"));
+        assert!(!pattern_matches("ad*.example.com", "bad.example.com"));
+        assert!(!pattern_matches("cdn-*.example.net", "cdn.example.net"));
+    }
+
+    #[test]
+    fn corrupt_files_are_reported() {
+        let raw = r#"{"entries":[{"pattern":"example.com"}]}"#;
+        assert_eq!(parse(raw).unwrap().entries[0].pattern, "example.com");
+        for raw in ["", "{\"entries\":[", "[]"] {
+            assert!(parse(raw)
+                .unwrap_err()
+                .starts_with("Allowlist file is corrupt"));
+        }
+    }
+
+    #[test]
+    fn filter_splits_kept_and_suppressed_domains() {
+        let allow = list(&["*.example.com", "tracker.example.net"]);
+        let (kept, suppressed) = allow.filter(&[
+            "ads.example.com",
+            "tracker.example.net",
+            "ads.example.net",
+            "example.com",
+        ]);
+        assert_eq!(kept, vec!["ads.example.net"]);
+        let pairs: Vec<(&str, &str)> = suppressed
+            .iter()
+            .map(|s| (s.domain.as_str(), s.pattern.as_str()))
+            .collect();
+        assert_eq!(
+            pairs,
+            vec![
+                ("ads.example.com", "*.example.com"),
+                ("tracker.example.net", "tracker.example.net"),
+                ("example.com", "*.example.com"),
+            ]
+        );
+        assert!(Allowlist::default().filter(&["a.example"]).1.is_empty());
+    }
+}
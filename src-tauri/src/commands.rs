@@ -7,8 +7,31 @@ use winreg::RegKey;
 #[cfg(windows)]
 use std::os::windows::process::CommandExt;
 
+use crate::allowlist;
+use crate::connectivity;
+use crate::dns;
+use crate::dns_bench;
+use crate::firewall;
+use crate::hosts;
+use crate::hosts_subscriptions;
+use crate::lan_discovery;
+use crate::net_monitor;
+use crate::network_snapshot;
+use crate::oui;
+use crate::policy_file;
+use crate::port_scan;
+use crate::privacy_firewall;
+use crate::registry;
+use crate::registry_search;
+use crate::registry_watch;
 use crate::security::*;
+use crate::service_baseline;
+use crate::service_deps;
+use crate::service_recovery;
+use crate::service_triggers;
+use crate::services;
 use crate::taskbar;
+use crate::util::{self, ps_quote};
 
 fn check_auth() -> Result<(), String> {
     Ok(())
@@ -50,21 +73,6 @@ fn hosts_blocklist_domains(list_type: &str) -> Result<(&'static str, Vec<&'stati
     }
 }
 
-fn privacy_firewall_domains() -> Vec<&'static str> {
-    vec![
-        "vortex.data.microsoft.com",
-        "vortex-win.data.microsoft.com",
-        "telemetry.microsoft.com",
-        "settings-win.data.microsoft.com",
-        "watson.telemetry.microsoft.com",
-        "oca.telemetry.microsoft.com",
-        "sqm.telemetry.microsoft.com",
-        "telecommand.telemetry.microsoft.com",
-        "telecommand.telemetry.microsoft.com.nsatc.net",
-        "wes.df.telemetry.microsoft.com",
-    ]
-}
-
 static CLONE_MESSAGES_CANCELLED: AtomicBool = AtomicBool::new(false);
 static DISCORD_CLONE_CANCELLED: AtomicBool = AtomicBool::new(false);
 
@@ -216,7 +224,7 @@ pub async fn run_powershell(command: String) -> Result<String, String> {
     run_powershell_internal(command, false, false).await
 }
 
-async fn run_powershell_no_rate_limit(command: String) -> Result<String, String> {
+pub(crate) async fn run_powershell_no_rate_limit(command: String) -> Result<String, String> {
     run_powershell_internal(command, true, false).await
 }
 
@@ -245,23 +253,154 @@ pub async fn start_service(service_name: String) -> Result<String, String> {
 }
 
 #[tauri::command]
-pub async fn stop_service(service_name: String) -> Result<String, String> {
+pub async fn stop_service(service_name: String, force: Option<bool>) -> Result<String, String> {
     check_auth()?;
 
     let validated_name = validate_service_name(&service_name)?;
+    let force = force.unwrap_or(false);
+    let impact = service_impact(
+        std::slice::from_ref(&validated_name),
+        service_deps::Operation::Stop,
+    )
+    .await?;
+    if impact.blocked && !force {
+        return Err(format!(
+            "Refusing to stop {}: {}",
+            validated_name,
+            impact.warnings.join("; ")
+        ));
+    }
 
     let command = format!(
         r#"
         try {{
-            Stop-Service -Name "{}" -ErrorAction Stop
+            Stop-Service -Name "{}" -ErrorAction Stop{}
             "Service {} stopped successfully"
         }} catch {{
             "Failed to stop service {}: " + $_.Exception.Message
         }}
         "#,
-        validated_name, validated_name, validated_name
+        validated_name,
+        if force { " -Force" } else { "" },
+        validated_name,
+        validated_name
     );
-    run_powershell_internal(command, false, false).await
+    let result = run_powershell_internal(command, false, false).await?;
+    Ok(with_impact_warnings(result, &impact))
+}
+
+async fn fetch_dependency_graph() -> Result<service_deps::DependencyGraph, String> {
+    let result =
+        run_powershell_no_rate_limit(service_deps::dependencies_script().to_string()).await?;
+    let edges = service_deps::parse_dependency_edges(&result)?;
+    Ok(service_deps::DependencyGraph::from_edges(&edges))
+}
+
+async fn service_impact(
+    names: &[String],
+    operation: service_deps::Operation,
+) -> Result<service_deps::ImpactReport, String> {
+    fetch_dependency_graph().await?.impact(names, operation)
+}
+
+fn with_impact_warnings(result: String, impact: &service_deps::ImpactReport) -> String {
+    if impact.warnings.is_empty() {
+        result
+    } else {
+        format!("{} ({})", result, impact.warnings.join("; "))
+    }
+}
+
+#[tauri::command]
+pub async fn get_service_impact(
+    service_names: Vec<String>,
+    operation: String,
+) -> Result<String, String> {
+    check_auth()?;
+    let operation = service_deps::Operation::parse(&operation)?;
+    let names = service_names
+        .iter()
+        .map(|n| validate_service_name(n))
+        .collect::<Result<Vec<_>, _>>()?;
+    let impact = service_impact(&names, operation).await?;
+    serde_json::to_string(&impact).map_err(|e| format!("Failed to serialize impact: {}", e))
+}
+
+async fn run_ordered_service_operation(
+    service_names: Vec<String>,
+    operation: service_deps::Operation,
+    force: bool,
+) -> Result<String, String> {
+    let names = service_names
+        .iter()
+        .map(|n| validate_service_name(n))
+        .collect::<Result<Vec<_>, _>>()?;
+    if names.is_empty() {
+        return Err("No services given".to_string());
+    }
+    let impact = service_impact(&names, operation).await?;
+    if impact.blocked && !force {
+        return Err(format!(
+            "Refusing to continue: {}",
+            impact.warnings.join("; ")
+        ));
+    }
+
+    let action = match operation {
+        service_deps::Operation::Start => "Start-Service",
+        _ => "Stop-Service",
+    };
+    let command = format!(
+        r#"
+        $order = @({})
+        $results = @()
+        foreach ($name in $order) {{
+            try {{
+                {} -Name $name -ErrorAction Stop
+                $results += @{{Name = $name; Ok = $true}}
+            }} catch {{
+                $results += @{{Name = $name; Ok = $false; Error = $_.Exception.Message}}
+            }}
+        }}
+        ConvertTo-Json -InputObject @($results) -Compress
+        "#,
+        impact
+            .order
+            .iter()
+            .map(|n| ps_quote(n))
+            .collect::<Vec<_>>()
+            .join(", "),
+        action
+    );
+    let result = run_powershell_internal(command, false, false).await?;
+    let results: serde_json::Value =
+        serde_json::from_str(&result).unwrap_or(serde_json::Value::Array(Vec::new()));
+    Ok(serde_json::json!({
+        "order": impact.order,
+        "warnings": impact.warnings,
+        "results": results,
+    })
+    .to_string())
+}
+
+#[tauri::command]
+pub async fn stop_services(
+    service_names: Vec<String>,
+    force: Option<bool>,
+) -> Result<String, String> {
+    check_auth()?;
+    run_ordered_service_operation(
+        service_names,
+        service_deps::Operation::Stop,
+        force.unwrap_or(false),
+    )
+    .await
+}
+
+#[tauri::command]
+pub async fn start_services(service_names: Vec<String>) -> Result<String, String> {
+    check_auth()?;
+    run_ordered_service_operation(service_names, service_deps::Operation::Start, false).await
 }
 
 #[tauri::command]
@@ -284,123 +423,245 @@ pub async fn get_service_status(service_name: String) -> Result<String, String>
     run_powershell_internal(command, false, false).await
 }
 
+async fn fetch_services() -> Result<Vec<services::ServiceInfo>, String> {
+    let command = format!(
+        r#"
+        [Console]::OutputEncoding = [System.Text.Encoding]::UTF8
+        $OutputEncoding = [System.Text.Encoding]::UTF8
+        chcp 65001 | Out-Null
+        {}
+    "#,
+        services::list_services_script()
+    );
+    let result = run_powershell_no_rate_limit(command).await?;
+    let mut list = services::parse_service_list(&result)?;
+    tokio::task::spawn_blocking(move || {
+        for service in list.iter_mut() {
+            service.trigger_start = service_triggers::has_triggers(&service.name);
+        }
+        list
+    })
+    .await
+    .map_err(|e| format!("Service trigger task failed: {}", e))
+}
+
 #[tauri::command]
 pub async fn list_services() -> Result<String, String> {
     check_auth()?;
+    let list = fetch_services().await?;
+    serde_json::to_string(&list).map_err(|e| e.to_string())
+}
 
-    let command = r#"
+#[tauri::command]
+pub async fn get_service_details(service_name: String) -> Result<String, String> {
+    check_auth()?;
+
+    let validated_name = validate_service_name(&service_name)?;
+
+    let command = format!(
+        r#"
         [Console]::OutputEncoding = [System.Text.Encoding]::UTF8
         $OutputEncoding = [System.Text.Encoding]::UTF8
         chcp 65001 | Out-Null
-        
-        try {
-            $services = Get-Service | Select-Object Name, DisplayName, Status, StartType
-            if ($services) {
-                $services | ConvertTo-Json -Compress
-            } else {
-                "[]"
-            }
-        } catch {
-            "[]"
+        {}
+    "#,
+        services::service_details_script(&validated_name)
+    );
+    let result = run_powershell_no_rate_limit(command).await?;
+    match services::parse_service_details(&result) {
+        Ok(mut details) => {
+            let name = details.info.name.clone();
+            let (triggers, failure_actions) = tokio::task::spawn_blocking(move || {
+                (
+                    service_triggers::read_triggers(&name),
+                    service_recovery::read_failure_actions(&name).ok(),
+                )
+            })
+            .await
+            .map_err(|e| format!("Service details task failed: {}", e))?;
+            details.triggers = triggers;
+            details.info.trigger_start = !details.triggers.is_empty();
+            details.failure_actions = failure_actions;
+            serde_json::to_string(&details).map_err(|e| e.to_string())
         }
-    "#;
-    let result = run_powershell_no_rate_limit(command.to_string()).await?;
+        Err(e) => Ok(serde_json::json!({ "Error": e }).to_string()),
+    }
+}
 
-    if result.trim().is_empty() || result.trim() == "null" {
-        Ok("[]".to_string())
-    } else {
-        match serde_json::from_str::<serde_json::Value>(&result) {
-            Ok(_) => Ok(result),
-            Err(_) => {
-                if result.trim().starts_with('[') {
-                    Ok(result)
-                } else {
-                    Ok("[]".to_string())
-                }
-            }
+#[tauri::command]
+pub async fn get_service_failure_actions(service_name: String) -> Result<String, String> {
+    check_auth()?;
+    let validated_name = validate_service_name(&service_name)?;
+    let actions = tokio::task::spawn_blocking(move || {
+        service_recovery::read_failure_actions(&validated_name)
+    })
+    .await
+    .map_err(|e| format!("Service recovery task failed: {}", e))??;
+    serde_json::to_string(&actions)
+        .map_err(|e| format!("Failed to serialize failure actions: {}", e))
+}
+
+async fn apply_failure_actions(
+    service_name: &str,
+    config: &service_recovery::FailureActions,
+) -> Result<String, String> {
+    service_recovery::validate(config)?;
+    let name = service_name.to_string();
+    let key_path = service_recovery::service_key_path(service_name);
+    tokio::task::spawn_blocking(move || {
+        service_recovery::remember_original(&name)?;
+        for value in [
+            "FailureActions",
+            "FailureCommand",
+            "FailureActionsOnNonCrashFailures",
+        ] {
+            registry::backup_value("write_value", "HKLM", &key_path, value)?;
         }
-    }
+        Ok::<(), String>(())
+    })
+    .await
+    .map_err(|e| format!("Registry backup task failed: {}", e))??;
+    run_powershell_internal(
+        service_recovery::apply_script(service_name, config),
+        false,
+        false,
+    )
+    .await
 }
 
 #[tauri::command]
-pub async fn get_service_details(service_name: String) -> Result<String, String> {
+pub async fn set_service_failure_actions(
+    service_name: String,
+    config: service_recovery::FailureActions,
+) -> Result<String, String> {
     check_auth()?;
+    let validated_name = validate_service_name(&service_name)?;
+    apply_failure_actions(&validated_name, &config).await
+}
 
+#[tauri::command]
+pub async fn reset_service_failure_actions(service_name: String) -> Result<String, String> {
+    check_auth()?;
+    let validated_name = validate_service_name(&service_name)?;
+    let name = validated_name.clone();
+    let defaults = tokio::task::spawn_blocking(move || {
+        let built_in = service_baseline::current_build()
+            .and_then(service_baseline::load_baseline)
+            .map(|baseline| baseline.expected(&name).is_some())
+            .unwrap_or(false);
+        service_recovery::windows_default(&name, built_in)
+    })
+    .await
+    .map_err(|e| format!("Service recovery task failed: {}", e))??;
+    apply_failure_actions(&validated_name, &defaults).await
+}
+
+#[tauri::command]
+pub async fn undo_service_failure_actions(service_name: String) -> Result<String, String> {
+    check_auth()?;
+    let validated_name = validate_service_name(&service_name)?;
+    let name = validated_name.clone();
+    let original = tokio::task::spawn_blocking(move || service_recovery::original(&name))
+        .await
+        .map_err(|e| format!("Service recovery task failed: {}", e))??;
+    apply_failure_actions(&validated_name, &original).await
+}
+
+#[tauri::command]
+pub async fn get_service_triggers(service_name: String) -> Result<String, String> {
+    check_auth()?;
+    let validated_name = validate_service_name(&service_name)?;
+    let triggers =
+        tokio::task::spawn_blocking(move || service_triggers::read_triggers(&validated_name))
+            .await
+            .map_err(|e| format!("Service trigger task failed: {}", e))?;
+    serde_json::to_string(&triggers).map_err(|e| format!("Failed to serialize triggers: {}", e))
+}
+
+#[tauri::command]
+pub async fn remove_service_triggers(service_name: String) -> Result<String, String> {
+    check_auth()?;
     let validated_name = validate_service_name(&service_name)?;
+    let key_path = service_triggers::trigger_key_path(&validated_name);
+    let backup_id = tokio::task::spawn_blocking(move || registry::backup_key("HKLM", &key_path))
+        .await
+        .map_err(|e| format!("Registry backup task failed: {}", e))??;
+    let backup_id = match backup_id {
+        Some(id) => id,
+        None => return Ok(format!("Service {} has no triggers", validated_name)),
+    };
 
     let command = format!(
         r#"
-        [Console]::OutputEncoding = [System.Text.Encoding]::UTF8
-        $OutputEncoding = [System.Text.Encoding]::UTF8
-        chcp 65001 | Out-Null
-        
-        try {{
-            $service = Get-Service -Name "{}" -ErrorAction Stop
-            $wmiService = Get-WmiObject Win32_Service -Filter "Name='{}'" -ErrorAction SilentlyContinue
-            $description = if ($wmiService) {{ $wmiService.Description }} else {{ "" }}
-            
-            $requiredServices = @()
-            $dependentServices = @()
-            
-            try {{
-                $requiredServices = $service.ServicesDependedOn | ForEach-Object {{ @{{Name = $_.Name}} }}
-            }} catch {{
-                $requiredServices = @()
-            }}
-            
-            try {{
-                $dependentServices = $service.DependentServices | ForEach-Object {{ @{{Name = $_.Name}} }}
-            }} catch {{
-                $dependentServices = @()
-            }}
-            
-            @{{
-                Name = $service.Name
-                DisplayName = $service.DisplayName
-                Status = $service.Status.ToString()
-                StartType = $service.StartType.ToString()
-                Description = $description
-                ServiceName = $service.Name
-                RequiredServices = $requiredServices
-                DependentServices = $dependentServices
-            }} | ConvertTo-Json -Compress
-        }} catch {{
-            @{{Error = "Service not found: {}"}} | ConvertTo-Json -Compress
-        }}
+        $out = & sc.exe triggerinfo "{}" delete 2>&1
+        if ($LASTEXITCODE -ne 0) {{ throw ($out | Out-String).Trim() }}
+        "Triggers removed"
         "#,
-        validated_name, validated_name, validated_name
+        validated_name
     );
-    run_powershell_no_rate_limit(command).await
+    run_powershell_internal(command, false, false).await?;
+    Ok(format!(
+        "Removed triggers for service {} (registry backup {})",
+        validated_name, backup_id
+    ))
 }
 
 #[tauri::command]
 pub async fn set_service_startup_type(
     service_name: String,
     startup_type: String,
+    force: Option<bool>,
 ) -> Result<String, String> {
     check_auth()?;
 
     let validated_name = validate_service_name(&service_name)?;
 
-    let valid_types = ["Automatic", "Manual", "Disabled"];
-    if !valid_types.contains(&startup_type.as_str()) {
-        return Err(
-            "Geçersiz başlangıç türü. Automatic, Manual veya Disabled olmalıdır".to_string(),
-        );
-    }
+    let start_type =
+        services::StartType::parse(&serde_json::Value::String(startup_type.clone()), false);
+    let sc_value = match start_type.sc_value() {
+        Some(value) => value,
+        None => return Err(
+            "Geçersiz başlangıç türü. Automatic, AutomaticDelayed, Manual veya Disabled olmalıdır"
+                .to_string(),
+        ),
+    };
+    let startup_type = start_type.label();
+
+    let impact = if start_type == services::StartType::Disabled {
+        let impact = service_impact(
+            std::slice::from_ref(&validated_name),
+            service_deps::Operation::Disable,
+        )
+        .await?;
+        if impact.blocked && !force.unwrap_or(false) {
+            return Err(format!(
+                "Refusing to disable {}: {}",
+                validated_name,
+                impact.warnings.join("; ")
+            ));
+        }
+        Some(impact)
+    } else {
+        None
+    };
 
     let command = format!(
         r#"
         try {{
-            Set-Service -Name "{}" -StartupType {} -ErrorAction Stop
+            $out = & sc.exe config "{}" start= {} 2>&1
+            if ($LASTEXITCODE -ne 0) {{ throw ($out | Out-String).Trim() }}
             "Service {} startup type changed to {}"
         }} catch {{
             "Failed to change startup type for service {}: " + $_.Exception.Message
         }}
         "#,
-        validated_name, startup_type, validated_name, startup_type, validated_name
+        validated_name, sc_value, validated_name, startup_type, validated_name
     );
-    run_powershell_internal(command, false, false).await
+    let result = run_powershell_internal(command, false, false).await?;
+    Ok(match impact {
+        Some(impact) => with_impact_warnings(result, &impact),
+        None => result,
+    })
 }
 
 #[tauri::command]
@@ -472,46 +733,504 @@ pub async fn write_registry(
         return Err("Registry değeri çok uzun (max 10000 karakter)".to_string());
     }
 
-    let reg_path = format!("{}:\\{}", hive, path);
-    let escaped_value = value.replace('"', "`\"");
-    let type_str = value_type.unwrap_or_else(|| "String".to_string());
+    let (backup_hive, backup_path, backup_name) = (hive.clone(), path.clone(), name.clone());
+    tokio::task::spawn_blocking(move || {
+        registry::backup_value("write_value", &backup_hive, &backup_path, &backup_name)
+    })
+    .await
+    .map_err(|e| format!("Registry backup task failed: {}", e))??;
+
+    let reg_path = format!("{}:\\{}", hive, path);
+    let escaped_value = value.replace('"', "`\"");
+    let type_str = value_type.unwrap_or_else(|| "String".to_string());
+
+    let command = format!(
+        r#"if (-not (Test-Path "{path}")) {{ New-Item -Path "{path}" -Force | Out-Null }}; Set-ItemProperty -Path "{path}" -Name "{name}" -Value "{value}" -Type {reg_type} -Force; "Registry value written successfully""#,
+        path = reg_path,
+        name = name,
+        value = escaped_value,
+        reg_type = type_str
+    );
+    run_powershell_internal(command, false, false).await
+}
+
+#[tauri::command]
+pub async fn delete_registry_value(
+    hive: String,
+    path: String,
+    name: String,
+) -> Result<String, String> {
+    check_auth()?;
+    let _validated_path = validate_registry_path(&format!("{}:{}", hive, path))?;
+    let (backup_hive, backup_path, backup_name) = (hive.clone(), path.clone(), name.clone());
+    tokio::task::spawn_blocking(move || {
+        registry::backup_value("delete_value", &backup_hive, &backup_path, &backup_name)
+    })
+    .await
+    .map_err(|e| format!("Registry backup task failed: {}", e))??;
+
+    let reg_path = format!("{}:\\{}", hive, path);
+    let command = format!(
+        r#"Remove-ItemProperty -Path "{}" -Name "{}" -Force -ErrorAction SilentlyContinue; "Registry value deleted""#,
+        reg_path, name
+    );
+    run_powershell_internal(command, false, false).await
+}
+
+#[tauri::command]
+pub async fn delete_registry_key(hive: String, path: String) -> Result<String, String> {
+    check_auth()?;
+    let _validated_path = validate_registry_path(&format!("{}:{}", hive, path))?;
+    let (backup_hive, backup_path) = (hive.clone(), path.clone());
+    tokio::task::spawn_blocking(move || registry::backup_key(&backup_hive, &backup_path))
+        .await
+        .map_err(|e| format!("Registry backup task failed: {}", e))??;
+
+    let reg_path = format!("{}:\\{}", hive, path);
+    let command = format!(
+        r#"Remove-Item -Path "{}" -Recurse -Force -ErrorAction SilentlyContinue; "Registry key deleted""#,
+        reg_path
+    );
+    run_powershell_internal(command, false, false).await
+}
+
+#[tauri::command]
+pub async fn list_registry_subkeys(
+    hive: String,
+    path: String,
+    offset: Option<usize>,
+    limit: Option<usize>,
+) -> Result<String, String> {
+    check_auth()?;
+    let _validated_path = validate_registry_path(&format!("{}:{}", hive, path))?;
+    let page =
+        tokio::task::spawn_blocking(move || registry::list_subkeys(&hive, &path, offset, limit))
+            .await
+            .map_err(|e| format!("Registry browse task failed: {}", e))??;
+    serde_json::to_string(&page).map_err(|e| format!("Failed to serialize subkeys: {}", e))
+}
+
+#[tauri::command]
+pub async fn list_registry_values(
+    hive: String,
+    path: String,
+    offset: Option<usize>,
+    limit: Option<usize>,
+) -> Result<String, String> {
+    check_auth()?;
+    let _validated_path = validate_registry_path(&format!("{}:{}", hive, path))?;
+    let page =
+        tokio::task::spawn_blocking(move || registry::list_values(&hive, &path, offset, limit))
+            .await
+            .map_err(|e| format!("Registry browse task failed: {}", e))??;
+    serde_json::to_string(&page).map_err(|e| format!("Failed to serialize values: {}", e))
+}
+
+#[tauri::command]
+pub async fn start_registry_search(
+    app: tauri::AppHandle,
+    query: registry_search::SearchQuery,
+) -> Result<String, String> {
+    check_auth()?;
+    let _validated_path = validate_registry_path(&format!("{}:{}", query.hive, query.path))?;
+
+    let job_id = util::new_id();
+    let cancelled = registry_search::register_job(&job_id);
+    let worker_job_id = job_id.clone();
+
+    tokio::task::spawn_blocking(move || {
+        let mut batch = Vec::new();
+        let result = registry_search::search(&query, &cancelled, |hit| {
+            batch.push(hit);
+            if batch.len() >= 50 {
+                let _ = app.emit(
+                    "registry-search-hits",
+                    serde_json::json!({ "job_id": worker_job_id, "hits": batch }),
+                );
+                batch.clear();
+            }
+        });
+        if !batch.is_empty() {
+            let _ = app.emit(
+                "registry-search-hits",
+                serde_json::json!({ "job_id": worker_job_id, "hits": batch }),
+            );
+        }
+        let payload = match result {
+            Ok(summary) => serde_json::json!({ "job_id": worker_job_id, "summary": summary }),
+            Err(e) => serde_json::json!({ "job_id": worker_job_id, "error": e }),
+        };
+        let _ = app.emit("registry-search-done", payload);
+        registry_search::finish_job(&worker_job_id);
+    });
+
+    Ok(job_id)
+}
+
+#[tauri::command]
+pub async fn cancel_registry_search(job_id: String) -> Result<String, String> {
+    check_auth()?;
+    if registry_search::cancel_job(&job_id) {
+        Ok("Registry search cancelled".to_string())
+    } else {
+        Err("Registry search not found".to_string())
+    }
+}
+
+#[tauri::command]
+pub async fn start_registry_watch(
+    app: tauri::AppHandle,
+    keys: Vec<registry_watch::WatchedKey>,
+    poll_seconds: Option<u64>,
+) -> Result<String, String> {
+    check_auth()?;
+    for key in &keys {
+        let _validated_path = validate_registry_path(&format!("{}:{}", key.hive, key.path))?;
+    }
+    let keys = registry_watch::normalize_keys(keys)?;
+    let count = keys.len();
+    let interval = std::time::Duration::from_secs(poll_seconds.unwrap_or(5).clamp(1, 3600));
+
+    registry_watch::start(keys, interval, move |change| {
+        let _ = app.emit("registry-watch-change", change);
+    })?;
+    Ok(format!("Watching {} registry keys", count))
+}
+
+#[tauri::command]
+pub async fn stop_registry_watch() -> Result<String, String> {
+    check_auth()?;
+    Ok(if registry_watch::stop() {
+        "Registry watcher stopped".to_string()
+    } else {
+        "Registry watcher was not running".to_string()
+    })
+}
+
+#[tauri::command]
+pub async fn get_registry_watch_status() -> Result<String, String> {
+    check_auth()?;
+    serde_json::to_string(&registry_watch::status())
+        .map_err(|e| format!("Failed to serialize watcher status: {}", e))
+}
+
+#[tauri::command]
+pub async fn get_registry_audit_log(limit: Option<usize>) -> Result<String, String> {
+    check_auth()?;
+    let limit = limit.unwrap_or(200).clamp(1, 5000);
+    let records = tokio::task::spawn_blocking(move || registry_watch::read_audit_log(limit))
+        .await
+        .map_err(|e| format!("Audit log task failed: {}", e))?;
+    serde_json::to_string(&records).map_err(|e| format!("Failed to serialize audit log: {}", e))
+}
+
+fn parse_policy_scope(scope: &str) -> Result<policy_file::PolicyScope, String> {
+    match scope.to_lowercase().as_str() {
+        "machine" | "computer" | "hklm" => Ok(policy_file::PolicyScope::Machine),
+        "user" | "hkcu" => Ok(policy_file::PolicyScope::User),
+        _ => Err("Invalid policy scope: machine or user expected".to_string()),
+    }
+}
+
+#[tauri::command]
+pub async fn read_policy_file(scope: String) -> Result<String, String> {
+    check_auth()?;
+    let scope = parse_policy_scope(&scope)?;
+    let file = tokio::task::spawn_blocking(move || policy_file::load(scope))
+        .await
+        .map_err(|e| format!("Policy file task failed: {}", e))??;
+    let entries: Vec<_> = file.entries.iter().map(|e| e.view()).collect();
+    serde_json::to_string(&entries).map_err(|e| format!("Failed to serialize policy: {}", e))
+}
+
+#[tauri::command]
+pub async fn check_policy_conflicts(
+    entries: Vec<policy_file::TweakRegistryEntry>,
+) -> Result<String, String> {
+    check_auth()?;
+    let conflicts = tokio::task::spawn_blocking(move || -> Result<_, String> {
+        let machine = policy_file::load(policy_file::PolicyScope::Machine)?;
+        let user = policy_file::load(policy_file::PolicyScope::User)?;
+        Ok(policy_file::find_conflicts(&entries, &machine, &user))
+    })
+    .await
+    .map_err(|e| format!("Policy file task failed: {}", e))??;
+    serde_json::to_string(&conflicts).map_err(|e| format!("Failed to serialize conflicts: {}", e))
+}
+
+#[tauri::command]
+pub async fn apply_policy_tweaks(
+    entries: Vec<policy_file::TweakRegistryEntry>,
+) -> Result<String, String> {
+    check_auth()?;
+    for entry in &entries {
+        let _validated_path = validate_registry_path(&entry.path)?;
+    }
+    let applied = tokio::task::spawn_blocking(move || policy_file::apply_tweaks(&entries))
+        .await
+        .map_err(|e| format!("Policy file task failed: {}", e))??;
+    let _ = run_powershell_no_rate_limit("gpupdate /force | Out-Null".to_string()).await;
+    Ok(format!(
+        "{} policy entries written to Registry.pol",
+        applied
+    ))
+}
+
+#[tauri::command]
+pub async fn create_service_snapshot(
+    name: String,
+    service_names: Option<Vec<String>>,
+) -> Result<String, String> {
+    check_auth()?;
+    if let Some(names) = &service_names {
+        for service in names {
+            validate_service_name(service)?;
+        }
+    }
+    let current = fetch_services().await?;
+    let snapshot = services::capture_snapshot(name, false, current, service_names).await?;
+    if snapshot.services.is_empty() {
+        return Err("No matching services to snapshot".to_string());
+    }
+    let summary = serde_json::json!({
+        "id": snapshot.id,
+        "name": snapshot.name,
+        "service_count": snapshot.services.len(),
+    });
+    tokio::task::spawn_blocking(move || services::save_snapshot(&snapshot))
+        .await
+        .map_err(|e| format!("Service snapshot task failed: {}", e))??;
+    Ok(summary.to_string())
+}
+
+#[tauri::command]
+pub async fn list_service_snapshots() -> Result<String, String> {
+    check_auth()?;
+    let snapshots = tokio::task::spawn_blocking(services::list_snapshots)
+        .await
+        .map_err(|e| format!("Service snapshot task failed: {}", e))?;
+    serde_json::to_string(&snapshots).map_err(|e| format!("Failed to serialize snapshots: {}", e))
+}
+
+#[tauri::command]
+pub async fn diff_service_snapshot(snapshot_id: String) -> Result<String, String> {
+    check_auth()?;
+    let snapshot = services::load_snapshot(&snapshot_id)?;
+    let current = fetch_services().await?;
+    let (snapshot, diff) = tokio::task::spawn_blocking(move || {
+        let diff = services::diff_snapshot(&snapshot, &current);
+        (snapshot, diff)
+    })
+    .await
+    .map_err(|e| format!("Service snapshot task failed: {}", e))?;
+    serde_json::to_string(&serde_json::json!({
+        "snapshot": snapshot.id,
+        "name": snapshot.name,
+        "created_unix": snapshot.created_unix,
+        "changes": diff,
+    }))
+    .map_err(|e| format!("Failed to serialize snapshot diff: {}", e))
+}
+
+#[tauri::command]
+pub async fn restore_service_snapshot(
+    snapshot_id: String,
+    service_names: Option<Vec<String>>,
+    restore_state: Option<bool>,
+) -> Result<String, String> {
+    check_auth()?;
+    let snapshot = services::load_snapshot(&snapshot_id)?;
+    let current = fetch_services().await?;
+    let restore_state = restore_state.unwrap_or(false);
+
+    let mut skipped = Vec::new();
+    let mut changes = Vec::new();
+    let mut trigger_names = Vec::new();
+    let (snapshot, current, diffs) = tokio::task::spawn_blocking(move || {
+        let diffs = services::diff_snapshot(&snapshot, &current);
+        (snapshot, current, diffs)
+    })
+    .await
+    .map_err(|e| format!("Service snapshot task failed: {}", e))?;
+    for diff in diffs {
+        if let Some(names) = &service_names {
+            if !names.iter().any(|n| n.eq_ignore_ascii_case(&diff.name)) {
+                continue;
+            }
+        }
+        if diff.missing || validate_service_name(&diff.name).is_err() {
+            skipped.push(diff.name);
+            continue;
+        }
+        let start = if diff.start_type_changed {
+            diff.snapshot_start_type.sc_value()
+        } else {
+            None
+        };
+        let status = if restore_state && diff.status_changed {
+            Some(diff.snapshot_status)
+        } else {
+            None
+        };
+        if diff.triggers_changed {
+            trigger_names.push(diff.name.clone());
+        }
+        if start.is_some() || status.is_some() {
+            changes.push((diff.name, start, status));
+        }
+    }
+
+    if changes.is_empty() && trigger_names.is_empty() {
+        return Ok(
+            serde_json::json!({ "restored": 0, "failed": [], "skipped": skipped }).to_string(),
+        );
+    }
+
+    let mut names: Vec<String> = changes.iter().map(|c| c.0.clone()).collect();
+    names.extend(trigger_names.iter().cloned());
+    let undo = services::capture_snapshot(
+        format!("Before restoring {}", snapshot.name),
+        true,
+        current,
+        Some(names),
+    )
+    .await?;
+    tokio::task::spawn_blocking(move || services::save_snapshot(&undo))
+        .await
+        .map_err(|e| format!("Service snapshot task failed: {}", e))??;
+
+    let mut report = if changes.is_empty() {
+        serde_json::json!({ "restored": 0, "failed": [] })
+    } else {
+        let result =
+            run_powershell_internal(services::restore_script(&changes), false, false).await?;
+        serde_json::from_str(&result)
+            .map_err(|e| format!("Failed to parse restore result: {}", e))?
+    };
+
+    if !trigger_names.is_empty() {
+        let trigger_failures = tokio::task::spawn_blocking(move || {
+            trigger_names
+                .iter()
+                .filter_map(|name| {
+                    let saved = snapshot
+                        .services
+                        .iter()
+                        .find(|s| s.name.eq_ignore_ascii_case(name))?;
+                    service_triggers::replace_triggers(name, saved.triggers.as_ref())
+                        .err()
+                        .map(|e| serde_json::json!({ "Name": name, "Error": e }))
+                })
+                .collect::<Vec<_>>()
+        })
+        .await
+        .map_err(|e| format!("Service trigger task failed: {}", e))?;
+        report["trigger_failures"] = serde_json::json!(trigger_failures);
+        report["reboot_required"] = serde_json::json!(true);
+    }
+    report["skipped"] = serde_json::json!(skipped);
+    Ok(report.to_string())
+}
+
+#[tauri::command]
+pub async fn delete_service_snapshot(snapshot_id: String) -> Result<String, String> {
+    check_auth()?;
+    tokio::task::spawn_blocking(move || services::delete_snapshot(&snapshot_id))
+        .await
+        .map_err(|e| format!("Service snapshot task failed: {}", e))??;
+    Ok("Service snapshot deleted".to_string())
+}
+
+#[tauri::command]
+pub async fn compare_services_to_baseline() -> Result<String, String> {
+    check_auth()?;
+    let build = service_baseline::current_build()?;
+    let baseline = service_baseline::load_baseline(build)?;
+    let current = fetch_services().await?;
+    let report = baseline.compare(build, &current);
+    serde_json::to_string(&report)
+        .map_err(|e| format!("Failed to serialize baseline report: {}", e))
+}
+
+#[tauri::command]
+pub async fn restore_service_defaults(service_names: Vec<String>) -> Result<String, String> {
+    check_auth()?;
+    let build = service_baseline::current_build()?;
+    let baseline = service_baseline::load_baseline(build)?;
+    let names = service_names
+        .iter()
+        .map(|n| validate_service_name(n))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if names.is_empty() {
+        return Err("No services given".to_string());
+    }
+
+    // Boot and System start types cannot be set through sc.exe, so those
+    // entries are reported back instead of being run with an empty start.
+    let mut changes = Vec::new();
+    let mut skipped = Vec::new();
+    for name in &names {
+        let expected = baseline.expected(name).ok_or_else(|| {
+            format!(
+                "Service {} is not part of the {} baseline",
+                name, baseline.name
+            )
+        })?;
+        match expected.sc_value() {
+            Some(start) => changes.push((name.clone(), Some(start), None)),
+            None => skipped.push(name.clone()),
+        }
+    }
+    if changes.is_empty() {
+        return Ok(
+            serde_json::json!({ "restored": 0, "failed": [], "skipped": skipped }).to_string(),
+        );
+    }
+
+    let current = fetch_services().await?;
+    let undo = services::capture_snapshot(
+        format!("Before restoring {} defaults", baseline.name),
+        true,
+        current,
+        Some(changes.iter().map(|c| c.0.clone()).collect()),
+    )
+    .await?;
+    tokio::task::spawn_blocking(move || services::save_snapshot(&undo))
+        .await
+        .map_err(|e| format!("Service snapshot task failed: {}", e))??;
 
-    let command = format!(
-        r#"if (-not (Test-Path "{path}")) {{ New-Item -Path "{path}" -Force | Out-Null }}; Set-ItemProperty -Path "{path}" -Name "{name}" -Value "{value}" -Type {reg_type} -Force; "Registry value written successfully""#,
-        path = reg_path,
-        name = name,
-        value = escaped_value,
-        reg_type = type_str
-    );
-    run_powershell_internal(command, false, false).await
+    let result = run_powershell_internal(services::restore_script(&changes), false, false).await?;
+    let mut report: serde_json::Value = serde_json::from_str(&result)
+        .map_err(|e| format!("Failed to parse restore result: {}", e))?;
+    report["skipped"] = serde_json::json!(skipped);
+    Ok(report.to_string())
 }
 
 #[tauri::command]
-pub async fn delete_registry_value(
-    hive: String,
-    path: String,
-    name: String,
-) -> Result<String, String> {
+pub async fn list_registry_backups() -> Result<String, String> {
     check_auth()?;
-    let _validated_path = validate_registry_path(&format!("{}:{}", hive, path))?;
-    let reg_path = format!("{}:\\{}", hive, path);
-    let command = format!(
-        r#"Remove-ItemProperty -Path "{}" -Name "{}" -Force -ErrorAction SilentlyContinue; "Registry value deleted""#,
-        reg_path, name
-    );
-    run_powershell_internal(command, false, false).await
+    let backups = tokio::task::spawn_blocking(registry::list_backups)
+        .await
+        .map_err(|e| format!("Registry backup task failed: {}", e))??;
+    serde_json::to_string(&backups).map_err(|e| format!("Failed to serialize backups: {}", e))
 }
 
 #[tauri::command]
-pub async fn delete_registry_key(hive: String, path: String) -> Result<String, String> {
+pub async fn restore_registry_backup(backup_id: String) -> Result<String, String> {
     check_auth()?;
-    let _validated_path = validate_registry_path(&format!("{}:{}", hive, path))?;
-    let reg_path = format!("{}:\\{}", hive, path);
-    let command = format!(
-        r#"Remove-Item -Path "{}" -Recurse -Force -ErrorAction SilentlyContinue; "Registry key deleted""#,
-        reg_path
-    );
-    run_powershell_internal(command, false, false).await
+    tokio::task::spawn_blocking(move || registry::restore_backup(&backup_id))
+        .await
+        .map_err(|e| format!("Registry backup task failed: {}", e))?
+}
+
+#[tauri::command]
+pub async fn delete_registry_backup(backup_id: String) -> Result<String, String> {
+    check_auth()?;
+    tokio::task::spawn_blocking(move || registry::delete_backup(&backup_id))
+        .await
+        .map_err(|e| format!("Registry backup task failed: {}", e))??;
+    Ok("Registry backup deleted".to_string())
 }
 
 #[tauri::command]
@@ -820,6 +1539,60 @@ pub async fn get_network_stats() -> Result<String, String> {
     run_powershell_internal(command.to_string(), true, false).await
 }
 
+#[tauri::command]
+pub async fn start_network_monitor(
+    app: tauri::AppHandle,
+    interval_ms: Option<u64>,
+    resolve_hostnames: Option<bool>,
+) -> Result<String, String> {
+    check_auth()?;
+    let interval = net_monitor::clamp_interval(interval_ms);
+    let dns_servers = if resolve_hostnames.unwrap_or(true) {
+        lan_discovery::parse_context(
+            &run_powershell_no_rate_limit(lan_discovery::context_script().to_string()).await?,
+        )?
+        .dns_servers()
+    } else {
+        Vec::new()
+    };
+
+    net_monitor::start(dns_servers, interval, move |sample| {
+        let _ = app.emit("network-usage-sample", sample);
+    })?;
+    Ok(format!(
+        "Network monitor started ({} ms interval)",
+        interval.as_millis()
+    ))
+}
+
+#[tauri::command]
+pub async fn stop_network_monitor() -> Result<String, String> {
+    check_auth()?;
+    Ok(if net_monitor::stop() {
+        "Network monitor stopped".to_string()
+    } else {
+        "Network monitor was not running".to_string()
+    })
+}
+
+#[tauri::command]
+pub async fn get_network_monitor_status() -> Result<String, String> {
+    check_auth()?;
+    serde_json::to_string(&net_monitor::status())
+        .map_err(|e| format!("Failed to serialize monitor status: {}", e))
+}
+
+#[tauri::command]
+pub async fn get_network_usage_history(
+    pid: Option<u32>,
+    limit: Option<usize>,
+) -> Result<String, String> {
+    check_auth()?;
+    let limit = limit.unwrap_or(300).clamp(1, 600);
+    serde_json::to_string(&net_monitor::history(pid, limit))
+        .map_err(|e| format!("Failed to serialize usage history: {}", e))
+}
+
 #[tauri::command]
 pub async fn get_uptime() -> Result<String, String> {
     check_auth()?;
@@ -1120,13 +1893,15 @@ pub async fn remove_home_gallery() -> Result<String, String> {
 #[tauri::command]
 pub async fn disable_teredo() -> Result<String, String> {
     check_auth()?;
+    let snapshot = snapshot_network_before("disable_teredo").await?;
 
     let command = r#"
         Set-NetTeredoConfiguration -Type Disabled -ErrorAction SilentlyContinue
         netsh interface teredo set state disabled
         "Teredo disabled successfully"
     "#;
-    run_powershell_internal(command.to_string(), false, false).await
+    let result = run_powershell_internal(command.to_string(), false, false).await?;
+    Ok(format!("{} (snapshot {})", result, snapshot))
 }
 
 #[tauri::command]
@@ -1158,43 +1933,29 @@ pub async fn apply_hosts_blocklist(list_type: String) -> Result<String, String>
 
     let list_key = list_type.trim().to_lowercase();
     let (marker, domains) = hosts_blocklist_domains(&list_key)?;
-    let domains_literal = domains
-        .iter()
-        .map(|domain| format!("\"{}\"", domain))
-        .collect::<Vec<_>>()
-        .join(", ");
-
-    let command = format!(
-        r#"
-        $hostsPath = "$env:SystemRoot\System32\drivers\etc\hosts"
-        $start = '# ConfUtils Blocklist {marker} Start'
-        $end = '# ConfUtils Blocklist {marker} End'
-        $domains = @({domains_literal})
-        $content = Get-Content $hostsPath -ErrorAction SilentlyContinue
-        $filtered = @()
-        $inBlock = $false
-        foreach ($line in $content) {{
-            if ($line -eq $start) {{ $inBlock = $true; continue }}
-            if ($line -eq $end) {{ $inBlock = $false; continue }}
-            if (-not $inBlock) {{ $filtered += $line }}
-        }}
-        $block = @()
-        $block += $start
-        foreach ($domain in $domains) {{
-            $block += "0.0.0.0 $domain"
-        }}
-        $block += $end
-        $final = $filtered + $block
-        Set-Content -Path $hostsPath -Value $final -Encoding ASCII
-        ipconfig /flushdns | Out-Null
-        "Hosts blocklist applied: {marker} ({count})"
-    "#,
-        marker = marker,
-        domains_literal = domains_literal,
-        count = domains.len()
-    );
+    let (domains, suppressed) = allowlist::load()?.filter(&domains);
+    let count = domains.len();
+
+    tokio::task::spawn_blocking(move || {
+        let _guard = hosts::lock();
+        let mut file = hosts::load()?;
+        file.set_section(marker, "0.0.0.0", &domains);
+        hosts::save(&file)
+    })
+    .await
+    .map_err(|e| format!("Hosts file task failed: {}", e))??;
+    flush_dns_quiet().await;
 
-    run_powershell_internal(command.to_string(), false, false).await
+    if suppressed.is_empty() {
+        Ok(format!("Hosts blocklist applied: {} ({})", marker, count))
+    } else {
+        Ok(format!(
+            "Hosts blocklist applied: {} ({}, {} allowlisted)",
+            marker,
+            count,
+            suppressed.len()
+        ))
+    }
 }
 
 #[tauri::command]
@@ -1203,27 +1964,22 @@ pub async fn remove_hosts_blocklist(list_type: String) -> Result<String, String>
 
     let list_key = list_type.trim().to_lowercase();
     let (marker, _) = hosts_blocklist_domains(&list_key)?;
-    let command = format!(
-        r#"
-        $hostsPath = "$env:SystemRoot\System32\drivers\etc\hosts"
-        $start = '# ConfUtils Blocklist {marker} Start'
-        $end = '# ConfUtils Blocklist {marker} End'
-        $content = Get-Content $hostsPath -ErrorAction SilentlyContinue
-        $filtered = @()
-        $inBlock = $false
-        foreach ($line in $content) {{
-            if ($line -eq $start) {{ $inBlock = $true; continue }}
-            if ($line -eq $end) {{ $inBlock = $false; continue }}
-            if (-not $inBlock) {{ $filtered += $line }}
-        }}
-        Set-Content -Path $hostsPath -Value $filtered -Encoding ASCII
-        ipconfig /flushdns | Out-Null
-        "Hosts blocklist removed: {marker}"
-    "#,
-        marker = marker
-    );
 
-    run_powershell_internal(command.to_string(), false, false).await
+    let removed = tokio::task::spawn_blocking(move || {
+        let _guard = hosts::lock();
+        let mut file = hosts::load()?;
+        if !file.remove_section(marker) {
+            return Ok(false);
+        }
+        hosts::save(&file).map(|_| true)
+    })
+    .await
+    .map_err(|e| format!("Hosts file task failed: {}", e))??;
+    if removed {
+        flush_dns_quiet().await;
+    }
+
+    Ok(format!("Hosts blocklist removed: {}", marker))
 }
 
 #[tauri::command]
@@ -1231,80 +1987,329 @@ pub async fn get_hosts_blocklist_status(list_type: String) -> Result<String, Str
     check_auth()?;
 
     let list_key = list_type.trim().to_lowercase();
-    let (marker, _) = hosts_blocklist_domains(&list_key)?;
+    let (marker, domains) = hosts_blocklist_domains(&list_key)?;
+    let (_, suppressed) = allowlist::load()?.filter(&domains);
+    let file = tokio::task::spawn_blocking(hosts::load)
+        .await
+        .map_err(|e| format!("Hosts file task failed: {}", e))??;
+
+    serde_json::to_string(&serde_json::json!({
+        "applied": file.section(marker).is_some(),
+        "suppressed": suppressed,
+    }))
+    .map_err(|e| format!("Failed to serialize status: {}", e))
+}
+
+fn reapply_hosts_allowlist() -> Result<usize, String> {
+    let allow = allowlist::load()?;
+    let _guard = hosts::lock();
+    let mut file = hosts::load()?;
+    let before = file.render();
+    let mut suppressed = 0;
+    for key in ["ads", "telemetry"] {
+        let (marker, domains) = hosts_blocklist_domains(key)?;
+        if file.section(marker).is_some() {
+            let (domains, skipped) = allow.filter(&domains);
+            suppressed += skipped.len();
+            file.set_section(marker, "0.0.0.0", &domains);
+        }
+    }
+    if file.render() != before {
+        hosts::save(&file)?;
+        hosts::flush_dns();
+    }
+    Ok(suppressed)
+}
+
+// The allowlist entry is already saved at this point, so failures to apply it
+// are reported alongside the new list instead of failing the command. Only
+// cached blocklists and recorded firewall addresses are used; nothing is
+// downloaded or resolved.
+async fn allowlist_changed() -> Result<serde_json::Value, String> {
+    let mut errors = Vec::new();
+    let hosts = tokio::task::spawn_blocking(|| {
+        let suppressed = reapply_hosts_allowlist()?;
+        let subscriptions = hosts_subscriptions::reapply_cached()?;
+        Ok::<usize, String>(suppressed + subscriptions.allowlisted)
+    })
+    .await
+    .map_err(|e| format!("Hosts file task failed: {}", e))?;
+    let hosts_allowlisted = hosts.unwrap_or_else(|e| {
+        errors.push(e);
+        0
+    });
+    let firewall = if privacy_firewall::load_state().enabled {
+        privacy_firewall::reapply_allowlist()
+            .await
+            .map_err(|e| errors.push(e))
+            .ok()
+    } else {
+        None
+    };
+    Ok(serde_json::json!({
+        "allowlist": allowlist::load()?.entries,
+        "hosts_allowlisted": hosts_allowlisted,
+        "firewall": firewall,
+        "errors": errors,
+    }))
+}
+
+#[tauri::command]
+pub async fn get_allowlist() -> Result<String, String> {
+    check_auth()?;
+    serde_json::to_string(&allowlist::load()?.entries)
+        .map_err(|e| format!("Failed to serialize allowlist: {}", e))
+}
+
+#[tauri::command]
+pub async fn add_allowlist_entry(pattern: String, note: Option<String>) -> Result<String, String> {
+    check_auth()?;
+    allowlist::add(&pattern, note)?;
+    serde_json::to_string(&allowlist_changed().await?)
+        .map_err(|e| format!("Failed to serialize allowlist: {}", e))
+}
+
+#[tauri::command]
+pub async fn remove_allowlist_entry(pattern: String) -> Result<String, String> {
+    check_auth()?;
+    if !allowlist::remove(&pattern)? {
+        return Err(format!("Allowlist entry not found: {}", pattern.trim()));
+    }
+    serde_json::to_string(&allowlist_changed().await?)
+        .map_err(|e| format!("Failed to serialize allowlist: {}", e))
+}
+
+#[tauri::command]
+pub async fn get_hosts_file() -> Result<String, String> {
+    check_auth()?;
+    let file = tokio::task::spawn_blocking(hosts::load)
+        .await
+        .map_err(|e| format!("Hosts file task failed: {}", e))??;
+    serde_json::to_string(&serde_json::json!({
+        "path": hosts::hosts_path(),
+        "sections": file.section_summaries(),
+        "user_entries": file.user_entries(),
+    }))
+    .map_err(|e| format!("Failed to serialize hosts file: {}", e))
+}
+
+#[tauri::command]
+pub async fn list_hosts_subscriptions() -> Result<String, String> {
+    check_auth()?;
+    serde_json::to_string(&hosts_subscriptions::load_subscriptions())
+        .map_err(|e| format!("Failed to serialize subscriptions: {}", e))
+}
+
+#[tauri::command]
+pub async fn add_hosts_subscription(
+    name: String,
+    url: String,
+    format: Option<String>,
+    refresh_hours: Option<u64>,
+) -> Result<String, String> {
+    check_auth()?;
+    let format = hosts_subscriptions::ListFormat::parse(format.as_deref())?;
+    let subscription = hosts_subscriptions::add_subscription(&name, &url, format, refresh_hours)?;
+    let summary = hosts_subscriptions::refresh(false).await?;
+    serde_json::to_string(&serde_json::json!({
+        "subscription": subscription,
+        "refresh": summary,
+    }))
+    .map_err(|e| format!("Failed to serialize subscription: {}", e))
+}
+
+#[tauri::command]
+pub async fn remove_hosts_subscription(id: String) -> Result<String, String> {
+    check_auth()?;
+    hosts_subscriptions::remove_subscription(&id)?;
+    let summary = hosts_subscriptions::refresh(false).await?;
+    serde_json::to_string(&summary).map_err(|e| format!("Failed to serialize result: {}", e))
+}
+
+#[tauri::command]
+pub async fn set_hosts_subscription_enabled(id: String, enabled: bool) -> Result<String, String> {
+    check_auth()?;
+    hosts_subscriptions::set_enabled(&id, enabled)?;
+    let summary = hosts_subscriptions::refresh(false).await?;
+    serde_json::to_string(&summary).map_err(|e| format!("Failed to serialize result: {}", e))
+}
+
+#[tauri::command]
+pub async fn refresh_hosts_subscriptions(force: Option<bool>) -> Result<String, String> {
+    check_auth()?;
+    let summary = hosts_subscriptions::refresh(force.unwrap_or(true)).await?;
+    serde_json::to_string(&summary).map_err(|e| format!("Failed to serialize result: {}", e))
+}
+
+async fn flush_dns_quiet() {
+    let _ =
+        run_powershell_internal("ipconfig /flushdns | Out-Null".to_string(), false, false).await;
+}
+
+#[tauri::command]
+pub async fn apply_privacy_firewall_rules() -> Result<String, String> {
+    check_auth()?;
+
+    let report = privacy_firewall::refresh(true, true).await?;
+    let mut message = if report.addresses == 0 {
+        "Firewall rule not applied yet. No IPs resolved; retrying in the background.".to_string()
+    } else {
+        format!(
+            "Privacy firewall rules applied. Blocked IPs: {}",
+            report.addresses
+        )
+    };
+    if !report.suppressed.is_empty() {
+        message.push_str(&format!(
+            " (allowlisted: {})",
+            report
+                .suppressed
+                .iter()
+                .map(|s| s.domain.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ));
+    }
+    Ok(message)
+}
+
+async fn run_firewall_script(script: String) -> Result<Vec<firewall::FirewallRule>, String> {
     let command = format!(
         r#"
-        $hostsPath = "$env:SystemRoot\System32\drivers\etc\hosts"
-        $start = '# ConfUtils Blocklist {marker} Start'
-        $content = Get-Content $hostsPath -ErrorAction SilentlyContinue
-        if ($content -contains $start) {{ "true" }} else {{ "false" }}
+        [Console]::OutputEncoding = [System.Text.Encoding]::UTF8
+        $OutputEncoding = [System.Text.Encoding]::UTF8
+        {}
     "#,
-        marker = marker
+        script
     );
+    let result = run_powershell_internal(command, false, false).await?;
+    firewall::parse_rules(&result)
+}
 
-    run_powershell_no_rate_limit(command.to_string()).await
+fn first_rule(rules: Vec<firewall::FirewallRule>) -> Result<String, String> {
+    let rule = rules
+        .into_iter()
+        .next()
+        .ok_or_else(|| "Firewall rule not found".to_string())?;
+    serde_json::to_string(&rule).map_err(|e| format!("Failed to serialize firewall rule: {}", e))
 }
 
 #[tauri::command]
-pub async fn apply_privacy_firewall_rules() -> Result<String, String> {
+pub async fn list_firewall_rules(all: Option<bool>) -> Result<String, String> {
     check_auth()?;
-
-    let domains = privacy_firewall_domains()
-        .iter()
-        .map(|domain| format!("\"{}\"", domain))
-        .collect::<Vec<_>>()
-        .join(", ");
-
+    let group = if all.unwrap_or(false) {
+        None
+    } else {
+        Some(firewall::CONFUTILS_GROUP)
+    };
     let command = format!(
         r#"
-        $ruleName = "ConfUtils Telemetry Block"
-        Get-NetFirewallRule -DisplayName $ruleName -ErrorAction SilentlyContinue | Remove-NetFirewallRule -ErrorAction SilentlyContinue
-        $domains = @({domains})
-        $ips = @()
-        foreach ($domain in $domains) {{
-            $records = Resolve-DnsName -Name $domain -Type A -ErrorAction SilentlyContinue
-            foreach ($record in $records) {{
-                if ($record.IPAddress) {{ $ips += $record.IPAddress }}
-            }}
-        }}
-        $ips = $ips | Sort-Object -Unique
-        if ($ips.Count -eq 0) {{
-            "Firewall rule not applied. No IPs resolved."
-        }} else {{
-            New-NetFirewallRule -DisplayName $ruleName -Group "ConfUtils Privacy" -Direction Outbound -Action Block -RemoteAddress ($ips -join ",") -Profile Any | Out-Null
-            "Privacy firewall rules applied. Blocked IPs: " + $ips.Count
-        }}
+        [Console]::OutputEncoding = [System.Text.Encoding]::UTF8
+        $OutputEncoding = [System.Text.Encoding]::UTF8
+        {}
     "#,
-        domains = domains
+        firewall::list_rules_script(group)
     );
+    let result = run_powershell_no_rate_limit(command).await?;
+    let rules = firewall::parse_rules(&result)?;
+    serde_json::to_string(&rules).map_err(|e| format!("Failed to serialize firewall rules: {}", e))
+}
 
-    run_powershell_internal(command.to_string(), false, false).await
+#[tauri::command]
+pub async fn create_firewall_rule(rule: firewall::RuleSpec) -> Result<String, String> {
+    check_auth()?;
+    let spec = firewall::validate(&rule)?;
+    first_rule(run_firewall_script(firewall::create_rule_script(&spec)).await?)
 }
 
 #[tauri::command]
-pub async fn remove_privacy_firewall_rules() -> Result<String, String> {
+pub async fn update_firewall_rule(
+    name: String,
+    rule: firewall::RuleSpec,
+) -> Result<String, String> {
     check_auth()?;
+    let name = firewall::validate_rule_name(&name)?;
+    let spec = firewall::validate(&rule)?;
+    first_rule(run_firewall_script(firewall::update_rule_script(name, &spec)).await?)
+}
 
-    let command = r#"
-        $ruleName = "ConfUtils Telemetry Block"
-        Get-NetFirewallRule -DisplayName $ruleName -ErrorAction SilentlyContinue | Remove-NetFirewallRule -ErrorAction SilentlyContinue
-        "Privacy firewall rules removed"
-    "#;
+#[tauri::command]
+pub async fn set_firewall_rule_enabled(name: String, enabled: bool) -> Result<String, String> {
+    check_auth()?;
+    let name = firewall::validate_rule_name(&name)?;
+    first_rule(run_firewall_script(firewall::set_enabled_script(name, enabled)).await?)
+}
 
-    run_powershell_internal(command.to_string(), false, false).await
+#[tauri::command]
+pub async fn delete_firewall_rule(name: String) -> Result<String, String> {
+    check_auth()?;
+    let name = firewall::validate_rule_name(&name)?;
+    run_powershell_internal(firewall::delete_rule_script(name), false, false).await
+}
+
+#[tauri::command]
+pub async fn block_program_internet(program_path: String) -> Result<String, String> {
+    check_auth()?;
+    let program = program_path.trim().to_string();
+    if !std::path::Path::new(&program).is_file() {
+        return Err(format!("Program not found: {}", program));
+    }
+    let specs = firewall::block_program_specs(&program)
+        .iter()
+        .map(firewall::validate)
+        .collect::<Result<Vec<_>, _>>()?;
+    let rules = run_firewall_script(firewall::block_program_script(&specs)).await?;
+    serde_json::to_string(&rules).map_err(|e| format!("Failed to serialize firewall rules: {}", e))
+}
+
+#[tauri::command]
+pub async fn remove_privacy_firewall_rules() -> Result<String, String> {
+    check_auth()?;
+    privacy_firewall::disable().await
 }
 
 #[tauri::command]
 pub async fn get_privacy_firewall_status() -> Result<String, String> {
     check_auth()?;
 
-    let command = r#"
-        $ruleName = "ConfUtils Telemetry Block"
-        $rule = Get-NetFirewallRule -DisplayName $ruleName -ErrorAction SilentlyContinue
-        if ($rule) { "true" } else { "false" }
-    "#;
+    let state = privacy_firewall::load_state();
+    let (_, suppressed) = allowlist::load()?.filter(&privacy_firewall::domains());
+    serde_json::to_string(&serde_json::json!({
+        "applied": privacy_firewall::rule_exists().await,
+        "auto_refresh": state.enabled,
+        "interval_hours": state.interval_hours,
+        "last_refresh_unix": state.last_refresh_unix,
+        "addresses": state.rule_addresses.len(),
+        "suppressed": suppressed,
+    }))
+    .map_err(|e| format!("Failed to serialize status: {}", e))
+}
 
-    run_powershell_no_rate_limit(command.to_string()).await
+#[tauri::command]
+pub async fn get_privacy_firewall_history() -> Result<String, String> {
+    check_auth()?;
+    serde_json::to_string(&privacy_firewall::load_state())
+        .map_err(|e| format!("Failed to serialize firewall history: {}", e))
+}
+
+#[tauri::command]
+pub async fn refresh_privacy_firewall() -> Result<String, String> {
+    check_auth()?;
+    if !privacy_firewall::load_state().enabled {
+        return Err("Privacy firewall rules are not applied".to_string());
+    }
+    let report = privacy_firewall::refresh(false, true).await?;
+    serde_json::to_string(&report).map_err(|e| format!("Failed to serialize result: {}", e))
+}
+
+#[tauri::command]
+pub async fn set_privacy_firewall_interval(hours: u64) -> Result<String, String> {
+    check_auth()?;
+    let state = privacy_firewall::set_interval(hours).await?;
+    Ok(format!(
+        "Privacy firewall refresh interval set to {} hours",
+        state.interval_hours
+    ))
 }
 
 #[tauri::command]
@@ -1358,26 +2363,33 @@ pub async fn scan_app_leftovers(app_name: String) -> Result<String, String> {
                 }}
             }}
         }}
-        $regHits = @()
-        $regRoots = @(
-            "HKCU:\Software",
-            "HKLM:\SOFTWARE",
-            "HKLM:\SOFTWARE\WOW6432Node"
-        )
-        foreach ($root in $regRoots) {{
-            Get-ChildItem -Path $root -ErrorAction SilentlyContinue | Where-Object {{ $_.Name -like "*$appName*" }} | ForEach-Object {{
-                $regHits += $_.Name
-            }}
-        }}
-        [pscustomobject]@{{
-            files = $paths
-            registry = $regHits
-        }} | ConvertTo-Json -Depth 3
+        ConvertTo-Json -InputObject @($paths) -Compress
     "#,
         app_name = safe_name
     );
 
-    run_powershell_no_rate_limit(command.to_string()).await
+    let files_raw = run_powershell_no_rate_limit(command.to_string()).await?;
+    let files: Vec<String> = serde_json::from_str(files_raw.trim()).unwrap_or_default();
+
+    let search_name = safe_name.clone();
+    let registry_hits = tokio::task::spawn_blocking(move || {
+        registry_search::find_leftover_keys(
+            &search_name,
+            &[
+                ("HKCU", "Software"),
+                ("HKLM", "SOFTWARE"),
+                ("HKLM", "SOFTWARE\\WOW6432Node"),
+            ],
+        )
+    })
+    .await
+    .map_err(|e| format!("Registry search task failed: {}", e))?;
+
+    serde_json::to_string_pretty(&serde_json::json!({
+        "files": files,
+        "registry": registry_hits,
+    }))
+    .map_err(|e| format!("Failed to serialize leftovers: {}", e))
 }
 
 #[tauri::command]
@@ -1643,62 +2655,38 @@ pub async fn scan_hidden_services() -> Result<String, String> {
             $_.PathName -notmatch "Windows\\System32" -and $_.PathName -notmatch "Microsoft" -and $_.PathName -notmatch "Windows\\WinSxS"
         } | Select-Object Name, DisplayName, PathName, StartMode, State
         $services | ConvertTo-Json -Depth 3
-    "#;
-
-    run_powershell_no_rate_limit(command.to_string()).await
-}
-
-#[tauri::command]
-pub async fn scan_open_ports() -> Result<String, String> {
-    check_auth()?;
-
-    let command = r#"
-        $recommendations = @{
-            21 = "FTP portu acik. Kullanilmiyorsa kapatmaniz onerilir."
-            22 = "SSH portu acik. Uzak erisim gerekmiyorsa kapatmaniz onerilir."
-            23 = "Telnet portu acik. Guvenli degildir, kapatmaniz onerilir."
-            80 = "HTTP portu acik. Web sunucu kullanmiyorsaniz kapatabilirsiniz."
-            135 = "RPC portu acik. Yerel ag disina acmayin."
-            139 = "NetBIOS portu acik. Dosya paylasimi yoksa kapatabilirsiniz."
-            443 = "HTTPS portu acik. Web sunucu kullanmiyorsaniz kapatabilirsiniz."
-            445 = "SMB portu acik. Dosya paylasimi yoksa kapatabilirsiniz."
-            3389 = "RDP portu acik. Uzak masaustu kullanmiyorsaniz kapatin."
-        }
-
-        $items = @()
-        $connections = Get-NetTCPConnection -State Listen -ErrorAction SilentlyContinue
-        foreach ($conn in $connections) {
-            $procName = ""
-            try {
-                $proc = Get-Process -Id $conn.OwningProcess -ErrorAction SilentlyContinue
-                if ($proc) { $procName = $proc.ProcessName }
-            } catch {
-                $procName = ""
-            }
+    "#;
 
-            $rec = $null
-            if ($recommendations.ContainsKey($conn.LocalPort)) {
-                $rec = $recommendations[$conn.LocalPort]
-            }
+    run_powershell_no_rate_limit(command.to_string()).await
+}
 
-            $items += [pscustomobject]@{
-                LocalAddress = $conn.LocalAddress
-                LocalPort = $conn.LocalPort
-                OwningProcess = $conn.OwningProcess
-                ProcessName = $procName
-                Protocol = "TCP"
-                Recommendation = $rec
-            }
-        }
+#[tauri::command]
+pub async fn scan_open_ports() -> Result<String, String> {
+    check_auth()?;
+    let rules = port_scan::load_rules()?;
+    let result = run_powershell_no_rate_limit(port_scan::scan_script().to_string()).await?;
+    let endpoints = port_scan::parse_endpoints(&result)?;
+    serde_json::to_string(&rules.scan(&endpoints))
+        .map_err(|e| format!("Failed to serialize port scan: {}", e))
+}
 
-        if ($items.Count -gt 0) {
-            $items | Sort-Object LocalPort | ConvertTo-Json -Depth 3
-        } else {
-            "[]"
+#[tauri::command]
+pub async fn apply_port_remediation(
+    remediation: port_scan::Remediation,
+    force: Option<bool>,
+) -> Result<String, String> {
+    check_auth()?;
+    match remediation {
+        port_scan::Remediation::BlockInbound { protocol, port } => {
+            let spec = firewall::validate(&port_scan::block_inbound_spec(protocol, port))?;
+            let rules =
+                run_firewall_script(firewall::block_program_script(std::slice::from_ref(&spec)))
+                    .await?;
+            serde_json::to_string(&rules)
+                .map_err(|e| format!("Failed to serialize firewall rules: {}", e))
         }
-    "#;
-
-    run_powershell_no_rate_limit(command.to_string()).await
+        port_scan::Remediation::StopService { service } => stop_service(service, force).await,
+    }
 }
 
 #[tauri::command]
@@ -2004,14 +2992,17 @@ pub async fn get_end_task_status() -> Result<bool, String> {
 
 #[tauri::command]
 pub async fn prefer_ipv4_over_ipv6() -> Result<String, String> {
-    write_registry(
+    check_auth()?;
+    let snapshot = snapshot_network_before("prefer_ipv4_over_ipv6").await?;
+    let result = write_registry(
         "HKEY_LOCAL_MACHINE".to_string(),
         r"SYSTEM\CurrentControlSet\Services\Tcpip6\Parameters".to_string(),
         "DisabledComponents".to_string(),
         "32".to_string(),
         None,
     )
-    .await
+    .await?;
+    Ok(format!("{} (snapshot {})", result, snapshot))
 }
 
 #[tauri::command]
@@ -2060,13 +3051,16 @@ pub async fn disable_intel_mm() -> Result<String, String> {
 
 #[tauri::command]
 pub async fn disable_ipv6() -> Result<String, String> {
+    check_auth()?;
+    let snapshot = snapshot_network_before("disable_ipv6").await?;
     let command = r#"
         $path = "HKLM:\SYSTEM\CurrentControlSet\Services\Tcpip6\Parameters"
         if (-not (Test-Path $path)) { New-Item -Path $path -Force | Out-Null }
         Set-ItemProperty -Path $path -Name "DisabledComponents" -Value 255 -Type DWord -Force
         "IPv6 disabled (Restart required)"
     "#;
-    run_powershell(command.to_string()).await
+    let result = run_powershell(command.to_string()).await?;
+    Ok(format!("{} (snapshot {})", result, snapshot))
 }
 
 #[tauri::command]
@@ -2102,169 +3096,259 @@ pub async fn disable_notification_tray() -> Result<String, String> {
     run_powershell_no_rate_limit(command.to_string()).await
 }
 
+async fn snapshot_network_before(action: &str) -> Result<String, String> {
+    let snapshot = network_snapshot::capture(&format!("Before {}", action), true).await?;
+    let id = snapshot.id.clone();
+    tokio::task::spawn_blocking(move || network_snapshot::save_snapshot(&snapshot))
+        .await
+        .map_err(|e| format!("Network snapshot task failed: {}", e))??;
+    Ok(id)
+}
+
 #[tauri::command]
-pub async fn set_dns(dns_type: String) -> Result<String, String> {
+pub async fn create_network_snapshot(name: String) -> Result<String, String> {
     check_auth()?;
+    let snapshot = network_snapshot::capture(&name, false).await?;
+    let summary = serde_json::json!({
+        "id": snapshot.id,
+        "name": snapshot.name,
+        "adapter_count": snapshot.adapters.len(),
+    });
+    tokio::task::spawn_blocking(move || network_snapshot::save_snapshot(&snapshot))
+        .await
+        .map_err(|e| format!("Network snapshot task failed: {}", e))??;
+    Ok(summary.to_string())
+}
 
-    let (dns1_chars, dns2_chars) = match dns_type.as_str() {
-        "cloudflare" => (vec![49, 49, 49, 49], vec![49, 48, 48, 49]),
-        "google" => (vec![56, 56, 56, 56], vec![56, 56, 52, 52]),
-        "quad9" => (
-            vec![57, 57, 57, 57],
-            vec![49, 52, 57, 49, 49, 50, 49, 49, 50],
-        ),
-        _ => return Err("Geçersiz DNS tipi".to_string()),
-    };
+#[tauri::command]
+pub async fn list_network_snapshots() -> Result<String, String> {
+    check_auth()?;
+    let snapshots = tokio::task::spawn_blocking(network_snapshot::list_snapshots)
+        .await
+        .map_err(|e| format!("Network snapshot task failed: {}", e))?;
+    serde_json::to_string(&snapshots).map_err(|e| format!("Failed to serialize snapshots: {}", e))
+}
 
-    let dns1_str = dns1_chars
-        .iter()
-        .map(|c| format!("[char]{}", c))
-        .collect::<Vec<_>>()
-        .join(", ");
-    let dns2_str = dns2_chars
+#[tauri::command]
+pub async fn diff_network_snapshot(snapshot_id: String) -> Result<String, String> {
+    check_auth()?;
+    let snapshot = network_snapshot::load_snapshot(&snapshot_id)?;
+    let current = network_snapshot::capture("Current", true).await?;
+    serde_json::to_string(&serde_json::json!({
+        "snapshot": snapshot.id,
+        "name": snapshot.name,
+        "created_unix": snapshot.created_unix,
+        "changes": network_snapshot::diff(&snapshot, &current),
+    }))
+    .map_err(|e| format!("Failed to serialize snapshot diff: {}", e))
+}
+
+#[tauri::command]
+pub async fn restore_network_snapshot(snapshot_id: String) -> Result<String, String> {
+    check_auth()?;
+    let snapshot = network_snapshot::load_snapshot(&snapshot_id)?;
+    let current =
+        network_snapshot::capture(&format!("Before restoring {}", snapshot.name), true).await?;
+    let changes = network_snapshot::diff(&snapshot, &current);
+    let skipped: Vec<String> = changes
         .iter()
-        .map(|c| format!("[char]{}", c))
-        .collect::<Vec<_>>()
-        .join(", ");
+        .filter(|c| c.setting == "adapter")
+        .filter_map(|c| c.adapter.clone())
+        .collect();
+    if changes.len() == skipped.len() {
+        return Ok(
+            serde_json::json!({ "restored": 0, "failed": [], "skipped": skipped }).to_string(),
+        );
+    }
 
-    let command = format!(
-        r#"
-        try {{
-            [Console]::OutputEncoding = [System.Text.Encoding]::UTF8
-            $OutputEncoding = [System.Text.Encoding]::UTF8
-            chcp 65001 | Out-Null
-            
-            $statusUp = [char]85 + [char]112
-            $adapters = Get-NetAdapter | Where-Object {{ $_.Status -eq $statusUp }}
-            if (-not $adapters -or (@($adapters).Count -eq 0)) {{
-                $errorMsg = [char]65 + [char]107 + [char]116 + [char]105 + [char]102 + [char]32 + [char]97 + [char]196 + [char]177 + [char]32 + [char]97 + [char]100 + [char]97 + [char]112 + [char]116 + [char]195 + [char]182 + [char]114 + [char]252 + [char]32 + [char]98 + [char]117 + [char]108 + [char]117 + [char]110 + [char]97 + [char]109 + [char]97 + [char]100 + [char]196 + [char]177
-                throw $errorMsg
-            }}
-            
-            $dns1Parts = @({})
-            $dns2Parts = @({})
-            $dns1 = [string]::Join([char]46, $dns1Parts)
-            $dns2 = [string]::Join([char]46, $dns2Parts)
-            $dnsServers = @($dns1, $dns2)
-            $successCount = 0
-            $errors = @()
-            
-            foreach ($adapter in $adapters) {{
-                try {{
-                    Set-DnsClientServerAddress -InterfaceIndex $adapter.ifIndex -ServerAddresses $dnsServers -ErrorAction Stop
-                    $successCount++
-                }} catch {{
-                    $adapterName = $adapter.Name
-                    $errorMsg = $_.Exception.Message
-                    $errorText = [char]65 + [char]100 + [char]97 + [char]112 + [char]116 + [char]195 + [char]182 + [char]114 + [char]32 + $adapterName + [char]58 + [char]32 + $errorMsg
-                    $errors += $errorText
-                }}
-            }}
-            
-            if ($successCount -eq 0) {{
-                $errorMsg = if ($errors.Count -gt 0) {{ $errors -join ([char]59 + [char]32) }} else {{ [char]72 + [char]105 + [char]231 + [char]98 + [char]105 + [char]114 + [char]32 + [char]97 + [char]100 + [char]97 + [char]112 + [char]116 + [char]195 + [char]182 + [char]114 + [char]32 + [char]105 + [char]231 + [char]105 + [char]110 + [char]32 + [char]68 + [char]78 + [char]83 + [char]32 + [char]97 + [char]121 + [char]97 + [char]114 + [char]108 + [char]97 + [char]110 + [char]97 + [char]109 + [char]97 + [char]100 + [char]196 + [char]177 }}
-                throw $errorMsg
-            }}
-            
-            $messagePart1 = $successCount.ToString()
-            $utf8Bytes = [byte[]]([byte]97, [byte]100, [byte]97, [byte]112, [byte]116, [byte]195, [byte]182, [byte]114, [byte]32, [byte]105, [byte]195, [byte]167, [byte]105, [byte]110, [byte]32, [byte]68, [byte]78, [byte]83, [byte]32, [byte]97, [byte]121, [byte]97, [byte]114, [byte]108, [byte]97, [byte]110, [byte]100, [byte]196, [byte]177)
-            $messagePart2 = [System.Text.Encoding]::UTF8.GetString($utf8Bytes)
-            $message = $messagePart1 + [char]32 + $messagePart2
-            if ($errors.Count -gt 0) {{
-                $errorPartBytes = [byte[]]([byte]32, [byte]40, [byte]66, [byte]97, [byte]122, [byte]196, [byte]177, [byte]32, [byte]97, [byte]100, [byte]97, [byte]112, [byte]116, [byte]195, [byte]182, [byte]114, [byte]108, [byte]101, [byte]114, [byte]100, [byte]101, [byte]32, [byte]104, [byte]97, [byte]116, [byte]97, [byte]58, [byte]32)
-                $errorPart = [System.Text.Encoding]::UTF8.GetString($errorPartBytes)
-                $errorJoin = $errors -join ([char]44 + [char]32)
-                $message += $errorPart + $errorJoin + [char]41
-            }}
-            
-            $result = @{{
-                Success = $true
-                Message = $message
-                Primary = $dns1
-                Secondary = $dns2
-            }}
-            $result | ConvertTo-Json -Compress
-        }} catch {{
-            $errorMsg = if ($_.Exception.Message) {{ $_.Exception.Message }} else {{ $_.ToString() }}
-            $errorResult = @{{
-                Success = $false
-                Error = $errorMsg
-            }}
-            $errorResult | ConvertTo-Json -Compress
-        }}
-        "#,
-        dns1_str, dns2_str
+    let script = network_snapshot::restore_script(&snapshot, &current, &changes)?;
+    let undo_id = current.id.clone();
+    tokio::task::spawn_blocking(move || network_snapshot::save_snapshot(&current))
+        .await
+        .map_err(|e| format!("Network snapshot task failed: {}", e))??;
+
+    let mut results = network_snapshot::parse_restore_results(
+        &run_powershell_internal(script, false, false).await?,
+    )?;
+    let reboot_required = changes
+        .iter()
+        .any(|c| c.setting == "ipv6_disabled_components");
+    results.extend(
+        tokio::task::spawn_blocking(move || {
+            network_snapshot::restore_registry(&snapshot, &changes)
+        })
+        .await
+        .map_err(|e| format!("Network snapshot task failed: {}", e))?,
     );
+    let failed: Vec<_> = results.iter().filter(|r| !r.success).collect();
+    serde_json::to_string(&serde_json::json!({
+        "restored": results.len() - failed.len(),
+        "failed": failed,
+        "skipped": skipped,
+        "undo_snapshot": undo_id,
+        "reboot_required": reboot_required,
+    }))
+    .map_err(|e| format!("Failed to serialize restore result: {}", e))
+}
 
-    let result = run_powershell_no_rate_limit(command).await?;
+#[tauri::command]
+pub async fn delete_network_snapshot(snapshot_id: String) -> Result<String, String> {
+    check_auth()?;
+    tokio::task::spawn_blocking(move || network_snapshot::delete_snapshot(&snapshot_id))
+        .await
+        .map_err(|e| format!("Network snapshot task failed: {}", e))??;
+    Ok("Network snapshot deleted".to_string())
+}
 
-    let trimmed = result.trim();
-    if trimmed.is_empty() {
-        return Err(
-            "DNS ayarlama komutu boş yanıt döndürdü. Yönetici yetkisi gerekebilir.".to_string(),
-        );
+async fn run_dns_script(script: String) -> Result<Vec<dns::AdapterResult>, String> {
+    let result = run_powershell_no_rate_limit(script).await?;
+    let results = dns::parse_results(&result)?;
+    if results.is_empty() {
+        return Err("No matching network adapter found".to_string());
     }
-
-    match serde_json::from_str::<serde_json::Value>(&trimmed) {
-        Ok(json) => {
-            if let Some(success) = json.get("Success") {
-                if success.as_bool().unwrap_or(false) {
-                    if let Some(msg) = json.get("Message") {
-                        Ok(msg
-                            .as_str()
-                            .unwrap_or("DNS başarıyla ayarlandı")
-                            .to_string())
-                    } else {
-                        Ok("DNS başarıyla ayarlandı".to_string())
-                    }
-                } else {
-                    if let Some(err) = json.get("Error") {
-                        let error_msg = err.as_str().unwrap_or("DNS ayarlanamadı");
-                        if error_msg.contains("Access is denied")
-                            || error_msg.contains("Yetki reddedildi")
-                        {
-                            Err("DNS ayarlanamadı: Yönetici yetkisi gerekli. Lütfen uygulamayı yönetici olarak çalıştırın.".to_string())
-                        } else {
-                            Err(error_msg.to_string())
-                        }
-                    } else {
-                        Err("DNS ayarlanamadı".to_string())
-                    }
-                }
-            } else {
-                Err("DNS ayarlama yanıtı geçersiz".to_string())
-            }
+    if results.iter().all(|r| !r.success) {
+        let errors = results
+            .iter()
+            .map(|r| {
+                format!(
+                    "{}: {}",
+                    r.alias,
+                    r.error.as_deref().unwrap_or("unknown error")
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("; ");
+        if errors.contains("Access is denied") {
+            return Err(format!("Administrator rights are required. {}", errors));
         }
-        Err(parse_err) => {
-            let trimmed_lower = trimmed.to_lowercase();
-            if trimmed_lower.contains("success")
-                || trimmed_lower.contains("ayarlandı")
-                || trimmed_lower.contains("dns")
-            {
-                if trimmed_lower.contains("error")
-                    || trimmed_lower.contains("hata")
-                    || trimmed_lower.contains("failed")
-                    || trimmed_lower.contains("access denied")
-                    || trimmed_lower.contains("yetki")
-                {
-                    if trimmed_lower.contains("access denied")
-                        || trimmed_lower.contains("yetki reddedildi")
-                    {
-                        Err("DNS ayarlanamadı: Yönetici yetkisi gerekli. Lütfen uygulamayı yönetici olarak çalıştırın.".to_string())
-                    } else {
-                        Err(format!("DNS ayarlama hatası: {}", trimmed))
-                    }
-                } else {
-                    Ok(trimmed.to_string())
-                }
-            } else {
-                Err(format!(
-                    "DNS ayarlama hatası (JSON parse): {} - Raw: {}",
-                    parse_err, trimmed
-                ))
-            }
+        return Err(errors);
+    }
+    Ok(results)
+}
+
+#[tauri::command]
+pub async fn set_dns(dns_type: String) -> Result<String, String> {
+    check_auth()?;
+
+    let provider = dns::find_provider(&dns_type)?;
+    let snapshot = snapshot_network_before("set_dns").await?;
+    let results = run_dns_script(dns::apply_script(&[], &provider.servers())).await?;
+    let applied = results.iter().filter(|r| r.success).count();
+    let failed = results
+        .iter()
+        .filter(|r| !r.success)
+        .map(|r| {
+            format!(
+                "{}: {}",
+                r.alias,
+                r.error.as_deref().unwrap_or("unknown error")
+            )
+        })
+        .collect::<Vec<_>>();
+    let mut message = format!("{} DNS set on {} adapter(s)", provider.name, applied);
+    if !failed.is_empty() {
+        message.push_str(&format!(" (failed: {})", failed.join(", ")));
+    }
+    message.push_str(&format!(" (snapshot {})", snapshot));
+    Ok(message)
+}
+
+#[tauri::command]
+pub async fn list_dns_providers() -> Result<String, String> {
+    check_auth()?;
+    serde_json::to_string(&dns::providers()?)
+        .map_err(|e| format!("Failed to serialize DNS providers: {}", e))
+}
+
+#[tauri::command]
+pub async fn get_dns_configuration() -> Result<String, String> {
+    check_auth()?;
+    let result = run_powershell_no_rate_limit(dns::adapters_script().to_string()).await?;
+    serde_json::to_string(&dns::parse_adapters(&result)?)
+        .map_err(|e| format!("Failed to serialize DNS configuration: {}", e))
+}
+
+#[tauri::command]
+pub async fn set_adapter_dns(
+    interface_indexes: Vec<u32>,
+    provider: Option<String>,
+    ipv4: Option<Vec<String>>,
+    ipv6: Option<Vec<String>>,
+) -> Result<String, String> {
+    check_auth()?;
+    let servers = match provider.as_deref().map(str::trim).filter(|p| !p.is_empty()) {
+        Some(id) => dns::find_provider(id)?.servers(),
+        None => dns::parse_servers(&ipv4.unwrap_or_default(), &ipv6.unwrap_or_default())?,
+    };
+    snapshot_network_before("set_adapter_dns").await?;
+    let results = run_dns_script(dns::apply_script(&interface_indexes, &servers)).await?;
+    serde_json::to_string(&results).map_err(|e| format!("Failed to serialize result: {}", e))
+}
+
+#[tauri::command]
+pub async fn set_dns_over_https(
+    interface_indexes: Vec<u32>,
+    provider: Option<String>,
+    mode: String,
+) -> Result<String, String> {
+    check_auth()?;
+    let mode = dns::DohMode::parse(&mode)?;
+    if service_baseline::current_build()? < dns::DOH_MIN_BUILD {
+        return Err("DNS over HTTPS requires Windows 11".to_string());
+    }
+
+    let provider = match provider.as_deref().map(str::trim).filter(|p| !p.is_empty()) {
+        Some(id) => Some(dns::find_provider(id)?),
+        None if mode == dns::DohMode::Off => None,
+        None => return Err("A DNS provider is required to enable DNS over HTTPS".to_string()),
+    };
+    let entries = match &provider {
+        Some(provider) => {
+            let template = provider
+                .doh_template
+                .clone()
+                .ok_or_else(|| format!("{} has no DNS-over-HTTPS template", provider.name))?;
+            provider
+                .ipv4
+                .iter()
+                .chain(&provider.ipv6)
+                .map(|ip| (*ip, template.clone()))
+                .collect()
         }
+        None => Vec::new(),
+    };
+
+    snapshot_network_before("set_dns_over_https").await?;
+    // DoH only applies to the servers an adapter actually uses.
+    if let (Some(provider), true) = (&provider, mode != dns::DohMode::Off) {
+        run_dns_script(dns::apply_script(&interface_indexes, &provider.servers())).await?;
     }
+    let results = run_dns_script(dns::apply_doh_script(&interface_indexes, &entries, mode)).await?;
+    serde_json::to_string(&results).map_err(|e| format!("Failed to serialize result: {}", e))
+}
+
+#[tauri::command]
+pub async fn get_dns_encryption_status() -> Result<String, String> {
+    check_auth()?;
+    let result = run_powershell_no_rate_limit(dns::doh_status_script()).await?;
+    serde_json::to_string(&dns::parse_doh_status(&result)?)
+        .map_err(|e| format!("Failed to serialize DNS encryption status: {}", e))
+}
+
+#[tauri::command]
+pub async fn run_dns_benchmark(config: Option<dns_bench::BenchConfig>) -> Result<String, String> {
+    check_auth()?;
+    let results = dns_bench::run(config.unwrap_or_default()).await?;
+    serde_json::to_string(&results)
+        .map_err(|e| format!("Failed to serialize benchmark results: {}", e))
+}
+
+#[tauri::command]
+pub async fn reset_adapter_dns(interface_indexes: Vec<u32>) -> Result<String, String> {
+    check_auth()?;
+    snapshot_network_before("reset_adapter_dns").await?;
+    let results = run_dns_script(dns::reset_script(&interface_indexes)).await?;
+    serde_json::to_string(&results).map_err(|e| format!("Failed to serialize result: {}", e))
 }
 
 #[tauri::command]
@@ -2718,6 +3802,8 @@ pub async fn remove_adobe_creative_cloud() -> Result<String, String> {
 
 #[tauri::command]
 pub async fn reset_network() -> Result<String, String> {
+    check_auth()?;
+    let snapshot = snapshot_network_before("reset_network").await?;
     let command = r#"
         netsh int ip reset 2>$null
         netsh winsock reset 2>$null
@@ -2726,7 +3812,8 @@ pub async fn reset_network() -> Result<String, String> {
         ipconfig /flushdns 2>$null
         "Network reset. Restart recommended."
     "#;
-    run_powershell(command.to_string()).await
+    let result = run_powershell(command.to_string()).await?;
+    Ok(format!("{} (snapshot {})", result, snapshot))
 }
 
 #[tauri::command]
@@ -2771,62 +3858,55 @@ pub async fn reinstall_winget() -> Result<String, String> {
 
 #[tauri::command]
 pub async fn set_services_manual() -> Result<String, String> {
-    let command = r#"
-        $services = @(
-            "ALG"                       # Application Layer Gateway Service
-            "AJRouter"                  # AllJoyn Router Service
-            "AppVClient"                # Microsoft App-V Client
-            "tzautoupdate"              # Auto Time Zone Updater
-            "bthserv"                   # Bluetooth Support Service (if not using Bluetooth)
-            "dmwappushservice"          # Device Management Wireless Application Protocol
-            "MapsBroker"                # Downloaded Maps Manager
-            "lfsvc"                     # Geolocation Service
-            "SharedAccess"              # Internet Connection Sharing (if not using ICS)
-            "lltdsvc"                   # Link-Layer Topology Discovery Mapper
-            "wlpasvc"                   # Local Profile Assistant Service
-            "NetTcpPortSharing"         # Net.Tcp Port Sharing Service
-            "CscService"                # Offline Files
-            "PhoneSvc"                  # Phone Service
-            "PcaSvc"                    # Program Compatibility Assistant Service
-            "QWAVE"                     # Quality Windows Audio Video Experience
-            "RmSvc"                     # Radio Management Service
-            "SensorDataService"         # Sensor Data Service
-            "SensrSvc"                  # Sensor Monitoring Service
-            "SensorService"             # Sensor Service
-            "ShellHWDetection"          # Shell Hardware Detection
-            "SCardSvr"                  # Smart Card
-            "ScDeviceEnum"              # Smart Card Device Enumeration Service
-            "SSDPSRV"                   # SSDP Discovery
-            "WiaRpc"                    # Still Image Acquisition Events
-            "OneSyncSvc"                # Sync Host Service
-            "TabletInputService"        # Touch Keyboard and Handwriting Panel Service
-            "upnphost"                  # UPnP Device Host
-            "WalletService"             # WalletService
-            "FrameServer"               # Windows Camera Frame Server
-            "stisvc"                    # Windows Image Acquisition (WIA)
-            "wisvc"                     # Windows Insider Service
-            "icssvc"                    # Windows Mobile Hotspot Service
-            "WpnService"                # Windows Push Notifications System Service
-            "WSearch"                   # Windows Search (if not using search frequently)
-        )
+    check_auth()?;
+
+    let candidates: Vec<String> = services::MANUAL_CANDIDATES
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+    let current = fetch_services().await?;
+    let snapshot = services::capture_snapshot(
+        "Before set_services_manual".to_string(),
+        true,
+        current,
+        Some(candidates),
+    )
+    .await?;
+    tokio::task::spawn_blocking({
+        let snapshot = snapshot.clone();
+        move || services::save_snapshot(&snapshot)
+    })
+    .await
+    .map_err(|e| format!("Service snapshot task failed: {}", e))??;
+
+    let command = format!(
+        r#"
+        $services = @({})
 
         $count = 0
-        foreach ($service in $services) {
-            try {
+        foreach ($service in $services) {{
+            try {{
                 $svc = Get-Service -Name $service -ErrorAction SilentlyContinue
-                if ($svc) {
+                if ($svc) {{
                     Set-Service -Name $service -StartupType Manual -ErrorAction SilentlyContinue
                     $count++
-                }
-            } catch {
+                }}
+            }} catch {{
                 # Skip services that don't exist or can't be modified
                 continue
-            }
-        }
+            }}
+        }}
 
         "Set $count services to manual startup"
-    "#;
-    run_powershell(command.to_string()).await
+    "#,
+        services::MANUAL_CANDIDATES
+            .iter()
+            .map(|s| format!("\"{}\"", s))
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+    let result = run_powershell(command).await?;
+    Ok(format!("{} (snapshot {})", result, snapshot.id))
 }
 
 #[tauri::command]
@@ -4050,10 +5130,6 @@ fn driver_install_script_all() -> &'static str {
     "$s=New-Object -ComObject Microsoft.Update.Session;$searcher=$s.CreateUpdateSearcher();$res=$searcher.Search(\"IsInstalled=0 and Type='Driver' and IsHidden=0\");$coll=New-Object -ComObject Microsoft.Update.UpdateColl;foreach($u in $res.Updates){if(-not $u.EulaAccepted){try{$u.AcceptEula()|Out-Null}catch{}};[void]$coll.Add($u)};if($coll.Count -eq 0){@{selected=0;installed=0;failed=0;rebootRequired=$false}|ConvertTo-Json -Compress;exit};$downloader=$s.CreateUpdateDownloader();$downloader.Updates=$coll;$null=$downloader.Download();$installer=$s.CreateUpdateInstaller();$installer.Updates=$coll;$ires=$installer.Install();$ok=0;$fail=0;for($i=0;$i -lt $coll.Count;$i++){$r=$ires.GetUpdateResult($i);if($r.ResultCode -eq 2 -or $r.ResultCode -eq 3){$ok++}elseif($r.ResultCode -ge 4){$fail++}};@{selected=$coll.Count;installed=$ok;failed=$fail;rebootRequired=[bool]$ires.RebootRequired}|ConvertTo-Json -Compress"
 }
 
-fn ps_quote(s: &str) -> String {
-    format!("'{}'", s.replace('\'', "''"))
-}
-
 fn driver_install_script_selected(keys: &[String]) -> String {
     let arr = keys
         .iter()
@@ -5977,6 +7053,26 @@ struct ConnectedDevice {
     mac_address: String,
     device_type: String,
     hostname: String,
+    vendor: Option<String>,
+    category: oui::DeviceCategory,
+    randomized_mac: bool,
+    entry_type: String,
+}
+
+#[tauri::command]
+pub async fn get_oui_database_info() -> Result<String, String> {
+    check_auth()?;
+    let info = tokio::task::spawn_blocking(oui::database_info)
+        .await
+        .map_err(|e| format!("OUI database task failed: {}", e))?;
+    serde_json::to_string(&info).map_err(|e| format!("Failed to serialize OUI info: {}", e))
+}
+
+#[tauri::command]
+pub async fn update_oui_database() -> Result<String, String> {
+    check_auth()?;
+    let info = oui::update_database().await?;
+    serde_json::to_string(&info).map_err(|e| format!("Failed to serialize OUI info: {}", e))
 }
 
 #[tauri::command]
@@ -5992,43 +7088,81 @@ pub async fn get_connected_devices() -> Result<String, String> {
 
     for line in arp_output.lines() {
         let parts: Vec<&str> = line.split_whitespace().collect();
-        if parts.len() >= 3 {
-            let ip = parts[0].to_string();
-            let mac = parts[1].to_string();
-
-            if mac != "ff-ff-ff-ff-ff-ff" && !mac.starts_with("ff:ff:ff") && mac.contains("-") {
-                let hostname = if parts.len() > 2 {
-                    parts[2..].join(" ")
-                } else {
-                    "Unknown".to_string()
-                };
-
-                let device_type = if mac.starts_with("00-50-56")
-                    || mac.starts_with("00-0c-29")
-                    || mac.starts_with("00-05-69")
-                {
-                    "Virtual Machine".to_string()
-                } else if mac.starts_with("00-1b-44") || mac.starts_with("00-1e-c2") {
-                    "Router/Gateway".to_string()
-                } else if mac.starts_with("00-1a-79") || mac.starts_with("00-1d-7e") {
-                    "Mobile Device".to_string()
-                } else {
-                    "Unknown Device".to_string()
-                };
+        if parts.len() >= 3 && parts[0].parse::<std::net::IpAddr>().is_ok() {
+            // Broadcast and multicast entries are not devices.
+            let Some(info) = oui::identify(parts[1]).filter(|info| !info.multicast) else {
+                continue;
+            };
 
-                devices.push(ConnectedDevice {
-                    ip_address: ip,
-                    mac_address: mac,
-                    device_type,
-                    hostname,
-                });
-            }
+            devices.push(ConnectedDevice {
+                ip_address: parts[0].to_string(),
+                mac_address: parts[1].to_string(),
+                device_type: info.category.label().to_string(),
+                hostname: "Unknown".to_string(),
+                vendor: info.vendor,
+                category: info.category,
+                randomized_mac: info.randomized,
+                entry_type: parts[2..].join(" "),
+            });
         }
     }
 
     serde_json::to_string(&devices).map_err(|e| e.to_string())
 }
 
+#[tauri::command]
+pub async fn start_lan_discovery(
+    app: tauri::AppHandle,
+    options: Option<lan_discovery::DiscoveryOptions>,
+) -> Result<String, String> {
+    check_auth()?;
+    let options = options.unwrap_or_default();
+    let context = lan_discovery::parse_context(
+        &run_powershell_no_rate_limit(lan_discovery::context_script().to_string()).await?,
+    )?;
+    let targets = lan_discovery::targets(&options, &context)?;
+
+    let job_id = util::new_id();
+    let cancelled = lan_discovery::register_job(&job_id);
+    let worker_job_id = job_id.clone();
+
+    tokio::spawn(async move {
+        let summary =
+            lan_discovery::discover(options, context, targets, &cancelled, |event| match event {
+                lan_discovery::DiscoveryEvent::Host(host) => {
+                    let _ = app.emit(
+                        "lan-discovery-host",
+                        serde_json::json!({ "job_id": worker_job_id, "host": host }),
+                    );
+                }
+                progress => {
+                    let _ = app.emit(
+                        "lan-discovery-progress",
+                        serde_json::json!({ "job_id": worker_job_id, "progress": progress }),
+                    );
+                }
+            })
+            .await;
+        let _ = app.emit(
+            "lan-discovery-done",
+            serde_json::json!({ "job_id": worker_job_id, "summary": summary }),
+        );
+        lan_discovery::finish_job(&worker_job_id);
+    });
+
+    Ok(job_id)
+}
+
+#[tauri::command]
+pub async fn cancel_lan_discovery(job_id: String) -> Result<String, String> {
+    check_auth()?;
+    if lan_discovery::cancel_job(&job_id) {
+        Ok("LAN discovery cancelled".to_string())
+    } else {
+        Err("LAN discovery not found".to_string())
+    }
+}
+
 #[derive(serde::Serialize)]
 struct ActiveConnection {
     name: String,
@@ -6317,11 +7451,9 @@ pub async fn get_router_info() -> Result<String, String> {
         }
     }
 
-    let router_brand = if router_mac.starts_with("00-1b-44") || router_mac.starts_with("00-1e-c2") {
-        "Likely Router/Gateway".to_string()
-    } else {
-        "Unknown".to_string()
-    };
+    let router_brand = oui::identify(&router_mac)
+        .and_then(|info| info.vendor)
+        .unwrap_or_else(|| "Unknown".to_string());
 
     let admin_url = format!("http://{}", gateway_ip);
 
@@ -6490,33 +7622,36 @@ pub fn force_exit() {
 }
 #[tauri::command]
 pub async fn check_online_status() -> Result<bool, String> {
-    use obfstr::obfstr;
-    use std::net::TcpStream;
-    use std::time::Duration;
-
-    let check = tokio::task::spawn_blocking(|| {
-        if TcpStream::connect_timeout(
-            &obfstr!("8.8.8.8:53").parse().unwrap(),
-            Duration::from_secs(2),
-        )
-        .is_ok()
-        {
-            return true;
-        }
-        if TcpStream::connect_timeout(
-            &obfstr!("1.1.1.1:53").parse().unwrap(),
-            Duration::from_secs(2),
-        )
-        .is_ok()
-        {
-            return true;
-        }
-        false
-    })
-    .await
-    .map_err(|e| format!("Online check task failed: {}", e))?;
+    let report = connectivity::check(&connectivity::load_config()).await?;
+    Ok(connectivity::reachable(&report.probes))
+}
+
+#[tauri::command]
+pub async fn check_connectivity(
+    config: Option<connectivity::ProbeConfig>,
+) -> Result<String, String> {
+    check_auth()?;
+    let config = config.unwrap_or_else(connectivity::load_config);
+    let report = connectivity::check(&config).await?;
+    serde_json::to_string(&report)
+        .map_err(|e| format!("Failed to serialize connectivity report: {}", e))
+}
+
+#[tauri::command]
+pub async fn get_connectivity_probes() -> Result<String, String> {
+    check_auth()?;
+    serde_json::to_string(&connectivity::load_config())
+        .map_err(|e| format!("Failed to serialize probe config: {}", e))
+}
 
-    Ok(check)
+#[tauri::command]
+pub async fn set_connectivity_probes(
+    config: Option<connectivity::ProbeConfig>,
+) -> Result<String, String> {
+    check_auth()?;
+    let config = config.unwrap_or_default();
+    connectivity::save_config(&config)?;
+    serde_json::to_string(&config).map_err(|e| format!("Failed to serialize probe config: {}", e))
 }
 
 #[tauri::command]
@@ -0,0 +1,593 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use winreg::enums::*;
+use winreg::RegKey;
+
+use crate::util;
+
+const MAX_PROBES_PER_KIND: usize = 10;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HttpProbe {
+    pub url: String,
+    pub expect_status: u16,
+    #[serde(default)]
+    pub expect_body: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProbeConfig {
+    pub tcp: Vec<String>,
+    pub http: Vec<HttpProbe>,
+    pub dns: Vec<String>,
+    pub timeout_ms: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Verdict {
+    Online,
+    CaptivePortal,
+    DnsBroken,
+    Offline,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ProbeResult {
+    pub kind: String,
+    pub target: String,
+    pub success: bool,
+    pub latency_ms: Option<f64>,
+    pub status: Option<u16>,
+    pub captive: bool,
+    pub detail: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ProxyInfo {
+    pub server: Option<String>,
+    pub auto_config_url: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct PacRoute {
+    url: String,
+    proxy: Option<String>,
+}
+
+// Proxy chosen by the PAC script for each probe origin; None means DIRECT.
+// Keyed by origin because reqwest only hands scheme, host and port to a
+// custom proxy.
+type PacRoutes = HashMap<String, Option<reqwest::Url>>;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ConnectivityReport {
+    pub verdict: Verdict,
+    pub latency_ms: Option<f64>,
+    pub portal_url: Option<String>,
+    pub proxy: Option<ProxyInfo>,
+    pub probes: Vec<ProbeResult>,
+}
+
+impl Default for ProbeConfig {
+    fn default() -> Self {
+        ProbeConfig {
+            tcp: vec![
+                "1.1.1.1:443".to_string(),
+                "8.8.8.8:443".to_string(),
+                "9.9.9.9:443".to_string(),
+                "1.1.1.1:53".to_string(),
+            ],
+            http: vec![
+                HttpProbe {
+                    url: "http://www.msftconnecttest.com/connecttest.txt".to_string(),
+                    expect_status: 200,
+                    expect_body: Some("Microsoft Connect Test".to_string()),
+                },
+                HttpProbe {
+                    url: "http://connectivitycheck.gstatic.com/generate_204".to_string(),
+                    expect_status: 204,
+                    expect_body: None,
+                },
+                HttpProbe {
+                    url: "http://cp.cloudflare.com/generate_204".to_string(),
+                    expect_status: 204,
+                    expect_body: None,
+                },
+            ],
+            dns: vec![
+                "www.microsoft.com".to_string(),
+                "cloudflare.com".to_string(),
+            ],
+            timeout_ms: 3000,
+        }
+    }
+}
+
+fn config_path() -> PathBuf {
+    util::data_dir().join("connectivity.json")
+}
+
+pub fn load_config() -> ProbeConfig {
+    std::fs::read_to_string(config_path())
+        .ok()
+        .and_then(|raw| serde_json::from_str(&raw).ok())
+        .unwrap_or_default()
+}
+
+pub fn validate(config: &ProbeConfig) -> Result<(), String> {
+    if config.tcp.len() > MAX_PROBES_PER_KIND
+        || config.http.len() > MAX_PROBES_PER_KIND
+        || config.dns.len() > MAX_PROBES_PER_KIND
+    {
+        return Err(format!(
+            "At most {} probes of each kind are supported",
+            MAX_PROBES_PER_KIND
+        ));
+    }
+    if config.tcp.is_empty() && config.http.is_empty() {
+        return Err("At least one TCP or HTTP probe is required".to_string());
+    }
+    for target in &config.tcp {
+        let (host, port) = target
+            .rsplit_once(':')
+            .ok_or_else(|| format!("TCP probe needs host:port: {}", target))?;
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        if host.is_empty() || port.parse::<u16>().map_or(true, |p| p == 0) {
+            return Err(format!("Invalid TCP probe: {}", target));
+        }
+    }
+    for probe in &config.http {
+        let url = reqwest::Url::parse(&probe.url)
+            .map_err(|e| format!("Invalid HTTP probe {}: {}", probe.url, e))?;
+        if !matches!(url.scheme(), "http" | "https") {
+            return Err(format!("HTTP probe must be http or https: {}", probe.url));
+        }
+        if !(100..600).contains(&probe.expect_status) {
+            return Err(format!("Invalid expected status for {}", probe.url));
+        }
+    }
+    if let Some(bad) = config
+        .dns
+        .iter()
+        .find(|d| !crate::hosts::is_valid_hostname(d))
+    {
+        return Err(format!("Invalid DNS probe: {}", bad));
+    }
+    Ok(())
+}
+
+pub fn save_config(config: &ProbeConfig) -> Result<(), String> {
+    validate(config)?;
+    let path = config_path();
+    if let Some(parent) = path.parent() {
+        let _ = std::fs::create_dir_all(parent);
+    }
+    let raw = serde_json::to_string_pretty(config)
+        .map_err(|e| format!("Failed to serialize probe config: {}", e))?;
+    std::fs::write(path, raw).map_err(|e| format!("Failed to save probe config: {}", e))
+}
+
+// "http=host:port;https=host:port" or a single "host:port" for all schemes.
+pub fn parse_proxy_server(raw: &str) -> Option<String> {
+    let raw = raw.trim();
+    if raw.is_empty() {
+        return None;
+    }
+    let server = if raw.contains('=') {
+        raw.split(';').find_map(|part| {
+            let (scheme, server) = part.split_once('=')?;
+            scheme
+                .trim()
+                .eq_ignore_ascii_case("http")
+                .then(|| server.trim())
+        })?
+    } else {
+        raw
+    };
+    Some(if server.contains("://") {
+        server.to_string()
+    } else {
+        format!("http://{}", server)
+    })
+}
+
+pub fn system_proxy() -> Option<ProxyInfo> {
+    let key = RegKey::predef(HKEY_CURRENT_USER)
+        .open_subkey_with_flags(
+            "Software\\Microsoft\\Windows\\CurrentVersion\\Internet Settings",
+            KEY_READ,
+        )
+        .ok()?;
+    let enabled = key.get_value::<u32, _>("ProxyEnable").unwrap_or(0) != 0;
+    let server = enabled
+        .then(|| key.get_value::<String, _>("ProxyServer").ok())
+        .flatten()
+        .and_then(|s| parse_proxy_server(&s));
+    let auto_config_url = key
+        .get_value::<String, _>("AutoConfigURL")
+        .ok()
+        .filter(|s| !s.trim().is_empty());
+    (server.is_some() || auto_config_url.is_some()).then_some(ProxyInfo {
+        server,
+        auto_config_url,
+    })
+}
+
+// .NET's system proxy runs the PAC script (or WPAD) the same way WinINet
+// does, which reqwest cannot do itself.
+fn pac_script(urls: &[String]) -> String {
+    format!(
+        r#"
+        $proxy = [System.Net.WebRequest]::GetSystemWebProxy()
+        $routes = foreach ($url in @({})) {{
+            $uri = [Uri]$url
+            $resolved = $proxy.GetProxy($uri)
+            [pscustomobject]@{{
+                Url = $url
+                Proxy = if ($resolved -and $resolved.AbsoluteUri -ne $uri.AbsoluteUri) {{ $resolved.AbsoluteUri }} else {{ $null }}
+            }}
+        }}
+        ConvertTo-Json -InputObject @($routes) -Compress
+    "#,
+        urls.iter()
+            .map(|u| util::ps_quote(u))
+            .collect::<Vec<_>>()
+            .join(",")
+    )
+}
+
+fn parse_pac_routes(raw: &str) -> Result<PacRoutes, String> {
+    let routes: Vec<PacRoute> = serde_json::from_str(raw.trim())
+        .map_err(|e| format!("Failed to parse proxy script result: {}", e))?;
+    routes
+        .into_iter()
+        .map(|route| {
+            let proxy = match route.proxy.as_deref().filter(|p| !p.trim().is_empty()) {
+                Some(p) => Some(
+                    reqwest::Url::parse(p)
+                        .map_err(|e| format!("Invalid PAC proxy {}: {}", p, e))?,
+                ),
+                None => None,
+            };
+            let url = reqwest::Url::parse(&route.url)
+                .map_err(|e| format!("Invalid probe URL {}: {}", route.url, e))?;
+            Ok((url.origin().ascii_serialization(), proxy))
+        })
+        .collect()
+}
+
+async fn resolve_pac(config: &ProbeConfig) -> Result<PacRoutes, String> {
+    let urls: Vec<String> = config
+        .http
+        .iter()
+        .filter_map(|p| reqwest::Url::parse(&p.url).ok())
+        .map(|u| u.to_string())
+        .collect();
+    let raw = crate::commands::run_powershell_no_rate_limit(pac_script(&urls)).await?;
+    parse_pac_routes(&raw)
+}
+
+// With an AutoConfigURL, Windows lets the PAC script decide per URL and only
+// falls back to the static server when the script cannot be evaluated.
+fn http_client(
+    timeout: Duration,
+    proxy: Option<&ProxyInfo>,
+    pac: Option<PacRoutes>,
+) -> Result<reqwest::Client, String> {
+    // Probes must see redirects themselves, otherwise a portal login page
+    // would be followed and compared instead of reported.
+    let mut builder = reqwest::Client::builder()
+        .timeout(timeout)
+        .redirect(reqwest::redirect::Policy::none());
+    if let Some(routes) = pac {
+        builder = builder.proxy(reqwest::Proxy::custom(move |url| {
+            routes
+                .get(&url.origin().ascii_serialization())
+                .cloned()
+                .flatten()
+        }));
+    } else if let Some(server) = proxy.and_then(|p| p.server.as_deref()) {
+        if let Ok(proxy) = reqwest::Proxy::all(server) {
+            builder = builder.proxy(proxy);
+        }
+    }
+    builder
+        .build()
+        .map_err(|e| format!("Failed to create HTTP client: {}", e))
+}
+
+fn millis(elapsed: Duration) -> f64 {
+    (elapsed.as_secs_f64() * 100_000.0).round() / 100.0
+}
+
+async fn probe_tcp(target: String, timeout: Duration) -> ProbeResult {
+    let started = Instant::now();
+    let attempt = async {
+        let addrs: Vec<SocketAddr> = tokio::net::lookup_host(target.as_str())
+            .await
+            .map_err(|e| format!("Lookup failed: {}", e))?
+            .collect();
+        let addr = addrs
+            .first()
+            .ok_or_else(|| "Lookup returned no addresses".to_string())?;
+        tokio::net::TcpStream::connect(addr)
+            .await
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    };
+    let result = tokio::time::timeout(timeout, attempt)
+        .await
+        .unwrap_or_else(|_| Err("Timed out".to_string()));
+    ProbeResult {
+        kind: "tcp".to_string(),
+        success: result.is_ok(),
+        latency_ms: result.is_ok().then(|| millis(started.elapsed())),
+        status: None,
+        captive: false,
+        detail: result.err(),
+        target,
+    }
+}
+
+async fn probe_dns(name: String, timeout: Duration) -> ProbeResult {
+    let started = Instant::now();
+    let result = tokio::time::timeout(timeout, tokio::net::lookup_host((name.as_str(), 0)))
+        .await
+        .map_err(|_| "Timed out".to_string())
+        .and_then(|r| r.map_err(|e| e.to_string()))
+        .map(|addrs| addrs.count());
+    ProbeResult {
+        kind: "dns".to_string(),
+        success: matches!(result, Ok(n) if n > 0),
+        latency_ms: result.is_ok().then(|| millis(started.elapsed())),
+        status: None,
+        captive: false,
+        detail: match result {
+            Ok(n) => Some(format!("{} address(es)", n)),
+            Err(e) => Some(e),
+        },
+        target: name,
+    }
+}
+
+async fn probe_http(client: reqwest::Client, probe: HttpProbe) -> (ProbeResult, Option<String>) {
+    let started = Instant::now();
+    let mut result = ProbeResult {
+        kind: "http".to_string(),
+        target: probe.url.clone(),
+        success: false,
+        latency_ms: None,
+        status: None,
+        captive: false,
+        detail: None,
+    };
+    let response = match client.get(&probe.url).send().await {
+        Ok(response) => response,
+        Err(e) => {
+            result.detail = Some(e.to_string());
+            return (result, None);
+        }
+    };
+    result.latency_ms = Some(millis(started.elapsed()));
+    let status = response.status().as_u16();
+    result.status = Some(status);
+    let location = response
+        .headers()
+        .get(reqwest::header::LOCATION)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.to_string());
+    let body = response.text().await.unwrap_or_default();
+
+    let body_ok = probe
+        .expect_body
+        .as_deref()
+        .is_none_or(|expected| body.trim().starts_with(expected));
+    if status == probe.expect_status && body_ok {
+        result.success = true;
+        return (result, None);
+    }
+    // Anything that answers the probe with a different page is intercepting
+    // plain HTTP, which is what captive portals do.
+    result.captive = true;
+    result.detail = Some(format!("Unexpected response (HTTP {})", status));
+    (result, location.or_else(|| Some(probe.url.clone())))
+}
+
+// Whether anything outside answered at all, regardless of DNS or portals.
+pub fn reachable(probes: &[ProbeResult]) -> bool {
+    probes
+        .iter()
+        .any(|p| (p.kind == "tcp" || p.kind == "http") && p.success)
+}
+
+pub fn verdict(probes: &[ProbeResult]) -> Verdict {
+    let ok = |kind: &str| probes.iter().any(|p| p.kind == kind && p.success);
+    let captive = probes.iter().any(|p| p.kind == "http" && p.captive);
+    let dns_probes = probes.iter().any(|p| p.kind == "dns");
+    if ok("http") {
+        Verdict::Online
+    } else if captive {
+        Verdict::CaptivePortal
+    } else if ok("tcp") && dns_probes && !ok("dns") {
+        Verdict::DnsBroken
+    } else if ok("tcp") {
+        Verdict::Online
+    } else {
+        Verdict::Offline
+    }
+}
+
+pub async fn check(config: &ProbeConfig) -> Result<ConnectivityReport, String> {
+    validate(config)?;
+    let timeout = Duration::from_millis(config.timeout_ms.clamp(500, 15_000));
+    let proxy = tokio::task::spawn_blocking(system_proxy)
+        .await
+        .unwrap_or(None);
+    let pac = match proxy.as_ref().and_then(|p| p.auto_config_url.as_ref()) {
+        Some(_) if !config.http.is_empty() => resolve_pac(config).await.ok(),
+        _ => None,
+    };
+    let client = http_client(timeout, proxy.as_ref(), pac)?;
+
+    let mut tasks = tokio::task::JoinSet::new();
+    for target in config.tcp.clone() {
+        tasks.spawn(async move { (probe_tcp(target, timeout).await, None) });
+    }
+    for name in config.dns.clone() {
+        tasks.spawn(async move { (probe_dns(name, timeout).await, None) });
+    }
+    for probe in config.http.clone() {
+        tasks.spawn(probe_http(client.clone(), probe));
+    }
+
+    let mut probes = Vec::new();
+    let mut portal_url = None;
+    while let Some(joined) = tasks.join_next().await {
+        let (result, portal) = joined.map_err(|e| format!("Probe task failed: {}", e))?;
+        if portal_url.is_none() {
+            portal_url = portal;
+        }
+        probes.push(result);
+    }
+    probes.sort_by(|a, b| a.kind.cmp(&b.kind).then(a.target.cmp(&b.target)));
+
+    let verdict = verdict(&probes);
+    let latency_ms = probes
+        .iter()
+        .filter(|p| p.success && p.kind != "dns")
+        .filter_map(|p| p.latency_ms)
+        .min_by(|a, b| a.total_cmp(b));
+    Ok(ConnectivityReport {
+        verdict,
+        latency_ms,
+        portal_url: portal_url.filter(|_| verdict == Verdict::CaptivePortal),
+        proxy,
+        probes,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    fn probe(kind: &str, success: bool, captive: bool) -> ProbeResult {
+        ProbeResult {
+            kind: kind.to_string(),
+            target: kind.to_string(),
+            success,
+            latency_ms: None,
+            status: None,
+            captive,
+            detail: None,
+        }
+    }
+
+    // Answers one request with 204 and hands back its request line.
+    async fn serve_once() -> (SocketAddr, tokio::task::JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let handle = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0u8; 1024];
+            while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+                let n = stream.read(&mut buf).await.unwrap();
+                if n == 0 {
+                    break;
+                }
+                request.extend_from_slice(&buf[..n]);
+            }
+            stream
+                .write_all(
+                    b"HTTP/1.1 204 No Content\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                )
+                .await
+                .unwrap();
+            let text = String::from_utf8_lossy(&request).to_string();
+            text.lines().next().unwrap_or_default().to_string()
+        });
+        (addr, handle)
+    }
+
+    #[test]
+    fn reachable_ignores_dns_and_portal_verdicts() {
+        let portal = [probe("tcp", true, false), probe("http", false, true)];
+        assert_eq!(verdict(&portal), Verdict::CaptivePortal);
+        assert!(reachable(&portal));
+
+        let dns_broken = [probe("tcp", true, false), probe("dns", false, false)];
+        assert_eq!(verdict(&dns_broken), Verdict::DnsBroken);
+        assert!(reachable(&dns_broken));
+
+        let offline = [probe("tcp", false, false), probe("dns", true, false)];
+        assert_eq!(verdict(&offline), Verdict::Offline);
+        assert!(!reachable(&offline));
+        assert_eq!(verdict(&[probe("http", true, false)]), Verdict::Online);
+    }
+
+    #[test]
+    fn parse_proxy_server_picks_http_entry() {
+        assert_eq!(
+            parse_proxy_server("proxy.corp:8080").as_deref(),
+            Some("http://proxy.corp:8080")
+        );
+        assert_eq!(
+            parse_proxy_server("ftp=f:21;http=h:3128;https=s:3129").as_deref(),
+            Some("http://h:3128")
+        );
+        assert_eq!(parse_proxy_server("https=s:3129"), None);
+        assert_eq!(parse_proxy_server("  "), None);
+    }
+
+    #[test]
+    fn pac_routes_parse_direct_and_proxied() {
+        let routes = parse_pac_routes(
+            r#"[{"Url":"http://a.test/","Proxy":"http://proxy.corp:8080/"},{"Url":"http://b.test/","Proxy":null}]"#,
+        )
+        .unwrap();
+        assert_eq!(
+            routes["http://a.test"].as_ref().map(|u| u.as_str()),
+            Some("http://proxy.corp:8080/")
+        );
+        assert_eq!(routes["http://b.test"], None);
+        assert!(parse_pac_routes(r#"[{"Url":"http://a.test/","Proxy":"::"}]"#).is_err());
+        assert!(pac_script(&["http://it's.test/".to_string()]).contains("@('http://it''s.test/')"));
+    }
+
+    #[tokio::test]
+    async fn http_client_follows_pac_routes() {
+        let (proxy_addr, proxy) = serve_once().await;
+        let (origin_addr, origin) = serve_once().await;
+        let proxied = "http://probe.invalid/generate_204".to_string();
+        let direct = format!("http://{}/direct", origin_addr);
+        let routes = parse_pac_routes(
+            &serde_json::json!([
+                { "Url": proxied, "Proxy": format!("http://{}/", proxy_addr) },
+                { "Url": direct, "Proxy": null },
+            ])
+            .to_string(),
+        )
+        .unwrap();
+        // A static server that would swallow everything if it were used.
+        let info = ProxyInfo {
+            server: Some("http://127.0.0.1:9".to_string()),
+            auto_config_url: Some("http://wpad/wpad.dat".to_string()),
+        };
+        let client = http_client(Duration::from_secs(5), Some(&info), Some(routes)).unwrap();
+
+        assert_eq!(client.get(&proxied).send().await.unwrap().status(), 204);
+        assert_eq!(
+            proxy.await.unwrap(),
+            "GET http://probe.invalid/generate_204 HTTP/1.1"
+        );
+        assert_eq!(client.get(&direct).send().await.unwrap().status(), 204);
+        assert_eq!(origin.await.unwrap(), "GET /direct HTTP/1.1");
+    }
+}
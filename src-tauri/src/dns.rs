@@ -0,0 +1,678 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::net::IpAddr;
+
+const PROVIDER_DATA: &str = include_str!("../../src/premium/data/toolbox_dns.json");
+const MAX_SERVERS_PER_FAMILY: usize = 4;
+pub const DOH_MIN_BUILD: u32 = 22000;
+
+// DohFlags bits under Dnscache\InterfaceSpecificParameters\<guid>\DohInterfaceSettings.
+const DOH_FLAG_AUTO_TEMPLATE: u64 = 0x1;
+const DOH_FLAG_ALLOW_FALLBACK: u64 = 0x10;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ProviderEntry {
+    primary: String,
+    #[serde(default)]
+    secondary: Option<String>,
+    #[serde(default)]
+    primary6: Option<String>,
+    #[serde(default)]
+    secondary6: Option<String>,
+    #[serde(default)]
+    doh: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DnsProvider {
+    pub id: String,
+    pub name: String,
+    pub ipv4: Vec<IpAddr>,
+    pub ipv6: Vec<IpAddr>,
+    pub doh_template: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DohMode {
+    Off,
+    Auto,
+    Required,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ServerEncryption {
+    pub address: String,
+    pub template: Option<String>,
+    pub mode: DohMode,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AdapterEncryption {
+    pub interface_index: u32,
+    pub alias: String,
+    pub servers: Vec<ServerEncryption>,
+    pub status: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DnsServers {
+    #[serde(default)]
+    pub ipv4: Vec<IpAddr>,
+    #[serde(default)]
+    pub ipv6: Vec<IpAddr>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AdapterDns {
+    pub interface_index: u32,
+    pub alias: String,
+    pub description: String,
+    pub status: String,
+    pub ipv4_servers: Vec<String>,
+    pub ipv6_servers: Vec<String>,
+    pub ipv4_dhcp: bool,
+    pub ipv6_dhcp: bool,
+    pub provider: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AdapterResult {
+    pub interface_index: u32,
+    pub alias: String,
+    pub success: bool,
+    pub error: Option<String>,
+}
+
+fn parse_ip(raw: Option<&str>, v6: bool) -> Option<IpAddr> {
+    raw.and_then(|s| s.trim().parse::<IpAddr>().ok())
+        .filter(|ip| ip.is_ipv6() == v6)
+}
+
+pub fn providers() -> Result<Vec<DnsProvider>, String> {
+    let entries: BTreeMap<String, ProviderEntry> = serde_json::from_str(PROVIDER_DATA)
+        .map_err(|e| format!("Corrupt DNS provider data: {}", e))?;
+    Ok(entries
+        .into_iter()
+        .map(|(id, entry)| DnsProvider {
+            name: id.replace('_', " "),
+            ipv4: [Some(entry.primary.as_str()), entry.secondary.as_deref()]
+                .into_iter()
+                .filter_map(|s| parse_ip(s, false))
+                .collect(),
+            ipv6: [entry.primary6.as_deref(), entry.secondary6.as_deref()]
+                .into_iter()
+                .filter_map(|s| parse_ip(s, true))
+                .collect(),
+            doh_template: entry.doh.filter(|t| validate_template(t).is_ok()),
+            id: id.to_lowercase(),
+        })
+        .collect())
+}
+
+pub fn find_provider(id: &str) -> Result<DnsProvider, String> {
+    let key = id.trim().to_lowercase().replace([' ', '-'], "_");
+    providers()?
+        .into_iter()
+        .find(|p| p.id == key)
+        .ok_or_else(|| format!("Unknown DNS provider: {}", id.trim()))
+}
+
+pub fn parse_servers(ipv4: &[String], ipv6: &[String]) -> Result<DnsServers, String> {
+    let parse = |items: &[String], v6: bool| -> Result<Vec<IpAddr>, String> {
+        let mut out: Vec<IpAddr> = Vec::new();
+        for raw in items.iter().map(|s| s.trim()).filter(|s| !s.is_empty()) {
+            let ip: IpAddr = raw
+                .parse()
+                .map_err(|_| format!("Invalid DNS server address: {}", raw))?;
+            if ip.is_ipv6() != v6 {
+                return Err(format!(
+                    "{} is not an {} address",
+                    raw,
+                    if v6 { "IPv6" } else { "IPv4" }
+                ));
+            }
+            if ip.is_unspecified() || ip.is_multicast() {
+                return Err(format!("Invalid DNS server address: {}", raw));
+            }
+            if !out.contains(&ip) {
+                out.push(ip);
+            }
+        }
+        if out.len() > MAX_SERVERS_PER_FAMILY {
+            return Err(format!(
+                "At most {} DNS servers per address family are supported",
+                MAX_SERVERS_PER_FAMILY
+            ));
+        }
+        Ok(out)
+    };
+    let servers = DnsServers {
+        ipv4: parse(ipv4, false)?,
+        ipv6: parse(ipv6, true)?,
+    };
+    if servers.ipv4.is_empty() && servers.ipv6.is_empty() {
+        return Err("At least one DNS server is required".to_string());
+    }
+    Ok(servers)
+}
+
+impl DohMode {
+    pub fn parse(raw: &str) -> Result<Self, String> {
+        match raw.trim().to_lowercase().as_str() {
+            "off" | "disabled" => Ok(DohMode::Off),
+            "auto" | "automatic" => Ok(DohMode::Auto),
+            "required" | "on" | "encrypted" => Ok(DohMode::Required),
+            other => Err(format!("Unknown DNS-over-HTTPS mode: {}", other)),
+        }
+    }
+
+    fn flags(&self) -> Option<u64> {
+        match self {
+            DohMode::Off => None,
+            DohMode::Auto => Some(DOH_FLAG_AUTO_TEMPLATE | DOH_FLAG_ALLOW_FALLBACK),
+            DohMode::Required => Some(DOH_FLAG_AUTO_TEMPLATE),
+        }
+    }
+
+    fn from_flags(flags: Option<u64>) -> Self {
+        match flags {
+            Some(f) if f & DOH_FLAG_ALLOW_FALLBACK != 0 => DohMode::Auto,
+            Some(f) if f != 0 => DohMode::Required,
+            _ => DohMode::Off,
+        }
+    }
+}
+
+pub fn validate_template(template: &str) -> Result<(), String> {
+    let valid = template.starts_with("https://")
+        && template.len() <= 512
+        && !template
+            .chars()
+            .any(|c| c.is_whitespace() || c.is_control() || matches!(c, '\'' | '"' | '`'));
+    if valid {
+        Ok(())
+    } else {
+        Err(format!("Invalid DNS-over-HTTPS template: {}", template))
+    }
+}
+
+impl DnsProvider {
+    pub fn servers(&self) -> DnsServers {
+        DnsServers {
+            ipv4: self.ipv4.clone(),
+            ipv6: self.ipv6.clone(),
+        }
+    }
+}
+
+pub fn match_provider(providers: &[DnsProvider], ipv4: &[String]) -> Option<String> {
+    let first = ipv4.first()?.parse::<IpAddr>().ok()?;
+    providers
+        .iter()
+        .find(|p| p.ipv4.first() == Some(&first))
+        .map(|p| p.id.clone())
+}
+
+fn string_list(value: Option<&Value>) -> Vec<String> {
+    match value {
+        Some(Value::Array(items)) => items
+            .iter()
+            .filter_map(|v| v.as_str())
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+            .collect(),
+        Some(Value::String(s)) if !s.trim().is_empty() => vec![s.trim().to_string()],
+        _ => Vec::new(),
+    }
+}
+
+fn json_items(raw: &str) -> Result<Vec<Value>, String> {
+    let trimmed = raw.trim();
+    if trimmed.is_empty() || trimmed == "null" {
+        return Ok(Vec::new());
+    }
+    match serde_json::from_str::<Value>(trimmed)
+        .map_err(|e| format!("Failed to parse DNS data: {}", e))?
+    {
+        Value::Array(items) => Ok(items),
+        Value::Null => Ok(Vec::new()),
+        single => Ok(vec![single]),
+    }
+}
+
+pub fn parse_adapters(raw: &str) -> Result<Vec<AdapterDns>, String> {
+    let providers = providers().unwrap_or_default();
+    Ok(json_items(raw)?
+        .iter()
+        .filter_map(|obj| {
+            let ipv4_servers = string_list(obj.get("IPv4"));
+            Some(AdapterDns {
+                interface_index: obj.get("Index")?.as_u64()? as u32,
+                alias: obj.get("Alias")?.as_str()?.to_string(),
+                description: obj
+                    .get("Description")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or_default()
+                    .to_string(),
+                status: obj
+                    .get("Status")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or_default()
+                    .to_string(),
+                provider: match_provider(&providers, &ipv4_servers),
+                ipv6_servers: string_list(obj.get("IPv6")),
+                ipv4_servers,
+                ipv4_dhcp: obj
+                    .get("IPv4Dhcp")
+                    .and_then(|v| v.as_bool())
+                    .unwrap_or(true),
+                ipv6_dhcp: obj
+                    .get("IPv6Dhcp")
+                    .and_then(|v| v.as_bool())
+                    .unwrap_or(true),
+            })
+        })
+        .collect())
+}
+
+pub fn parse_results(raw: &str) -> Result<Vec<AdapterResult>, String> {
+    Ok(json_items(raw)?
+        .iter()
+        .filter_map(|obj| {
+            Some(AdapterResult {
+                interface_index: obj.get("Index")?.as_u64()? as u32,
+                alias: obj.get("Alias")?.as_str()?.to_string(),
+                success: obj.get("Success")?.as_bool()?,
+                error: obj
+                    .get("Error")
+                    .and_then(|v| v.as_str())
+                    .map(|s| s.to_string()),
+            })
+        })
+        .collect())
+}
+
+// Static servers live in the Tcpip/Tcpip6 NameServer values; an empty value
+// means the adapter takes its DNS servers from DHCP or router advertisements.
+pub fn adapters_script() -> &'static str {
+    r#"
+        @(Get-NetAdapter -ErrorAction SilentlyContinue | Where-Object { $_.HardwareInterface -or $_.Status -eq 'Up' } | ForEach-Object {
+            $a = $_
+            $v4 = (Get-DnsClientServerAddress -InterfaceIndex $a.ifIndex -AddressFamily IPv4 -ErrorAction SilentlyContinue).ServerAddresses
+            $v6 = (Get-DnsClientServerAddress -InterfaceIndex $a.ifIndex -AddressFamily IPv6 -ErrorAction SilentlyContinue).ServerAddresses
+            $ns4 = (Get-ItemProperty "HKLM:\SYSTEM\CurrentControlSet\Services\Tcpip\Parameters\Interfaces\$($a.InterfaceGuid)" -Name NameServer -ErrorAction SilentlyContinue).NameServer
+            $ns6 = (Get-ItemProperty "HKLM:\SYSTEM\CurrentControlSet\Services\Tcpip6\Parameters\Interfaces\$($a.InterfaceGuid)" -Name NameServer -ErrorAction SilentlyContinue).NameServer
+            [pscustomobject]@{
+                Index = [int]$a.ifIndex
+                Alias = $a.Name
+                Description = $a.InterfaceDescription
+                Status = [string]$a.Status
+                IPv4 = @($v4)
+                IPv6 = @($v6 | Where-Object { $_ -notlike 'fec0:0:0:ffff::*' })
+                IPv4Dhcp = [string]::IsNullOrWhiteSpace($ns4)
+                IPv6Dhcp = [string]::IsNullOrWhiteSpace($ns6)
+            }
+        }) | ConvertTo-Json -Depth 3 -Compress
+    "#
+}
+
+fn target_adapters(indexes: &[u32]) -> String {
+    if indexes.is_empty() {
+        "Get-NetAdapter -ErrorAction SilentlyContinue | Where-Object { $_.Status -eq 'Up' }"
+            .to_string()
+    } else {
+        format!(
+            "Get-NetAdapter -InterfaceIndex {} -ErrorAction SilentlyContinue",
+            indexes
+                .iter()
+                .map(|i| i.to_string())
+                .collect::<Vec<_>>()
+                .join(",")
+        )
+    }
+}
+
+fn per_adapter_script(indexes: &[u32], action: &str) -> String {
+    format!(
+        r#"
+        $adapters = @({adapters})
+        @($adapters | ForEach-Object {{
+            $a = $_
+            try {{
+                {action}
+                [pscustomobject]@{{ Index = [int]$a.ifIndex; Alias = $a.Name; Success = $true; Error = $null }}
+            }} catch {{
+                [pscustomobject]@{{ Index = [int]$a.ifIndex; Alias = $a.Name; Success = $false; Error = $_.Exception.Message }}
+            }}
+        }}) | ConvertTo-Json -Compress
+    "#,
+        adapters = target_adapters(indexes),
+        action = action
+    )
+}
+
+// An empty index list targets every adapter that is currently up.
+pub fn apply_script(indexes: &[u32], servers: &DnsServers) -> String {
+    let list = servers
+        .ipv4
+        .iter()
+        .chain(&servers.ipv6)
+        .map(|ip| format!("'{}'", ip))
+        .collect::<Vec<_>>()
+        .join(",");
+    // Set-DnsClientServerAddress only touches the families it is given, so
+    // reset first to drop stale static servers of a family we are not setting.
+    let action = format!(
+        "Set-DnsClientServerAddress -InterfaceIndex $a.ifIndex -ResetServerAddresses -ErrorAction Stop\n                Set-DnsClientServerAddress -InterfaceIndex $a.ifIndex -ServerAddresses @({}) -ErrorAction Stop",
+        list
+    );
+    per_adapter_script(indexes, &action)
+}
+
+pub fn reset_script(indexes: &[u32]) -> String {
+    per_adapter_script(
+        indexes,
+        "Set-DnsClientServerAddress -InterfaceIndex $a.ifIndex -ResetServerAddresses -ErrorAction Stop",
+    )
+}
+
+fn doh_key() -> &'static str {
+    "HKLM:\\SYSTEM\\CurrentControlSet\\Services\\Dnscache\\InterfaceSpecificParameters\\$($a.InterfaceGuid)\\DohInterfaceSettings"
+}
+
+fn doh_subkey(ip: &IpAddr) -> &'static str {
+    if ip.is_ipv6() {
+        "Doh6"
+    } else {
+        "Doh"
+    }
+}
+
+// Registers the provider templates globally, then sets the per-interface
+// DohFlags for every provider address on the selected adapters. The global
+// entry is shared by every adapter using that server, so it only carries the
+// template; fallback and upgrade behaviour come from the interface flags.
+pub fn apply_doh_script(indexes: &[u32], entries: &[(IpAddr, String)], mode: DohMode) -> String {
+    let mut prelude = Vec::new();
+    let mut actions = Vec::new();
+    if mode != DohMode::Off {
+        for (ip, template) in entries {
+            prelude.push(format!(
+                "if (Get-DnsClientDohServerAddress -ServerAddress '{ip}' -ErrorAction SilentlyContinue) {{ Set-DnsClientDohServerAddress -ServerAddress '{ip}' -DohTemplate '{template}' -ErrorAction Stop | Out-Null }} else {{ Add-DnsClientDohServerAddress -ServerAddress '{ip}' -DohTemplate '{template}' -ErrorAction Stop | Out-Null }}",
+                ip = ip,
+                template = template
+            ));
+        }
+    }
+    if entries.is_empty() {
+        actions.push(format!(
+            "Remove-Item \"{}\" -Recurse -ErrorAction SilentlyContinue",
+            doh_key()
+        ));
+    }
+    for (ip, _) in entries {
+        let key = format!("{}\\{}\\{}", doh_key(), doh_subkey(ip), ip);
+        match mode.flags() {
+            Some(flags) => actions.push(format!(
+                "New-Item \"{key}\" -Force | Out-Null; New-ItemProperty \"{key}\" -Name DohFlags -PropertyType QWord -Value {flags} -Force | Out-Null",
+                key = key,
+                flags = flags
+            )),
+            None => actions.push(format!(
+                "Remove-Item \"{}\" -Recurse -ErrorAction SilentlyContinue",
+                key
+            )),
+        }
+    }
+    actions.push("Clear-DnsClientCache -ErrorAction SilentlyContinue".to_string());
+    format!(
+        "{}\n{}",
+        prelude.join("\n"),
+        per_adapter_script(indexes, &actions.join("\n                "))
+    )
+}
+
+pub fn doh_status_script() -> String {
+    format!(
+        r#"
+        $templates = @{{}}
+        Get-DnsClientDohServerAddress -ErrorAction SilentlyContinue | ForEach-Object {{ $templates[$_.ServerAddress] = $_.DohTemplate }}
+        @(Get-NetAdapter -ErrorAction SilentlyContinue | Where-Object {{ $_.Status -eq 'Up' }} | ForEach-Object {{
+            $a = $_
+            $base = "{key}"
+            $addresses = @(Get-DnsClientServerAddress -InterfaceIndex $a.ifIndex -ErrorAction SilentlyContinue | ForEach-Object {{ $_.ServerAddresses }}) | Where-Object {{ $_ -notlike 'fec0:0:0:ffff::*' }}
+            [pscustomobject]@{{
+                Index = [int]$a.ifIndex
+                Alias = $a.Name
+                Servers = @($addresses | ForEach-Object {{
+                    $sub = if ($_ -like '*:*') {{ 'Doh6' }} else {{ 'Doh' }}
+                    $flags = (Get-ItemProperty "$base\$sub\$_" -Name DohFlags -ErrorAction SilentlyContinue).DohFlags
+                    [pscustomobject]@{{ Address = $_; Template = $templates[$_]; Flags = $flags }}
+                }})
+            }}
+        }}) | ConvertTo-Json -Depth 4 -Compress
+    "#,
+        key = doh_key()
+    )
+}
+
+fn encryption_status(servers: &[ServerEncryption]) -> &'static str {
+    let encrypted = |s: &&ServerEncryption| s.template.is_some() && s.mode != DohMode::Off;
+    if servers.is_empty() {
+        "none"
+    } else if servers
+        .iter()
+        .all(|s| s.template.is_some() && s.mode == DohMode::Required)
+    {
+        "encrypted"
+    } else if servers.iter().all(|s| encrypted(&s)) {
+        "opportunistic"
+    } else if servers.iter().any(|s| encrypted(&s)) {
+        "partial"
+    } else {
+        "plaintext"
+    }
+}
+
+pub fn parse_doh_status(raw: &str) -> Result<Vec<AdapterEncryption>, String> {
+    Ok(json_items(raw)?
+        .iter()
+        .filter_map(|obj| {
+            let servers: Vec<ServerEncryption> = match obj.get("Servers") {
+                Some(Value::Array(items)) => items.clone(),
+                Some(Value::Object(_)) => vec![obj["Servers"].clone()],
+                _ => Vec::new(),
+            }
+            .iter()
+            .filter_map(|s| {
+                Some(ServerEncryption {
+                    address: s.get("Address")?.as_str()?.to_string(),
+                    template: s
+                        .get("Template")
+                        .and_then(|v| v.as_str())
+                        .filter(|t| !t.is_empty())
+                        .map(|t| t.to_string()),
+                    mode: DohMode::from_flags(s.get("Flags").and_then(|v| v.as_u64())),
+                })
+            })
+            .collect();
+            Some(AdapterEncryption {
+                interface_index: obj.get("Index")?.as_u64()? as u32,
+                alias: obj.get("Alias")?.as_str()?.to_string(),
+                status: encryption_status(&servers).to_string(),
+                servers,
+            })
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cloudflare() -> Vec<(IpAddr, String)> {
+        vec![
+            (
+                "1.1.1.1".parse().unwrap(),
+                "https://cloudflare-dns.com/dns-query".to_string(),
+            ),
+            (
+                "2606:4700:4700::1111".parse().unwrap(),
+                "https://cloudflare-dns.com/dns-query".to_string(),
+            ),
+        ]
+    }
+
+    fn strings(raw: &[&str]) -> Vec<String> {
+        raw.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn parse_servers_splits_families_and_dedups() {
+        let servers = parse_servers(
+            &strings(&[" 1.1.1.1 ", "", "1.0.0.1", "1.1.1.1"]),
+            &strings(&["2606:4700:4700::1111", "2606:4700:4700::1111"]),
+        )
+        .unwrap();
+        assert_eq!(servers.ipv4.len(), 2);
+        assert_eq!(servers.ipv6.len(), 1);
+        assert_eq!(servers.ipv4[1].to_string(), "1.0.0.1");
+
+        let only_v6 = parse_servers(&[], &strings(&["2620:fe::fe"])).unwrap();
+        assert!(only_v6.ipv4.is_empty());
+    }
+
+    #[test]
+    fn parse_servers_rejects_invalid_input() {
+        let cases: &[(&[&str], &[&str], &str)] = &[
+            (&["1.1.1"], &[], "Invalid DNS server address: 1.1.1"),
+            (&["2620:fe::fe"], &[], "2620:fe::fe is not an IPv4 address"),
+            (&[], &["9.9.9.9"], "9.9.9.9 is not an IPv6 address"),
+            (&["0.0.0.0"], &[], "Invalid DNS server address: 0.0.0.0"),
+            (&["224.0.0.1"], &[], "Invalid DNS server address: 224.0.0.1"),
+            (&["", " "], &[], "At least one DNS server is required"),
+            (
+                &["1.1.1.1; Remove-Item C:\\"],
+                &[],
+                "Invalid DNS server address",
+            ),
+        ];
+        for (v4, v6, error) in cases {
+            let result = parse_servers(&strings(v4), &strings(v6));
+            assert!(result.unwrap_err().starts_with(error), "{:?}", v4);
+        }
+        let five = strings(&["1.1.1.1", "1.0.0.1", "8.8.8.8", "8.8.4.4", "9.9.9.9"]);
+        assert!(parse_servers(&five, &[])
+            .unwrap_err()
+            .starts_with("At most 4 DNS servers"));
+    }
+
+    #[test]
+    fn providers_are_found_by_id_and_address() {
+        let all = providers().unwrap();
+        let cloudflare = find_provider(" Cloudflare ").unwrap();
+        assert_eq!(cloudflare.id, "cloudflare");
+        assert_eq!(cloudflare.ipv4[0].to_string(), "1.1.1.1");
+        assert_eq!(cloudflare.ipv6.len(), 2);
+        assert_eq!(
+            cloudflare.doh_template.as_deref(),
+            Some("https://cloudflare-dns.com/dns-query")
+        );
+        assert_eq!(
+            find_provider("cloudflare-malware").unwrap().name,
+            "Cloudflare Malware"
+        );
+        assert!(find_provider("Nonexistent")
+            .unwrap_err()
+            .starts_with("Unknown DNS provider"));
+
+        assert_eq!(
+            match_provider(&all, &strings(&["8.8.8.8", "1.1.1.1"])).as_deref(),
+            Some("google")
+        );
+        assert_eq!(
+            match_provider(&all, &strings(&["1.1.1.2"])).as_deref(),
+            Some("cloudflare_malware")
+        );
+        assert_eq!(match_provider(&all, &strings(&["8.8.4.4"])), None);
+        assert_eq!(match_provider(&all, &strings(&["192.168.1.1"])), None);
+        assert_eq!(match_provider(&all, &[]), None);
+    }
+
+    #[test]
+    fn parse_adapters_accepts_single_object_and_array() {
+        let single = r#"{"Index":12,"Alias":"Ethernet","Description":"Intel(R) Ethernet","Status":"Up","IPv4":"1.1.1.1","IPv6":[],"IPv4Dhcp":false,"IPv6Dhcp":true}"#;
+        let adapters = parse_adapters(single).unwrap();
+        assert_eq!(adapters.len(), 1);
+        assert_eq!(adapters[0].interface_index, 12);
+        assert_eq!(adapters[0].ipv4_servers, vec!["1.1.1.1"]);
+        assert!(adapters[0].ipv6_servers.is_empty());
+        assert!(!adapters[0].ipv4_dhcp);
+        assert_eq!(adapters[0].provider.as_deref(), Some("cloudflare"));
+
+        let array = r#"[
+            {"Index":12,"Alias":"Ethernet","Status":"Up","IPv4":["192.168.1.1"],"IPv6":["fe80::1"]},
+            {"Index":7,"Alias":"Wi-Fi","Description":"Wireless","Status":"Disconnected","IPv4":null},
+            {"Alias":"No index"}
+        ]"#;
+        let adapters = parse_adapters(array).unwrap();
+        assert_eq!(adapters.len(), 2);
+        assert_eq!(adapters[0].ipv6_servers, vec!["fe80::1"]);
+        assert_eq!(adapters[0].provider, None);
+        assert!(adapters[0].ipv4_dhcp && adapters[0].ipv6_dhcp);
+        assert_eq!(adapters[1].alias, "Wi-Fi");
+        assert!(adapters[1].ipv4_servers.is_empty());
+
+        assert!(parse_adapters("").unwrap().is_empty());
+        assert!(parse_adapters("null").unwrap().is_empty());
+        assert!(parse_adapters("{not json").is_err());
+    }
+
+    #[test]
+    fn apply_script_quotes_every_address() {
+        let servers = parse_servers(
+            &strings(&["1.1.1.1", "1.0.0.1"]),
+            &strings(&["2606:4700:4700::1111"]),
+        )
+        .unwrap();
+        let script = apply_script(&[3, 7], &servers);
+        assert!(script.contains(
+            "-ServerAddresses @('1.1.1.1','1.0.0.1','2606:4700:4700::1111') -ErrorAction Stop"
+        ));
+        assert!(script.contains("Get-NetAdapter -InterfaceIndex 3,7 -ErrorAction SilentlyContinue"));
+        assert!(script.contains("-ResetServerAddresses"));
+        assert!(apply_script(&[], &servers).contains("Where-Object { $_.Status -eq 'Up' }"));
+    }
+
+    #[test]
+    fn doh_global_entry_is_mode_neutral() {
+        let auto = apply_doh_script(&[12], &cloudflare(), DohMode::Auto);
+        let required = apply_doh_script(&[12], &cloudflare(), DohMode::Required);
+        for script in [&auto, &required] {
+            assert!(!script.contains("AllowFallbackToUdp"));
+            assert!(!script.contains("AutoUpgrade"));
+            assert!(script.contains("Add-DnsClientDohServerAddress -ServerAddress '1.1.1.1' -DohTemplate 'https://cloudflare-dns.com/dns-query' -ErrorAction Stop"));
+        }
+        assert_eq!(auto.split("\n").next(), required.split("\n").next());
+        assert!(auto.contains("\\Doh\\1.1.1.1\" -Name DohFlags -PropertyType QWord -Value 17 "));
+        assert!(required.contains(
+            "\\Doh6\\2606:4700:4700::1111\" -Name DohFlags -PropertyType QWord -Value 1 "
+        ));
+    }
+
+    #[test]
+    fn doh_off_removes_interface_flags_only() {
+        let off = apply_doh_script(&[12], &cloudflare(), DohMode::Off);
+        assert!(!off.contains("DnsClientDohServerAddress"));
+        assert!(off.contains("Remove-Item \"HKLM:\\SYSTEM\\CurrentControlSet\\Services\\Dnscache\\InterfaceSpecificParameters\\$($a.InterfaceGuid)\\DohInterfaceSettings\\Doh\\1.1.1.1\" -Recurse"));
+        assert_eq!(DohMode::from_flags(DohMode::Auto.flags()), DohMode::Auto);
+        assert_eq!(
+            DohMode::from_flags(DohMode::Required.flags()),
+            DohMode::Required
+        );
+        assert_eq!(DohMode::from_flags(None), DohMode::Off);
+    }
+}
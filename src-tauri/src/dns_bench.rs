@@ -0,0 +1,685 @@
+use serde::{Deserialize, Serialize};
+use std::net::{IpAddr, SocketAddr};
+use std::time::{Duration, Instant};
+use tokio::net::UdpSocket;
+
+use crate::dns;
+
+const DEFAULT_DOMAINS: &[&str] = &[
+    "google.com",
+    "microsoft.com",
+    "wikipedia.org",
+    "github.com",
+    "amazon.com",
+    "cloudflare.com",
+    "apple.com",
+    "netflix.com",
+];
+const MAX_DOMAINS: usize = 50;
+const MAX_ROUNDS: usize = 10;
+const MAX_EXTRA_SERVERS: usize = 8;
+
+pub const TYPE_A: u16 = 1;
+pub const TYPE_AAAA: u16 = 28;
+pub const TYPE_PTR: u16 = 12;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct BenchConfig {
+    #[serde(default)]
+    pub domains: Vec<String>,
+    #[serde(default)]
+    pub providers: Vec<String>,
+    #[serde(default)]
+    pub extra_servers: Vec<String>,
+    #[serde(default = "default_rounds")]
+    pub rounds: usize,
+    #[serde(default = "default_timeout_ms")]
+    pub timeout_ms: u64,
+    #[serde(default)]
+    pub include_doh: bool,
+    #[serde(default)]
+    pub include_ipv6: bool,
+}
+
+impl Default for BenchConfig {
+    fn default() -> Self {
+        BenchConfig {
+            domains: Vec::new(),
+            providers: Vec::new(),
+            extra_servers: Vec::new(),
+            rounds: default_rounds(),
+            timeout_ms: default_timeout_ms(),
+            include_doh: false,
+            include_ipv6: false,
+        }
+    }
+}
+
+fn default_rounds() -> usize {
+    3
+}
+
+fn default_timeout_ms() -> u64 {
+    2000
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DnsAnswer {
+    pub rcode: u8,
+    pub truncated: bool,
+    pub addresses: Vec<IpAddr>,
+    pub names: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub enum Transport {
+    Udp(SocketAddr),
+    Doh(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct Target {
+    pub provider: String,
+    pub name: String,
+    pub transport: Transport,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct LatencyStats {
+    pub samples: usize,
+    pub failures: usize,
+    pub median_ms: Option<f64>,
+    pub p95_ms: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ResolverResult {
+    pub rank: usize,
+    pub provider: String,
+    pub name: String,
+    pub server: String,
+    pub protocol: String,
+    pub cached: LatencyStats,
+    pub uncached: LatencyStats,
+    pub failure_rate: f64,
+    pub score: Option<f64>,
+}
+
+fn encode_name(name: &str, out: &mut Vec<u8>) -> Result<(), String> {
+    for label in name.trim_end_matches('.').split('.') {
+        if label.is_empty() || label.len() > 63 {
+            return Err(format!("Invalid domain name: {}", name));
+        }
+        out.push(label.len() as u8);
+        out.extend_from_slice(label.as_bytes());
+    }
+    out.push(0);
+    Ok(())
+}
+
+pub fn build_query(id: u16, name: &str, qtype: u16) -> Result<Vec<u8>, String> {
+    let mut out = Vec::with_capacity(32 + name.len());
+    out.extend_from_slice(&id.to_be_bytes());
+    out.extend_from_slice(&0x0100u16.to_be_bytes()); // standard query, recursion desired
+    out.extend_from_slice(&1u16.to_be_bytes());
+    out.extend_from_slice(&[0, 0, 0, 0, 0, 0]);
+    encode_name(name, &mut out)?;
+    out.extend_from_slice(&qtype.to_be_bytes());
+    out.extend_from_slice(&1u16.to_be_bytes());
+    Ok(out)
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> Result<u16, String> {
+    bytes
+        .get(offset..offset + 2)
+        .map(|b| u16::from_be_bytes([b[0], b[1]]))
+        .ok_or_else(|| "Truncated DNS message".to_string())
+}
+
+// Returns the offset just past a (possibly compressed) name.
+fn skip_name(bytes: &[u8], mut offset: usize) -> Result<usize, String> {
+    loop {
+        let len = *bytes
+            .get(offset)
+            .ok_or_else(|| "Truncated DNS name".to_string())?;
+        match len {
+            0 => return Ok(offset + 1),
+            l if l & 0xC0 == 0xC0 => return Ok(offset + 2),
+            l if l & 0xC0 == 0 => offset += 1 + l as usize,
+            _ => return Err("Unsupported DNS label type".to_string()),
+        }
+    }
+}
+
+fn read_name(bytes: &[u8], mut offset: usize) -> Result<String, String> {
+    let mut labels = Vec::new();
+    // Bounded so a compression loop cannot spin forever.
+    for _ in 0..128 {
+        let len = *bytes
+            .get(offset)
+            .ok_or_else(|| "Truncated DNS name".to_string())?;
+        match len {
+            0 => return Ok(labels.join(".")),
+            l if l & 0xC0 == 0xC0 => offset = (read_u16(bytes, offset)? & 0x3FFF) as usize,
+            l if l & 0xC0 == 0 => {
+                let label = bytes
+                    .get(offset + 1..offset + 1 + l as usize)
+                    .ok_or_else(|| "Truncated DNS name".to_string())?;
+                labels.push(String::from_utf8_lossy(label).to_string());
+                offset += 1 + l as usize;
+            }
+            _ => return Err("Unsupported DNS label type".to_string()),
+        }
+    }
+    Err("DNS name compression loop".to_string())
+}
+
+pub fn parse_response(bytes: &[u8], expected_id: u16) -> Result<DnsAnswer, String> {
+    if bytes.len() < 12 {
+        return Err("DNS response too short".to_string());
+    }
+    if read_u16(bytes, 0)? != expected_id {
+        return Err("DNS response id mismatch".to_string());
+    }
+    let flags = read_u16(bytes, 2)?;
+    if flags & 0x8000 == 0 {
+        return Err("DNS message is not a response".to_string());
+    }
+    let qdcount = read_u16(bytes, 4)?;
+    let ancount = read_u16(bytes, 6)?;
+
+    let mut offset = 12;
+    for _ in 0..qdcount {
+        offset = skip_name(bytes, offset)? + 4;
+    }
+    let mut addresses = Vec::new();
+    let mut names = Vec::new();
+    for _ in 0..ancount {
+        offset = skip_name(bytes, offset)?;
+        let rtype = read_u16(bytes, offset)?;
+        let rdlen = read_u16(bytes, offset + 8)? as usize;
+        let start = offset + 10;
+        let data = bytes
+            .get(start..start + rdlen)
+            .ok_or_else(|| "Truncated DNS record".to_string())?;
+        match (rtype, rdlen) {
+            (TYPE_A, 4) => addresses.push(IpAddr::from([data[0], data[1], data[2], data[3]])),
+            (TYPE_AAAA, 16) => {
+                let mut raw = [0u8; 16];
+                raw.copy_from_slice(data);
+                addresses.push(IpAddr::from(raw));
+            }
+            (TYPE_PTR, _) => names.push(read_name(bytes, start)?),
+            _ => {}
+        }
+        offset = start + rdlen;
+    }
+    Ok(DnsAnswer {
+        rcode: (flags & 0x000F) as u8,
+        truncated: flags & 0x0200 != 0,
+        addresses,
+        names,
+    })
+}
+
+// NOERROR and NXDOMAIN are both real answers from the resolver; anything
+// else (SERVFAIL, REFUSED, ...) counts as a failure.
+pub fn is_answer(answer: &DnsAnswer) -> bool {
+    answer.rcode == 0 || answer.rcode == 3
+}
+
+pub async fn query_udp(
+    server: SocketAddr,
+    name: &str,
+    qtype: u16,
+    timeout: Duration,
+) -> Result<(Duration, DnsAnswer), String> {
+    let id: u16 = rand::random();
+    let query = build_query(id, name, qtype)?;
+    let bind: SocketAddr = if server.is_ipv6() {
+        "[::]:0".parse().unwrap()
+    } else {
+        "0.0.0.0:0".parse().unwrap()
+    };
+    let socket = UdpSocket::bind(bind)
+        .await
+        .map_err(|e| format!("Failed to open UDP socket: {}", e))?;
+    socket
+        .connect(server)
+        .await
+        .map_err(|e| format!("Failed to reach {}: {}", server, e))?;
+
+    let started = Instant::now();
+    socket
+        .send(&query)
+        .await
+        .map_err(|e| format!("Failed to send DNS query: {}", e))?;
+    let mut buf = [0u8; 4096];
+    let deadline = started + timeout;
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        let len = tokio::time::timeout(remaining, socket.recv(&mut buf))
+            .await
+            .map_err(|_| "DNS query timed out".to_string())?
+            .map_err(|e| format!("DNS receive failed: {}", e))?;
+        // Stray datagrams with another id are ignored rather than failing.
+        match parse_response(&buf[..len], id) {
+            Ok(answer) => return Ok((started.elapsed(), answer)),
+            Err(e) if e.contains("id mismatch") => continue,
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+pub async fn query_doh(
+    client: &reqwest::Client,
+    template: &str,
+    name: &str,
+    qtype: u16,
+) -> Result<(Duration, DnsAnswer), String> {
+    // RFC 8484 recommends id 0 so responses stay cacheable by HTTP caches.
+    let query = build_query(0, name, qtype)?;
+    let started = Instant::now();
+    let response = client
+        .post(template)
+        .header(reqwest::header::CONTENT_TYPE, "application/dns-message")
+        .header(reqwest::header::ACCEPT, "application/dns-message")
+        .body(query)
+        .send()
+        .await
+        .map_err(|e| format!("DoH request failed: {}", e))?;
+    if !response.status().is_success() {
+        return Err(format!("DoH server returned HTTP {}", response.status()));
+    }
+    let body = response
+        .bytes()
+        .await
+        .map_err(|e| format!("DoH response failed: {}", e))?;
+    let elapsed = started.elapsed();
+    Ok((elapsed, parse_response(&body, 0)?))
+}
+
+pub fn stats(samples: &[Option<f64>]) -> LatencyStats {
+    let mut ok: Vec<f64> = samples.iter().flatten().copied().collect();
+    ok.sort_by(|a, b| a.total_cmp(b));
+    let percentile = |p: f64| -> Option<f64> {
+        if ok.is_empty() {
+            return None;
+        }
+        let index = ((p * ok.len() as f64).ceil() as usize).clamp(1, ok.len()) - 1;
+        Some((ok[index] * 100.0).round() / 100.0)
+    };
+    LatencyStats {
+        samples: samples.len(),
+        failures: samples.len() - ok.len(),
+        median_ms: percentile(0.5),
+        p95_ms: percentile(0.95),
+    }
+}
+
+fn parse_server(raw: &str) -> Result<SocketAddr, String> {
+    let raw = raw.trim();
+    raw.parse::<SocketAddr>()
+        .or_else(|_| raw.parse::<IpAddr>().map(|ip| SocketAddr::new(ip, 53)))
+        .map_err(|_| format!("Invalid DNS server: {}", raw))
+}
+
+pub fn targets(config: &BenchConfig) -> Result<Vec<Target>, String> {
+    let wanted: Vec<String> = config
+        .providers
+        .iter()
+        .map(|p| p.trim().to_lowercase())
+        .collect();
+    let mut targets = Vec::new();
+    for provider in dns::providers()? {
+        if !wanted.is_empty() && !wanted.contains(&provider.id) {
+            continue;
+        }
+        let families = provider
+            .ipv4
+            .first()
+            .into_iter()
+            .chain(provider.ipv6.first().filter(|_| config.include_ipv6));
+        for ip in families {
+            targets.push(Target {
+                provider: provider.id.clone(),
+                name: provider.name.clone(),
+                transport: Transport::Udp(SocketAddr::new(*ip, 53)),
+            });
+        }
+        if let (true, Some(template)) = (config.include_doh, &provider.doh_template) {
+            targets.push(Target {
+                provider: provider.id.clone(),
+                name: provider.name.clone(),
+                transport: Transport::Doh(template.clone()),
+            });
+        }
+    }
+    if config.extra_servers.len() > MAX_EXTRA_SERVERS {
+        return Err(format!(
+            "At most {} custom servers can be benchmarked",
+            MAX_EXTRA_SERVERS
+        ));
+    }
+    for raw in &config.extra_servers {
+        let server = parse_server(raw)?;
+        targets.push(Target {
+            provider: "custom".to_string(),
+            name: server.to_string(),
+            transport: Transport::Udp(server),
+        });
+    }
+    if targets.is_empty() {
+        return Err("No DNS resolvers selected".to_string());
+    }
+    Ok(targets)
+}
+
+pub fn domains(config: &BenchConfig) -> Result<Vec<String>, String> {
+    let mut domains: Vec<String> = if config.domains.is_empty() {
+        DEFAULT_DOMAINS.iter().map(|d| d.to_string()).collect()
+    } else {
+        config
+            .domains
+            .iter()
+            .map(|d| d.trim().trim_end_matches('.').to_lowercase())
+            .filter(|d| !d.is_empty())
+            .collect()
+    };
+    let mut seen = std::collections::HashSet::new();
+    domains.retain(|d| seen.insert(d.clone()));
+    if domains.len() > MAX_DOMAINS {
+        return Err(format!(
+            "At most {} test domains are supported",
+            MAX_DOMAINS
+        ));
+    }
+    if let Some(bad) = domains.iter().find(|d| !crate::hosts::is_valid_hostname(d)) {
+        return Err(format!("Invalid test domain: {}", bad));
+    }
+    Ok(domains)
+}
+
+fn random_label() -> String {
+    format!("cu{:016x}", rand::random::<u64>())
+}
+
+async fn measure(
+    target: &Target,
+    client: &reqwest::Client,
+    name: &str,
+    timeout: Duration,
+) -> Option<f64> {
+    let result = match &target.transport {
+        Transport::Udp(server) => query_udp(*server, name, TYPE_A, timeout).await,
+        Transport::Doh(template) => {
+            tokio::time::timeout(timeout, query_doh(client, template, name, TYPE_A))
+                .await
+                .unwrap_or_else(|_| Err("DoH query timed out".to_string()))
+        }
+    };
+    match result {
+        Ok((elapsed, answer)) if is_answer(&answer) => Some(elapsed.as_secs_f64() * 1000.0),
+        _ => None,
+    }
+}
+
+// Each domain is primed once, then queried again per round: the repeat hits
+// the resolver cache, while a random subdomain forces a full recursive lookup.
+async fn bench_target(
+    target: Target,
+    domains: Vec<String>,
+    rounds: usize,
+    timeout: Duration,
+) -> (Target, Vec<Option<f64>>, Vec<Option<f64>>) {
+    let client = reqwest::Client::builder()
+        .timeout(timeout)
+        .build()
+        .unwrap_or_default();
+    let mut cached = Vec::new();
+    let mut uncached = Vec::new();
+    for domain in &domains {
+        let _ = measure(&target, &client, domain, timeout).await;
+    }
+    for _ in 0..rounds {
+        for domain in &domains {
+            cached.push(measure(&target, &client, domain, timeout).await);
+            let cold = format!("{}.{}", random_label(), domain);
+            uncached.push(measure(&target, &client, &cold, timeout).await);
+        }
+    }
+    (target, cached, uncached)
+}
+
+pub fn rank(mut results: Vec<ResolverResult>) -> Vec<ResolverResult> {
+    for result in results.iter_mut() {
+        let total = result.cached.samples + result.uncached.samples;
+        let failures = result.cached.failures + result.uncached.failures;
+        result.failure_rate = if total == 0 {
+            1.0
+        } else {
+            failures as f64 / total as f64
+        };
+        // Cached latency dominates day-to-day browsing; every percent of
+        // failed queries costs as much as 10 ms of extra latency.
+        result.score = match (result.cached.median_ms, result.uncached.median_ms) {
+            (Some(cached), Some(uncached)) => Some(
+                ((cached * 0.7 + uncached * 0.3 + result.failure_rate * 1000.0) * 100.0).round()
+                    / 100.0,
+            ),
+            _ => None,
+        };
+    }
+    results.sort_by(|a, b| match (a.score, b.score) {
+        (Some(x), Some(y)) => x.total_cmp(&y),
+        (Some(_), None) => std::cmp::Ordering::Less,
+        (None, Some(_)) => std::cmp::Ordering::Greater,
+        (None, None) => a.name.cmp(&b.name),
+    });
+    for (i, result) in results.iter_mut().enumerate() {
+        result.rank = i + 1;
+    }
+    results
+}
+
+pub async fn run(config: BenchConfig) -> Result<Vec<ResolverResult>, String> {
+    let domains = domains(&config)?;
+    let targets = targets(&config)?;
+    let rounds = config.rounds.clamp(1, MAX_ROUNDS);
+    let timeout = Duration::from_millis(config.timeout_ms.clamp(200, 10_000));
+
+    let mut tasks = tokio::task::JoinSet::new();
+    for target in targets {
+        tasks.spawn(bench_target(target, domains.clone(), rounds, timeout));
+    }
+    let mut results = Vec::new();
+    while let Some(joined) = tasks.join_next().await {
+        let (target, cached, uncached) =
+            joined.map_err(|e| format!("DNS benchmark task failed: {}", e))?;
+        let (server, protocol) = match &target.transport {
+            Transport::Udp(addr) => (addr.to_string(), "udp"),
+            Transport::Doh(template) => (template.clone(), "doh"),
+        };
+        results.push(ResolverResult {
+            rank: 0,
+            provider: target.provider,
+            name: target.name,
+            server,
+            protocol: protocol.to_string(),
+            cached: stats(&cached),
+            uncached: stats(&uncached),
+            failure_rate: 0.0,
+            score: None,
+        });
+    }
+    Ok(rank(results))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(out: &mut Vec<u8>, name: &[u8], rtype: u16, rdata: &[u8]) {
+        out.extend_from_slice(name);
+        out.extend_from_slice(&rtype.to_be_bytes());
+        out.extend_from_slice(&[0, 1, 0, 0, 0x0e, 0x10]);
+        out.extend_from_slice(&(rdata.len() as u16).to_be_bytes());
+        out.extend_from_slice(rdata);
+    }
+
+    // Answers a query the way a recursive resolver would, with every owner
+    // name compressed to a pointer at the question (offset 12).
+    fn response(query: &[u8], flags: u16) -> Vec<u8> {
+        let mut out = query.to_vec();
+        out[2..4].copy_from_slice(&flags.to_be_bytes());
+        out[6..8].copy_from_slice(&4u16.to_be_bytes());
+        record(&mut out, &[0xC0, 12], TYPE_A, &[93, 184, 215, 14]);
+        let mut v6 = [0u8; 16];
+        v6[..4].copy_from_slice(&[0x26, 0x06, 0x28, 0x00]);
+        v6[15] = 1;
+        record(&mut out, &[0xC0, 12], TYPE_AAAA, &v6);
+        // CNAME records are skipped, not decoded.
+        record(&mut out, &[0xC0, 12], 5, &[3, b'w', b'w', b'w', 0xC0, 12]);
+        // PTR target "host" followed by a pointer to "example.com".
+        record(
+            &mut out,
+            &[0xC0, 12],
+            TYPE_PTR,
+            &[4, b'h', b'o', b's', b't', 0xC0, 12],
+        );
+        out
+    }
+
+    #[test]
+    fn build_query_encodes_header_and_question() {
+        let query = build_query(0xBEEF, "example.com.", TYPE_AAAA).unwrap();
+        assert_eq!(
+            query,
+            [
+                &[0xBE, 0xEF, 0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0][..],
+                b"\x07example\x03com\x00",
+                &[0, 28, 0, 1],
+            ]
+            .concat()
+        );
+        assert!(build_query(1, "a..b", TYPE_A).is_err());
+        assert!(build_query(1, "", TYPE_A).is_err());
+        assert!(build_query(1, &format!("{}.com", "a".repeat(64)), TYPE_A).is_err());
+    }
+
+    #[test]
+    fn parse_response_follows_compression_pointers() {
+        let query = build_query(7, "example.com", TYPE_A).unwrap();
+        let answer = parse_response(&response(&query, 0x8180), 7).unwrap();
+        assert_eq!(answer.rcode, 0);
+        assert!(!answer.truncated);
+        assert_eq!(
+            answer.addresses,
+            vec![
+                "93.184.215.14".parse::<IpAddr>().unwrap(),
+                "2606:2800::1".parse::<IpAddr>().unwrap(),
+            ]
+        );
+        assert_eq!(answer.names, vec!["host.example.com"]);
+        assert!(is_answer(&answer));
+    }
+
+    #[test]
+    fn parse_response_reports_flags_and_rcode() {
+        let query = build_query(7, "example.com", TYPE_A).unwrap();
+        // TC bit set, NXDOMAIN.
+        let mut bytes = query.clone();
+        bytes[2..4].copy_from_slice(&0x8383u16.to_be_bytes());
+        let answer = parse_response(&bytes, 7).unwrap();
+        assert!(answer.truncated);
+        assert_eq!(answer.rcode, 3);
+        assert!(is_answer(&answer));
+        // SERVFAIL.
+        bytes[2..4].copy_from_slice(&0x8182u16.to_be_bytes());
+        assert!(!is_answer(&parse_response(&bytes, 7).unwrap()));
+    }
+
+    #[test]
+    fn parse_response_rejects_malformed_messages() {
+        let query = build_query(7, "example.com", TYPE_A).unwrap();
+        let full = response(&query, 0x8180);
+        assert_eq!(
+            parse_response(&full[..8], 7).unwrap_err(),
+            "DNS response too short"
+        );
+        assert_eq!(
+            parse_response(&full, 8).unwrap_err(),
+            "DNS response id mismatch"
+        );
+        assert_eq!(
+            parse_response(&query, 7).unwrap_err(),
+            "DNS message is not a response"
+        );
+        for cut in [full.len() - 3, query.len() + 5, query.len() - 2] {
+            let err = parse_response(&full[..cut], 7).unwrap_err();
+            assert!(err.starts_with("Truncated DNS"), "{}: {}", cut, err);
+        }
+
+        // A PTR whose target points at itself must not loop forever.
+        let mut looped = query.clone();
+        looped[2..4].copy_from_slice(&0x8180u16.to_be_bytes());
+        looped[6..8].copy_from_slice(&1u16.to_be_bytes());
+        let rdata_at = (looped.len() + 12) as u16;
+        record(
+            &mut looped,
+            &[0xC0, 12],
+            TYPE_PTR,
+            &(0xC000 | rdata_at).to_be_bytes(),
+        );
+        assert_eq!(
+            parse_response(&looped, 7).unwrap_err(),
+            "DNS name compression loop"
+        );
+    }
+
+    #[tokio::test]
+    async fn query_udp_against_local_stub() {
+        let stub = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let server = stub.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut buf = [0u8; 512];
+            let (len, peer) = stub.recv_from(&mut buf).await.unwrap();
+            let query = buf[..len].to_vec();
+            // A stray reply with another id is ignored, not treated as failure.
+            let mut stray = response(&query, 0x8180);
+            stray[0] ^= 0xFF;
+            stub.send_to(&stray, peer).await.unwrap();
+            stub.send_to(&response(&query, 0x8180), peer).await.unwrap();
+        });
+
+        let (elapsed, answer) = query_udp(server, "example.com", TYPE_A, Duration::from_secs(2))
+            .await
+            .unwrap();
+        assert!(elapsed < Duration::from_secs(2));
+        assert_eq!(answer.addresses.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn query_udp_times_out_on_silent_server() {
+        let silent = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let result = query_udp(
+            silent.local_addr().unwrap(),
+            "example.com",
+            TYPE_A,
+            Duration::from_millis(100),
+        )
+        .await;
+        assert_eq!(result.unwrap_err(), "DNS query timed out");
+    }
+
+    #[test]
+    fn stats_use_nearest_rank_percentiles() {
+        let samples = [Some(10.0), None, Some(30.0), Some(20.0), Some(40.0)];
+        let stats = stats(&samples);
+        assert_eq!(stats.samples, 5);
+        assert_eq!(stats.failures, 1);
+        assert_eq!(stats.median_ms, Some(20.0));
+        assert_eq!(stats.p95_ms, Some(40.0));
+        assert_eq!(super::stats(&[None]).median_ms, None);
+    }
+}
@@ -0,0 +1,803 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::net::IpAddr;
+
+use crate::util::ps_quote;
+
+pub const CONFUTILS_GROUP: &str = "ConfUtils";
+
+const MAX_NAME_LEN: usize = 255;
+const MAX_DESCRIPTION_LEN: usize = 1024;
+const MAX_LIST_ITEMS: usize = 1000;
+
+const ADDRESS_KEYWORDS: &[&str] = &[
+    "Any",
+    "LocalSubnet",
+    "LocalSubnet4",
+    "LocalSubnet6",
+    "DNS",
+    "DHCP",
+    "WINS",
+    "DefaultGateway",
+    "Internet",
+    "Intranet",
+    "IntranetRemoteAccess",
+    "PlayToDevice",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Direction {
+    Inbound,
+    Outbound,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RuleAction {
+    Allow,
+    Block,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Protocol {
+    Any,
+    Tcp,
+    Udp,
+    Icmpv4,
+    Icmpv6,
+    Number(u8),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Profile {
+    Domain,
+    Private,
+    Public,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct FirewallRule {
+    pub name: String,
+    pub display_name: String,
+    pub description: Option<String>,
+    pub group: Option<String>,
+    pub enabled: bool,
+    pub direction: Direction,
+    pub action: RuleAction,
+    pub program: Option<String>,
+    pub protocol: Protocol,
+    pub local_ports: Vec<String>,
+    pub remote_ports: Vec<String>,
+    pub remote_addresses: Vec<String>,
+    pub profiles: Vec<Profile>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct RuleSpec {
+    pub display_name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    pub direction: Direction,
+    pub action: RuleAction,
+    #[serde(default)]
+    pub program: Option<String>,
+    #[serde(default = "default_protocol")]
+    pub protocol: Protocol,
+    #[serde(default)]
+    pub local_ports: Vec<String>,
+    #[serde(default)]
+    pub remote_ports: Vec<String>,
+    #[serde(default)]
+    pub remote_addresses: Vec<String>,
+    #[serde(default)]
+    pub profiles: Vec<Profile>,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+fn default_protocol() -> Protocol {
+    Protocol::Any
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+impl Direction {
+    fn parse(raw: &str) -> Option<Self> {
+        match raw.trim().to_lowercase().as_str() {
+            "inbound" | "1" => Some(Direction::Inbound),
+            "outbound" | "2" => Some(Direction::Outbound),
+            _ => None,
+        }
+    }
+
+    fn ps_name(&self) -> &'static str {
+        match self {
+            Direction::Inbound => "Inbound",
+            Direction::Outbound => "Outbound",
+        }
+    }
+}
+
+impl RuleAction {
+    fn parse(raw: &str) -> Option<Self> {
+        match raw.trim().to_lowercase().as_str() {
+            "allow" | "2" => Some(RuleAction::Allow),
+            "block" | "4" => Some(RuleAction::Block),
+            _ => None,
+        }
+    }
+
+    fn ps_name(&self) -> &'static str {
+        match self {
+            RuleAction::Allow => "Allow",
+            RuleAction::Block => "Block",
+        }
+    }
+}
+
+impl Protocol {
+    pub fn parse(raw: &str) -> Option<Self> {
+        match raw.trim().to_lowercase().as_str() {
+            "" | "any" => Some(Protocol::Any),
+            "tcp" | "6" => Some(Protocol::Tcp),
+            "udp" | "17" => Some(Protocol::Udp),
+            "icmpv4" | "1" => Some(Protocol::Icmpv4),
+            "icmpv6" | "58" => Some(Protocol::Icmpv6),
+            other => other.parse().ok().map(Protocol::Number),
+        }
+    }
+
+    fn ps_value(&self) -> String {
+        match self {
+            Protocol::Any => "Any".to_string(),
+            Protocol::Tcp => "TCP".to_string(),
+            Protocol::Udp => "UDP".to_string(),
+            Protocol::Icmpv4 => "ICMPv4".to_string(),
+            Protocol::Icmpv6 => "ICMPv6".to_string(),
+            Protocol::Number(n) => n.to_string(),
+        }
+    }
+
+    fn has_ports(&self) -> bool {
+        matches!(self, Protocol::Tcp | Protocol::Udp)
+    }
+}
+
+impl Profile {
+    fn ps_name(&self) -> &'static str {
+        match self {
+            Profile::Domain => "Domain",
+            Profile::Private => "Private",
+            Profile::Public => "Public",
+        }
+    }
+}
+
+fn parse_profiles(raw: &str) -> Vec<Profile> {
+    let mut profiles = Vec::new();
+    for part in raw
+        .split([',', ' '])
+        .map(str::trim)
+        .filter(|p| !p.is_empty())
+    {
+        let profile = match part.to_lowercase().as_str() {
+            "domain" => Profile::Domain,
+            "private" => Profile::Private,
+            "public" => Profile::Public,
+            _ => continue,
+        };
+        if !profiles.contains(&profile) {
+            profiles.push(profile);
+        }
+    }
+    // "Any" and an explicit list of all three are the same scope.
+    if profiles.len() == 3 {
+        profiles.clear();
+    }
+    profiles
+}
+
+fn value_str(obj: &Value, name: &str) -> Option<String> {
+    match obj.get(name)? {
+        Value::String(s) if !s.trim().is_empty() => Some(s.trim().to_string()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+fn value_list(obj: &Value, name: &str) -> Vec<String> {
+    let items: Vec<String> = match obj.get(name) {
+        Some(Value::Array(items)) => items
+            .iter()
+            .filter_map(|v| match v {
+                Value::String(s) => Some(s.trim().to_string()),
+                Value::Number(n) => Some(n.to_string()),
+                _ => None,
+            })
+            .collect(),
+        Some(_) => value_str(obj, name).into_iter().collect(),
+        None => Vec::new(),
+    };
+    items
+        .into_iter()
+        .filter(|s| !s.is_empty() && !s.eq_ignore_ascii_case("any"))
+        .collect()
+}
+
+pub fn parse_rule(obj: &Value) -> Option<FirewallRule> {
+    Some(FirewallRule {
+        name: value_str(obj, "Name")?,
+        display_name: value_str(obj, "DisplayName").unwrap_or_default(),
+        description: value_str(obj, "Description"),
+        group: value_str(obj, "Group"),
+        enabled: value_str(obj, "Enabled")
+            .is_some_and(|v| v.eq_ignore_ascii_case("true") || v == "1"),
+        direction: Direction::parse(&value_str(obj, "Direction")?)?,
+        action: RuleAction::parse(&value_str(obj, "Action")?)?,
+        program: value_str(obj, "Program").filter(|p| !p.eq_ignore_ascii_case("any")),
+        protocol: Protocol::parse(&value_str(obj, "Protocol").unwrap_or_default())
+            .unwrap_or(Protocol::Any),
+        local_ports: value_list(obj, "LocalPort"),
+        remote_ports: value_list(obj, "RemotePort"),
+        remote_addresses: value_list(obj, "RemoteAddress"),
+        profiles: parse_profiles(&value_str(obj, "Profile").unwrap_or_default()),
+    })
+}
+
+pub fn parse_rules(raw: &str) -> Result<Vec<FirewallRule>, String> {
+    let trimmed = raw.trim();
+    if trimmed.is_empty() || trimmed == "null" {
+        return Ok(Vec::new());
+    }
+    let items = match serde_json::from_str::<Value>(trimmed)
+        .map_err(|e| format!("Failed to parse firewall rules: {}", e))?
+    {
+        Value::Array(items) => items,
+        Value::Null => Vec::new(),
+        single => vec![single],
+    };
+    let mut rules: Vec<FirewallRule> = items.iter().filter_map(parse_rule).collect();
+    rules.sort_by_key(|r| r.display_name.to_lowercase());
+    Ok(rules)
+}
+
+fn check_text(label: &str, value: &str, max: usize) -> Result<(), String> {
+    if value.chars().count() > max {
+        return Err(format!("{} is too long (max {} characters)", label, max));
+    }
+    if value.chars().any(|c| c.is_control()) {
+        return Err(format!("{} contains invalid characters", label));
+    }
+    Ok(())
+}
+
+pub fn validate_rule_name(name: &str) -> Result<&str, String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("Firewall rule name is required".to_string());
+    }
+    check_text("Firewall rule name", name, MAX_NAME_LEN)?;
+    Ok(name)
+}
+
+fn validate_port(raw: &str) -> Result<(), String> {
+    let parse = |s: &str| -> Result<u16, String> {
+        s.trim()
+            .parse::<u16>()
+            .ok()
+            .filter(|p| *p > 0)
+            .ok_or_else(|| format!("Invalid port: {}", raw))
+    };
+    match raw.split_once('-') {
+        Some((start, end)) if parse(start)? <= parse(end)? => Ok(()),
+        Some(_) => Err(format!("Invalid port range: {}", raw)),
+        None => parse(raw).map(|_| ()),
+    }
+}
+
+fn validate_address(raw: &str) -> Result<(), String> {
+    if ADDRESS_KEYWORDS.iter().any(|k| k.eq_ignore_ascii_case(raw)) {
+        return Ok(());
+    }
+    if let Some((ip, prefix)) = raw.split_once('/') {
+        let ip: IpAddr = ip
+            .parse()
+            .map_err(|_| format!("Invalid remote address: {}", raw))?;
+        let max = if ip.is_ipv4() { 32 } else { 128 };
+        // Windows also accepts a dotted subnet mask after the slash.
+        let valid_mask = prefix.parse::<u8>().is_ok_and(|p| p <= max)
+            || (ip.is_ipv4() && prefix.parse::<std::net::Ipv4Addr>().is_ok());
+        return if valid_mask {
+            Ok(())
+        } else {
+            Err(format!("Invalid remote address: {}", raw))
+        };
+    }
+    if let Some((start, end)) = raw.split_once('-') {
+        return match (start.trim().parse::<IpAddr>(), end.trim().parse::<IpAddr>()) {
+            (Ok(a), Ok(b)) if a.is_ipv4() == b.is_ipv4() && a <= b => Ok(()),
+            _ => Err(format!("Invalid remote address range: {}", raw)),
+        };
+    }
+    raw.parse::<IpAddr>()
+        .map(|_| ())
+        .map_err(|_| format!("Invalid remote address: {}", raw))
+}
+
+fn validate_program(path: &str) -> Result<(), String> {
+    check_text("Program path", path, 260)?;
+    if path.contains('"') {
+        return Err("Program path must not contain quotes".to_string());
+    }
+    let bytes = path.as_bytes();
+    let absolute = (bytes.len() > 2 && bytes[0].is_ascii_alphabetic() && &bytes[1..3] == b":\\")
+        || path.starts_with("\\\\")
+        || path.starts_with('%');
+    if !absolute {
+        return Err(format!("Program path must be absolute: {}", path));
+    }
+    Ok(())
+}
+
+fn clean_list(items: &[String]) -> Vec<String> {
+    items
+        .iter()
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty() && !s.eq_ignore_ascii_case("any"))
+        .collect()
+}
+
+pub fn validate(spec: &RuleSpec) -> Result<RuleSpec, String> {
+    let display_name = spec.display_name.trim().to_string();
+    if display_name.is_empty() {
+        return Err("Firewall rule display name is required".to_string());
+    }
+    check_text("Display name", &display_name, MAX_NAME_LEN)?;
+
+    let description = spec
+        .description
+        .as_deref()
+        .map(str::trim)
+        .filter(|d| !d.is_empty())
+        .map(|d| check_text("Description", d, MAX_DESCRIPTION_LEN).map(|_| d.to_string()))
+        .transpose()?;
+    let program = spec
+        .program
+        .as_deref()
+        .map(str::trim)
+        .filter(|p| !p.is_empty() && !p.eq_ignore_ascii_case("any"))
+        .map(|p| validate_program(p).map(|_| p.to_string()))
+        .transpose()?;
+
+    let local_ports = clean_list(&spec.local_ports);
+    let remote_ports = clean_list(&spec.remote_ports);
+    let remote_addresses = clean_list(&spec.remote_addresses);
+    if local_ports.len() + remote_ports.len() + remote_addresses.len() > MAX_LIST_ITEMS {
+        return Err(format!(
+            "Too many ports or addresses (max {})",
+            MAX_LIST_ITEMS
+        ));
+    }
+    let has_ports = !local_ports.is_empty() || !remote_ports.is_empty();
+    if has_ports && !spec.protocol.has_ports() {
+        return Err("Ports can only be set for TCP or UDP rules".to_string());
+    }
+    for port in local_ports.iter().chain(&remote_ports) {
+        validate_port(port)?;
+    }
+    for address in &remote_addresses {
+        validate_address(address)?;
+    }
+
+    let mut profiles = Vec::new();
+    for profile in &spec.profiles {
+        if !profiles.contains(profile) {
+            profiles.push(*profile);
+        }
+    }
+    if profiles.len() == 3 {
+        profiles.clear();
+    }
+
+    Ok(RuleSpec {
+        display_name,
+        description,
+        direction: spec.direction,
+        action: spec.action,
+        program,
+        protocol: spec.protocol,
+        local_ports,
+        remote_ports,
+        remote_addresses,
+        profiles,
+        enabled: spec.enabled,
+    })
+}
+
+fn quote_list(items: &[String]) -> String {
+    if items.is_empty() {
+        return ps_quote("Any");
+    }
+    items
+        .iter()
+        .map(|s| ps_quote(s))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+// Parameters shared by New-NetFirewallRule and Set-NetFirewallRule. The spec
+// must already have been through validate().
+pub fn rule_parameters(spec: &RuleSpec) -> String {
+    let profiles = if spec.profiles.is_empty() {
+        "Any".to_string()
+    } else {
+        spec.profiles
+            .iter()
+            .map(|p| p.ps_name())
+            .collect::<Vec<_>>()
+            .join(",")
+    };
+    let mut params = vec![
+        format!("-Direction {}", spec.direction.ps_name()),
+        format!("-Action {}", spec.action.ps_name()),
+        format!("-Protocol {}", ps_quote(&spec.protocol.ps_value())),
+        format!("-Profile {}", profiles),
+        format!("-RemoteAddress {}", quote_list(&spec.remote_addresses)),
+        format!(
+            "-Program {}",
+            ps_quote(spec.program.as_deref().unwrap_or("Any"))
+        ),
+        format!("-Enabled {}", if spec.enabled { "True" } else { "False" }),
+    ];
+    if let Some(description) = &spec.description {
+        params.push(format!("-Description {}", ps_quote(description)));
+    }
+    if spec.protocol.has_ports() {
+        params.push(format!("-LocalPort {}", quote_list(&spec.local_ports)));
+        params.push(format!("-RemotePort {}", quote_list(&spec.remote_ports)));
+    }
+    params.join(" ")
+}
+
+fn rule_projection() -> &'static str {
+    r#"
+        @($rules | ForEach-Object {
+            $p = Get-NetFirewallPortFilter -AssociatedNetFirewallRule $_ -ErrorAction SilentlyContinue
+            $a = Get-NetFirewallAddressFilter -AssociatedNetFirewallRule $_ -ErrorAction SilentlyContinue
+            $app = Get-NetFirewallApplicationFilter -AssociatedNetFirewallRule $_ -ErrorAction SilentlyContinue
+            [pscustomobject]@{
+                Name = $_.Name
+                DisplayName = $_.DisplayName
+                Description = $_.Description
+                Group = $_.Group
+                Enabled = [string]$_.Enabled
+                Direction = [string]$_.Direction
+                Action = [string]$_.Action
+                Profile = [string]$_.Profile
+                Program = if ($app) { $app.Program } else { $null }
+                Protocol = if ($p) { [string]$p.Protocol } else { 'Any' }
+                LocalPort = if ($p) { @($p.LocalPort) } else { @() }
+                RemotePort = if ($p) { @($p.RemotePort) } else { @() }
+                RemoteAddress = if ($a) { @($a.RemoteAddress) } else { @() }
+            }
+        }) | ConvertTo-Json -Depth 3 -Compress
+    "#
+}
+
+pub fn list_rules_script(group: Option<&str>) -> String {
+    let filter = match group {
+        Some(group) => format!("-Group {}", ps_quote(group)),
+        None => "-All".to_string(),
+    };
+    format!(
+        "$rules = @(Get-NetFirewallRule {} -ErrorAction SilentlyContinue)\n{}",
+        filter,
+        rule_projection()
+    )
+}
+
+pub fn get_rule_script(name: &str) -> String {
+    format!(
+        "$rules = @(Get-NetFirewallRule -Name {} -ErrorAction Stop)\n{}",
+        ps_quote(name),
+        rule_projection()
+    )
+}
+
+pub fn create_rule_script(spec: &RuleSpec) -> String {
+    format!(
+        "$rules = @(New-NetFirewallRule -DisplayName {} -Group {} {} -ErrorAction Stop)\n{}",
+        ps_quote(&spec.display_name),
+        ps_quote(CONFUTILS_GROUP),
+        rule_parameters(spec),
+        rule_projection()
+    )
+}
+
+// Set-NetFirewallRule keeps the old ports when they are not given, and a
+// port-less protocol is rejected while ports are still set.
+pub fn update_rule_script(name: &str, spec: &RuleSpec) -> String {
+    let mut params = rule_parameters(spec);
+    if !spec.protocol.has_ports() {
+        params.push_str(" -LocalPort Any -RemotePort Any");
+    }
+    format!(
+        "Set-NetFirewallRule -Name {} -NewDisplayName {} {} -ErrorAction Stop\n{}",
+        ps_quote(name),
+        ps_quote(&spec.display_name),
+        params,
+        get_rule_script(name)
+    )
+}
+
+pub fn set_enabled_script(name: &str, enabled: bool) -> String {
+    format!(
+        "{} -Name {} -ErrorAction Stop\n{}",
+        if enabled {
+            "Enable-NetFirewallRule"
+        } else {
+            "Disable-NetFirewallRule"
+        },
+        ps_quote(name),
+        get_rule_script(name)
+    )
+}
+
+pub fn delete_rule_script(name: &str) -> String {
+    format!(
+        "Remove-NetFirewallRule -Name {} -ErrorAction Stop\n\"Firewall rule deleted\"",
+        ps_quote(name)
+    )
+}
+
+// Scoped to the "Internet" keyword so the program keeps LAN access (file
+// shares, printers, local game servers) while its internet traffic is blocked.
+pub fn block_program_specs(program: &str) -> Vec<RuleSpec> {
+    let file_name = program
+        .rsplit(['\\', '/'])
+        .next()
+        .unwrap_or(program)
+        .to_string();
+    [Direction::Outbound, Direction::Inbound]
+        .into_iter()
+        .map(|direction| RuleSpec {
+            display_name: format!("ConfUtils Block {} ({})", file_name, direction.ps_name()),
+            description: Some(format!("Blocks internet access for {}", program)),
+            direction,
+            action: RuleAction::Block,
+            program: Some(program.to_string()),
+            protocol: Protocol::Any,
+            local_ports: Vec::new(),
+            remote_ports: Vec::new(),
+            remote_addresses: vec!["Internet".to_string()],
+            profiles: Vec::new(),
+            enabled: true,
+        })
+        .collect()
+}
+
+pub fn block_program_script(specs: &[RuleSpec]) -> String {
+    let removals = specs
+        .iter()
+        .map(|spec| {
+            format!(
+                "Get-NetFirewallRule -DisplayName {} -ErrorAction SilentlyContinue | Remove-NetFirewallRule -ErrorAction SilentlyContinue",
+                ps_quote(&spec.display_name)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    let creations = specs
+        .iter()
+        .map(|spec| {
+            format!(
+                "New-NetFirewallRule -DisplayName {} -Group {} {} -ErrorAction Stop",
+                ps_quote(&spec.display_name),
+                ps_quote(CONFUTILS_GROUP),
+                rule_parameters(spec)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    format!(
+        "{}\n$rules = @(\n{}\n)\n{}",
+        removals,
+        creations,
+        rule_projection()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spec() -> RuleSpec {
+        RuleSpec {
+            display_name: "  Game server  ".to_string(),
+            description: Some("  ".to_string()),
+            direction: Direction::Inbound,
+            action: RuleAction::Allow,
+            program: Some(r"C:\Games\server.exe".to_string()),
+            protocol: Protocol::Tcp,
+            local_ports: vec!["27015".to_string(), " 27020-27030 ".to_string()],
+            remote_ports: vec!["any".to_string()],
+            remote_addresses: vec!["LocalSubnet".to_string(), "10.0.0.0/8".to_string()],
+            profiles: vec![Profile::Private, Profile::Private],
+            enabled: true,
+        }
+    }
+
+    #[test]
+    fn validate_normalizes_spec() {
+        let spec = validate(&spec()).unwrap();
+        assert_eq!(spec.display_name, "Game server");
+        assert_eq!(spec.description, None);
+        assert_eq!(spec.local_ports, vec!["27015", "27020-27030"]);
+        assert!(spec.remote_ports.is_empty());
+        assert_eq!(spec.profiles, vec![Profile::Private]);
+
+        let mut all = self::spec();
+        all.profiles = vec![Profile::Domain, Profile::Private, Profile::Public];
+        assert!(validate(&all).unwrap().profiles.is_empty());
+    }
+
+    #[test]
+    fn validate_rejects_bad_input() {
+        let cases: Vec<(&str, Box<dyn Fn(&mut RuleSpec)>)> = vec![
+            ("empty name", Box::new(|s| s.display_name = " ".to_string())),
+            (
+                "control char",
+                Box::new(|s| s.display_name = "a\nb".to_string()),
+            ),
+            (
+                "port zero",
+                Box::new(|s| s.local_ports = vec!["0".to_string()]),
+            ),
+            (
+                "reversed range",
+                Box::new(|s| s.local_ports = vec!["90-80".to_string()]),
+            ),
+            ("ports on any", Box::new(|s| s.protocol = Protocol::Any)),
+            (
+                "bad address",
+                Box::new(|s| s.remote_addresses = vec!["10.0.0.1/33".to_string()]),
+            ),
+            (
+                "mixed range",
+                Box::new(|s| s.remote_addresses = vec!["10.0.0.1-::1".to_string()]),
+            ),
+            (
+                "relative program",
+                Box::new(|s| s.program = Some("server.exe".to_string())),
+            ),
+            (
+                "quoted program",
+                Box::new(|s| s.program = Some("C:\\a\"b.exe".to_string())),
+            ),
+        ];
+        for (label, mutate) in cases {
+            let mut spec = spec();
+            mutate(&mut spec);
+            assert!(validate(&spec).is_err(), "{}", label);
+        }
+
+        let mut spec = spec();
+        spec.remote_addresses = vec![
+            "192.168.1.0/255.255.255.0".to_string(),
+            "fe80::/64".to_string(),
+            "10.0.0.1-10.0.0.9".to_string(),
+            "internet".to_string(),
+        ];
+        assert!(validate(&spec).is_ok());
+    }
+
+    #[test]
+    fn rule_parameters_quote_every_value() {
+        let mut spec = validate(&spec()).unwrap();
+        spec.program = Some(r"C:\Users\O'Brien\server.exe".to_string());
+        spec.description = Some("Bob's rule".to_string());
+        assert_eq!(
+            rule_parameters(&spec),
+            "-Direction Inbound -Action Allow -Protocol 'TCP' -Profile Private \
+             -RemoteAddress 'LocalSubnet','10.0.0.0/8' -Program 'C:\\Users\\O''Brien\\server.exe' \
+             -Enabled True -Description 'Bob''s rule' -LocalPort '27015','27020-27030' -RemotePort 'Any'"
+        );
+
+        spec.protocol = Protocol::Icmpv4;
+        spec.local_ports.clear();
+        spec.profiles.clear();
+        spec.remote_addresses.clear();
+        spec.program = None;
+        spec.enabled = false;
+        let params = rule_parameters(&spec);
+        assert!(params.contains(
+            "-Protocol 'ICMPv4' -Profile Any -RemoteAddress 'Any' -Program 'Any' -Enabled False"
+        ));
+        assert!(!params.contains("-LocalPort"));
+    }
+
+    #[test]
+    fn update_clears_ports_for_portless_protocols() {
+        let tcp = validate(&spec()).unwrap();
+        let script = update_rule_script("{abc}", &tcp);
+        assert!(
+            script.starts_with("Set-NetFirewallRule -Name '{abc}' -NewDisplayName 'Game server' ")
+        );
+        assert!(
+            script.contains("-LocalPort '27015','27020-27030' -RemotePort 'Any' -ErrorAction Stop")
+        );
+        assert!(!script.contains("-LocalPort Any"));
+
+        for protocol in [
+            Protocol::Any,
+            Protocol::Icmpv4,
+            Protocol::Icmpv6,
+            Protocol::Number(47),
+        ] {
+            let spec = RuleSpec {
+                protocol,
+                local_ports: Vec::new(),
+                ..tcp.clone()
+            };
+            let script = update_rule_script("{abc}", &spec);
+            assert!(
+                script.contains("-LocalPort Any -RemotePort Any -ErrorAction Stop"),
+                "{:?}",
+                protocol
+            );
+            assert_eq!(script.matches("-LocalPort").count(), 1);
+        }
+        assert!(!create_rule_script(&RuleSpec {
+            protocol: Protocol::Any,
+            local_ports: Vec::new(),
+            ..tcp
+        })
+        .contains("-LocalPort"));
+    }
+
+    #[test]
+    fn rule_projection_reads_filters_per_rule() {
+        let script = get_rule_script("{abc}");
+        for filter in ["PortFilter", "AddressFilter", "ApplicationFilter"] {
+            assert!(script.contains(&format!(
+                "Get-NetFirewall{} -AssociatedNetFirewallRule $_",
+                filter
+            )));
+            assert!(!script.contains(&format!("Get-NetFirewall{} -All", filter)));
+        }
+    }
+
+    #[test]
+    fn block_program_leaves_lan_reachable() {
+        let specs = block_program_specs(r"C:\Apps\tool.exe");
+        assert_eq!(specs.len(), 2);
+        for spec in &specs {
+            let spec = validate(spec).unwrap();
+            assert_eq!(spec.action, RuleAction::Block);
+            assert_eq!(spec.remote_addresses, vec!["Internet"]);
+            assert!(rule_parameters(&spec).contains("-RemoteAddress 'Internet'"));
+        }
+        assert_eq!(specs[0].display_name, "ConfUtils Block tool.exe (Outbound)");
+        assert_eq!(specs[1].direction, Direction::Inbound);
+    }
+
+    #[test]
+    fn parses_rule_projection() {
+        let raw = r#"{"Name":"{abc}","DisplayName":"Test","Enabled":"True","Direction":"Outbound","Action":"Block","Profile":"Domain, Private, Public","Program":"Any","Protocol":"6","LocalPort":["Any"],"RemotePort":443,"RemoteAddress":["Internet"]}"#;
+        let rules = parse_rules(raw).unwrap();
+        assert_eq!(rules.len(), 1);
+        let rule = &rules[0];
+        assert!(rule.enabled);
+        assert_eq!(rule.direction, Direction::Outbound);
+        assert_eq!(rule.protocol, Protocol::Tcp);
+        assert!(rule.program.is_none());
+        assert!(rule.local_ports.is_empty());
+        assert_eq!(rule.remote_ports, vec!["443"]);
+        assert!(rule.profiles.is_empty());
+        assert!(parse_rules("null").unwrap().is_empty());
+    }
+}
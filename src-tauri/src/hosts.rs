@@ -0,0 +1,527 @@
+use serde::Serialize;
+use std::net::IpAddr;
+use std::path::PathBuf;
+
+const SECTION_PREFIX: &str = "# ConfUtils Blocklist ";
+const SECTION_START: &str = " Start";
+const SECTION_END: &str = " End";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HostsEncoding {
+    Utf8,
+    Utf8Bom,
+    Utf16Le,
+    Latin1,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct HostsEntry {
+    pub ip: String,
+    pub hostnames: Vec<String>,
+    pub comment: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HostsLine {
+    Blank(String),
+    Comment(String),
+    Entry { raw: String, entry: HostsEntry },
+    Invalid(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HostsSection {
+    pub name: String,
+    pub lines: Vec<HostsLine>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HostsItem {
+    Line(HostsLine),
+    Section(HostsSection),
+}
+
+#[derive(Debug, Clone)]
+pub struct HostsFile {
+    pub items: Vec<HostsItem>,
+    pub encoding: HostsEncoding,
+    pub line_ending: &'static str,
+    pub trailing_newline: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SectionSummary {
+    pub name: String,
+    pub entries: usize,
+}
+
+fn parse_ip(raw: &str) -> Option<IpAddr> {
+    let without_zone = raw.split('%').next().unwrap_or(raw);
+    without_zone.parse().ok()
+}
+
+pub fn is_valid_hostname(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= 253
+        && parse_ip(name).is_none()
+        && name.split('.').all(|label| {
+            !label.is_empty()
+                && label.len() <= 63
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        })
+}
+
+impl HostsLine {
+    pub fn parse(raw: &str) -> Self {
+        let trimmed = raw.trim();
+        if trimmed.is_empty() {
+            return HostsLine::Blank(raw.to_string());
+        }
+        if trimmed.starts_with('#') {
+            return HostsLine::Comment(raw.to_string());
+        }
+        let (body, comment) = match trimmed.split_once('#') {
+            Some((body, comment)) => (body, Some(comment.trim().to_string())),
+            None => (trimmed, None),
+        };
+        let mut tokens = body.split_whitespace();
+        let ip = match tokens.next() {
+            Some(ip) if parse_ip(ip).is_some() => ip.to_string(),
+            _ => return HostsLine::Invalid(raw.to_string()),
+        };
+        let hostnames: Vec<String> = tokens.map(|t| t.to_string()).collect();
+        if hostnames.is_empty() {
+            return HostsLine::Invalid(raw.to_string());
+        }
+        HostsLine::Entry {
+            raw: raw.to_string(),
+            entry: HostsEntry {
+                ip,
+                hostnames,
+                comment,
+            },
+        }
+    }
+
+    pub fn entry(ip: &str, hostname: &str) -> Self {
+        let raw = format!("{} {}", ip, hostname);
+        HostsLine::Entry {
+            raw,
+            entry: HostsEntry {
+                ip: ip.to_string(),
+                hostnames: vec![hostname.to_string()],
+                comment: None,
+            },
+        }
+    }
+
+    fn raw(&self) -> &str {
+        match self {
+            HostsLine::Blank(raw)
+            | HostsLine::Comment(raw)
+            | HostsLine::Invalid(raw)
+            | HostsLine::Entry { raw, .. } => raw,
+        }
+    }
+
+    fn as_entry(&self) -> Option<&HostsEntry> {
+        match self {
+            HostsLine::Entry { entry, .. } => Some(entry),
+            _ => None,
+        }
+    }
+}
+
+fn section_marker(name: &str, suffix: &str) -> String {
+    format!("{}{}{}", SECTION_PREFIX, name, suffix)
+}
+
+fn parse_marker<'a>(line: &'a str, suffix: &str) -> Option<&'a str> {
+    line.trim()
+        .strip_prefix(SECTION_PREFIX)?
+        .strip_suffix(suffix)
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+}
+
+impl HostsFile {
+    pub fn parse(text: &str) -> Self {
+        let line_ending = if text.contains('\n') && !text.contains("\r\n") {
+            "\n"
+        } else {
+            "\r\n"
+        };
+        let trailing_newline = text.is_empty() || text.ends_with('\n');
+        let mut items = Vec::new();
+        let mut open: Option<HostsSection> = None;
+
+        for raw in text.lines() {
+            if let Some(section) = open.as_mut() {
+                if parse_marker(raw, SECTION_END) == Some(section.name.as_str()) {
+                    items.push(HostsItem::Section(open.take().unwrap()));
+                } else {
+                    section.lines.push(HostsLine::parse(raw));
+                }
+                continue;
+            }
+            match parse_marker(raw, SECTION_START) {
+                Some(name) => {
+                    open = Some(HostsSection {
+                        name: name.to_string(),
+                        lines: Vec::new(),
+                    })
+                }
+                None => items.push(HostsItem::Line(HostsLine::parse(raw))),
+            }
+        }
+        if let Some(section) = open {
+            items.push(HostsItem::Section(section));
+        }
+
+        HostsFile {
+            items,
+            encoding: HostsEncoding::Utf8,
+            line_ending,
+            trailing_newline,
+        }
+    }
+
+    pub fn decode(bytes: &[u8]) -> Self {
+        let (text, encoding) = if let Some(rest) = bytes.strip_prefix(&[0xEF, 0xBB, 0xBF]) {
+            (
+                String::from_utf8_lossy(rest).to_string(),
+                HostsEncoding::Utf8Bom,
+            )
+        } else if let Some(rest) = bytes.strip_prefix(&[0xFF, 0xFE]) {
+            let wide: Vec<u16> = rest
+                .chunks_exact(2)
+                .map(|c| u16::from_le_bytes([c[0], c[1]]))
+                .collect();
+            (String::from_utf16_lossy(&wide), HostsEncoding::Utf16Le)
+        } else {
+            match std::str::from_utf8(bytes) {
+                Ok(text) => (text.to_string(), HostsEncoding::Utf8),
+                Err(_) => (
+                    bytes.iter().map(|b| *b as char).collect(),
+                    HostsEncoding::Latin1,
+                ),
+            }
+        };
+        let mut file = HostsFile::parse(&text);
+        file.encoding = encoding;
+        file
+    }
+
+    pub fn render(&self) -> String {
+        let mut lines: Vec<String> = Vec::new();
+        for item in &self.items {
+            match item {
+                HostsItem::Line(line) => lines.push(line.raw().to_string()),
+                HostsItem::Section(section) => {
+                    lines.push(section_marker(&section.name, SECTION_START));
+                    lines.extend(section.lines.iter().map(|l| l.raw().to_string()));
+                    lines.push(section_marker(&section.name, SECTION_END));
+                }
+            }
+        }
+        let mut text = lines.join(self.line_ending);
+        if self.trailing_newline && !text.is_empty() {
+            text.push_str(self.line_ending);
+        }
+        text
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let text = self.render();
+        match self.encoding {
+            HostsEncoding::Utf8 => text.into_bytes(),
+            HostsEncoding::Utf8Bom => [&[0xEF, 0xBB, 0xBF][..], text.as_bytes()].concat(),
+            HostsEncoding::Utf16Le => {
+                let mut bytes = vec![0xFF, 0xFE];
+                for unit in text.encode_utf16() {
+                    bytes.extend_from_slice(&unit.to_le_bytes());
+                }
+                bytes
+            }
+            // Anything that did not decode as UTF-8 came in byte-per-char;
+            // characters added since then are replaced rather than widened.
+            HostsEncoding::Latin1 => text
+                .chars()
+                .map(|c| if (c as u32) < 256 { c as u8 } else { b'?' })
+                .collect(),
+        }
+    }
+
+    pub fn sections(&self) -> impl Iterator<Item = &HostsSection> {
+        self.items.iter().filter_map(|item| match item {
+            HostsItem::Section(section) => Some(section),
+            _ => None,
+        })
+    }
+
+    pub fn section_names(&self) -> Vec<String> {
+        self.sections().map(|s| s.name.clone()).collect()
+    }
+
+    pub fn section(&self, name: &str) -> Option<&HostsSection> {
+        self.sections().find(|s| s.name.eq_ignore_ascii_case(name))
+    }
+
+    pub fn section_summaries(&self) -> Vec<SectionSummary> {
+        self.sections()
+            .map(|s| SectionSummary {
+                name: s.name.clone(),
+                entries: s.lines.iter().filter(|l| l.as_entry().is_some()).count(),
+            })
+            .collect()
+    }
+
+    pub fn user_entries(&self) -> Vec<&HostsEntry> {
+        self.items
+            .iter()
+            .filter_map(|item| match item {
+                HostsItem::Line(line) => line.as_entry(),
+                _ => None,
+            })
+            .collect()
+    }
+
+    pub fn set_section(&mut self, name: &str, ip: &str, hostnames: &[String]) {
+        let lines: Vec<HostsLine> = hostnames
+            .iter()
+            .map(|host| HostsLine::entry(ip, host))
+            .collect();
+        let existing = self.items.iter_mut().find_map(|item| match item {
+            HostsItem::Section(section) if section.name.eq_ignore_ascii_case(name) => Some(section),
+            _ => None,
+        });
+        match existing {
+            Some(section) => section.lines = lines,
+            None => self.items.push(HostsItem::Section(HostsSection {
+                name: name.to_string(),
+                lines,
+            })),
+        }
+    }
+
+    pub fn remove_section(&mut self, name: &str) -> bool {
+        let before = self.items.len();
+        self.items.retain(|item| {
+            !matches!(item, HostsItem::Section(section) if section.name.eq_ignore_ascii_case(name))
+        });
+        self.items.len() != before
+    }
+}
+
+pub fn hosts_path() -> PathBuf {
+    let root = std::env::var("SystemRoot").unwrap_or_else(|_| "C:\\Windows".to_string());
+    PathBuf::from(root)
+        .join("System32")
+        .join("drivers")
+        .join("etc")
+        .join("hosts")
+}
+
+pub fn flush_dns() {
+    let mut cmd = std::process::Command::new("ipconfig");
+    cmd.arg("/flushdns");
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+        cmd.creation_flags(0x08000000);
+    }
+    let _ = cmd.output();
+}
+
+lazy_static::lazy_static! {
+    // Held across every load-modify-save so concurrent writers don't drop
+    // each other's sections.
+    static ref HOSTS_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+}
+
+pub fn lock() -> std::sync::MutexGuard<'static, ()> {
+    HOSTS_LOCK.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+pub fn load() -> Result<HostsFile, String> {
+    match std::fs::read(hosts_path()) {
+        Ok(bytes) => Ok(HostsFile::decode(&bytes)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(HostsFile::parse("")),
+        Err(e) => Err(format!("Failed to read hosts file: {}", e)),
+    }
+}
+
+pub fn save(file: &HostsFile) -> Result<(), String> {
+    let path = hosts_path();
+    if path.exists() {
+        std::fs::copy(&path, path.with_extension("confutils.bak"))
+            .map_err(|e| format!("Failed to back up hosts file: {}", e))?;
+    }
+    let tmp = path.with_extension("confutils.tmp");
+    std::fs::write(&tmp, file.encode())
+        .map_err(|e| format!("Failed to write hosts file: {}", e))?;
+    std::fs::rename(&tmp, &path).map_err(|e| {
+        let _ = std::fs::remove_file(&tmp);
+        format!("Failed to replace hosts file: {}", e)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = "# Copyright (c) 1993-2009 Microsoft Corp.\r\n\
+        \r\n\
+        127.0.0.1 localhost\r\n\
+        192.168.1.10   nas.lan nas   # storage\r\n\
+        not-an-ip example.com\r\n\
+        # ConfUtils Blocklist ads Start\r\n\
+        0.0.0.0 ads.example.com\r\n\
+        # ConfUtils Blocklist ads End\r\n\
+        fe80::1%eth0 router.lan\r\n";
+
+    fn hosts(section: &HostsSection) -> Vec<&str> {
+        section
+            .lines
+            .iter()
+            .filter_map(|l| l.as_entry())
+            .flat_map(|e| e.hostnames.iter().map(String::as_str))
+            .collect()
+    }
+
+    #[test]
+    fn parse_render_round_trips() {
+        let file = HostsFile::parse(SAMPLE);
+        assert_eq!(file.line_ending, "\r\n");
+        assert_eq!(file.render(), SAMPLE);
+
+        let lf = SAMPLE.replace("\r\n", "\n");
+        let file = HostsFile::parse(&lf);
+        assert_eq!(file.line_ending, "\n");
+        assert_eq!(file.render(), lf);
+
+        let unterminated = "127.0.0.1 localhost";
+        assert_eq!(HostsFile::parse(unterminated).render(), unterminated);
+        assert_eq!(HostsFile::parse("").render(), "");
+    }
+
+    #[test]
+    fn classifies_lines() {
+        let file = HostsFile::parse(SAMPLE);
+        let entries = file.user_entries();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[1].ip, "192.168.1.10");
+        assert_eq!(entries[1].hostnames, vec!["nas.lan", "nas"]);
+        assert_eq!(entries[1].comment.as_deref(), Some("storage"));
+        assert_eq!(entries[2].ip, "fe80::1%eth0");
+        assert!(file
+            .items
+            .iter()
+            .any(|i| matches!(i, HostsItem::Line(HostsLine::Invalid(raw)) if raw == "not-an-ip example.com")));
+        assert!(matches!(
+            HostsLine::parse("10.0.0.1   # nothing"),
+            HostsLine::Invalid(_)
+        ));
+        assert_eq!(
+            file.section_summaries()
+                .iter()
+                .map(|s| (s.name.as_str(), s.entries))
+                .collect::<Vec<_>>(),
+            vec![("ads", 1)]
+        );
+    }
+
+    #[test]
+    fn set_section_replaces_in_place_or_appends() {
+        let mut file = HostsFile::parse(SAMPLE);
+        let domains = vec!["a.example".to_string(), "b.example".to_string()];
+        file.set_section("ADS", "0.0.0.0", &domains);
+        file.set_section("telemetry", "0.0.0.0", &["t.example".to_string()]);
+        assert_eq!(file.section_names(), vec!["ads", "telemetry"]);
+        assert_eq!(
+            hosts(file.section("ads").unwrap()),
+            vec!["a.example", "b.example"]
+        );
+
+        let rendered = file.render();
+        assert!(rendered.contains(
+            "# ConfUtils Blocklist ads Start\r\n0.0.0.0 a.example\r\n0.0.0.0 b.example\r\n# ConfUtils Blocklist ads End\r\nfe80::1%eth0 router.lan\r\n"
+        ));
+        assert!(rendered.ends_with(
+            "# ConfUtils Blocklist telemetry Start\r\n0.0.0.0 t.example\r\n# ConfUtils Blocklist telemetry End\r\n"
+        ));
+        // User lines survive untouched.
+        assert!(rendered.contains("192.168.1.10   nas.lan nas   # storage\r\n"));
+        assert_eq!(HostsFile::parse(&rendered).render(), rendered);
+    }
+
+    #[test]
+    fn remove_section_restores_user_content() {
+        let mut file = HostsFile::parse(SAMPLE);
+        assert!(file.remove_section("Ads"));
+        assert!(!file.remove_section("ads"));
+        let rendered = file.render();
+        assert!(!rendered.contains("ConfUtils"));
+        assert!(!rendered.contains("ads.example.com"));
+        assert!(rendered.contains("127.0.0.1 localhost\r\n"));
+        assert!(rendered.ends_with("fe80::1%eth0 router.lan\r\n"));
+    }
+
+    #[test]
+    fn unterminated_section_keeps_its_lines() {
+        let text = "# ConfUtils Blocklist ads Start\n0.0.0.0 ads.example.com\n";
+        let file = HostsFile::parse(text);
+        assert_eq!(hosts(file.section("ads").unwrap()), vec!["ads.example.com"]);
+        assert!(file.user_entries().is_empty());
+    }
+
+    #[test]
+    fn encodings_round_trip() {
+        let text = "127.0.0.1 localhost\r\n";
+        let mut utf16 = vec![0xFF, 0xFE];
+        for unit in text.encode_utf16() {
+            utf16.extend_from_slice(&unit.to_le_bytes());
+        }
+        let cases = [
+            (text.as_bytes().to_vec(), HostsEncoding::Utf8),
+            (
+                [&[0xEF, 0xBB, 0xBF][..], text.as_bytes()].concat(),
+                HostsEncoding::Utf8Bom,
+            ),
+            (utf16, HostsEncoding::Utf16Le),
+            (b"127.0.0.1 caf\xe9.lan\r\n".to_vec(), HostsEncoding::Latin1),
+        ];
+        for (bytes, encoding) in cases {
+            let file = HostsFile::decode(&bytes);
+            assert_eq!(file.encoding, encoding);
+            assert_eq!(file.encode(), bytes);
+        }
+    }
+
+    #[test]
+    fn validates_hostnames() {
+        for valid in [
+            "example.com",
+            "a-b.example",
+            "_dmarc.example.com",
+            "localhost",
+        ] {
+            assert!(is_valid_hostname(valid), "{}", valid);
+        }
+        for invalid in [
+            "",
+            "-a.example",
+            "a..b",
+            "10.0.0.1",
+            "::1",
+            "exa mple.com",
+            &"a".repeat(64),
+        ] {
+            assert!(!is_valid_hostname(invalid), "{}", invalid);
+        }
+    }
+}
@@ -0,0 +1,724 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::allowlist::{self, Allowlist};
+use crate::hosts::{self, HostsFile};
+use crate::util;
+
+const MAX_SUBSCRIPTIONS: usize = 32;
+const MAX_DOWNLOAD_BYTES: usize = 16 * 1024 * 1024;
+const MAX_DOMAINS_PER_LIST: usize = 200_000;
+const DEFAULT_REFRESH_HOURS: u64 = 24;
+const SCHEDULER_TICK_SECS: u64 = 600;
+const FETCH_TIMEOUT_SECS: u64 = 60;
+const SECTION_PREFIX: &str = "SUB-";
+const BLOCK_IP: &str = "0.0.0.0";
+
+const IGNORED_HOSTNAMES: [&str; 5] = [
+    "localhost",
+    "localhost.localdomain",
+    "local",
+    "broadcasthost",
+    "ip6-localhost",
+];
+
+lazy_static::lazy_static! {
+    static ref REFRESH_LOCK: tokio::sync::Mutex<()> = tokio::sync::Mutex::new(());
+    // Serializes load-modify-save of the subscription file. A refresh holds
+    // REFRESH_LOCK across network fetches, so it only takes this lock to merge
+    // its results into whatever is on disk by then.
+    static ref STORE_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ListFormat {
+    Auto,
+    Hosts,
+    Domains,
+    Adblock,
+}
+
+impl ListFormat {
+    pub fn parse(raw: Option<&str>) -> Result<Self, String> {
+        match raw.map(|s| s.trim().to_lowercase()).as_deref() {
+            None | Some("") | Some("auto") => Ok(ListFormat::Auto),
+            Some("hosts") => Ok(ListFormat::Hosts),
+            Some("domains") => Ok(ListFormat::Domains),
+            Some("adblock") => Ok(ListFormat::Adblock),
+            Some(other) => Err(format!("Unknown blocklist format: {}", other)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Subscription {
+    pub id: String,
+    pub name: String,
+    pub url: String,
+    pub format: ListFormat,
+    pub enabled: bool,
+    pub refresh_hours: u64,
+    #[serde(default)]
+    pub etag: Option<String>,
+    #[serde(default)]
+    pub last_modified: Option<String>,
+    #[serde(default)]
+    pub last_checked_unix: Option<u64>,
+    #[serde(default)]
+    pub last_updated_unix: Option<u64>,
+    #[serde(default)]
+    pub domain_count: usize,
+    #[serde(default)]
+    pub truncated: bool,
+    #[serde(default)]
+    pub last_error: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RefreshSummary {
+    pub checked: usize,
+    pub updated: usize,
+    pub not_modified: usize,
+    pub failed: Vec<String>,
+    pub hosts_changed: bool,
+    pub blocked_domains: usize,
+    pub allowlisted: usize,
+}
+
+enum FetchOutcome {
+    Updated {
+        body: String,
+        etag: Option<String>,
+        last_modified: Option<String>,
+    },
+    NotModified,
+}
+
+fn subscriptions_path() -> PathBuf {
+    util::data_dir().join("hosts_subscriptions.json")
+}
+
+fn cache_path(id: &str) -> PathBuf {
+    util::data_subdir("blocklists").join(format!("{}.txt", id))
+}
+
+pub fn section_name(id: &str) -> String {
+    format!("{}{}", SECTION_PREFIX, id.to_uppercase())
+}
+
+fn normalize_domain(raw: &str) -> Option<String> {
+    let domain = raw.trim().trim_end_matches('.').to_lowercase();
+    if IGNORED_HOSTNAMES.contains(&domain.as_str()) || !hosts::is_valid_hostname(&domain) {
+        return None;
+    }
+    Some(domain)
+}
+
+fn parse_hosts_line(line: &str) -> Vec<String> {
+    let body = line.split('#').next().unwrap_or("");
+    let mut tokens = body.split_whitespace();
+    match tokens.next() {
+        Some(ip) if ip.parse::<std::net::IpAddr>().is_ok() => {
+            tokens.filter_map(normalize_domain).collect()
+        }
+        _ => Vec::new(),
+    }
+}
+
+fn parse_adblock_line(line: &str) -> Option<String> {
+    let rule = line.strip_prefix("||")?;
+    let (domain, rest) = rule.split_once('^')?;
+    // Rules with modifiers other than a bare "$important" only apply to some
+    // request types and cannot be expressed as a hosts entry.
+    if !(rest.is_empty() || rest == "$important") {
+        return None;
+    }
+    normalize_domain(domain)
+}
+
+fn parse_domain_line(line: &str) -> Option<String> {
+    let body = line.split('#').next().unwrap_or("").trim();
+    if body.contains(char::is_whitespace) {
+        return None;
+    }
+    normalize_domain(body)
+}
+
+pub fn parse_list(text: &str, format: ListFormat) -> (Vec<String>, bool) {
+    let mut domains = BTreeSet::new();
+    let mut truncated = false;
+    for raw in text.lines() {
+        let line = raw.trim();
+        if line.is_empty()
+            || line.starts_with('#')
+            || line.starts_with('!')
+            || line.starts_with('[')
+        {
+            continue;
+        }
+        let found = match format {
+            ListFormat::Hosts => parse_hosts_line(line),
+            ListFormat::Domains => parse_domain_line(line).into_iter().collect(),
+            ListFormat::Adblock => parse_adblock_line(line).into_iter().collect(),
+            ListFormat::Auto => {
+                if line.starts_with("||") {
+                    parse_adblock_line(line).into_iter().collect()
+                } else if line.contains(char::is_whitespace) {
+                    parse_hosts_line(line)
+                } else {
+                    parse_domain_line(line).into_iter().collect()
+                }
+            }
+        };
+        for domain in found {
+            if domains.len() >= MAX_DOMAINS_PER_LIST {
+                truncated = true;
+                break;
+            }
+            domains.insert(domain);
+        }
+        if truncated {
+            break;
+        }
+    }
+    (domains.into_iter().collect(), truncated)
+}
+
+pub fn validate_url(url: &str) -> Result<String, String> {
+    let parsed =
+        reqwest::Url::parse(url.trim()).map_err(|e| format!("Invalid blocklist URL: {}", e))?;
+    if !matches!(parsed.scheme(), "http" | "https") || parsed.host_str().is_none() {
+        return Err("Blocklist URL must be http or https".to_string());
+    }
+    Ok(parsed.to_string())
+}
+
+pub fn load_subscriptions() -> Vec<Subscription> {
+    std::fs::read_to_string(subscriptions_path())
+        .ok()
+        .and_then(|raw| serde_json::from_str(&raw).ok())
+        .unwrap_or_default()
+}
+
+fn save_subscriptions(subscriptions: &[Subscription]) -> Result<(), String> {
+    let raw = serde_json::to_string_pretty(subscriptions)
+        .map_err(|e| format!("Failed to serialize subscriptions: {}", e))?;
+    std::fs::write(subscriptions_path(), raw)
+        .map_err(|e| format!("Failed to save subscriptions: {}", e))
+}
+
+fn lock_store() -> std::sync::MutexGuard<'static, ()> {
+    STORE_LOCK.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn read_cached_domains(id: &str) -> Vec<String> {
+    std::fs::read_to_string(cache_path(id))
+        .map(|raw| raw.lines().map(|l| l.to_string()).collect())
+        .unwrap_or_default()
+}
+
+pub fn add_subscription(
+    name: &str,
+    url: &str,
+    format: ListFormat,
+    refresh_hours: Option<u64>,
+) -> Result<Subscription, String> {
+    let url = validate_url(url)?;
+    let _guard = lock_store();
+    let mut subscriptions = load_subscriptions();
+    if subscriptions.len() >= MAX_SUBSCRIPTIONS {
+        return Err(format!(
+            "Too many blocklist subscriptions (max {})",
+            MAX_SUBSCRIPTIONS
+        ));
+    }
+    if subscriptions.iter().any(|s| s.url == url) {
+        return Err("This blocklist URL is already subscribed".to_string());
+    }
+    let subscription = Subscription {
+        id: util::new_id(),
+        name: if name.trim().is_empty() {
+            url.clone()
+        } else {
+            name.trim().chars().take(64).collect()
+        },
+        url,
+        format,
+        enabled: true,
+        refresh_hours: refresh_hours
+            .unwrap_or(DEFAULT_REFRESH_HOURS)
+            .clamp(1, 24 * 30),
+        etag: None,
+        last_modified: None,
+        last_checked_unix: None,
+        last_updated_unix: None,
+        domain_count: 0,
+        truncated: false,
+        last_error: None,
+    };
+    subscriptions.push(subscription.clone());
+    save_subscriptions(&subscriptions)?;
+    Ok(subscription)
+}
+
+pub fn remove_subscription(id: &str) -> Result<(), String> {
+    let _guard = lock_store();
+    let mut subscriptions = load_subscriptions();
+    let before = subscriptions.len();
+    subscriptions.retain(|s| s.id != id);
+    if subscriptions.len() == before {
+        return Err(format!("Blocklist subscription not found: {}", id));
+    }
+    save_subscriptions(&subscriptions)?;
+    let _ = std::fs::remove_file(cache_path(id));
+    Ok(())
+}
+
+pub fn set_enabled(id: &str, enabled: bool) -> Result<(), String> {
+    let _guard = lock_store();
+    let mut subscriptions = load_subscriptions();
+    let subscription = subscriptions
+        .iter_mut()
+        .find(|s| s.id == id)
+        .ok_or_else(|| format!("Blocklist subscription not found: {}", id))?;
+    subscription.enabled = enabled;
+    save_subscriptions(&subscriptions)
+}
+
+async fn fetch(
+    client: &reqwest::Client,
+    subscription: &Subscription,
+) -> Result<FetchOutcome, String> {
+    let mut request = client.get(&subscription.url);
+    if let Some(etag) = &subscription.etag {
+        request = request.header(reqwest::header::IF_NONE_MATCH, etag);
+    }
+    if let Some(modified) = &subscription.last_modified {
+        request = request.header(reqwest::header::IF_MODIFIED_SINCE, modified);
+    }
+    let mut response = request
+        .send()
+        .await
+        .map_err(|e| format!("Request failed: {}", e))?;
+    if response.status() == reqwest::StatusCode::NOT_MODIFIED {
+        return Ok(FetchOutcome::NotModified);
+    }
+    if !response.status().is_success() {
+        return Err(format!("HTTP {}", response.status()));
+    }
+    if response
+        .content_length()
+        .is_some_and(|len| len as usize > MAX_DOWNLOAD_BYTES)
+    {
+        return Err("Blocklist is larger than 16 MB".to_string());
+    }
+
+    let header = |name: reqwest::header::HeaderName| {
+        response
+            .headers()
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.to_string())
+    };
+    let etag = header(reqwest::header::ETAG);
+    let last_modified = header(reqwest::header::LAST_MODIFIED);
+
+    let mut body = Vec::new();
+    while let Some(chunk) = response
+        .chunk()
+        .await
+        .map_err(|e| format!("Download failed: {}", e))?
+    {
+        if body.len() + chunk.len() > MAX_DOWNLOAD_BYTES {
+            return Err("Blocklist is larger than 16 MB".to_string());
+        }
+        body.extend_from_slice(&chunk);
+    }
+    Ok(FetchOutcome::Updated {
+        body: String::from_utf8_lossy(&body).to_string(),
+        etag,
+        last_modified,
+    })
+}
+
+fn is_due(subscription: &Subscription, now: u64) -> bool {
+    match subscription.last_checked_unix {
+        Some(checked) => now.saturating_sub(checked) >= subscription.refresh_hours * 3600,
+        None => true,
+    }
+}
+
+pub fn merge_into_hosts(
+    file: &mut HostsFile,
+    subscriptions: &[Subscription],
+    lists: &[(String, Vec<String>)],
+    allow: &Allowlist,
+) -> (usize, usize) {
+    let mut seen = BTreeSet::new();
+    let mut total = 0usize;
+    let mut allowlisted = 0usize;
+    for subscription in subscriptions.iter().filter(|s| s.enabled) {
+        let domains: Vec<String> = lists
+            .iter()
+            .find(|(id, _)| id == &subscription.id)
+            .map(|(_, domains)| {
+                domains
+                    .iter()
+                    .filter(|d| seen.insert((*d).clone()))
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default();
+        let (domains, suppressed) = allow.filter(&domains);
+        allowlisted += suppressed.len();
+        total += domains.len();
+        file.set_section(&section_name(&subscription.id), BLOCK_IP, &domains);
+    }
+
+    let active: BTreeSet<String> = subscriptions
+        .iter()
+        .filter(|s| s.enabled)
+        .map(|s| section_name(&s.id))
+        .collect();
+    for name in file.section_names() {
+        if name.to_uppercase().starts_with(SECTION_PREFIX) && !active.contains(&name.to_uppercase())
+        {
+            file.remove_section(&name);
+        }
+    }
+    (total, allowlisted)
+}
+
+// Copies the fetch results of a refresh onto the current subscription list,
+// keeping edits made while the refresh was running. Subscriptions removed in
+// the meantime are not resurrected.
+fn merge_fetch_state(current: &mut [Subscription], refreshed: &[Subscription]) {
+    for subscription in current.iter_mut() {
+        let Some(fresh) = refreshed.iter().find(|r| r.id == subscription.id) else {
+            continue;
+        };
+        subscription.etag = fresh.etag.clone();
+        subscription.last_modified = fresh.last_modified.clone();
+        subscription.last_checked_unix = fresh.last_checked_unix;
+        subscription.last_updated_unix = fresh.last_updated_unix;
+        subscription.domain_count = fresh.domain_count;
+        subscription.truncated = fresh.truncated;
+        subscription.last_error = fresh.last_error.clone();
+    }
+}
+
+fn apply_to_hosts(subscriptions: &[Subscription]) -> Result<(bool, usize, usize), String> {
+    let lists: Vec<(String, Vec<String>)> = subscriptions
+        .iter()
+        .filter(|s| s.enabled)
+        .map(|s| (s.id.clone(), read_cached_domains(&s.id)))
+        .collect();
+    let _guard = hosts::lock();
+    let mut file = hosts::load()?;
+    let before = file.render();
+    let (total, allowlisted) =
+        merge_into_hosts(&mut file, subscriptions, &lists, &allowlist::load()?);
+    if file.render() == before {
+        return Ok((false, total, allowlisted));
+    }
+    hosts::save(&file)?;
+    hosts::flush_dns();
+    Ok((true, total, allowlisted))
+}
+
+// Rewrites the subscription sections from the cached lists, e.g. after an
+// allowlist change. Nothing is downloaded.
+pub fn reapply_cached() -> Result<RefreshSummary, String> {
+    let _guard = lock_store();
+    let (hosts_changed, blocked_domains, allowlisted) = apply_to_hosts(&load_subscriptions())?;
+    Ok(RefreshSummary {
+        hosts_changed,
+        blocked_domains,
+        allowlisted,
+        ..Default::default()
+    })
+}
+
+pub async fn refresh(force: bool) -> Result<RefreshSummary, String> {
+    let _guard = REFRESH_LOCK.lock().await;
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(FETCH_TIMEOUT_SECS))
+        .user_agent("ConfUtils")
+        .build()
+        .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
+
+    let mut subscriptions = tokio::task::spawn_blocking(load_subscriptions)
+        .await
+        .map_err(|e| format!("Subscription task failed: {}", e))?;
+    let now = util::now_unix();
+    let mut summary = RefreshSummary::default();
+
+    for subscription in subscriptions.iter_mut() {
+        if !subscription.enabled || !(force || is_due(subscription, now)) {
+            continue;
+        }
+        summary.checked += 1;
+        subscription.last_checked_unix = Some(now);
+        match fetch(&client, subscription).await {
+            Ok(FetchOutcome::NotModified) => {
+                summary.not_modified += 1;
+                subscription.last_error = None;
+            }
+            Ok(FetchOutcome::Updated {
+                body,
+                etag,
+                last_modified,
+            }) => {
+                let (domains, truncated) = parse_list(&body, subscription.format);
+                if let Err(e) =
+                    tokio::fs::write(cache_path(&subscription.id), domains.join("\n")).await
+                {
+                    let e = format!("Failed to cache blocklist: {}", e);
+                    subscription.last_error = Some(e.clone());
+                    summary.failed.push(format!("{}: {}", subscription.name, e));
+                    continue;
+                }
+                subscription.etag = etag;
+                subscription.last_modified = last_modified;
+                subscription.last_updated_unix = Some(now);
+                subscription.domain_count = domains.len();
+                subscription.truncated = truncated;
+                subscription.last_error = None;
+                summary.updated += 1;
+            }
+            Err(e) => {
+                subscription.last_error = Some(e.clone());
+                summary.failed.push(format!("{}: {}", subscription.name, e));
+            }
+        }
+    }
+
+    let (hosts_changed, blocked_domains, allowlisted) = tokio::task::spawn_blocking(move || {
+        let _guard = lock_store();
+        let mut current = load_subscriptions();
+        merge_fetch_state(&mut current, &subscriptions);
+        for removed in subscriptions
+            .iter()
+            .filter(|s| !current.iter().any(|c| c.id == s.id))
+        {
+            let _ = std::fs::remove_file(cache_path(&removed.id));
+        }
+        save_subscriptions(&current)?;
+        apply_to_hosts(&current)
+    })
+    .await
+    .map_err(|e| format!("Subscription task failed: {}", e))??;
+    summary.hosts_changed = hosts_changed;
+    summary.blocked_domains = blocked_domains;
+    summary.allowlisted = allowlisted;
+    Ok(summary)
+}
+
+pub async fn run_scheduler() {
+    loop {
+        tokio::time::sleep(Duration::from_secs(SCHEDULER_TICK_SECS)).await;
+        let due = load_subscriptions()
+            .iter()
+            .any(|s| s.enabled && is_due(s, util::now_unix()));
+        if due {
+            let _ = refresh(false).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+
+    fn subscription(id: &str, url: &str) -> Subscription {
+        Subscription {
+            id: id.to_string(),
+            name: id.to_string(),
+            url: url.to_string(),
+            format: ListFormat::Auto,
+            enabled: true,
+            refresh_hours: DEFAULT_REFRESH_HOURS,
+            etag: None,
+            last_modified: None,
+            last_checked_unix: None,
+            last_updated_unix: None,
+            domain_count: 0,
+            truncated: false,
+            last_error: None,
+        }
+    }
+
+    // Serves a single ETag-tagged list and answers 304 to a matching
+    // If-None-Match. Returns the base URL and the request headers it saw.
+    fn serve(requests: usize) -> (String, std::thread::JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/list.txt", listener.local_addr().unwrap());
+        let handle = std::thread::spawn(move || {
+            let mut seen = Vec::new();
+            for _ in 0..requests {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut raw = Vec::new();
+                let mut buf = [0u8; 1024];
+                while !raw.windows(4).any(|w| w == b"\r\n\r\n") {
+                    let n = stream.read(&mut buf).unwrap();
+                    if n == 0 {
+                        break;
+                    }
+                    raw.extend_from_slice(&buf[..n]);
+                }
+                let request = String::from_utf8_lossy(&raw).to_lowercase();
+                let response = if request.contains("if-none-match: \"v1\"") {
+                    "HTTP/1.1 304 Not Modified\r\nETag: \"v1\"\r\nConnection: close\r\n\r\n"
+                        .to_string()
+                } else {
+                    let body = "0.0.0.0 ads.example.com\n0.0.0.0 tracker.example.net\n";
+                    format!(
+                        "HTTP/1.1 200 OK\r\nETag: \"v1\"\r\nLast-Modified: Mon, 05 Oct 2026 10:00:00 GMT\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                        body.len(),
+                        body
+                    )
+                };
+                stream.write_all(response.as_bytes()).unwrap();
+                seen.push(request);
+            }
+            seen
+        });
+        (url, handle)
+    }
+
+    fn client() -> reqwest::Client {
+        reqwest::Client::builder().no_proxy().build().unwrap()
+    }
+
+    #[tokio::test]
+    async fn fetch_sends_validators_and_honours_not_modified() {
+        let (url, server) = serve(2);
+        let mut sub = subscription("a", &url);
+
+        let FetchOutcome::Updated {
+            body,
+            etag,
+            last_modified,
+        } = fetch(&client(), &sub).await.unwrap()
+        else {
+            panic!("expected a full download");
+        };
+        assert_eq!(etag.as_deref(), Some("\"v1\""));
+        assert_eq!(
+            last_modified.as_deref(),
+            Some("Mon, 05 Oct 2026 10:00:00 GMT")
+        );
+        assert_eq!(
+            parse_list(&body, sub.format).0,
+            vec!["ads.example.com", "tracker.example.net"]
+        );
+
+        sub.etag = etag;
+        sub.last_modified = last_modified;
+        assert!(matches!(
+            fetch(&client(), &sub).await.unwrap(),
+            FetchOutcome::NotModified
+        ));
+
+        let requests = server.join().unwrap();
+        assert!(!requests[0].contains("if-none-match"));
+        assert!(requests[1].contains("if-none-match: \"v1\""));
+        assert!(requests[1].contains("if-modified-since: mon, 05 oct 2026 10:00:00 gmt"));
+    }
+
+    #[tokio::test]
+    async fn fetch_reports_http_errors() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/missing", listener.local_addr().unwrap());
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut buf = [0u8; 1024];
+            let _ = stream.read(&mut buf);
+            stream
+                .write_all(
+                    b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                )
+                .unwrap();
+        });
+        let result = fetch(&client(), &subscription("a", &url)).await;
+        server.join().unwrap();
+        assert_eq!(result.err().as_deref(), Some("HTTP 404 Not Found"));
+    }
+
+    #[test]
+    fn parse_list_reads_plain_domain_lists() {
+        let text = "# comment\nAds.Example.com\ntracker.example.net. # trailing\n\
+            localhost\nnot a domain\nads.example.com\n-bad-.example\n";
+        let (domains, truncated) = parse_list(text, ListFormat::Domains);
+        assert_eq!(domains, vec!["ads.example.com", "tracker.example.net"]);
+        assert!(!truncated);
+    }
+
+    #[test]
+    fn parse_list_reads_adblock_rules() {
+        let text = "[Adblock Plus 2.0]\n! comment\n||ads.example.com^\n\
+            ||cdn.example.org^$important\n||tracker.example.net^$third-party\n\
+            @@||allowed.example.com^\n||ads.example.com^\nexample.com##.banner\n";
+        let (domains, _) = parse_list(text, ListFormat::Adblock);
+        assert_eq!(domains, vec!["ads.example.com", "cdn.example.org"]);
+        // Auto detection handles a mix of all three formats.
+        let mixed = "||a.example^\n0.0.0.0 b.example c.example\nd.example\n@@||e.example^\n";
+        assert_eq!(
+            parse_list(mixed, ListFormat::Auto).0,
+            vec!["a.example", "b.example", "c.example", "d.example"]
+        );
+    }
+
+    #[test]
+    fn parse_list_dedups_and_truncates() {
+        let (domains, truncated) = parse_list(
+            "0.0.0.0 a.example a.example\n127.0.0.1 A.EXAMPLE\n",
+            ListFormat::Hosts,
+        );
+        assert_eq!(domains, vec!["a.example"]);
+        assert!(!truncated);
+
+        let text: String = (0..=MAX_DOMAINS_PER_LIST)
+            .map(|i| format!("d{}.example\n", i))
+            .collect();
+        let (domains, truncated) = parse_list(&text, ListFormat::Domains);
+        assert_eq!(domains.len(), MAX_DOMAINS_PER_LIST);
+        assert!(truncated);
+    }
+
+    #[test]
+    fn merge_keeps_concurrent_edits_and_drops_removed_subscriptions() {
+        let mut refreshed = vec![
+            subscription("a", "https://a.example/list"),
+            subscription("b", "https://b.example/list"),
+        ];
+        refreshed[0].etag = Some("\"v2\"".to_string());
+        refreshed[0].domain_count = 42;
+        refreshed[0].last_checked_unix = Some(100);
+        refreshed[1].last_error = Some("HTTP 500".to_string());
+
+        // While the refresh ran, "a" was disabled and renamed, "b" was
+        // removed and "c" was added.
+        let mut current = vec![
+            subscription("a", "https://a.example/list"),
+            subscription("c", "https://c.example/list"),
+        ];
+        current[0].enabled = false;
+        current[0].name = "Renamed".to_string();
+
+        merge_fetch_state(&mut current, &refreshed);
+        assert_eq!(current.len(), 2);
+        assert!(!current[0].enabled);
+        assert_eq!(current[0].name, "Renamed");
+        assert_eq!(current[0].etag.as_deref(), Some("\"v2\""));
+        assert_eq!(current[0].domain_count, 42);
+        assert_eq!(current[0].last_checked_unix, Some(100));
+        assert_eq!(current[1].id, "c");
+        assert!(current[1].last_checked_unix.is_none());
+    }
+}
@@ -0,0 +1,1061 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::net::{TcpStream, UdpSocket};
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+
+use crate::dns_bench;
+use crate::oui;
+
+const MAX_HOSTS: usize = 4096;
+const MAX_CONCURRENCY: usize = 256;
+const MAX_PORTS: usize = 64;
+const PROGRESS_EVERY: usize = 16;
+const DEFAULT_PING_PORTS: &[u16] = &[445, 139, 80, 443, 22, 3389, 62078, 8080];
+const COMMON_PORTS: &[u16] = &[
+    21, 22, 23, 53, 80, 139, 443, 445, 515, 554, 631, 1883, 3389, 5000, 5900, 8080, 8443, 9100,
+];
+const DNS_PORT: u16 = 53;
+const MDNS_PORT: u16 = 5353;
+const NETBIOS_PORT: u16 = 137;
+const NBSTAT_TYPE: u16 = 0x0021;
+
+lazy_static::lazy_static! {
+    static ref DISCOVERY_JOBS: Mutex<HashMap<String, Arc<AtomicBool>>> = Mutex::new(HashMap::new());
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct DiscoveryOptions {
+    #[serde(default)]
+    pub targets: Option<String>,
+    #[serde(default = "default_true")]
+    pub icmp: bool,
+    #[serde(default = "default_true")]
+    pub arp: bool,
+    #[serde(default)]
+    pub ping_ports: Vec<u16>,
+    #[serde(default = "default_true")]
+    pub resolve_names: bool,
+    #[serde(default)]
+    pub probe_ports: bool,
+    #[serde(default)]
+    pub ports: Vec<u16>,
+    #[serde(default = "default_concurrency")]
+    pub concurrency: usize,
+    #[serde(default = "default_timeout_ms")]
+    pub timeout_ms: u64,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, rename_all = "PascalCase")]
+pub struct LocalAddress {
+    pub address: String,
+    pub prefix: u8,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, rename_all = "PascalCase")]
+pub struct NetworkContext {
+    pub addresses: Vec<LocalAddress>,
+    pub gateways: Vec<String>,
+    pub dns_servers: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HostName {
+    pub source: String,
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DiscoveredHost {
+    pub ip: String,
+    pub mac: Option<String>,
+    pub vendor: Option<String>,
+    pub category: oui::DeviceCategory,
+    pub randomized_mac: bool,
+    pub hostname: Option<String>,
+    pub names: Vec<HostName>,
+    pub workgroup: Option<String>,
+    pub methods: Vec<String>,
+    pub latency_ms: Option<f64>,
+    pub open_ports: Vec<u16>,
+    pub is_gateway: bool,
+    pub is_self: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum DiscoveryEvent {
+    Host(DiscoveredHost),
+    Progress { scanned: usize, total: usize },
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DiscoverySummary {
+    pub scanned: usize,
+    pub alive: usize,
+    pub cancelled: bool,
+    pub elapsed_ms: u64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NetbiosInfo {
+    pub name: Option<String>,
+    pub workgroup: Option<String>,
+    pub mac: Option<[u8; 6]>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_concurrency() -> usize {
+    64
+}
+
+fn default_timeout_ms() -> u64 {
+    800
+}
+
+impl Default for DiscoveryOptions {
+    fn default() -> Self {
+        DiscoveryOptions {
+            targets: None,
+            icmp: true,
+            arp: true,
+            ping_ports: Vec::new(),
+            resolve_names: true,
+            probe_ports: false,
+            ports: Vec::new(),
+            concurrency: default_concurrency(),
+            timeout_ms: default_timeout_ms(),
+        }
+    }
+}
+
+pub fn register_job(job_id: &str) -> Arc<AtomicBool> {
+    let flag = Arc::new(AtomicBool::new(false));
+    if let Ok(mut jobs) = DISCOVERY_JOBS.lock() {
+        jobs.insert(job_id.to_string(), flag.clone());
+    }
+    flag
+}
+
+pub fn finish_job(job_id: &str) {
+    if let Ok(mut jobs) = DISCOVERY_JOBS.lock() {
+        jobs.remove(job_id);
+    }
+}
+
+pub fn cancel_job(job_id: &str) -> bool {
+    match DISCOVERY_JOBS.lock() {
+        Ok(jobs) => match jobs.get(job_id) {
+            Some(flag) => {
+                flag.store(true, Ordering::SeqCst);
+                true
+            }
+            None => false,
+        },
+        Err(_) => false,
+    }
+}
+
+pub fn context_script() -> &'static str {
+    r#"
+        $addresses = @(Get-NetIPAddress -AddressFamily IPv4 -AddressState Preferred -ErrorAction SilentlyContinue | Where-Object { $_.IPAddress -notlike '127.*' -and $_.IPAddress -notlike '169.254.*' } | ForEach-Object {
+            [pscustomobject]@{ Address = $_.IPAddress; Prefix = [int]$_.PrefixLength }
+        })
+        $gateways = @(Get-NetRoute -AddressFamily IPv4 -DestinationPrefix '0.0.0.0/0' -ErrorAction SilentlyContinue | ForEach-Object { [string]$_.NextHop } | Where-Object { $_ -ne '0.0.0.0' })
+        $dns = @(Get-DnsClientServerAddress -AddressFamily IPv4 -ErrorAction SilentlyContinue | ForEach-Object { $_.ServerAddresses })
+        [pscustomobject]@{
+            Addresses = $addresses
+            Gateways = @($gateways | Select-Object -Unique)
+            DnsServers = @($dns | Select-Object -Unique)
+        } | ConvertTo-Json -Depth 3 -Compress
+    "#
+}
+
+pub fn parse_context(raw: &str) -> Result<NetworkContext, String> {
+    serde_json::from_str(raw.trim()).map_err(|e| format!("Failed to parse network context: {}", e))
+}
+
+impl NetworkContext {
+    fn local_subnets(&self) -> Vec<(Ipv4Addr, u8)> {
+        self.addresses
+            .iter()
+            .filter_map(|a| Some((a.address.parse::<Ipv4Addr>().ok()?, a.prefix)))
+            .filter(|(ip, prefix)| !ip.is_loopback() && !ip.is_link_local() && *prefix <= 32)
+            .collect()
+    }
+
+    fn is_on_link(&self, ip: Ipv4Addr) -> bool {
+        self.local_subnets()
+            .iter()
+            .any(|(local, prefix)| network(*local, *prefix) == network(ip, *prefix))
+    }
+
+    fn is_local_address(&self, ip: Ipv4Addr) -> bool {
+        self.local_subnets().iter().any(|(local, _)| *local == ip)
+    }
+
+    fn is_gateway(&self, ip: Ipv4Addr) -> bool {
+        self.gateways
+            .iter()
+            .any(|g| g.parse::<Ipv4Addr>().ok() == Some(ip))
+    }
+
+    pub fn dns_servers(&self) -> Vec<IpAddr> {
+        self.dns_servers
+            .iter()
+            .filter_map(|s| s.parse::<IpAddr>().ok())
+            .take(2)
+            .collect()
+    }
+}
+
+fn mask(prefix: u8) -> u32 {
+    if prefix == 0 {
+        0
+    } else {
+        u32::MAX << (32 - prefix.min(32) as u32)
+    }
+}
+
+fn network(ip: Ipv4Addr, prefix: u8) -> u32 {
+    u32::from(ip) & mask(prefix)
+}
+
+fn subnet_hosts(ip: Ipv4Addr, prefix: u8) -> Vec<Ipv4Addr> {
+    let start = network(ip, prefix);
+    let size = 1u64 << (32 - prefix as u32);
+    if prefix >= 31 {
+        return (0..size)
+            .map(|i| Ipv4Addr::from(start + i as u32))
+            .collect();
+    }
+    // Skip the network and broadcast addresses.
+    (1..size - 1)
+        .map(|i| Ipv4Addr::from(start + i as u32))
+        .collect()
+}
+
+fn parse_range(part: &str) -> Result<Vec<Ipv4Addr>, String> {
+    let invalid = || format!("Invalid target: {}", part);
+    if let Some((ip, prefix)) = part.split_once('/') {
+        let ip = ip.trim().parse::<Ipv4Addr>().map_err(|_| invalid())?;
+        let prefix = prefix
+            .trim()
+            .parse::<u8>()
+            .ok()
+            .filter(|p| (16..=32).contains(p))
+            .ok_or_else(|| format!("Subnet prefix must be between /16 and /32: {}", part))?;
+        return Ok(subnet_hosts(ip, prefix));
+    }
+    if let Some((start, end)) = part.split_once('-') {
+        let start = start.trim().parse::<Ipv4Addr>().map_err(|_| invalid())?;
+        let end = match end.trim().parse::<u8>() {
+            Ok(last) => {
+                let o = start.octets();
+                Ipv4Addr::new(o[0], o[1], o[2], last)
+            }
+            Err(_) => end.trim().parse::<Ipv4Addr>().map_err(|_| invalid())?,
+        };
+        let (start, end) = (u32::from(start), u32::from(end));
+        if end < start {
+            return Err(invalid());
+        }
+        if (end - start) as usize >= MAX_HOSTS {
+            return Err(format!("Target range too large (max {} hosts)", MAX_HOSTS));
+        }
+        return Ok((start..=end).map(Ipv4Addr::from).collect());
+    }
+    Ok(vec![part.parse::<Ipv4Addr>().map_err(|_| invalid())?])
+}
+
+// Accepts a comma separated mix of 192.168.1.0/24, 192.168.1.10-50,
+// 192.168.1.10-192.168.2.20 and single addresses.
+pub fn parse_targets(spec: &str) -> Result<Vec<Ipv4Addr>, String> {
+    let mut seen = BTreeSet::new();
+    let mut targets = Vec::new();
+    for part in spec.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+        for ip in parse_range(part)? {
+            if seen.insert(ip) {
+                targets.push(ip);
+            }
+        }
+        if targets.len() > MAX_HOSTS {
+            return Err(format!("Target range too large (max {} hosts)", MAX_HOSTS));
+        }
+    }
+    if targets.is_empty() {
+        return Err("No targets given".to_string());
+    }
+    Ok(targets)
+}
+
+// Without explicit targets every attached subnet is swept; anything wider
+// than a /22 is narrowed to the /24 around our own address. Each adapter gets
+// an equal share of MAX_HOSTS, filled with the addresses nearest our own.
+pub fn targets(
+    options: &DiscoveryOptions,
+    context: &NetworkContext,
+) -> Result<Vec<Ipv4Addr>, String> {
+    if let Some(spec) = options.targets.as_deref().filter(|s| !s.trim().is_empty()) {
+        return parse_targets(spec);
+    }
+    let subnets = context.local_subnets();
+    if subnets.is_empty() {
+        return Err("No IPv4 network found to scan".to_string());
+    }
+    let budget = (MAX_HOSTS / subnets.len()).max(1);
+    let mut seen = BTreeSet::new();
+    let mut targets = Vec::new();
+    for (ip, prefix) in subnets {
+        let mut hosts = subnet_hosts(ip, if prefix < 22 { 24 } else { prefix });
+        hosts.retain(|h| !seen.contains(h));
+        if hosts.len() > budget {
+            hosts.sort_by_key(|h| u32::from(*h).abs_diff(u32::from(ip)));
+            hosts.truncate(budget);
+            hosts.sort();
+        }
+        seen.extend(hosts.iter().copied());
+        targets.extend(hosts);
+    }
+    Ok(targets)
+}
+
+fn millis(elapsed: Duration) -> f64 {
+    (elapsed.as_secs_f64() * 100_000.0).round() / 100.0
+}
+
+// A refused connection still proves the host is up.
+async fn tcp_ping(ip: Ipv4Addr, ports: &[u16], timeout: Duration) -> Option<(u16, f64)> {
+    let mut tasks = JoinSet::new();
+    for &port in ports {
+        tasks.spawn(async move {
+            let started = Instant::now();
+            let addr = SocketAddr::from((ip, port));
+            match tokio::time::timeout(timeout, TcpStream::connect(addr)).await {
+                Ok(Ok(_)) => Some((port, millis(started.elapsed()))),
+                Ok(Err(e)) if e.kind() == std::io::ErrorKind::ConnectionRefused => {
+                    Some((port, millis(started.elapsed())))
+                }
+                _ => None,
+            }
+        });
+    }
+    while let Some(joined) = tasks.join_next().await {
+        if let Ok(Some(hit)) = joined {
+            return Some(hit);
+        }
+    }
+    None
+}
+
+pub async fn probe_ports(ip: IpAddr, ports: &[u16], timeout: Duration) -> Vec<u16> {
+    let mut tasks = JoinSet::new();
+    for &port in ports {
+        tasks.spawn(async move {
+            let addr = SocketAddr::new(ip, port);
+            matches!(
+                tokio::time::timeout(timeout, TcpStream::connect(addr)).await,
+                Ok(Ok(_))
+            )
+            .then_some(port)
+        });
+    }
+    let mut open = Vec::new();
+    while let Some(joined) = tasks.join_next().await {
+        if let Ok(Some(port)) = joined {
+            open.push(port);
+        }
+    }
+    open.sort_unstable();
+    open
+}
+
+pub fn reverse_name(ip: IpAddr) -> String {
+    match ip {
+        IpAddr::V4(v4) => {
+            let o = v4.octets();
+            format!("{}.{}.{}.{}.in-addr.arpa", o[3], o[2], o[1], o[0])
+        }
+        IpAddr::V6(v6) => {
+            let nibbles: Vec<String> = v6
+                .octets()
+                .iter()
+                .rev()
+                .flat_map(|b| [b & 0x0f, b >> 4])
+                .map(|n| format!("{:x}", n))
+                .collect();
+            format!("{}.ip6.arpa", nibbles.join("."))
+        }
+    }
+}
+
+// Reverse lookups go to the configured resolvers; mDNS responders answer the
+// same PTR question sent straight to port 5353 of the host.
+pub async fn resolve_ptr(server: SocketAddr, ip: IpAddr, timeout: Duration) -> Option<String> {
+    dns_bench::query_udp(server, &reverse_name(ip), dns_bench::TYPE_PTR, timeout)
+        .await
+        .ok()
+        .and_then(|(_, answer)| answer.names.into_iter().next())
+        .map(|name| name.trim_end_matches('.').to_string())
+        .filter(|name| !name.is_empty())
+}
+
+pub fn nbstat_query(id: u16) -> Vec<u8> {
+    let mut out = Vec::with_capacity(50);
+    out.extend_from_slice(&id.to_be_bytes());
+    out.extend_from_slice(&[0, 0]);
+    out.extend_from_slice(&1u16.to_be_bytes());
+    out.extend_from_slice(&[0, 0, 0, 0, 0, 0]);
+    // "*" padded to 16 bytes, in NetBIOS first-level encoding.
+    let mut name = [0u8; 16];
+    name[0] = b'*';
+    out.push(32);
+    for byte in name {
+        out.push(b'A' + (byte >> 4));
+        out.push(b'A' + (byte & 0x0F));
+    }
+    out.push(0);
+    out.extend_from_slice(&NBSTAT_TYPE.to_be_bytes());
+    out.extend_from_slice(&1u16.to_be_bytes());
+    out
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> Result<u16, String> {
+    bytes
+        .get(offset..offset + 2)
+        .map(|b| u16::from_be_bytes([b[0], b[1]]))
+        .ok_or_else(|| "Truncated NetBIOS message".to_string())
+}
+
+fn skip_name(bytes: &[u8], mut offset: usize) -> Result<usize, String> {
+    loop {
+        let len = *bytes
+            .get(offset)
+            .ok_or_else(|| "Truncated NetBIOS name".to_string())?;
+        match len {
+            0 => return Ok(offset + 1),
+            l if l & 0xC0 == 0xC0 => return Ok(offset + 2),
+            l => offset += 1 + l as usize,
+        }
+    }
+}
+
+pub fn parse_nbstat(bytes: &[u8], expected_id: u16) -> Result<NetbiosInfo, String> {
+    if bytes.len() < 12 || read_u16(bytes, 0)? != expected_id {
+        return Err("Unexpected NetBIOS response".to_string());
+    }
+    if read_u16(bytes, 2)? & 0x8000 == 0 || read_u16(bytes, 6)? == 0 {
+        return Err("NetBIOS response has no answer".to_string());
+    }
+    let mut offset = 12;
+    for _ in 0..read_u16(bytes, 4)? {
+        offset = skip_name(bytes, offset)? + 4;
+    }
+    offset = skip_name(bytes, offset)?;
+    if read_u16(bytes, offset)? != NBSTAT_TYPE {
+        return Err("Not a NetBIOS node status response".to_string());
+    }
+    offset += 10;
+    let count = *bytes
+        .get(offset)
+        .ok_or_else(|| "Truncated NetBIOS message".to_string())? as usize;
+    offset += 1;
+
+    let mut info = NetbiosInfo::default();
+    for _ in 0..count {
+        let entry = bytes
+            .get(offset..offset + 18)
+            .ok_or_else(|| "Truncated NetBIOS name table".to_string())?;
+        let name = String::from_utf8_lossy(&entry[..15])
+            .trim_end_matches([' ', '\0'])
+            .to_string();
+        let group = entry[16] & 0x80 != 0;
+        if entry[15] == 0x00 && !name.is_empty() {
+            if group {
+                info.workgroup.get_or_insert(name);
+            } else {
+                info.name.get_or_insert(name);
+            }
+        }
+        offset += 18;
+    }
+    if let Some(unit) = bytes.get(offset..offset + 6) {
+        if unit.iter().any(|b| *b != 0) {
+            let mut mac = [0u8; 6];
+            mac.copy_from_slice(unit);
+            info.mac = Some(mac);
+        }
+    }
+    Ok(info)
+}
+
+pub async fn query_netbios(target: SocketAddr, timeout: Duration) -> Result<NetbiosInfo, String> {
+    let id: u16 = rand::random();
+    let socket = UdpSocket::bind("0.0.0.0:0")
+        .await
+        .map_err(|e| format!("Failed to open UDP socket: {}", e))?;
+    socket
+        .send_to(&nbstat_query(id), target)
+        .await
+        .map_err(|e| format!("Failed to send NetBIOS query: {}", e))?;
+    let deadline = Instant::now() + timeout;
+    let mut buf = [0u8; 1024];
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        let (len, from) = tokio::time::timeout(remaining, socket.recv_from(&mut buf))
+            .await
+            .map_err(|_| "NetBIOS query timed out".to_string())?
+            .map_err(|e| format!("NetBIOS receive failed: {}", e))?;
+        if from.ip() != target.ip() {
+            continue;
+        }
+        if let Ok(info) = parse_nbstat(&buf[..len], id) {
+            return Ok(info);
+        }
+    }
+}
+
+async fn probe_host(
+    ip: Ipv4Addr,
+    options: Arc<DiscoveryOptions>,
+    context: Arc<NetworkContext>,
+) -> Option<DiscoveredHost> {
+    let timeout = Duration::from_millis(options.timeout_ms.clamp(100, 10_000));
+    let ping_ports: Vec<u16> = if options.ping_ports.is_empty() {
+        DEFAULT_PING_PORTS.to_vec()
+    } else {
+        options.ping_ports.iter().copied().take(MAX_PORTS).collect()
+    };
+    let on_link = context.is_on_link(ip);
+
+    let icmp = async {
+        if !options.icmp {
+            return None;
+        }
+        let timeout_ms = timeout.as_millis() as u32;
+        tokio::task::spawn_blocking(move || sys::icmp_echo(ip, timeout_ms))
+            .await
+            .ok()
+            .flatten()
+    };
+    // SendARP answers with the gateway's MAC for off-link hosts, so only ask
+    // for addresses on an attached subnet.
+    let arp = async {
+        if !options.arp || !on_link {
+            return None;
+        }
+        tokio::task::spawn_blocking(move || sys::send_arp(ip))
+            .await
+            .ok()
+            .flatten()
+    };
+    let tcp = tcp_ping(ip, &ping_ports, timeout);
+    let (icmp, arp, tcp) = tokio::join!(icmp, arp, tcp);
+
+    let mut methods = Vec::new();
+    let mut latency = None;
+    if let Some(rtt) = icmp {
+        methods.push("icmp".to_string());
+        latency = Some(rtt);
+    }
+    if arp.is_some() {
+        methods.push("arp".to_string());
+    }
+    if let Some((port, rtt)) = tcp {
+        methods.push(format!("tcp:{}", port));
+        latency.get_or_insert(rtt);
+    }
+    let is_self = context.is_local_address(ip);
+    if methods.is_empty() && !is_self {
+        return None;
+    }
+
+    let mut names = Vec::new();
+    let mut netbios = NetbiosInfo::default();
+    if options.resolve_names {
+        let dns = async {
+            for server in context.dns_servers() {
+                if let Some(name) =
+                    resolve_ptr(SocketAddr::new(server, DNS_PORT), ip.into(), timeout).await
+                {
+                    return Some(name);
+                }
+            }
+            None
+        };
+        let mdns = resolve_ptr(SocketAddr::from((ip, MDNS_PORT)), ip.into(), timeout);
+        let nb = query_netbios(SocketAddr::from((ip, NETBIOS_PORT)), timeout);
+        let (dns, mdns, nb) = tokio::join!(dns, mdns, nb);
+        for (source, name) in [("dns", dns), ("mdns", mdns)] {
+            if let Some(name) = name {
+                names.push(HostName {
+                    source: source.to_string(),
+                    name,
+                });
+            }
+        }
+        if let Ok(info) = nb {
+            if let Some(name) = &info.name {
+                names.push(HostName {
+                    source: "netbios".to_string(),
+                    name: name.clone(),
+                });
+            }
+            netbios = info;
+        }
+    }
+
+    let open_ports = if options.probe_ports {
+        let ports: Vec<u16> = if options.ports.is_empty() {
+            COMMON_PORTS.to_vec()
+        } else {
+            options.ports.iter().copied().take(MAX_PORTS).collect()
+        };
+        probe_ports(IpAddr::V4(ip), &ports, timeout).await
+    } else {
+        Vec::new()
+    };
+
+    let info = arp.or(netbios.mac).map(|mac| oui::identify_mac(&mac));
+    Some(DiscoveredHost {
+        ip: ip.to_string(),
+        mac: info.as_ref().map(|i| i.mac.clone()),
+        vendor: info.as_ref().and_then(|i| i.vendor.clone()),
+        category: if context.is_gateway(ip) {
+            oui::DeviceCategory::NetworkEquipment
+        } else {
+            info.as_ref()
+                .map(|i| i.category)
+                .unwrap_or(oui::DeviceCategory::Unknown)
+        },
+        randomized_mac: info.as_ref().is_some_and(|i| i.randomized),
+        hostname: names.first().map(|n| n.name.clone()),
+        names,
+        workgroup: netbios.workgroup,
+        methods,
+        latency_ms: latency,
+        open_ports,
+        is_gateway: context.is_gateway(ip),
+        is_self,
+    })
+}
+
+pub async fn discover<F>(
+    options: DiscoveryOptions,
+    context: NetworkContext,
+    targets: Vec<Ipv4Addr>,
+    cancelled: &AtomicBool,
+    mut on_event: F,
+) -> DiscoverySummary
+where
+    F: FnMut(DiscoveryEvent),
+{
+    let started = Instant::now();
+    let total = targets.len();
+    let semaphore = Arc::new(Semaphore::new(
+        options.concurrency.clamp(1, MAX_CONCURRENCY),
+    ));
+    let options = Arc::new(options);
+    let context = Arc::new(context);
+
+    let mut tasks = JoinSet::new();
+    for ip in targets {
+        let semaphore = semaphore.clone();
+        let options = options.clone();
+        let context = context.clone();
+        tasks.spawn(async move {
+            let _permit = semaphore.acquire_owned().await.ok()?;
+            probe_host(ip, options, context).await
+        });
+    }
+
+    let mut scanned = 0;
+    let mut alive = 0;
+    while let Some(joined) = tasks.join_next().await {
+        if cancelled.load(Ordering::SeqCst) {
+            tasks.abort_all();
+            break;
+        }
+        scanned += 1;
+        if let Ok(Some(host)) = joined {
+            alive += 1;
+            on_event(DiscoveryEvent::Host(host));
+        }
+        if scanned % PROGRESS_EVERY == 0 || scanned == total {
+            on_event(DiscoveryEvent::Progress { scanned, total });
+        }
+    }
+
+    DiscoverySummary {
+        scanned,
+        alive,
+        cancelled: cancelled.load(Ordering::SeqCst),
+        elapsed_ms: started.elapsed().as_millis() as u64,
+    }
+}
+
+#[cfg(windows)]
+mod sys {
+    use std::ffi::c_void;
+    use std::net::Ipv4Addr;
+    use std::time::Instant;
+    use winapi::shared::minwindef::FARPROC;
+    use winapi::um::libloaderapi::{GetProcAddress, LoadLibraryW};
+
+    type IcmpCreateFileFn = unsafe extern "system" fn() -> *mut c_void;
+    type IcmpCloseHandleFn = unsafe extern "system" fn(*mut c_void) -> i32;
+    type IcmpSendEchoFn = unsafe extern "system" fn(
+        *mut c_void,
+        u32,
+        *mut c_void,
+        u16,
+        *mut c_void,
+        *mut c_void,
+        u32,
+        u32,
+    ) -> u32;
+    type SendArpFn = unsafe extern "system" fn(u32, u32, *mut c_void, *mut u32) -> u32;
+
+    struct IpHelper {
+        icmp_create_file: IcmpCreateFileFn,
+        icmp_close_handle: IcmpCloseHandleFn,
+        icmp_send_echo: IcmpSendEchoFn,
+        send_arp: SendArpFn,
+    }
+
+    lazy_static::lazy_static! {
+        static ref IP_HELPER: Option<IpHelper> = unsafe { load() };
+    }
+
+    unsafe fn load() -> Option<IpHelper> {
+        let name: Vec<u16> = "iphlpapi.dll\0".encode_utf16().collect();
+        let module = LoadLibraryW(name.as_ptr());
+        if module.is_null() {
+            return None;
+        }
+        let proc = |name: &str| {
+            let ptr = GetProcAddress(module, name.as_ptr() as *const i8);
+            (!ptr.is_null()).then_some(ptr)
+        };
+        Some(IpHelper {
+            icmp_create_file: std::mem::transmute::<FARPROC, IcmpCreateFileFn>(proc(
+                "IcmpCreateFile\0",
+            )?),
+            icmp_close_handle: std::mem::transmute::<FARPROC, IcmpCloseHandleFn>(proc(
+                "IcmpCloseHandle\0",
+            )?),
+            icmp_send_echo: std::mem::transmute::<FARPROC, IcmpSendEchoFn>(proc("IcmpSendEcho\0")?),
+            send_arp: std::mem::transmute::<FARPROC, SendArpFn>(proc("SendARP\0")?),
+        })
+    }
+
+    // IPAddr is the address in network byte order.
+    fn ip_addr(ip: Ipv4Addr) -> u32 {
+        u32::from_ne_bytes(ip.octets())
+    }
+
+    pub fn icmp_echo(ip: Ipv4Addr, timeout_ms: u32) -> Option<f64> {
+        let api = IP_HELPER.as_ref()?;
+        unsafe {
+            let handle = (api.icmp_create_file)();
+            if handle.is_null() || handle as isize == -1 {
+                return None;
+            }
+            let mut payload = *b"ConfUtils discovery";
+            // ICMP_ECHO_REPLY plus payload; u64 keeps the buffer aligned.
+            let mut reply = [0u64; 32];
+            let started = Instant::now();
+            let count = (api.icmp_send_echo)(
+                handle,
+                ip_addr(ip),
+                payload.as_mut_ptr() as *mut c_void,
+                payload.len() as u16,
+                std::ptr::null_mut(),
+                reply.as_mut_ptr() as *mut c_void,
+                std::mem::size_of_val(&reply) as u32,
+                timeout_ms,
+            );
+            let elapsed = started.elapsed();
+            (api.icmp_close_handle)(handle);
+            // Address and Status are the first two fields of ICMP_ECHO_REPLY.
+            let address = reply[0] as u32;
+            let status = (reply[0] >> 32) as u32;
+            (count > 0 && status == 0 && address == ip_addr(ip))
+                .then(|| (elapsed.as_secs_f64() * 100_000.0).round() / 100.0)
+        }
+    }
+
+    pub fn send_arp(ip: Ipv4Addr) -> Option<[u8; 6]> {
+        let api = IP_HELPER.as_ref()?;
+        let mut buffer = [0u32; 2];
+        let mut len = 6u32;
+        let result =
+            unsafe { (api.send_arp)(ip_addr(ip), 0, buffer.as_mut_ptr() as *mut c_void, &mut len) };
+        if result != 0 || len < 6 {
+            return None;
+        }
+        let bytes: Vec<u8> = buffer.iter().flat_map(|w| w.to_ne_bytes()).collect();
+        let mut mac = [0u8; 6];
+        mac.copy_from_slice(&bytes[..6]);
+        mac.iter().any(|b| *b != 0).then_some(mac)
+    }
+}
+
+#[cfg(not(windows))]
+mod sys {
+    use std::net::Ipv4Addr;
+
+    pub fn icmp_echo(_ip: Ipv4Addr, _timeout_ms: u32) -> Option<f64> {
+        None
+    }
+
+    pub fn send_arp(_ip: Ipv4Addr) -> Option<[u8; 6]> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    fn ips(list: &[&str]) -> Vec<Ipv4Addr> {
+        list.iter().map(|s| s.parse().unwrap()).collect()
+    }
+
+    fn name_entry(name: &str, suffix: u8, flags: [u8; 2]) -> Vec<u8> {
+        let mut entry = format!("{:<15}", name).into_bytes();
+        entry.push(suffix);
+        entry.extend_from_slice(&flags);
+        entry
+    }
+
+    // Node status reply as sent by a Windows host: the "*" question name is
+    // echoed back, followed by the name table and the adapter MAC.
+    fn nbstat_fixture(id: u16, mac: [u8; 6]) -> Vec<u8> {
+        let query = nbstat_query(id);
+        let mut out = Vec::new();
+        out.extend_from_slice(&id.to_be_bytes());
+        out.extend_from_slice(&[0x84, 0x00, 0, 0, 0, 1, 0, 0, 0, 0]);
+        out.extend_from_slice(&query[12..query.len() - 4]);
+        out.extend_from_slice(&NBSTAT_TYPE.to_be_bytes());
+        out.extend_from_slice(&[0, 1, 0, 0, 0, 0]);
+        let entries = [
+            name_entry("DESKTOP-7Q2B", 0x00, [0x04, 0x00]),
+            name_entry("WORKGROUP", 0x00, [0x84, 0x00]),
+            name_entry("DESKTOP-7Q2B", 0x20, [0x04, 0x00]),
+            name_entry("WORKGROUP", 0x1E, [0x84, 0x00]),
+        ];
+        let mut rdata = vec![entries.len() as u8];
+        rdata.extend(entries.concat());
+        rdata.extend_from_slice(&mac);
+        rdata.extend_from_slice(&[0u8; 40]);
+        out.extend_from_slice(&(rdata.len() as u16).to_be_bytes());
+        out.extend(rdata);
+        out
+    }
+
+    #[test]
+    fn parse_targets_accepts_mixed_specs() {
+        assert_eq!(
+            parse_targets("10.0.0.5, 10.0.0.1-3,10.0.0.2").unwrap(),
+            ips(&["10.0.0.5", "10.0.0.1", "10.0.0.2", "10.0.0.3"])
+        );
+        assert_eq!(
+            parse_targets("10.0.0.254-10.0.1.1").unwrap(),
+            ips(&["10.0.0.254", "10.0.0.255", "10.0.1.0", "10.0.1.1"])
+        );
+        assert_eq!(parse_targets("192.168.1.77/24").unwrap().len(), 254);
+        assert_eq!(parse_targets("172.16.0.0/20").unwrap().len(), 4094);
+    }
+
+    #[test]
+    fn parse_targets_rejects_bad_input() {
+        assert!(parse_targets("").is_err());
+        assert!(parse_targets(" , ").is_err());
+        assert!(parse_targets("10.0.0.300").is_err());
+        assert!(parse_targets("10.0.0.9-3").is_err());
+        assert!(parse_targets("10.0.0.0/8").is_err());
+        assert!(parse_targets("10.0.0.0-10.0.32.0").is_err());
+        assert!(parse_targets("10.0.0.0/20,10.1.0.0/24").is_err());
+    }
+
+    #[test]
+    fn subnet_hosts_skips_network_and_broadcast() {
+        assert_eq!(
+            subnet_hosts("192.168.1.77".parse().unwrap(), 30),
+            ips(&["192.168.1.77", "192.168.1.78"])
+        );
+        assert_eq!(
+            subnet_hosts("192.168.1.77".parse().unwrap(), 31),
+            ips(&["192.168.1.76", "192.168.1.77"])
+        );
+        assert_eq!(
+            subnet_hosts("192.168.1.77".parse().unwrap(), 32),
+            ips(&["192.168.1.77"])
+        );
+        let hosts = subnet_hosts("192.168.1.77".parse().unwrap(), 24);
+        assert_eq!(hosts.first(), Some(&"192.168.1.1".parse().unwrap()));
+        assert_eq!(hosts.last(), Some(&"192.168.1.254".parse().unwrap()));
+    }
+
+    #[test]
+    fn targets_narrow_wide_subnets() {
+        let context = NetworkContext {
+            addresses: vec![
+                LocalAddress {
+                    address: "10.20.30.40".to_string(),
+                    prefix: 16,
+                },
+                LocalAddress {
+                    address: "169.254.1.1".to_string(),
+                    prefix: 16,
+                },
+            ],
+            ..Default::default()
+        };
+        let options: DiscoveryOptions = serde_json::from_str("{}").unwrap();
+        let list = targets(&options, &context).unwrap();
+        assert_eq!(list.len(), 254);
+        assert_eq!(list[0], "10.20.30.1".parse::<Ipv4Addr>().unwrap());
+        assert!(targets(&options, &NetworkContext::default()).is_err());
+    }
+
+    #[test]
+    fn targets_share_the_host_limit_between_adapters() {
+        let context = |addresses: &[&str]| NetworkContext {
+            addresses: addresses
+                .iter()
+                .map(|a| LocalAddress {
+                    address: a.to_string(),
+                    prefix: 22,
+                })
+                .collect(),
+            ..Default::default()
+        };
+        let options: DiscoveryOptions = serde_json::from_str("{}").unwrap();
+
+        let two = targets(&options, &context(&["10.0.0.10", "10.8.4.200"])).unwrap();
+        assert_eq!(two.len(), 2 * 1022);
+        assert_eq!(two[0], "10.0.0.1".parse::<Ipv4Addr>().unwrap());
+        assert!(two.contains(&"10.8.7.254".parse().unwrap()));
+
+        let many = [
+            "10.0.0.10",
+            "10.1.0.10",
+            "10.2.0.10",
+            "10.3.1.200",
+            "10.4.0.10",
+            "10.5.0.10",
+        ];
+        let list = targets(&options, &context(&many)).unwrap();
+        let share = MAX_HOSTS / many.len();
+        assert_eq!(list.len(), many.len() * share);
+        let fourth: Vec<_> = list
+            .iter()
+            .filter(|ip| ip.octets()[1] == 3)
+            .copied()
+            .collect();
+        assert_eq!(fourth.len(), share);
+        assert!(fourth.contains(&"10.3.1.200".parse().unwrap()));
+        assert!(fourth.windows(2).all(|w| w[0] < w[1]));
+        let spread = u32::from(fourth[share - 1]) - u32::from(fourth[0]);
+        assert!(spread as usize <= share + 1);
+
+        // Overlapping adapters are only scanned once.
+        let overlap = targets(&options, &context(&["10.0.0.10", "10.0.1.20"])).unwrap();
+        assert_eq!(overlap.len(), 1022);
+    }
+
+    #[test]
+    fn parse_nbstat_reads_name_table() {
+        let mac = [0x00, 0x15, 0x5d, 0x01, 0x02, 0x03];
+        let info = parse_nbstat(&nbstat_fixture(0x1234, mac), 0x1234).unwrap();
+        assert_eq!(
+            info,
+            NetbiosInfo {
+                name: Some("DESKTOP-7Q2B".to_string()),
+                workgroup: Some("WORKGROUP".to_string()),
+                mac: Some(mac),
+            }
+        );
+        // Samba reports an all-zero unit id.
+        let info = parse_nbstat(&nbstat_fixture(1, [0; 6]), 1).unwrap();
+        assert_eq!(info.mac, None);
+    }
+
+    #[test]
+    fn parse_nbstat_rejects_bad_replies() {
+        let reply = nbstat_fixture(0x1234, [1; 6]);
+        assert!(parse_nbstat(&reply, 0x4321).is_err());
+        assert!(parse_nbstat(&nbstat_query(0x1234), 0x1234).is_err());
+        assert_eq!(
+            parse_nbstat(&reply[..120], 0x1234).unwrap_err(),
+            "Truncated NetBIOS name table"
+        );
+        let mut wrong_type = reply.clone();
+        wrong_type[46] = 0x20;
+        assert_eq!(
+            parse_nbstat(&wrong_type, 0x1234).unwrap_err(),
+            "Not a NetBIOS node status response"
+        );
+    }
+
+    #[test]
+    fn reverse_name_formats_both_families() {
+        assert_eq!(
+            reverse_name("192.168.1.20".parse().unwrap()),
+            "20.1.168.192.in-addr.arpa"
+        );
+        assert!(reverse_name("2001:db8::1".parse().unwrap())
+            .starts_with("1.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.8.b.d.0.1.0.0.2"));
+    }
+
+    async fn closed_port() -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        listener.local_addr().unwrap().port()
+    }
+
+    #[tokio::test]
+    async fn tcp_ping_counts_open_and_refused_ports() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let open = listener.local_addr().unwrap().port();
+        let closed = closed_port().await;
+        let timeout = Duration::from_secs(2);
+
+        let hit = tcp_ping(Ipv4Addr::LOCALHOST, &[open], timeout).await;
+        assert_eq!(hit.map(|(port, _)| port), Some(open));
+        let hit = tcp_ping(Ipv4Addr::LOCALHOST, &[closed], timeout).await;
+        assert_eq!(hit.map(|(port, _)| port), Some(closed));
+        assert!(tcp_ping(Ipv4Addr::LOCALHOST, &[], timeout).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn probe_ports_reports_only_listening_ports() {
+        let first = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let second = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mut expected = vec![
+            first.local_addr().unwrap().port(),
+            second.local_addr().unwrap().port(),
+        ];
+        expected.sort_unstable();
+        let closed = closed_port().await;
+
+        let open = probe_ports(
+            IpAddr::V4(Ipv4Addr::LOCALHOST),
+            &[expected[1], closed, expected[0]],
+            Duration::from_secs(2),
+        )
+        .await;
+        assert_eq!(open, expected);
+    }
+}
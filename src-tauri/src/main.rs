@@ -1,10 +1,33 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+mod allowlist;
 mod anti_debug;
 mod commands;
+mod connectivity;
+mod dns;
+mod dns_bench;
+mod firewall;
+mod hosts;
+mod hosts_subscriptions;
 mod hwid;
+mod lan_discovery;
+mod net_monitor;
+mod network_snapshot;
+mod oui;
+mod policy_file;
+mod port_scan;
+mod privacy_firewall;
+mod registry;
+mod registry_search;
+mod registry_watch;
 mod security;
+mod service_baseline;
+mod service_deps;
+mod service_recovery;
+mod service_triggers;
+mod services;
 mod taskbar;
+mod util;
 
 use obfstr::obfstr;
 use tauri::menu::{Menu, MenuItem};
@@ -33,6 +56,8 @@ fn main() {
                 }
                 std::thread::sleep(std::time::Duration::from_secs(60));
             });
+            tauri::async_runtime::spawn(hosts_subscriptions::run_scheduler());
+            tauri::async_runtime::spawn(privacy_firewall::run_scheduler());
 
             let show_i = MenuItem::with_id(
                 app,
@@ -90,6 +115,9 @@ fn main() {
         })
         .invoke_handler(tauri::generate_handler![
             commands::check_online_status,
+            commands::check_connectivity,
+            commands::get_connectivity_probes,
+            commands::set_connectivity_probes,
             commands::run_powershell,
             commands::start_service,
             commands::stop_service,
@@ -103,6 +131,51 @@ fn main() {
             commands::write_registry,
             commands::delete_registry_value,
             commands::delete_registry_key,
+            commands::list_registry_subkeys,
+            commands::list_registry_values,
+            commands::start_registry_search,
+            commands::cancel_registry_search,
+            commands::start_registry_watch,
+            commands::stop_registry_watch,
+            commands::get_registry_watch_status,
+            commands::get_registry_audit_log,
+            commands::read_policy_file,
+            commands::check_policy_conflicts,
+            commands::apply_policy_tweaks,
+            commands::list_registry_backups,
+            commands::restore_registry_backup,
+            commands::delete_registry_backup,
+            commands::create_service_snapshot,
+            commands::list_service_snapshots,
+            commands::diff_service_snapshot,
+            commands::restore_service_snapshot,
+            commands::delete_service_snapshot,
+            commands::get_service_impact,
+            commands::stop_services,
+            commands::start_services,
+            commands::get_service_triggers,
+            commands::remove_service_triggers,
+            commands::get_service_failure_actions,
+            commands::set_service_failure_actions,
+            commands::reset_service_failure_actions,
+            commands::undo_service_failure_actions,
+            commands::compare_services_to_baseline,
+            commands::restore_service_defaults,
+            commands::get_hosts_file,
+            commands::list_hosts_subscriptions,
+            commands::add_hosts_subscription,
+            commands::remove_hosts_subscription,
+            commands::set_hosts_subscription_enabled,
+            commands::refresh_hosts_subscriptions,
+            commands::get_allowlist,
+            commands::add_allowlist_entry,
+            commands::remove_allowlist_entry,
+            commands::list_firewall_rules,
+            commands::create_firewall_rule,
+            commands::update_firewall_rule,
+            commands::set_firewall_rule_enabled,
+            commands::delete_firewall_rule,
+            commands::block_program_internet,
             commands::get_system_info,
             commands::get_disk_usage,
             commands::check_windows_updates,
@@ -120,6 +193,10 @@ fn main() {
             commands::get_disk_info,
             commands::get_battery_status,
             commands::get_network_stats,
+            commands::start_network_monitor,
+            commands::stop_network_monitor,
+            commands::get_network_monitor_status,
+            commands::get_network_usage_history,
             commands::get_uptime,
             commands::get_detailed_specs,
             commands::check_ssd_health,
@@ -150,6 +227,9 @@ fn main() {
             commands::apply_privacy_firewall_rules,
             commands::remove_privacy_firewall_rules,
             commands::get_privacy_firewall_status,
+            commands::get_privacy_firewall_history,
+            commands::refresh_privacy_firewall,
+            commands::set_privacy_firewall_interval,
             commands::open_device_manager,
             commands::scan_device_issues,
             commands::scan_outdated_drivers,
@@ -159,6 +239,7 @@ fn main() {
             commands::run_privacy_audit,
             commands::scan_hidden_services,
             commands::scan_open_ports,
+            commands::apply_port_remediation,
             commands::analyze_junk_origins,
             commands::apply_power_audio_optimizations,
             commands::revert_power_audio_optimizations,
@@ -186,6 +267,18 @@ fn main() {
             commands::disable_copilot,
             commands::disable_notification_tray,
             commands::set_dns,
+            commands::list_dns_providers,
+            commands::get_dns_configuration,
+            commands::set_adapter_dns,
+            commands::reset_adapter_dns,
+            commands::set_dns_over_https,
+            commands::get_dns_encryption_status,
+            commands::run_dns_benchmark,
+            commands::create_network_snapshot,
+            commands::list_network_snapshots,
+            commands::diff_network_snapshot,
+            commands::restore_network_snapshot,
+            commands::delete_network_snapshot,
             commands::remove_all_store_apps,
             commands::remove_edge,
             commands::set_classic_right_click,
@@ -240,6 +333,10 @@ fn main() {
             commands::force_exit,
             commands::get_wifi_passwords,
             commands::get_connected_devices,
+            commands::get_oui_database_info,
+            commands::update_oui_database,
+            commands::start_lan_discovery,
+            commands::cancel_lan_discovery,
             commands::get_active_connections,
             commands::disconnect_network,
             commands::get_router_info,
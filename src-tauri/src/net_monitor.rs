@@ -0,0 +1,928 @@
+use serde::Serialize;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::task::JoinSet;
+
+use crate::lan_discovery;
+use crate::util;
+
+pub const DEFAULT_INTERVAL_MS: u64 = 2000;
+const MIN_INTERVAL_MS: u64 = 500;
+const MAX_INTERVAL_MS: u64 = 60_000;
+const HISTORY_MAX_POINTS: usize = 600;
+const HOSTNAME_CACHE_MAX: usize = 4096;
+const RESOLVE_PER_TICK: usize = 32;
+const RESOLVE_TIMEOUT: Duration = Duration::from_millis(800);
+const DNS_PORT: u16 = 53;
+const STATE_LISTEN: u32 = 2;
+const SYSTEM_PID: u32 = 4;
+
+lazy_static::lazy_static! {
+    static ref MONITOR: Mutex<Option<MonitorHandle>> = Mutex::new(None);
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RawConnection {
+    pub pid: u32,
+    pub local: SocketAddr,
+    pub remote: SocketAddr,
+    pub state: u32,
+    // (sent, received) data bytes; None when extended statistics are not
+    // available, which is the case without elevation.
+    pub bytes: Option<(u64, u64)>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ConnectionInfo {
+    pub local_address: String,
+    pub local_port: u16,
+    pub remote_address: String,
+    pub remote_port: u16,
+    pub state: &'static str,
+    pub hostname: Option<String>,
+    pub bytes_sent: Option<u64>,
+    pub bytes_received: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ProcessUsage {
+    pub pid: u32,
+    pub name: String,
+    pub path: Option<String>,
+    pub send_rate: f64,
+    pub receive_rate: f64,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    pub connections: Vec<ConnectionInfo>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct UsageSample {
+    pub timestamp_ms: u64,
+    pub interval_ms: u64,
+    pub measured: bool,
+    // Windows keeps no per-socket byte counters for UDP, so rates and
+    // connection lists only cover TCP traffic.
+    pub tcp_only: bool,
+    pub send_rate: f64,
+    pub receive_rate: f64,
+    pub processes: Vec<ProcessUsage>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ProcessRate {
+    pub pid: u32,
+    pub name: String,
+    pub send_rate: f64,
+    pub receive_rate: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HistoryPoint {
+    pub timestamp_ms: u64,
+    pub processes: Vec<ProcessRate>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MonitorStatus {
+    pub running: bool,
+    pub interval_ms: Option<u64>,
+    pub started_unix: Option<u64>,
+    pub samples: u64,
+    pub measured: bool,
+}
+
+pub trait ConnectionSource {
+    fn connections(&self) -> Vec<RawConnection>;
+    fn process_image(&self, pid: u32) -> Option<String>;
+}
+
+struct MonitorHandle {
+    stop: Arc<AtomicBool>,
+    interval_ms: u64,
+    started_unix: u64,
+    samples: Arc<AtomicU64>,
+    measured: Arc<AtomicBool>,
+    history: Arc<Mutex<VecDeque<HistoryPoint>>>,
+}
+
+type ConnectionKey = (u32, SocketAddr, SocketAddr);
+
+#[derive(Debug, Default)]
+pub struct UsageTracker {
+    counters: HashMap<ConnectionKey, (u64, u64)>,
+    totals: HashMap<u32, (u64, u64)>,
+    images: HashMap<u32, Option<String>>,
+    hostnames: HashMap<IpAddr, Option<String>>,
+}
+
+pub fn clamp_interval(interval_ms: Option<u64>) -> Duration {
+    Duration::from_millis(
+        interval_ms
+            .unwrap_or(DEFAULT_INTERVAL_MS)
+            .clamp(MIN_INTERVAL_MS, MAX_INTERVAL_MS),
+    )
+}
+
+pub fn state_name(state: u32) -> &'static str {
+    match state {
+        1 => "Closed",
+        2 => "Listen",
+        3 => "SynSent",
+        4 => "SynReceived",
+        5 => "Established",
+        6 => "FinWait1",
+        7 => "FinWait2",
+        8 => "CloseWait",
+        9 => "Closing",
+        10 => "LastAck",
+        11 => "TimeWait",
+        12 => "DeleteTcb",
+        _ => "Unknown",
+    }
+}
+
+fn is_resolvable(ip: IpAddr) -> bool {
+    !ip.is_loopback() && !ip.is_unspecified() && !ip.is_multicast()
+}
+
+fn rate(bytes: u64, seconds: f64) -> f64 {
+    (bytes as f64 / seconds).round()
+}
+
+fn process_name(pid: u32, image: Option<&str>) -> String {
+    match image {
+        Some(path) => path.rsplit(['\\', '/']).next().unwrap_or(path).to_string(),
+        None if pid == SYSTEM_PID => "System".to_string(),
+        None => format!("PID {}", pid),
+    }
+}
+
+impl UsageTracker {
+    pub fn known_pids(&self) -> HashSet<u32> {
+        self.images.keys().copied().collect()
+    }
+
+    pub fn record_images(&mut self, images: Vec<(u32, Option<String>)>) {
+        self.images.extend(images);
+    }
+
+    pub fn pending_lookups(&self, connections: &[RawConnection]) -> Vec<IpAddr> {
+        let mut pending = Vec::new();
+        for conn in connections {
+            let ip = conn.remote.ip();
+            if conn.state != STATE_LISTEN
+                && is_resolvable(ip)
+                && !self.hostnames.contains_key(&ip)
+                && !pending.contains(&ip)
+            {
+                pending.push(ip);
+                if pending.len() == RESOLVE_PER_TICK {
+                    break;
+                }
+            }
+        }
+        pending
+    }
+
+    pub fn record_hostname(&mut self, ip: IpAddr, name: Option<String>) {
+        if self.hostnames.len() >= HOSTNAME_CACHE_MAX {
+            self.hostnames.clear();
+        }
+        self.hostnames.insert(ip, name);
+    }
+
+    // A connection seen for the first time only sets the baseline; bytes a
+    // connection moves after the last sample and before it closes are lost.
+    pub fn update(
+        &mut self,
+        connections: &[RawConnection],
+        elapsed: Duration,
+        timestamp_ms: u64,
+    ) -> UsageSample {
+        let seconds = elapsed.as_secs_f64().max(0.001);
+        let mut counters = HashMap::new();
+        let mut deltas: HashMap<u32, (u64, u64)> = HashMap::new();
+        let mut processes: HashMap<u32, ProcessUsage> = HashMap::new();
+        let mut measured = false;
+
+        for conn in connections {
+            if conn.pid == 0 || conn.state == STATE_LISTEN {
+                continue;
+            }
+            let usage = processes.entry(conn.pid).or_insert_with(|| {
+                let image = self.images.get(&conn.pid).cloned().flatten();
+                ProcessUsage {
+                    pid: conn.pid,
+                    name: process_name(conn.pid, image.as_deref()),
+                    path: image,
+                    send_rate: 0.0,
+                    receive_rate: 0.0,
+                    bytes_sent: 0,
+                    bytes_received: 0,
+                    connections: Vec::new(),
+                }
+            });
+            if let Some((sent, received)) = conn.bytes {
+                measured = true;
+                let key = (conn.pid, conn.local, conn.remote);
+                if let Some((prev_sent, prev_received)) = self.counters.get(&key) {
+                    // Counters restart when a connection tuple is reused.
+                    let delta = deltas.entry(conn.pid).or_default();
+                    delta.0 += sent.checked_sub(*prev_sent).unwrap_or(sent);
+                    delta.1 += received.checked_sub(*prev_received).unwrap_or(received);
+                }
+                counters.insert(key, (sent, received));
+            }
+            usage.connections.push(ConnectionInfo {
+                local_address: conn.local.ip().to_string(),
+                local_port: conn.local.port(),
+                remote_address: conn.remote.ip().to_string(),
+                remote_port: conn.remote.port(),
+                state: state_name(conn.state),
+                hostname: self.hostnames.get(&conn.remote.ip()).cloned().flatten(),
+                bytes_sent: conn.bytes.map(|b| b.0),
+                bytes_received: conn.bytes.map(|b| b.1),
+            });
+        }
+        self.counters = counters;
+        // Listen-only processes keep their image so it is not looked up again
+        // on every tick.
+        let live: HashSet<u32> = connections.iter().map(|c| c.pid).collect();
+        self.images.retain(|pid, _| live.contains(pid));
+        self.totals.retain(|pid, _| processes.contains_key(pid));
+
+        let mut send_total = 0;
+        let mut receive_total = 0;
+        for (pid, usage) in processes.iter_mut() {
+            let (sent, received) = deltas.get(pid).copied().unwrap_or_default();
+            let total = self.totals.entry(*pid).or_default();
+            total.0 += sent;
+            total.1 += received;
+            usage.bytes_sent = total.0;
+            usage.bytes_received = total.1;
+            usage.send_rate = rate(sent, seconds);
+            usage.receive_rate = rate(received, seconds);
+            send_total += sent;
+            receive_total += received;
+        }
+
+        let mut processes: Vec<ProcessUsage> = processes.into_values().collect();
+        processes.sort_by(|a, b| {
+            (b.send_rate + b.receive_rate)
+                .total_cmp(&(a.send_rate + a.receive_rate))
+                .then(b.connections.len().cmp(&a.connections.len()))
+                .then(a.name.to_lowercase().cmp(&b.name.to_lowercase()))
+        });
+        UsageSample {
+            timestamp_ms,
+            interval_ms: elapsed.as_millis() as u64,
+            measured,
+            tcp_only: true,
+            send_rate: rate(send_total, seconds),
+            receive_rate: rate(receive_total, seconds),
+            processes,
+        }
+    }
+}
+
+impl HistoryPoint {
+    fn from_sample(sample: &UsageSample) -> Self {
+        HistoryPoint {
+            timestamp_ms: sample.timestamp_ms,
+            processes: sample
+                .processes
+                .iter()
+                .filter(|p| p.send_rate > 0.0 || p.receive_rate > 0.0)
+                .map(|p| ProcessRate {
+                    pid: p.pid,
+                    name: p.name.clone(),
+                    send_rate: p.send_rate,
+                    receive_rate: p.receive_rate,
+                })
+                .collect(),
+        }
+    }
+}
+
+async fn resolve_hostname(servers: &[IpAddr], ip: IpAddr) -> Option<String> {
+    for server in servers {
+        let name =
+            lan_discovery::resolve_ptr(SocketAddr::new(*server, DNS_PORT), ip, RESOLVE_TIMEOUT)
+                .await;
+        if name.is_some() {
+            return name;
+        }
+    }
+    None
+}
+
+pub async fn run_monitor<S, F>(
+    source: S,
+    dns_servers: Vec<IpAddr>,
+    stop: Arc<AtomicBool>,
+    interval: Duration,
+    mut on_sample: F,
+) where
+    S: ConnectionSource + Clone + Send + 'static,
+    F: FnMut(UsageSample),
+{
+    let mut tracker = UsageTracker::default();
+    let mut last = Instant::now();
+    while !stop.load(Ordering::SeqCst) {
+        let known = tracker.known_pids();
+        let worker = source.clone();
+        let collected = tokio::task::spawn_blocking(move || {
+            let connections = worker.connections();
+            let new_pids: HashSet<u32> = connections
+                .iter()
+                .map(|c| c.pid)
+                .filter(|pid| *pid != 0 && !known.contains(pid))
+                .collect();
+            let images: Vec<(u32, Option<String>)> = new_pids
+                .into_iter()
+                .map(|pid| (pid, worker.process_image(pid)))
+                .collect();
+            (connections, images)
+        })
+        .await;
+        let Ok((connections, images)) = collected else {
+            break;
+        };
+        tracker.record_images(images);
+
+        if !dns_servers.is_empty() {
+            let mut lookups = JoinSet::new();
+            for ip in tracker.pending_lookups(&connections) {
+                let servers = dns_servers.clone();
+                lookups.spawn(async move { (ip, resolve_hostname(&servers, ip).await) });
+            }
+            while let Some(result) = lookups.join_next().await {
+                if let Ok((ip, name)) = result {
+                    tracker.record_hostname(ip, name);
+                }
+            }
+        }
+        if stop.load(Ordering::SeqCst) {
+            break;
+        }
+
+        let now = Instant::now();
+        on_sample(tracker.update(&connections, now - last, util::now_unix_millis()));
+        last = now;
+        tokio::time::sleep(interval).await;
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct WindowsConnections;
+
+impl ConnectionSource for WindowsConnections {
+    fn connections(&self) -> Vec<RawConnection> {
+        sys::tcp_connections()
+    }
+
+    fn process_image(&self, pid: u32) -> Option<String> {
+        sys::process_image(pid)
+    }
+}
+
+pub fn start<F>(dns_servers: Vec<IpAddr>, interval: Duration, on_sample: F) -> Result<(), String>
+where
+    F: Fn(&UsageSample) + Send + 'static,
+{
+    stop();
+    let stop_flag = Arc::new(AtomicBool::new(false));
+    let samples = Arc::new(AtomicU64::new(0));
+    let measured = Arc::new(AtomicBool::new(false));
+    let history = Arc::new(Mutex::new(VecDeque::new()));
+    {
+        let mut monitor = MONITOR
+            .lock()
+            .map_err(|_| "Network monitor state is poisoned".to_string())?;
+        *monitor = Some(MonitorHandle {
+            stop: stop_flag.clone(),
+            interval_ms: interval.as_millis() as u64,
+            started_unix: util::now_unix(),
+            samples: samples.clone(),
+            measured: measured.clone(),
+            history: history.clone(),
+        });
+    }
+
+    tokio::spawn(run_monitor(
+        WindowsConnections,
+        dns_servers,
+        stop_flag,
+        interval,
+        move |sample| {
+            samples.fetch_add(1, Ordering::SeqCst);
+            measured.store(sample.measured, Ordering::SeqCst);
+            if let Ok(mut points) = history.lock() {
+                if points.len() >= HISTORY_MAX_POINTS {
+                    points.pop_front();
+                }
+                points.push_back(HistoryPoint::from_sample(&sample));
+            }
+            on_sample(&sample);
+        },
+    ));
+    Ok(())
+}
+
+pub fn stop() -> bool {
+    let handle = MONITOR.lock().ok().and_then(|mut m| m.take());
+    match handle {
+        Some(h) => {
+            h.stop.store(true, Ordering::SeqCst);
+            true
+        }
+        None => false,
+    }
+}
+
+pub fn status() -> MonitorStatus {
+    let monitor = MONITOR.lock().ok();
+    match monitor.as_ref().and_then(|m| m.as_ref()) {
+        Some(h) => MonitorStatus {
+            running: true,
+            interval_ms: Some(h.interval_ms),
+            started_unix: Some(h.started_unix),
+            samples: h.samples.load(Ordering::SeqCst),
+            measured: h.measured.load(Ordering::SeqCst),
+        },
+        None => MonitorStatus {
+            running: false,
+            interval_ms: None,
+            started_unix: None,
+            samples: 0,
+            measured: false,
+        },
+    }
+}
+
+pub fn history(pid: Option<u32>, limit: usize) -> Vec<HistoryPoint> {
+    let history = match MONITOR.lock() {
+        Ok(monitor) => match monitor.as_ref() {
+            Some(h) => h.history.clone(),
+            None => return Vec::new(),
+        },
+        Err(_) => return Vec::new(),
+    };
+    let points = match history.lock() {
+        Ok(points) => points,
+        Err(_) => return Vec::new(),
+    };
+    let skip = points.len().saturating_sub(limit);
+    points
+        .iter()
+        .skip(skip)
+        .map(|point| HistoryPoint {
+            timestamp_ms: point.timestamp_ms,
+            processes: point
+                .processes
+                .iter()
+                .filter(|p| pid.is_none_or(|pid| p.pid == pid))
+                .cloned()
+                .collect(),
+        })
+        .collect()
+}
+
+#[cfg(windows)]
+mod sys {
+    use super::RawConnection;
+    use std::ffi::c_void;
+    use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV6};
+    use winapi::shared::minwindef::FARPROC;
+    use winapi::um::handleapi::CloseHandle;
+    use winapi::um::libloaderapi::{GetProcAddress, LoadLibraryW};
+    use winapi::um::processthreadsapi::OpenProcess;
+
+    const AF_INET: u32 = 2;
+    const AF_INET6: u32 = 23;
+    const TCP_TABLE_OWNER_PID_ALL: i32 = 5;
+    const TCP_CONNECTION_ESTATS_DATA: i32 = 1;
+    const ERROR_INSUFFICIENT_BUFFER: u32 = 122;
+    const PROCESS_QUERY_LIMITED_INFORMATION: u32 = 0x1000;
+
+    type GetExtendedTcpTableFn =
+        unsafe extern "system" fn(*mut c_void, *mut u32, i32, u32, i32, u32) -> u32;
+    type SetEstatsFn = unsafe extern "system" fn(*mut c_void, i32, *mut u8, u32, u32, u32) -> u32;
+    type GetEstatsFn = unsafe extern "system" fn(
+        *mut c_void,
+        i32,
+        *mut u8,
+        u32,
+        u32,
+        *mut u8,
+        u32,
+        u32,
+        *mut u8,
+        u32,
+        u32,
+    ) -> u32;
+    type QueryImageNameFn = unsafe extern "system" fn(*mut c_void, u32, *mut u16, *mut u32) -> i32;
+
+    struct IpHelper {
+        get_extended_tcp_table: GetExtendedTcpTableFn,
+        set_estats: SetEstatsFn,
+        get_estats: GetEstatsFn,
+        set_estats6: SetEstatsFn,
+        get_estats6: GetEstatsFn,
+        query_image_name: QueryImageNameFn,
+    }
+
+    lazy_static::lazy_static! {
+        static ref IP_HELPER: Option<IpHelper> = unsafe { load() };
+    }
+
+    // MIB_TCPROW_OWNER_PID; its first five fields are a MIB_TCPROW.
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    struct TcpRowOwnerPid {
+        state: u32,
+        local_addr: u32,
+        local_port: u32,
+        remote_addr: u32,
+        remote_port: u32,
+        pid: u32,
+    }
+
+    // MIB_TCP6ROW_OWNER_PID
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    struct Tcp6RowOwnerPid {
+        local_addr: [u8; 16],
+        local_scope: u32,
+        local_port: u32,
+        remote_addr: [u8; 16],
+        remote_scope: u32,
+        remote_port: u32,
+        state: u32,
+        pid: u32,
+    }
+
+    // MIB_TCP6ROW, the key GetPerTcp6ConnectionEStats expects.
+    #[repr(C)]
+    struct Tcp6Row {
+        state: u32,
+        local_addr: [u8; 16],
+        local_scope: u32,
+        local_port: u32,
+        remote_addr: [u8; 16],
+        remote_scope: u32,
+        remote_port: u32,
+    }
+
+    // TCP_ESTATS_DATA_ROD_v0; only the data byte counters are read.
+    #[repr(C)]
+    #[derive(Default)]
+    struct DataRod {
+        data_bytes_out: u64,
+        _data_segs_out: u64,
+        data_bytes_in: u64,
+        _rest: [u64; 9],
+    }
+
+    unsafe fn load() -> Option<IpHelper> {
+        let module = |name: &str| {
+            let wide: Vec<u16> = name.encode_utf16().collect();
+            let handle = LoadLibraryW(wide.as_ptr());
+            (!handle.is_null()).then_some(handle)
+        };
+        let iphlpapi = module("iphlpapi.dll\0")?;
+        let kernel32 = module("kernel32.dll\0")?;
+        let proc = |module, name: &str| {
+            let ptr = GetProcAddress(module, name.as_ptr() as *const i8);
+            (!ptr.is_null()).then_some(ptr)
+        };
+        Some(IpHelper {
+            get_extended_tcp_table: std::mem::transmute::<FARPROC, GetExtendedTcpTableFn>(proc(
+                iphlpapi,
+                "GetExtendedTcpTable\0",
+            )?),
+            set_estats: std::mem::transmute::<FARPROC, SetEstatsFn>(proc(
+                iphlpapi,
+                "SetPerTcpConnectionEStats\0",
+            )?),
+            get_estats: std::mem::transmute::<FARPROC, GetEstatsFn>(proc(
+                iphlpapi,
+                "GetPerTcpConnectionEStats\0",
+            )?),
+            set_estats6: std::mem::transmute::<FARPROC, SetEstatsFn>(proc(
+                iphlpapi,
+                "SetPerTcp6ConnectionEStats\0",
+            )?),
+            get_estats6: std::mem::transmute::<FARPROC, GetEstatsFn>(proc(
+                iphlpapi,
+                "GetPerTcp6ConnectionEStats\0",
+            )?),
+            query_image_name: std::mem::transmute::<FARPROC, QueryImageNameFn>(proc(
+                kernel32,
+                "QueryFullProcessImageNameW\0",
+            )?),
+        })
+    }
+
+    fn port(raw: u32) -> u16 {
+        u16::from_be(raw as u16)
+    }
+
+    unsafe fn read_table<T: Copy>(api: &IpHelper, family: u32) -> Vec<T> {
+        let mut size = 0u32;
+        let mut buffer: Vec<u64> = Vec::new();
+        for _ in 0..3 {
+            let status = (api.get_extended_tcp_table)(
+                buffer.as_mut_ptr() as *mut c_void,
+                &mut size,
+                0,
+                family,
+                TCP_TABLE_OWNER_PID_ALL,
+                0,
+            );
+            if status == ERROR_INSUFFICIENT_BUFFER {
+                buffer = vec![0u64; size as usize / 8 + 1];
+                continue;
+            }
+            if status != 0 || buffer.is_empty() {
+                return Vec::new();
+            }
+            let base = buffer.as_ptr() as *const u8;
+            let count = std::ptr::read_unaligned(base as *const u32) as usize;
+            let rows = base.add(4) as *const T;
+            let capacity = (buffer.len() * 8 - 4) / std::mem::size_of::<T>();
+            return (0..count.min(capacity))
+                .map(|i| std::ptr::read_unaligned(rows.add(i)))
+                .collect();
+        }
+        Vec::new()
+    }
+
+    // Collection has to be switched on per connection (admin only) before
+    // the data counters start moving; enabling it again is a no-op.
+    unsafe fn estats(row: *mut c_void, set: SetEstatsFn, get: GetEstatsFn) -> Option<(u64, u64)> {
+        let mut enable = 1u8;
+        if set(row, TCP_CONNECTION_ESTATS_DATA, &mut enable, 0, 1, 0) != 0 {
+            return None;
+        }
+        let mut rod = DataRod::default();
+        let status = get(
+            row,
+            TCP_CONNECTION_ESTATS_DATA,
+            std::ptr::null_mut(),
+            0,
+            0,
+            std::ptr::null_mut(),
+            0,
+            0,
+            &mut rod as *mut DataRod as *mut u8,
+            0,
+            std::mem::size_of::<DataRod>() as u32,
+        );
+        (status == 0).then_some((rod.data_bytes_out, rod.data_bytes_in))
+    }
+
+    pub fn tcp_connections() -> Vec<RawConnection> {
+        let Some(api) = IP_HELPER.as_ref() else {
+            return Vec::new();
+        };
+        let mut connections = Vec::new();
+        unsafe {
+            for mut row in read_table::<TcpRowOwnerPid>(api, AF_INET) {
+                let bytes = if row.state == super::STATE_LISTEN {
+                    None
+                } else {
+                    estats(
+                        &mut row as *mut TcpRowOwnerPid as *mut c_void,
+                        api.set_estats,
+                        api.get_estats,
+                    )
+                };
+                connections.push(RawConnection {
+                    pid: row.pid,
+                    local: SocketAddr::from((
+                        Ipv4Addr::from(row.local_addr.to_ne_bytes()),
+                        port(row.local_port),
+                    )),
+                    remote: SocketAddr::from((
+                        Ipv4Addr::from(row.remote_addr.to_ne_bytes()),
+                        port(row.remote_port),
+                    )),
+                    state: row.state,
+                    bytes,
+                });
+            }
+            for row in read_table::<Tcp6RowOwnerPid>(api, AF_INET6) {
+                let bytes = if row.state == super::STATE_LISTEN {
+                    None
+                } else {
+                    let mut key = Tcp6Row {
+                        state: row.state,
+                        local_addr: row.local_addr,
+                        local_scope: row.local_scope,
+                        local_port: row.local_port,
+                        remote_addr: row.remote_addr,
+                        remote_scope: row.remote_scope,
+                        remote_port: row.remote_port,
+                    };
+                    estats(
+                        &mut key as *mut Tcp6Row as *mut c_void,
+                        api.set_estats6,
+                        api.get_estats6,
+                    )
+                };
+                connections.push(RawConnection {
+                    pid: row.pid,
+                    local: SocketAddr::V6(SocketAddrV6::new(
+                        Ipv6Addr::from(row.local_addr),
+                        port(row.local_port),
+                        0,
+                        row.local_scope,
+                    )),
+                    remote: SocketAddr::V6(SocketAddrV6::new(
+                        Ipv6Addr::from(row.remote_addr),
+                        port(row.remote_port),
+                        0,
+                        row.remote_scope,
+                    )),
+                    state: row.state,
+                    bytes,
+                });
+            }
+        }
+        connections
+    }
+
+    pub fn process_image(pid: u32) -> Option<String> {
+        let api = IP_HELPER.as_ref()?;
+        unsafe {
+            let handle = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, pid);
+            if handle.is_null() {
+                return None;
+            }
+            let mut buffer = [0u16; 1024];
+            let mut len = buffer.len() as u32;
+            let ok =
+                (api.query_image_name)(handle as *mut c_void, 0, buffer.as_mut_ptr(), &mut len);
+            CloseHandle(handle);
+            (ok != 0).then(|| String::from_utf16_lossy(&buffer[..len as usize]))
+        }
+    }
+}
+
+#[cfg(not(windows))]
+mod sys {
+    use super::RawConnection;
+
+    pub fn tcp_connections() -> Vec<RawConnection> {
+        Vec::new()
+    }
+
+    pub fn process_image(_pid: u32) -> Option<String> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ESTABLISHED: u32 = 5;
+
+    fn conn(
+        pid: u32,
+        local: &str,
+        remote: &str,
+        state: u32,
+        bytes: Option<(u64, u64)>,
+    ) -> RawConnection {
+        RawConnection {
+            pid,
+            local: local.parse().unwrap(),
+            remote: remote.parse().unwrap(),
+            state,
+            bytes,
+        }
+    }
+
+    fn listener(pid: u32) -> RawConnection {
+        conn(pid, "0.0.0.0:445", "0.0.0.0:0", STATE_LISTEN, None)
+    }
+
+    fn browser(sent: u64, received: u64) -> RawConnection {
+        conn(
+            100,
+            "192.168.1.10:50000",
+            "93.184.215.14:443",
+            ESTABLISHED,
+            Some((sent, received)),
+        )
+    }
+
+    // Replays one connection table per tick and records image lookups.
+    #[derive(Clone)]
+    struct FakeSource {
+        ticks: Arc<Mutex<VecDeque<Vec<RawConnection>>>>,
+        lookups: Arc<Mutex<Vec<u32>>>,
+    }
+
+    impl FakeSource {
+        fn new(ticks: Vec<Vec<RawConnection>>) -> Self {
+            FakeSource {
+                ticks: Arc::new(Mutex::new(ticks.into())),
+                lookups: Arc::new(Mutex::new(Vec::new())),
+            }
+        }
+    }
+
+    impl ConnectionSource for FakeSource {
+        fn connections(&self) -> Vec<RawConnection> {
+            self.ticks.lock().unwrap().pop_front().unwrap_or_default()
+        }
+
+        fn process_image(&self, pid: u32) -> Option<String> {
+            self.lookups.lock().unwrap().push(pid);
+            Some(format!("C:\\Apps\\app{}.exe", pid))
+        }
+    }
+
+    #[test]
+    fn update_reports_deltas_after_baseline() {
+        let mut tracker = UsageTracker::default();
+        tracker.record_images(vec![(100, Some("C:\\Apps\\browser.exe".to_string()))]);
+        let second = Duration::from_secs(1);
+
+        let first = tracker.update(&[browser(1000, 5000), listener(4)], second, 1);
+        assert!(first.measured);
+        assert!(first.tcp_only);
+        assert_eq!(first.send_rate, 0.0);
+        assert_eq!(first.processes.len(), 1);
+        assert_eq!(first.processes[0].name, "browser.exe");
+
+        let next = tracker.update(&[browser(1500, 9000)], Duration::from_secs(2), 2);
+        assert_eq!(next.send_rate, 250.0);
+        assert_eq!(next.receive_rate, 2000.0);
+        assert_eq!(next.processes[0].bytes_sent, 500);
+        assert_eq!(next.processes[0].bytes_received, 4000);
+
+        // A reused tuple restarts its counters; the new values count in full.
+        let reused = tracker.update(&[browser(100, 200)], second, 3);
+        assert_eq!(reused.processes[0].bytes_sent, 600);
+        assert_eq!(reused.processes[0].bytes_received, 4200);
+    }
+
+    #[test]
+    fn update_without_estats_is_unmeasured() {
+        let mut tracker = UsageTracker::default();
+        let mut unelevated = browser(0, 0);
+        unelevated.bytes = None;
+        let sample = tracker.update(&[unelevated], Duration::from_secs(1), 1);
+        assert!(!sample.measured);
+        assert_eq!(sample.processes[0].name, "PID 100");
+        assert_eq!(sample.processes[0].connections[0].bytes_sent, None);
+    }
+
+    #[tokio::test]
+    async fn run_monitor_looks_up_listen_only_images_once() {
+        let source = FakeSource::new(vec![
+            vec![listener(200), browser(0, 0)],
+            vec![listener(200), browser(300, 600)],
+            vec![listener(200)],
+            vec![listener(200), browser(0, 10)],
+        ]);
+        let lookups = source.lookups.clone();
+        let stop = Arc::new(AtomicBool::new(false));
+        let mut samples = Vec::new();
+        let stop_after = stop.clone();
+        run_monitor(
+            source,
+            Vec::new(),
+            stop.clone(),
+            Duration::from_millis(1),
+            |sample| {
+                samples.push(sample);
+                if samples.len() == 4 {
+                    stop_after.store(true, Ordering::SeqCst);
+                }
+            },
+        )
+        .await;
+
+        assert_eq!(samples.len(), 4);
+        assert!(samples
+            .iter()
+            .all(|s| s.processes.iter().all(|p| p.pid != 200)));
+        assert_eq!(samples[1].processes[0].bytes_sent, 300);
+        assert!(samples[2].processes.is_empty());
+        // Process 100 disappeared for a tick, so its image is fetched again;
+        // the listener never is.
+        let mut seen = lookups.lock().unwrap().clone();
+        seen.sort_unstable();
+        assert_eq!(seen, vec![100, 100, 200]);
+        assert_eq!(samples[3].processes[0].name, "app100.exe");
+    }
+}
@@ -0,0 +1,964 @@
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::Value;
+use std::net::{IpAddr, Ipv4Addr};
+use std::path::PathBuf;
+use winreg::enums::*;
+use winreg::RegKey;
+
+use crate::util::{self, ps_quote};
+
+pub const SNAPSHOT_VERSION: u32 = 1;
+const SNAPSHOT_MAX_AUTOMATIC: usize = 20;
+const TCPIP6_PARAMETERS: &str = r"SYSTEM\CurrentControlSet\Services\Tcpip6\Parameters";
+const INTERNET_SETTINGS: &str = r"Software\Microsoft\Windows\CurrentVersion\Internet Settings";
+const TEREDO_TYPES: &[&str] = &[
+    "Default",
+    "Disabled",
+    "Client",
+    "EnterpriseClient",
+    "Server",
+    "NatawareClient",
+];
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "PascalCase")]
+pub struct AdapterConfig {
+    pub index: u32,
+    pub alias: String,
+    pub description: String,
+    pub mac: String,
+    // None when IPv4 is not bound to the adapter at all.
+    pub ipv4_dhcp: Option<bool>,
+    #[serde(deserialize_with = "string_list")]
+    pub ipv4_addresses: Vec<String>,
+    #[serde(deserialize_with = "string_list")]
+    pub ipv4_gateways: Vec<String>,
+    #[serde(deserialize_with = "string_list")]
+    pub dns_ipv4: Vec<String>,
+    #[serde(deserialize_with = "string_list")]
+    pub dns_ipv6: Vec<String>,
+    pub dns_ipv4_dhcp: bool,
+    pub dns_ipv6_dhcp: bool,
+    pub ipv6_bound: bool,
+    pub ipv4_metric: Option<u32>,
+    pub ipv4_auto_metric: bool,
+    pub ipv6_metric: Option<u32>,
+    pub ipv6_auto_metric: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ProxySettings {
+    pub enabled: bool,
+    pub server: Option<String>,
+    pub bypass: Option<String>,
+    pub auto_config_url: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NetworkSnapshot {
+    #[serde(default)]
+    pub version: u32,
+    pub id: String,
+    pub name: String,
+    pub created_unix: u64,
+    pub automatic: bool,
+    pub adapters: Vec<AdapterConfig>,
+    pub teredo: Option<String>,
+    pub ipv6_disabled_components: Option<u32>,
+    pub proxy: ProxySettings,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SnapshotSummary {
+    pub id: String,
+    pub name: String,
+    pub created_unix: u64,
+    pub automatic: bool,
+    pub adapter_count: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct NetworkChange {
+    // None for system-wide settings.
+    pub adapter: Option<String>,
+    pub setting: String,
+    pub snapshot: String,
+    pub current: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct StepResult {
+    pub scope: String,
+    pub setting: String,
+    pub success: bool,
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct CaptureOutput {
+    #[serde(default)]
+    adapters: Vec<AdapterConfig>,
+    #[serde(default)]
+    teredo: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct RestoreOutput {
+    #[serde(default)]
+    results: Vec<StepResult>,
+}
+
+// PowerShell emits an empty result as null or [null] and a single result as
+// a bare string rather than a one-element array.
+fn string_list<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    Ok(match Value::deserialize(deserializer)? {
+        Value::Array(items) => items
+            .into_iter()
+            .filter_map(|item| item.as_str().map(|s| s.trim().to_string()))
+            .filter(|s| !s.is_empty())
+            .collect(),
+        Value::String(s) if !s.trim().is_empty() => vec![s.trim().to_string()],
+        _ => Vec::new(),
+    })
+}
+
+fn snapshot_dir() -> PathBuf {
+    util::data_subdir("network_snapshots")
+}
+
+fn validate_snapshot_id(id: &str) -> Result<&str, String> {
+    let id = id.trim();
+    if id.is_empty()
+        || id.len() > 64
+        || !id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err("Invalid snapshot id".to_string());
+    }
+    Ok(id)
+}
+
+pub fn capture_script() -> &'static str {
+    r#"
+        $adapters = @(Get-NetAdapter -ErrorAction SilentlyContinue | Where-Object { $_.HardwareInterface -or $_.Status -eq 'Up' } | ForEach-Object {
+            $a = $_
+            $if4 = Get-NetIPInterface -InterfaceIndex $a.ifIndex -AddressFamily IPv4 -ErrorAction SilentlyContinue
+            $if6 = Get-NetIPInterface -InterfaceIndex $a.ifIndex -AddressFamily IPv6 -ErrorAction SilentlyContinue
+            $addresses = @(Get-NetIPAddress -InterfaceIndex $a.ifIndex -AddressFamily IPv4 -PrefixOrigin Manual -ErrorAction SilentlyContinue | ForEach-Object { "$($_.IPAddress)/$($_.PrefixLength)" })
+            $gateways = @(Get-NetRoute -InterfaceIndex $a.ifIndex -DestinationPrefix '0.0.0.0/0' -ErrorAction SilentlyContinue | ForEach-Object { [string]$_.NextHop })
+            $v4 = (Get-DnsClientServerAddress -InterfaceIndex $a.ifIndex -AddressFamily IPv4 -ErrorAction SilentlyContinue).ServerAddresses
+            $v6 = (Get-DnsClientServerAddress -InterfaceIndex $a.ifIndex -AddressFamily IPv6 -ErrorAction SilentlyContinue).ServerAddresses
+            $ns4 = (Get-ItemProperty "HKLM:\SYSTEM\CurrentControlSet\Services\Tcpip\Parameters\Interfaces\$($a.InterfaceGuid)" -Name NameServer -ErrorAction SilentlyContinue).NameServer
+            $ns6 = (Get-ItemProperty "HKLM:\SYSTEM\CurrentControlSet\Services\Tcpip6\Parameters\Interfaces\$($a.InterfaceGuid)" -Name NameServer -ErrorAction SilentlyContinue).NameServer
+            $binding = Get-NetAdapterBinding -Name $a.Name -ComponentID ms_tcpip6 -ErrorAction SilentlyContinue
+            [pscustomobject]@{
+                Index = [int]$a.ifIndex
+                Alias = $a.Name
+                Description = $a.InterfaceDescription
+                Mac = [string]$a.MacAddress
+                Ipv4Dhcp = if ($if4) { [string]$if4.Dhcp -eq 'Enabled' } else { $null }
+                Ipv4Addresses = @($addresses | Where-Object { $_ })
+                Ipv4Gateways = @($gateways | Where-Object { $_ -and $_ -ne '0.0.0.0' })
+                DnsIpv4 = @($v4 | Where-Object { $_ })
+                DnsIpv6 = @($v6 | Where-Object { $_ -and $_ -notlike 'fec0:0:0:ffff::*' })
+                DnsIpv4Dhcp = [string]::IsNullOrWhiteSpace($ns4)
+                DnsIpv6Dhcp = [string]::IsNullOrWhiteSpace($ns6)
+                Ipv6Bound = [bool]($binding -and $binding.Enabled)
+                Ipv4Metric = if ($if4) { [int]$if4.InterfaceMetric } else { $null }
+                Ipv4AutoMetric = [bool]($if4 -and [string]$if4.AutomaticMetric -eq 'Enabled')
+                Ipv6Metric = if ($if6) { [int]$if6.InterfaceMetric } else { $null }
+                Ipv6AutoMetric = [bool]($if6 -and [string]$if6.AutomaticMetric -eq 'Enabled')
+            }
+        })
+        $teredo = Get-NetTeredoConfiguration -ErrorAction SilentlyContinue
+        [pscustomobject]@{
+            Adapters = $adapters
+            Teredo = if ($teredo) { [string]$teredo.Type } else { $null }
+        } | ConvertTo-Json -Depth 4 -Compress
+    "#
+}
+
+fn read_string(key: &RegKey, name: &str) -> Option<String> {
+    key.get_value::<String, _>(name)
+        .ok()
+        .filter(|s| !s.trim().is_empty())
+}
+
+fn capture_registry() -> (Option<u32>, ProxySettings) {
+    let disabled_components = RegKey::predef(HKEY_LOCAL_MACHINE)
+        .open_subkey_with_flags(TCPIP6_PARAMETERS, KEY_READ)
+        .ok()
+        .and_then(|key| key.get_value::<u32, _>("DisabledComponents").ok());
+    let proxy = RegKey::predef(HKEY_CURRENT_USER)
+        .open_subkey_with_flags(INTERNET_SETTINGS, KEY_READ)
+        .map(|key| ProxySettings {
+            enabled: key.get_value::<u32, _>("ProxyEnable").unwrap_or(0) != 0,
+            server: read_string(&key, "ProxyServer"),
+            bypass: read_string(&key, "ProxyOverride"),
+            auto_config_url: read_string(&key, "AutoConfigURL"),
+        })
+        .unwrap_or_default();
+    (disabled_components, proxy)
+}
+
+pub fn build_snapshot(
+    name: &str,
+    automatic: bool,
+    capture_output: &str,
+    ipv6_disabled_components: Option<u32>,
+    proxy: ProxySettings,
+) -> Result<NetworkSnapshot, String> {
+    let output: CaptureOutput = serde_json::from_str(capture_output.trim())
+        .map_err(|e| format!("Failed to parse network configuration: {}", e))?;
+    Ok(NetworkSnapshot {
+        version: SNAPSHOT_VERSION,
+        id: util::new_id(),
+        name: if name.trim().is_empty() {
+            "Network snapshot".to_string()
+        } else {
+            name.trim().chars().take(128).collect()
+        },
+        created_unix: util::now_unix(),
+        automatic,
+        adapters: output.adapters,
+        teredo: output.teredo.filter(|t| !t.trim().is_empty()),
+        ipv6_disabled_components,
+        proxy,
+    })
+}
+
+pub async fn capture(name: &str, automatic: bool) -> Result<NetworkSnapshot, String> {
+    let output =
+        crate::commands::run_powershell_no_rate_limit(capture_script().to_string()).await?;
+    let (disabled_components, proxy) = tokio::task::spawn_blocking(capture_registry)
+        .await
+        .map_err(|e| format!("Network snapshot task failed: {}", e))?;
+    build_snapshot(name, automatic, &output, disabled_components, proxy)
+}
+
+pub fn save_snapshot(snapshot: &NetworkSnapshot) -> Result<(), String> {
+    let raw = serde_json::to_string_pretty(snapshot)
+        .map_err(|e| format!("Failed to serialize network snapshot: {}", e))?;
+    std::fs::write(snapshot_dir().join(format!("{}.json", snapshot.id)), raw)
+        .map_err(|e| format!("Failed to write network snapshot: {}", e))?;
+    prune_automatic_snapshots();
+    Ok(())
+}
+
+pub fn parse_snapshot(raw: &str) -> Result<NetworkSnapshot, String> {
+    let snapshot: NetworkSnapshot =
+        serde_json::from_str(raw).map_err(|e| format!("Corrupt network snapshot: {}", e))?;
+    if snapshot.version == 0 || snapshot.version > SNAPSHOT_VERSION {
+        return Err(format!(
+            "Unsupported network snapshot version: {}",
+            snapshot.version
+        ));
+    }
+    Ok(snapshot)
+}
+
+pub fn load_snapshot(id: &str) -> Result<NetworkSnapshot, String> {
+    let id = validate_snapshot_id(id)?;
+    let raw = std::fs::read_to_string(snapshot_dir().join(format!("{}.json", id)))
+        .map_err(|_| format!("Network snapshot not found: {}", id))?;
+    parse_snapshot(&raw)
+}
+
+fn read_all_snapshots() -> Vec<NetworkSnapshot> {
+    let mut snapshots: Vec<NetworkSnapshot> = match std::fs::read_dir(snapshot_dir()) {
+        Ok(entries) => entries
+            .filter_map(|e| e.ok())
+            .filter(|e| e.path().extension().and_then(|x| x.to_str()) == Some("json"))
+            .filter_map(|e| std::fs::read_to_string(e.path()).ok())
+            .filter_map(|raw| parse_snapshot(&raw).ok())
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    snapshots.sort_by_key(|s| std::cmp::Reverse(s.created_unix));
+    snapshots
+}
+
+pub fn list_snapshots() -> Vec<SnapshotSummary> {
+    read_all_snapshots()
+        .into_iter()
+        .map(|s| SnapshotSummary {
+            adapter_count: s.adapters.len(),
+            id: s.id,
+            name: s.name,
+            created_unix: s.created_unix,
+            automatic: s.automatic,
+        })
+        .collect()
+}
+
+pub fn delete_snapshot(id: &str) -> Result<(), String> {
+    let id = validate_snapshot_id(id)?;
+    std::fs::remove_file(snapshot_dir().join(format!("{}.json", id)))
+        .map_err(|_| format!("Network snapshot not found: {}", id))
+}
+
+fn prune_automatic_snapshots() {
+    for snapshot in read_all_snapshots()
+        .into_iter()
+        .filter(|s| s.automatic)
+        .skip(SNAPSHOT_MAX_AUTOMATIC)
+    {
+        let _ = std::fs::remove_file(snapshot_dir().join(format!("{}.json", snapshot.id)));
+    }
+}
+
+// Adapter indexes can change after a stack reset, the MAC address does not.
+pub fn find_adapter<'a>(
+    adapters: &'a [AdapterConfig],
+    saved: &AdapterConfig,
+) -> Option<&'a AdapterConfig> {
+    let mac = saved.mac.trim();
+    adapters
+        .iter()
+        .find(|a| !mac.is_empty() && a.mac.trim().eq_ignore_ascii_case(mac))
+        .or_else(|| {
+            adapters
+                .iter()
+                .find(|a| a.alias.eq_ignore_ascii_case(&saved.alias))
+        })
+}
+
+fn sorted(items: &[String]) -> Vec<String> {
+    let mut items = items.to_vec();
+    items.sort();
+    items
+}
+
+fn describe_addressing(adapter: &AdapterConfig) -> String {
+    match adapter.ipv4_dhcp {
+        None => "IPv4 unbound".to_string(),
+        Some(true) => "DHCP".to_string(),
+        Some(false) => format!(
+            "static {} via {}",
+            adapter.ipv4_addresses.join(", "),
+            if adapter.ipv4_gateways.is_empty() {
+                "no gateway".to_string()
+            } else {
+                adapter.ipv4_gateways.join(", ")
+            }
+        ),
+    }
+}
+
+fn addressing_equal(a: &AdapterConfig, b: &AdapterConfig) -> bool {
+    match (a.ipv4_dhcp, b.ipv4_dhcp) {
+        (Some(false), Some(false)) => {
+            sorted(&a.ipv4_addresses) == sorted(&b.ipv4_addresses)
+                && sorted(&a.ipv4_gateways) == sorted(&b.ipv4_gateways)
+        }
+        (x, y) => x == y,
+    }
+}
+
+fn describe_dns_family(dhcp: bool, servers: &[String]) -> String {
+    if dhcp {
+        "automatic".to_string()
+    } else if servers.is_empty() {
+        "none".to_string()
+    } else {
+        servers.join(", ")
+    }
+}
+
+fn describe_dns(adapter: &AdapterConfig) -> String {
+    format!(
+        "IPv4: {}; IPv6: {}",
+        describe_dns_family(adapter.dns_ipv4_dhcp, &adapter.dns_ipv4),
+        describe_dns_family(adapter.dns_ipv6_dhcp, &adapter.dns_ipv6)
+    )
+}
+
+fn describe_metric(auto: bool, metric: Option<u32>) -> String {
+    match (auto, metric) {
+        (true, _) => "automatic".to_string(),
+        (false, Some(m)) => m.to_string(),
+        (false, None) => "unbound".to_string(),
+    }
+}
+
+fn describe_proxy(proxy: &ProxySettings) -> String {
+    let mut parts = vec![if proxy.enabled {
+        format!("on ({})", proxy.server.as_deref().unwrap_or("no server"))
+    } else {
+        "off".to_string()
+    }];
+    if let Some(bypass) = &proxy.bypass {
+        parts.push(format!("bypass {}", bypass));
+    }
+    if let Some(pac) = &proxy.auto_config_url {
+        parts.push(format!("PAC {}", pac));
+    }
+    parts.join("; ")
+}
+
+fn describe_option<T: ToString>(value: &Option<T>) -> String {
+    value
+        .as_ref()
+        .map(|v| v.to_string())
+        .unwrap_or_else(|| "not set".to_string())
+}
+
+pub fn diff(snapshot: &NetworkSnapshot, current: &NetworkSnapshot) -> Vec<NetworkChange> {
+    let mut changes = Vec::new();
+    let mut push = |adapter: Option<&str>, setting: &str, saved: String, now: String| {
+        changes.push(NetworkChange {
+            adapter: adapter.map(|a| a.to_string()),
+            setting: setting.to_string(),
+            snapshot: saved,
+            current: now,
+        })
+    };
+
+    for saved in &snapshot.adapters {
+        let alias = Some(saved.alias.as_str());
+        let Some(now) = find_adapter(&current.adapters, saved) else {
+            push(
+                alias,
+                "adapter",
+                "present".to_string(),
+                "missing".to_string(),
+            );
+            continue;
+        };
+        if saved.ipv6_bound != now.ipv6_bound {
+            let state = |bound: bool| if bound { "enabled" } else { "disabled" }.to_string();
+            push(
+                alias,
+                "ipv6_binding",
+                state(saved.ipv6_bound),
+                state(now.ipv6_bound),
+            );
+        }
+        if !addressing_equal(saved, now) {
+            push(
+                alias,
+                "ipv4_addressing",
+                describe_addressing(saved),
+                describe_addressing(now),
+            );
+        }
+        if describe_dns(saved) != describe_dns(now) {
+            push(alias, "dns", describe_dns(saved), describe_dns(now));
+        }
+        let metrics = [
+            (
+                "ipv4_metric",
+                (saved.ipv4_auto_metric, saved.ipv4_metric),
+                (now.ipv4_auto_metric, now.ipv4_metric),
+            ),
+            (
+                "ipv6_metric",
+                (saved.ipv6_auto_metric, saved.ipv6_metric),
+                (now.ipv6_auto_metric, now.ipv6_metric),
+            ),
+        ];
+        for (setting, (saved_auto, saved_metric), (now_auto, now_metric)) in metrics {
+            let saved_metric = describe_metric(saved_auto, saved_metric);
+            let now_metric = describe_metric(now_auto, now_metric);
+            // An unbound family has nothing to restore.
+            if saved_metric != now_metric && saved_metric != "unbound" && now_metric != "unbound" {
+                push(alias, setting, saved_metric, now_metric);
+            }
+        }
+    }
+
+    let teredo_equal = match (&snapshot.teredo, &current.teredo) {
+        (Some(a), Some(b)) => a.eq_ignore_ascii_case(b),
+        (None, _) => true,
+        _ => false,
+    };
+    if !teredo_equal {
+        push(
+            None,
+            "teredo",
+            describe_option(&snapshot.teredo),
+            describe_option(&current.teredo),
+        );
+    }
+    if snapshot.ipv6_disabled_components != current.ipv6_disabled_components {
+        push(
+            None,
+            "ipv6_disabled_components",
+            describe_option(&snapshot.ipv6_disabled_components),
+            describe_option(&current.ipv6_disabled_components),
+        );
+    }
+    if snapshot.proxy != current.proxy {
+        push(
+            None,
+            "proxy",
+            describe_proxy(&snapshot.proxy),
+            describe_proxy(&current.proxy),
+        );
+    }
+    changes
+}
+
+fn parse_cidr(raw: &str) -> Result<(Ipv4Addr, u8), String> {
+    let (ip, prefix) = raw
+        .split_once('/')
+        .ok_or_else(|| format!("Invalid address in snapshot: {}", raw))?;
+    let ip = ip
+        .trim()
+        .parse::<Ipv4Addr>()
+        .map_err(|_| format!("Invalid address in snapshot: {}", raw))?;
+    let prefix = prefix
+        .trim()
+        .parse::<u8>()
+        .ok()
+        .filter(|p| *p <= 32)
+        .ok_or_else(|| format!("Invalid prefix length in snapshot: {}", raw))?;
+    Ok((ip, prefix))
+}
+
+fn parse_ips(items: &[String], v6: bool) -> Result<Vec<IpAddr>, String> {
+    items
+        .iter()
+        .map(|s| {
+            s.trim()
+                .parse::<IpAddr>()
+                .ok()
+                .filter(|ip| ip.is_ipv6() == v6)
+                .ok_or_else(|| format!("Invalid address in snapshot: {}", s))
+        })
+        .collect()
+}
+
+fn list(items: &[impl ToString]) -> String {
+    items
+        .iter()
+        .map(|i| ps_quote(&i.to_string()))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+fn addressing_action(index: u32, saved: &AdapterConfig) -> Result<String, String> {
+    let clear = format!(
+        "Get-NetIPAddress -InterfaceIndex {i} -AddressFamily IPv4 -PrefixOrigin Manual -ErrorAction SilentlyContinue | Remove-NetIPAddress -Confirm:$false -ErrorAction SilentlyContinue; Get-NetRoute -InterfaceIndex {i} -DestinationPrefix '0.0.0.0/0' -ErrorAction SilentlyContinue | Remove-NetRoute -Confirm:$false -ErrorAction SilentlyContinue",
+        i = index
+    );
+    if saved.ipv4_dhcp != Some(false) {
+        return Ok(format!(
+            "{}; Set-NetIPInterface -InterfaceIndex {} -AddressFamily IPv4 -Dhcp Enabled -ErrorAction Stop",
+            clear, index
+        ));
+    }
+    let mut action = format!(
+        "Set-NetIPInterface -InterfaceIndex {} -AddressFamily IPv4 -Dhcp Disabled -ErrorAction Stop; {}",
+        index, clear
+    );
+    for address in &saved.ipv4_addresses {
+        let (ip, prefix) = parse_cidr(address)?;
+        action.push_str(&format!(
+            "; New-NetIPAddress -InterfaceIndex {} -IPAddress '{}' -PrefixLength {} -ErrorAction Stop | Out-Null",
+            index, ip, prefix
+        ));
+    }
+    for gateway in parse_ips(&saved.ipv4_gateways, false)? {
+        action.push_str(&format!(
+            "; New-NetRoute -InterfaceIndex {} -DestinationPrefix '0.0.0.0/0' -NextHop '{}' -ErrorAction Stop | Out-Null",
+            index, gateway
+        ));
+    }
+    Ok(action)
+}
+
+fn dns_action(index: u32, saved: &AdapterConfig) -> Result<String, String> {
+    let mut servers = Vec::new();
+    if !saved.dns_ipv4_dhcp {
+        servers.extend(parse_ips(&saved.dns_ipv4, false)?);
+    }
+    if !saved.dns_ipv6_dhcp {
+        servers.extend(parse_ips(&saved.dns_ipv6, true)?);
+    }
+    let mut action = format!(
+        "Set-DnsClientServerAddress -InterfaceIndex {} -ResetServerAddresses -ErrorAction Stop",
+        index
+    );
+    if !servers.is_empty() {
+        action.push_str(&format!(
+            "; Set-DnsClientServerAddress -InterfaceIndex {} -ServerAddresses @({}) -ErrorAction Stop",
+            index,
+            list(&servers)
+        ));
+    }
+    Ok(action)
+}
+
+fn metric_action(index: u32, family: &str, auto: bool, metric: Option<u32>) -> String {
+    match (auto, metric) {
+        (false, Some(metric)) => format!(
+            "Set-NetIPInterface -InterfaceIndex {} -AddressFamily {} -InterfaceMetric {} -ErrorAction Stop",
+            index, family, metric
+        ),
+        _ => format!(
+            "Set-NetIPInterface -InterfaceIndex {} -AddressFamily {} -AutomaticMetric Enabled -ErrorAction Stop",
+            index, family
+        ),
+    }
+}
+
+fn step(scope: &str, setting: &str, action: &str) -> String {
+    format!(
+        r#"
+            try {{
+                {action}
+                $results += [pscustomobject]@{{ Scope = {scope}; Setting = {setting}; Success = $true; Error = $null }}
+            }} catch {{
+                $results += [pscustomobject]@{{ Scope = {scope}; Setting = {setting}; Success = $false; Error = $_.Exception.Message }}
+            }}"#,
+        action = action,
+        scope = ps_quote(scope),
+        setting = ps_quote(setting)
+    )
+}
+
+// Builds the PowerShell half of a restore: adapter settings and Teredo.
+// Registry-backed settings are written directly by `restore_registry`.
+pub fn restore_script(
+    snapshot: &NetworkSnapshot,
+    current: &NetworkSnapshot,
+    changes: &[NetworkChange],
+) -> Result<String, String> {
+    let mut steps = Vec::new();
+    for change in changes {
+        let Some(alias) = &change.adapter else {
+            if change.setting == "teredo" {
+                let teredo = snapshot.teredo.as_deref().unwrap_or_default();
+                let kind = TEREDO_TYPES
+                    .iter()
+                    .find(|t| t.eq_ignore_ascii_case(teredo))
+                    .ok_or_else(|| format!("Unknown Teredo state in snapshot: {}", teredo))?;
+                steps.push(step(
+                    "system",
+                    "teredo",
+                    &format!(
+                        "Set-NetTeredoConfiguration -Type {} -ErrorAction Stop",
+                        kind
+                    ),
+                ));
+            }
+            continue;
+        };
+        let Some(saved) = snapshot.adapters.iter().find(|a| &a.alias == alias) else {
+            continue;
+        };
+        let Some(now) = find_adapter(&current.adapters, saved) else {
+            continue;
+        };
+        let action = match change.setting.as_str() {
+            "ipv6_binding" => format!(
+                "{}-NetAdapterBinding -Name {} -ComponentID ms_tcpip6 -ErrorAction Stop",
+                if saved.ipv6_bound {
+                    "Enable"
+                } else {
+                    "Disable"
+                },
+                ps_quote(&now.alias)
+            ),
+            "ipv4_addressing" => addressing_action(now.index, saved)?,
+            "dns" => dns_action(now.index, saved)?,
+            "ipv4_metric" => {
+                metric_action(now.index, "IPv4", saved.ipv4_auto_metric, saved.ipv4_metric)
+            }
+            "ipv6_metric" => {
+                metric_action(now.index, "IPv6", saved.ipv6_auto_metric, saved.ipv6_metric)
+            }
+            _ => continue,
+        };
+        steps.push(step(&now.alias, &change.setting, &action));
+    }
+    Ok(format!(
+        r#"
+        $results = @()
+        {}
+        [pscustomobject]@{{ Results = $results }} | ConvertTo-Json -Depth 3 -Compress
+    "#,
+        steps.join("")
+    ))
+}
+
+pub fn parse_restore_results(raw: &str) -> Result<Vec<StepResult>, String> {
+    let output: RestoreOutput = serde_json::from_str(raw.trim())
+        .map_err(|e| format!("Failed to parse restore result: {}", e))?;
+    Ok(output.results)
+}
+
+fn set_optional_string(key: &RegKey, name: &str, value: &Option<String>) -> std::io::Result<()> {
+    match value {
+        Some(value) => key.set_value(name, value),
+        None => match key.delete_value(name) {
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            other => other,
+        },
+    }
+}
+
+fn registry_step(setting: &str, result: std::io::Result<()>) -> StepResult {
+    StepResult {
+        scope: "system".to_string(),
+        setting: setting.to_string(),
+        success: result.is_ok(),
+        error: result.err().map(|e| e.to_string()),
+    }
+}
+
+pub fn restore_registry(snapshot: &NetworkSnapshot, changes: &[NetworkChange]) -> Vec<StepResult> {
+    let mut results = Vec::new();
+    for change in changes.iter().filter(|c| c.adapter.is_none()) {
+        match change.setting.as_str() {
+            "ipv6_disabled_components" => {
+                let result = RegKey::predef(HKEY_LOCAL_MACHINE)
+                    .create_subkey(TCPIP6_PARAMETERS)
+                    .and_then(|(key, _)| match snapshot.ipv6_disabled_components {
+                        Some(value) => key.set_value("DisabledComponents", &value),
+                        None => match key.delete_value("DisabledComponents") {
+                            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+                            other => other,
+                        },
+                    });
+                results.push(registry_step(&change.setting, result));
+            }
+            "proxy" => {
+                let proxy = &snapshot.proxy;
+                let result = RegKey::predef(HKEY_CURRENT_USER)
+                    .create_subkey(INTERNET_SETTINGS)
+                    .and_then(|(key, _)| {
+                        key.set_value("ProxyEnable", &(proxy.enabled as u32))?;
+                        set_optional_string(&key, "ProxyServer", &proxy.server)?;
+                        set_optional_string(&key, "ProxyOverride", &proxy.bypass)?;
+                        set_optional_string(&key, "AutoConfigURL", &proxy.auto_config_url)
+                    });
+                results.push(registry_step(&change.setting, result));
+            }
+            _ => {}
+        }
+    }
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CAPTURE: &str = r#"{
+        "Adapters": [
+            {
+                "Index": 12,
+                "Alias": "Bob's Ethernet",
+                "Description": "Intel(R) Ethernet Connection",
+                "Mac": "00-1B-21-AA-BB-CC",
+                "Ipv4Dhcp": false,
+                "Ipv4Addresses": ["192.168.1.20/24"],
+                "Ipv4Gateways": "192.168.1.1",
+                "DnsIpv4": ["1.1.1.1", "9.9.9.9"],
+                "DnsIpv6": [null],
+                "DnsIpv4Dhcp": false,
+                "DnsIpv6Dhcp": true,
+                "Ipv6Bound": true,
+                "Ipv4Metric": 25,
+                "Ipv4AutoMetric": false,
+                "Ipv6Metric": 25,
+                "Ipv6AutoMetric": true
+            },
+            {
+                "Index": 7,
+                "Alias": "Wi-Fi",
+                "Mac": "AC-DE-48-00-11-22",
+                "Ipv4Dhcp": true,
+                "Ipv4Addresses": null,
+                "Ipv4Gateways": [],
+                "DnsIpv4": [null],
+                "DnsIpv6": null,
+                "DnsIpv4Dhcp": true,
+                "DnsIpv6Dhcp": true,
+                "Ipv6Bound": true,
+                "Ipv4AutoMetric": true,
+                "Ipv6AutoMetric": true
+            }
+        ],
+        "Teredo": "Default"
+    }"#;
+
+    fn snapshot() -> NetworkSnapshot {
+        build_snapshot(
+            " Before ",
+            false,
+            CAPTURE,
+            Some(0),
+            ProxySettings::default(),
+        )
+        .unwrap()
+    }
+
+    fn settings(changes: &[NetworkChange]) -> Vec<(Option<&str>, &str)> {
+        changes
+            .iter()
+            .map(|c| (c.adapter.as_deref(), c.setting.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn build_snapshot_tolerates_null_and_scalar_lists() {
+        let snapshot = snapshot();
+        assert_eq!(snapshot.version, SNAPSHOT_VERSION);
+        assert_eq!(snapshot.name, "Before");
+        assert_eq!(snapshot.adapters.len(), 2);
+
+        let wired = &snapshot.adapters[0];
+        assert_eq!(wired.ipv4_gateways, vec!["192.168.1.1"]);
+        assert_eq!(wired.dns_ipv4, vec!["1.1.1.1", "9.9.9.9"]);
+        assert!(wired.dns_ipv6.is_empty());
+        assert_eq!(wired.ipv4_metric, Some(25));
+
+        let wifi = &snapshot.adapters[1];
+        assert!(wifi.ipv4_addresses.is_empty());
+        assert!(wifi.dns_ipv4.is_empty());
+        assert!(wifi.dns_ipv6.is_empty());
+        assert_eq!(wifi.ipv4_metric, None);
+        assert_eq!(snapshot.teredo.as_deref(), Some("Default"));
+    }
+
+    #[test]
+    fn build_snapshot_defaults_name_and_drops_empty_teredo() {
+        let snapshot = build_snapshot(
+            "  ",
+            true,
+            r#"{"Adapters":[],"Teredo":" "}"#,
+            None,
+            ProxySettings::default(),
+        )
+        .unwrap();
+        assert_eq!(snapshot.name, "Network snapshot");
+        assert!(snapshot.automatic);
+        assert!(snapshot.teredo.is_none());
+        assert!(build_snapshot("x", false, "not json", None, ProxySettings::default()).is_err());
+    }
+
+    #[test]
+    fn snapshot_round_trips_through_json() {
+        let snapshot = snapshot();
+        let raw = serde_json::to_string(&snapshot).unwrap();
+        let parsed = parse_snapshot(&raw).unwrap();
+        assert_eq!(parsed.adapters, snapshot.adapters);
+
+        let mut future = serde_json::to_value(&snapshot).unwrap();
+        future["version"] = (SNAPSHOT_VERSION + 1).into();
+        assert!(parse_snapshot(&future.to_string()).is_err());
+    }
+
+    #[test]
+    fn diff_of_identical_snapshots_is_empty() {
+        assert!(diff(&snapshot(), &snapshot()).is_empty());
+    }
+
+    #[test]
+    fn diff_matches_adapters_by_mac_and_reports_changes() {
+        let saved = snapshot();
+        let mut current = snapshot();
+        // A stack reset renumbers and renames the adapter, the MAC stays.
+        current.adapters[0].index = 31;
+        current.adapters[0].alias = "Ethernet 2".to_string();
+        current.adapters[0].dns_ipv4_dhcp = true;
+        current.adapters[0].dns_ipv4.clear();
+        current.adapters[0].ipv6_bound = false;
+        current.adapters[0].ipv4_metric = Some(5);
+        current.adapters.remove(1);
+        current.teredo = Some("Disabled".to_string());
+        current.ipv6_disabled_components = Some(0xff);
+        current.proxy.enabled = true;
+        current.proxy.server = Some("127.0.0.1:8080".to_string());
+
+        let changes = diff(&saved, &current);
+        assert_eq!(
+            settings(&changes),
+            vec![
+                (Some("Bob's Ethernet"), "ipv6_binding"),
+                (Some("Bob's Ethernet"), "dns"),
+                (Some("Bob's Ethernet"), "ipv4_metric"),
+                (Some("Wi-Fi"), "adapter"),
+                (None, "teredo"),
+                (None, "ipv6_disabled_components"),
+                (None, "proxy"),
+            ]
+        );
+        let dns = &changes[1];
+        assert_eq!(dns.snapshot, "IPv4: 1.1.1.1, 9.9.9.9; IPv6: automatic");
+        assert_eq!(dns.current, "IPv4: automatic; IPv6: automatic");
+        assert_eq!(changes[6].current, "on (127.0.0.1:8080)");
+    }
+
+    #[test]
+    fn diff_ignores_teredo_case_and_unbound_metrics() {
+        let saved = snapshot();
+        let mut current = snapshot();
+        current.teredo = Some("default".to_string());
+        current.adapters[0].ipv4_auto_metric = false;
+        current.adapters[0].ipv4_metric = None;
+        assert!(diff(&saved, &current).is_empty());
+    }
+
+    #[test]
+    fn restore_script_targets_current_adapter_and_quotes_alias() {
+        let saved = snapshot();
+        let mut current = snapshot();
+        current.adapters[0].index = 31;
+        current.adapters[0].alias = "Bob's Ethernet".to_string();
+        current.adapters[0].dns_ipv4_dhcp = true;
+        current.adapters[0].dns_ipv4.clear();
+        current.adapters[0].ipv6_bound = false;
+        current.adapters[0].ipv4_dhcp = Some(true);
+        current.teredo = Some("Disabled".to_string());
+
+        let changes = diff(&saved, &current);
+        let script = restore_script(&saved, &current, &changes).unwrap();
+        assert!(script
+            .contains("Enable-NetAdapterBinding -Name 'Bob''s Ethernet' -ComponentID ms_tcpip6"));
+        assert!(script.contains(
+            "Set-DnsClientServerAddress -InterfaceIndex 31 -ServerAddresses @('1.1.1.1','9.9.9.9')"
+        ));
+        assert!(script.contains(
+            "New-NetIPAddress -InterfaceIndex 31 -IPAddress '192.168.1.20' -PrefixLength 24"
+        ));
+        assert!(script.contains("-DestinationPrefix '0.0.0.0/0' -NextHop '192.168.1.1'"));
+        assert!(script.contains("Set-NetTeredoConfiguration -Type Default"));
+        assert!(script.contains("Scope = 'Bob''s Ethernet'; Setting = 'dns'"));
+        assert!(!script.contains("InterfaceIndex 12"));
+    }
+
+    #[test]
+    fn restore_script_rejects_invalid_snapshot_values() {
+        let mut saved = snapshot();
+        let current = snapshot();
+        saved.teredo = Some("Bogus; Remove-Item C:\\".to_string());
+        let changes = diff(&saved, &current);
+        assert!(restore_script(&saved, &current, &changes).is_err());
+
+        let mut saved = snapshot();
+        saved.adapters[0].dns_ipv4 = vec!["1.1.1.1'; calc; '".to_string()];
+        let changes = diff(&saved, &current);
+        assert!(restore_script(&saved, &current, &changes).is_err());
+    }
+
+    #[test]
+    fn restore_results_parse() {
+        let results = parse_restore_results(
+            r#"{"Results":[{"Scope":"Wi-Fi","Setting":"dns","Success":false,"Error":"Access denied"},{"Scope":"system","Setting":"teredo","Success":true}]}"#,
+        )
+        .unwrap();
+        assert_eq!(results.len(), 2);
+        assert!(!results[0].success);
+        assert_eq!(results[0].error.as_deref(), Some("Access denied"));
+        assert!(results[1].error.is_none());
+    }
+}
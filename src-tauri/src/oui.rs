@@ -0,0 +1,688 @@
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use crate::util;
+
+// Tab separated `registry prefix organization` rows. The embedded file is a
+// curated seed of common MA-L blocks; data/update-oui.ps1 replaces it with
+// the full IEEE MA-L/MA-M/MA-S listing, and update_database() downloads the
+// same listing into the data directory, which takes precedence at runtime.
+const OUI_DATA: &[u8] = include_bytes!("../data/oui.tsv.gz");
+const IEEE_SOURCES: &[(Registry, &str)] = &[
+    (Registry::MaL, "https://standards-oui.ieee.org/oui/oui.csv"),
+    (
+        Registry::MaM,
+        "https://standards-oui.ieee.org/oui28/mam.csv",
+    ),
+    (
+        Registry::MaS,
+        "https://standards-oui.ieee.org/oui36/oui36.csv",
+    ),
+];
+const DOWNLOAD_TIMEOUT_SECS: u64 = 120;
+
+lazy_static::lazy_static! {
+    static ref DATABASE: RwLock<Arc<OuiDatabase>> = RwLock::new(Arc::new(load_database()));
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+pub enum Registry {
+    #[serde(rename = "MA-L")]
+    MaL,
+    #[serde(rename = "MA-M")]
+    MaM,
+    #[serde(rename = "MA-S")]
+    MaS,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeviceCategory {
+    VirtualMachine,
+    NetworkEquipment,
+    Computer,
+    Mobile,
+    Printer,
+    MediaPlayer,
+    GameConsole,
+    Camera,
+    Storage,
+    SmartHome,
+    Embedded,
+    Unknown,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Vendor {
+    pub name: String,
+    pub prefix: String,
+    pub registry: Registry,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MacInfo {
+    pub mac: String,
+    pub vendor: Option<String>,
+    pub prefix: Option<String>,
+    pub registry: Option<Registry>,
+    pub locally_administered: bool,
+    pub multicast: bool,
+    pub randomized: bool,
+    pub category: DeviceCategory,
+}
+
+#[derive(Debug, Default)]
+pub struct OuiDatabase {
+    large: HashMap<u64, String>,
+    medium: HashMap<u64, String>,
+    small: HashMap<u64, String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DatabaseInfo {
+    pub source: &'static str,
+    pub updated_unix: Option<u64>,
+    pub ma_l: usize,
+    pub ma_m: usize,
+    pub ma_s: usize,
+}
+
+// Locally administered prefixes handed out by well-known virtualization
+// stacks; these are stable, not randomized.
+const LOCAL_PREFIXES: &[(&str, &str)] = &[
+    ("525400", "QEMU/KVM virtual NIC"),
+    ("0242", "Docker container"),
+];
+
+// Checked in order against the MAC prefix first, then the vendor name.
+const PREFIX_CATEGORIES: &[(&str, DeviceCategory)] = &[
+    ("00155D", DeviceCategory::VirtualMachine),
+    ("525400", DeviceCategory::VirtualMachine),
+    ("0242", DeviceCategory::VirtualMachine),
+    ("7CED8D", DeviceCategory::GameConsole),
+    ("985FD3", DeviceCategory::GameConsole),
+];
+
+const VENDOR_CATEGORIES: &[(&str, DeviceCategory)] = &[
+    ("vmware", DeviceCategory::VirtualMachine),
+    ("pcs systemtechnik", DeviceCategory::VirtualMachine),
+    ("parallels", DeviceCategory::VirtualMachine),
+    ("xensource", DeviceCategory::VirtualMachine),
+    ("ubiquiti", DeviceCategory::NetworkEquipment),
+    ("routerboard", DeviceCategory::NetworkEquipment),
+    ("tp-link", DeviceCategory::NetworkEquipment),
+    ("netgear", DeviceCategory::NetworkEquipment),
+    ("d-link", DeviceCategory::NetworkEquipment),
+    ("linksys", DeviceCategory::NetworkEquipment),
+    ("avm gmbh", DeviceCategory::NetworkEquipment),
+    ("cisco", DeviceCategory::NetworkEquipment),
+    ("juniper", DeviceCategory::NetworkEquipment),
+    ("aruba", DeviceCategory::NetworkEquipment),
+    ("zyxel", DeviceCategory::NetworkEquipment),
+    ("brother industries", DeviceCategory::Printer),
+    ("seiko epson", DeviceCategory::Printer),
+    ("canon", DeviceCategory::Printer),
+    ("lexmark", DeviceCategory::Printer),
+    ("kyocera", DeviceCategory::Printer),
+    ("roku", DeviceCategory::MediaPlayer),
+    ("sonos", DeviceCategory::MediaPlayer),
+    ("nintendo", DeviceCategory::GameConsole),
+    ("sony interactive", DeviceCategory::GameConsole),
+    ("hikvision", DeviceCategory::Camera),
+    ("dahua", DeviceCategory::Camera),
+    ("axis communications", DeviceCategory::Camera),
+    ("synology", DeviceCategory::Storage),
+    ("qnap", DeviceCategory::Storage),
+    ("western digital", DeviceCategory::Storage),
+    ("nest labs", DeviceCategory::SmartHome),
+    ("philips lighting", DeviceCategory::SmartHome),
+    ("signify", DeviceCategory::SmartHome),
+    ("amazon technologies", DeviceCategory::SmartHome),
+    ("raspberry pi", DeviceCategory::Embedded),
+    ("espressif", DeviceCategory::Embedded),
+    ("xiaomi", DeviceCategory::Mobile),
+    ("huawei", DeviceCategory::Mobile),
+    ("samsung", DeviceCategory::Mobile),
+    ("apple", DeviceCategory::Mobile),
+    ("google", DeviceCategory::Mobile),
+    ("intel", DeviceCategory::Computer),
+    ("dell", DeviceCategory::Computer),
+    ("hewlett packard", DeviceCategory::Computer),
+    ("lenovo", DeviceCategory::Computer),
+    ("asustek", DeviceCategory::Computer),
+    ("micro-star", DeviceCategory::Computer),
+    ("gigabyte", DeviceCategory::Computer),
+    ("realtek", DeviceCategory::Computer),
+    ("microsoft", DeviceCategory::Computer),
+];
+
+impl Registry {
+    fn from_code(code: &str) -> Option<Self> {
+        match code.trim() {
+            "L" | "MA-L" => Some(Registry::MaL),
+            "M" | "MA-M" => Some(Registry::MaM),
+            "S" | "MA-S" => Some(Registry::MaS),
+            _ => None,
+        }
+    }
+
+    fn code(self) -> &'static str {
+        match self {
+            Registry::MaL => "L",
+            Registry::MaM => "M",
+            Registry::MaS => "S",
+        }
+    }
+
+    fn hex_digits(self) -> usize {
+        match self {
+            Registry::MaL => 6,
+            Registry::MaM => 7,
+            Registry::MaS => 9,
+        }
+    }
+}
+
+impl DeviceCategory {
+    pub fn label(self) -> &'static str {
+        match self {
+            DeviceCategory::VirtualMachine => "Virtual Machine",
+            DeviceCategory::NetworkEquipment => "Router/Network Device",
+            DeviceCategory::Computer => "Computer",
+            DeviceCategory::Mobile => "Mobile Device",
+            DeviceCategory::Printer => "Printer",
+            DeviceCategory::MediaPlayer => "Media Player",
+            DeviceCategory::GameConsole => "Game Console",
+            DeviceCategory::Camera => "Camera",
+            DeviceCategory::Storage => "Network Storage",
+            DeviceCategory::SmartHome => "Smart Home Device",
+            DeviceCategory::Embedded => "Embedded Device",
+            DeviceCategory::Unknown => "Unknown Device",
+        }
+    }
+}
+
+impl OuiDatabase {
+    pub fn parse(text: &str) -> Self {
+        let mut db = OuiDatabase::default();
+        for line in text.lines() {
+            if line.starts_with('#') {
+                continue;
+            }
+            let mut fields = line.splitn(3, '\t');
+            let (Some(code), Some(prefix), Some(name)) =
+                (fields.next(), fields.next(), fields.next())
+            else {
+                continue;
+            };
+            let Some(registry) = Registry::from_code(code) else {
+                continue;
+            };
+            let prefix = prefix.trim();
+            let name = name.trim();
+            if prefix.len() != registry.hex_digits() || name.is_empty() {
+                continue;
+            }
+            if let Ok(value) = u64::from_str_radix(prefix, 16) {
+                db.table_mut(registry).insert(value, name.to_string());
+            }
+        }
+        db
+    }
+
+    pub fn from_gzip(bytes: &[u8]) -> Result<Self, String> {
+        let mut text = String::new();
+        GzDecoder::new(bytes)
+            .read_to_string(&mut text)
+            .map_err(|e| format!("Failed to decompress OUI database: {}", e))?;
+        Ok(Self::parse(&text))
+    }
+
+    pub fn counts(&self) -> (usize, usize, usize) {
+        (self.large.len(), self.medium.len(), self.small.len())
+    }
+
+    fn table(&self, registry: Registry) -> &HashMap<u64, String> {
+        match registry {
+            Registry::MaL => &self.large,
+            Registry::MaM => &self.medium,
+            Registry::MaS => &self.small,
+        }
+    }
+
+    fn table_mut(&mut self, registry: Registry) -> &mut HashMap<u64, String> {
+        match registry {
+            Registry::MaL => &mut self.large,
+            Registry::MaM => &mut self.medium,
+            Registry::MaS => &mut self.small,
+        }
+    }
+
+    // MA-M and MA-S blocks are carved out of MA-L ranges the IEEE owns, so
+    // the longest registered prefix wins.
+    pub fn lookup(&self, mac: &[u8; 6]) -> Option<Vendor> {
+        let value = mac_value(mac);
+        [Registry::MaS, Registry::MaM, Registry::MaL]
+            .into_iter()
+            .find_map(|registry| {
+                let digits = registry.hex_digits();
+                let prefix = value >> (48 - digits * 4);
+                self.table(registry).get(&prefix).map(|name| Vendor {
+                    name: name.clone(),
+                    prefix: format!("{:0width$X}", prefix, width = digits),
+                    registry,
+                })
+            })
+    }
+}
+
+fn downloaded_path() -> PathBuf {
+    util::data_dir().join("oui.tsv.gz")
+}
+
+fn load_database() -> OuiDatabase {
+    std::fs::read(downloaded_path())
+        .ok()
+        .and_then(|bytes| OuiDatabase::from_gzip(&bytes).ok())
+        .filter(|db| !db.large.is_empty())
+        .or_else(|| OuiDatabase::from_gzip(OUI_DATA).ok())
+        .unwrap_or_default()
+}
+
+fn database() -> Arc<OuiDatabase> {
+    DATABASE
+        .read()
+        .map(|db| db.clone())
+        .unwrap_or_else(|_| Arc::new(OuiDatabase::default()))
+}
+
+// Splits IEEE CSV text into records; quoted fields may hold commas, doubled
+// quotes and line breaks.
+fn csv_records(text: &str) -> Vec<Vec<String>> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => record.push(std::mem::take(&mut field)),
+            '\r' if !quoted => {}
+            '\n' if !quoted => {
+                record.push(std::mem::take(&mut field));
+                records.push(std::mem::take(&mut record));
+            }
+            _ => field.push(c),
+        }
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+    records
+}
+
+// Converts one IEEE listing (Registry,Assignment,Organization Name,...) to
+// the tab separated rows the database is stored as.
+pub fn ieee_csv_rows(registry: Registry, text: &str) -> Vec<String> {
+    csv_records(text)
+        .into_iter()
+        .skip(1)
+        .filter_map(|record| {
+            let prefix = record.get(1)?.trim().to_uppercase();
+            let name = record
+                .get(2)?
+                .split_whitespace()
+                .collect::<Vec<_>>()
+                .join(" ");
+            (prefix.len() == registry.hex_digits()
+                && prefix.chars().all(|c| c.is_ascii_hexdigit())
+                && !name.is_empty())
+            .then(|| format!("{}\t{}\t{}", registry.code(), prefix, name))
+        })
+        .collect()
+}
+
+pub async fn update_database() -> Result<DatabaseInfo, String> {
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(DOWNLOAD_TIMEOUT_SECS))
+        .user_agent("ConfUtils OUI updater")
+        .build()
+        .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
+    let mut rows = Vec::new();
+    for (registry, url) in IEEE_SOURCES {
+        let text = client
+            .get(*url)
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(|e| format!("Failed to download {}: {}", url, e))?
+            .text()
+            .await
+            .map_err(|e| format!("Failed to read {}: {}", url, e))?;
+        let parsed = ieee_csv_rows(*registry, &text);
+        if parsed.is_empty() {
+            return Err(format!("No assignments found in {}", url));
+        }
+        rows.extend(parsed);
+    }
+    rows.sort();
+    rows.dedup();
+    let text = format!("# registry\tprefix\torganization\n{}\n", rows.join("\n"));
+
+    tokio::task::spawn_blocking(move || {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::best());
+        encoder
+            .write_all(text.as_bytes())
+            .and_then(|_| encoder.finish())
+            .and_then(|bytes| std::fs::write(downloaded_path(), bytes))
+            .map_err(|e| format!("Failed to save OUI database: {}", e))?;
+        let db = Arc::new(OuiDatabase::parse(&text));
+        if let Ok(mut current) = DATABASE.write() {
+            *current = db;
+        }
+        Ok(database_info())
+    })
+    .await
+    .map_err(|e| format!("OUI update task failed: {}", e))?
+}
+
+pub fn database_info() -> DatabaseInfo {
+    let (ma_l, ma_m, ma_s) = database().counts();
+    let updated_unix = std::fs::metadata(downloaded_path())
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|m| m.duration_since(std::time::UNIX_EPOCH).ok())
+        .map(|d| d.as_secs());
+    DatabaseInfo {
+        source: if updated_unix.is_some() {
+            "ieee"
+        } else {
+            "embedded"
+        },
+        updated_unix,
+        ma_l,
+        ma_m,
+        ma_s,
+    }
+}
+
+fn mac_value(mac: &[u8; 6]) -> u64 {
+    mac.iter().fold(0u64, |acc, b| (acc << 8) | *b as u64)
+}
+
+// Accepts aa-bb-cc-dd-ee-ff, aa:bb:cc:dd:ee:ff, aabb.ccdd.eeff and bare hex.
+pub fn parse_mac(raw: &str) -> Option<[u8; 6]> {
+    let hex: String = raw
+        .trim()
+        .chars()
+        .filter(|c| !matches!(c, '-' | ':' | '.'))
+        .collect();
+    if hex.len() != 12 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let mut mac = [0u8; 6];
+    for (i, byte) in mac.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).ok()?;
+    }
+    Some(mac)
+}
+
+pub fn format_mac(mac: &[u8; 6]) -> String {
+    mac.iter()
+        .map(|b| format!("{:02X}", b))
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+pub fn is_locally_administered(mac: &[u8; 6]) -> bool {
+    mac[0] & 0x02 != 0
+}
+
+pub fn is_multicast(mac: &[u8; 6]) -> bool {
+    mac[0] & 0x01 != 0
+}
+
+pub fn categorize(hex: &str, vendor: Option<&str>) -> DeviceCategory {
+    if let Some((_, category)) = PREFIX_CATEGORIES
+        .iter()
+        .find(|(prefix, _)| hex.starts_with(prefix))
+    {
+        return *category;
+    }
+    let Some(vendor) = vendor.map(|v| v.to_lowercase()) else {
+        return DeviceCategory::Unknown;
+    };
+    VENDOR_CATEGORIES
+        .iter()
+        .find(|(keyword, _)| vendor.contains(keyword))
+        .map(|(_, category)| *category)
+        .unwrap_or(DeviceCategory::Unknown)
+}
+
+pub fn identify_with(db: &OuiDatabase, mac: &[u8; 6]) -> MacInfo {
+    let hex = format!("{:012X}", mac_value(mac));
+    let locally_administered = is_locally_administered(mac);
+    let multicast = is_multicast(mac);
+    let local = LOCAL_PREFIXES
+        .iter()
+        .find(|(prefix, _)| hex.starts_with(prefix));
+    // Locally administered addresses never carry an IEEE assignment, so a
+    // registry hit on one would be a coincidence.
+    let vendor = if locally_administered {
+        None
+    } else {
+        db.lookup(mac)
+    };
+    let vendor_name = vendor
+        .as_ref()
+        .map(|v| v.name.clone())
+        .or_else(|| local.map(|(_, label)| label.to_string()));
+    MacInfo {
+        mac: format_mac(mac),
+        category: categorize(&hex, vendor_name.as_deref()),
+        prefix: vendor
+            .as_ref()
+            .map(|v| v.prefix.clone())
+            .or_else(|| local.map(|(prefix, _)| prefix.to_string())),
+        registry: vendor.as_ref().map(|v| v.registry),
+        vendor: vendor_name,
+        locally_administered,
+        multicast,
+        randomized: locally_administered && !multicast && local.is_none(),
+    }
+}
+
+pub fn identify_mac(mac: &[u8; 6]) -> MacInfo {
+    identify_with(&database(), mac)
+}
+
+pub fn identify(raw: &str) -> Option<MacInfo> {
+    parse_mac(raw).map(|mac| identify_mac(&mac))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIXTURE: &str = "# registry\tprefix\torganization\n\
+        L\t70B3D5\tIEEE Registration Authority\n\
+        M\t70B3D51\tMedium Block Org\n\
+        S\t70B3D5123\tSmall Block Org\n\
+        L\t00155D\tMicrosoft Corporation\n\
+        L\tB827EB\tRaspberry Pi Foundation\n\
+        L\t021122\tShould Never Match\n\
+        L\t12345\tWrong Length\n\
+        X\t001122\tUnknown Registry\n";
+
+    fn mac(raw: &str) -> [u8; 6] {
+        parse_mac(raw).unwrap()
+    }
+
+    #[test]
+    fn parse_mac_accepts_common_formats() {
+        let expected = [0xAA, 0xBB, 0xCC, 0xDD, 0xEE, 0xFF];
+        for raw in [
+            "aa-bb-cc-dd-ee-ff",
+            "AA:BB:CC:DD:EE:FF",
+            "aabb.ccdd.eeff",
+            "aabbccddeeff",
+            "  AA-BB-CC-DD-EE-FF\r\n",
+        ] {
+            assert_eq!(parse_mac(raw), Some(expected), "{}", raw);
+        }
+        for raw in ["", "aa:bb:cc", "aa:bb:cc:dd:ee:ff:00", "gg:bb:cc:dd:ee:ff"] {
+            assert_eq!(parse_mac(raw), None, "{}", raw);
+        }
+        assert_eq!(format_mac(&expected), "AA:BB:CC:DD:EE:FF");
+    }
+
+    #[test]
+    fn parse_skips_comments_and_malformed_rows() {
+        let db = OuiDatabase::parse(FIXTURE);
+        assert_eq!(db.counts(), (4, 1, 1));
+    }
+
+    #[test]
+    fn lookup_prefers_longest_registered_prefix() {
+        let db = OuiDatabase::parse(FIXTURE);
+
+        let small = db.lookup(&mac("70:B3:D5:12:34:56")).unwrap();
+        assert_eq!(small.name, "Small Block Org");
+        assert_eq!(small.prefix, "70B3D5123");
+        assert_eq!(small.registry, Registry::MaS);
+
+        let medium = db.lookup(&mac("70:B3:D5:1F:00:00")).unwrap();
+        assert_eq!(medium.name, "Medium Block Org");
+        assert_eq!(medium.prefix, "70B3D51");
+        assert_eq!(medium.registry, Registry::MaM);
+
+        let large = db.lookup(&mac("70:B3:D5:F0:00:00")).unwrap();
+        assert_eq!(large.name, "IEEE Registration Authority");
+        assert_eq!(large.registry, Registry::MaL);
+
+        assert!(db.lookup(&mac("00:00:00:00:00:01")).is_none());
+    }
+
+    #[test]
+    fn identify_flags_local_and_randomized_addresses() {
+        let db = OuiDatabase::parse(FIXTURE);
+
+        let random = identify_with(&db, &mac("02:11:22:33:44:55"));
+        assert!(random.locally_administered);
+        assert!(random.randomized);
+        assert!(!random.multicast);
+        assert_eq!(random.vendor, None);
+        assert_eq!(random.category, DeviceCategory::Unknown);
+
+        let qemu = identify_with(&db, &mac("52:54:00:12:34:56"));
+        assert!(qemu.locally_administered);
+        assert!(!qemu.randomized);
+        assert_eq!(qemu.vendor.as_deref(), Some("QEMU/KVM virtual NIC"));
+        assert_eq!(qemu.category, DeviceCategory::VirtualMachine);
+
+        let multicast = identify_with(&db, &mac("01:00:5E:00:00:FB"));
+        assert!(multicast.multicast);
+        assert!(!multicast.randomized);
+
+        let hyperv = identify_with(&db, &mac("00:15:5D:01:02:03"));
+        assert!(!hyperv.locally_administered);
+        assert_eq!(hyperv.vendor.as_deref(), Some("Microsoft Corporation"));
+        assert_eq!(hyperv.registry, Some(Registry::MaL));
+        assert_eq!(hyperv.category, DeviceCategory::VirtualMachine);
+    }
+
+    #[test]
+    fn categorize_checks_prefix_before_vendor() {
+        assert_eq!(
+            categorize("7CED8D000000", Some("Microsoft Corporation")),
+            DeviceCategory::GameConsole
+        );
+        assert_eq!(
+            categorize("281878000000", Some("Microsoft Corporation")),
+            DeviceCategory::Computer
+        );
+        assert_eq!(
+            categorize("B827EB000000", Some("Raspberry Pi Foundation")),
+            DeviceCategory::Embedded
+        );
+        assert_eq!(
+            categorize("000000000000", Some("Cisco Systems, Inc")),
+            DeviceCategory::NetworkEquipment
+        );
+        assert_eq!(categorize("000000000000", None), DeviceCategory::Unknown);
+    }
+
+    #[test]
+    fn ieee_csv_rows_handle_quoted_fields() {
+        let csv = "Registry,Assignment,Organization Name,Organization Address\r\n\
+            MA-L,00000C,\"Cisco Systems, Inc\",\"170 West Tasman Drive\r\nSan Jose CA 95134\"\r\n\
+            MA-L,b827eb,Raspberry   Pi Foundation,Cambridge\r\n\
+            MA-L,XYZ123,Invalid,Nowhere\r\n\
+            MA-L,001122,,Nowhere\r\n";
+        assert_eq!(
+            ieee_csv_rows(Registry::MaL, csv),
+            vec![
+                "L\t00000C\tCisco Systems, Inc".to_string(),
+                "L\tB827EB\tRaspberry Pi Foundation".to_string(),
+            ]
+        );
+        let mam = "Registry,Assignment,Organization Name,Organization Address\n\
+            MA-M,70B3D51,\"Say \"\"Hi\"\" Ltd\",Somewhere\n";
+        assert_eq!(
+            ieee_csv_rows(Registry::MaM, mam),
+            vec!["M\t70B3D51\tSay \"Hi\" Ltd".to_string()]
+        );
+    }
+
+    #[test]
+    fn ieee_listings_resolve_medium_and_small_blocks() {
+        let header = "Registry,Assignment,Organization Name,Organization Address\n";
+        let mut rows = ieee_csv_rows(
+            Registry::MaL,
+            &format!(
+                "{}MA-L,8C1F64,IEEE Registration Authority,Piscataway\n",
+                header
+            ),
+        );
+        rows.extend(ieee_csv_rows(
+            Registry::MaM,
+            &format!("{}MA-M,8C1F645,\"Medium Vendor, Inc\",Somewhere\n", header),
+        ));
+        rows.extend(ieee_csv_rows(
+            Registry::MaS,
+            &format!("{}MA-S,8C1F64ABC,Small Vendor GmbH,Elsewhere\n", header),
+        ));
+        let db = OuiDatabase::parse(&rows.join("\n"));
+        assert_eq!(db.counts(), (1, 1, 1));
+
+        let small = db.lookup(&mac("8C:1F:64:AB:C1:23")).unwrap();
+        assert_eq!(
+            (small.prefix.as_str(), small.registry),
+            ("8C1F64ABC", Registry::MaS)
+        );
+        let medium = db.lookup(&mac("8C:1F:64:5A:BC:DE")).unwrap();
+        assert_eq!(medium.name, "Medium Vendor, Inc");
+        assert_eq!(medium.registry, Registry::MaM);
+        let large = db.lookup(&mac("8C:1F:64:AB:D0:00")).unwrap();
+        assert_eq!(large.registry, Registry::MaL);
+    }
+
+    #[test]
+    fn embedded_database_loads() {
+        let db = OuiDatabase::from_gzip(OUI_DATA).unwrap();
+        assert!(db.counts().0 > 0);
+    }
+}
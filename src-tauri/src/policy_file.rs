@@ -0,0 +1,710 @@
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+const PREG_SIGNATURE: &[u8; 4] = b"PReg";
+const PREG_VERSION: u32 = 1;
+const REGISTRY_CSE_GUID: &str = "{35378EAC-683F-11D2-A89A-00C04FBBCFA2}";
+const MACHINE_TOOL_GUID: &str = "{D02B1F72-3407-48AE-BA88-E8213C6761F1}";
+const USER_TOOL_GUID: &str = "{D02B1F73-3407-48AE-BA88-E8213C6761F1}";
+
+pub const REG_SZ: u32 = 1;
+pub const REG_EXPAND_SZ: u32 = 2;
+pub const REG_BINARY: u32 = 3;
+pub const REG_DWORD: u32 = 4;
+pub const REG_MULTI_SZ: u32 = 7;
+pub const REG_QWORD: u32 = 11;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PolicyScope {
+    Machine,
+    User,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PolicyEntry {
+    pub key: String,
+    pub value_name: String,
+    pub value_type: u32,
+    pub data: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PolicyFile {
+    pub entries: Vec<PolicyEntry>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PolicyEntryView {
+    pub key: String,
+    pub value_name: String,
+    pub value_type: String,
+    pub data: serde_json::Value,
+    pub action: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TweakRegistryEntry {
+    #[serde(rename = "Path")]
+    pub path: String,
+    #[serde(rename = "Name")]
+    pub name: String,
+    #[serde(rename = "Type")]
+    pub value_type: String,
+    #[serde(rename = "Value")]
+    pub value: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PolicyConflict {
+    pub path: String,
+    pub name: String,
+    pub scope: PolicyScope,
+    pub status: String,
+    pub wanted: serde_json::Value,
+    pub gpo_value: Option<serde_json::Value>,
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn u16(&mut self) -> Result<u16, String> {
+        let b = self
+            .bytes
+            .get(self.pos..self.pos + 2)
+            .ok_or_else(|| format!("Unexpected end of Registry.pol at offset {}", self.pos))?;
+        self.pos += 2;
+        Ok(u16::from_le_bytes([b[0], b[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        let b = self
+            .bytes
+            .get(self.pos..self.pos + 4)
+            .ok_or_else(|| format!("Unexpected end of Registry.pol at offset {}", self.pos))?;
+        self.pos += 4;
+        Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn expect(&mut self, ch: char) -> Result<(), String> {
+        let at = self.pos;
+        let got = self.u16()?;
+        if got != ch as u16 {
+            return Err(format!(
+                "Malformed Registry.pol: expected '{}' at offset {}",
+                ch, at
+            ));
+        }
+        Ok(())
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        let mut units = Vec::new();
+        loop {
+            let unit = self.u16()?;
+            if unit == 0 {
+                break;
+            }
+            units.push(unit);
+        }
+        String::from_utf16(&units).map_err(|_| {
+            format!(
+                "Malformed Registry.pol: invalid UTF-16 string before offset {}",
+                self.pos
+            )
+        })
+    }
+
+    fn data(&mut self, size: usize) -> Result<Vec<u8>, String> {
+        let b = self
+            .bytes
+            .get(self.pos..self.pos + size)
+            .ok_or_else(|| format!("Registry.pol data overruns file at offset {}", self.pos))?;
+        self.pos += size;
+        Ok(b.to_vec())
+    }
+}
+
+fn push_utf16(out: &mut Vec<u8>, text: &str) {
+    for unit in text.encode_utf16() {
+        out.extend_from_slice(&unit.to_le_bytes());
+    }
+}
+
+fn push_char(out: &mut Vec<u8>, ch: char) {
+    out.extend_from_slice(&(ch as u16).to_le_bytes());
+}
+
+pub fn type_name(value_type: u32) -> &'static str {
+    match value_type {
+        0 => "REG_NONE",
+        REG_SZ => "REG_SZ",
+        REG_EXPAND_SZ => "REG_EXPAND_SZ",
+        REG_BINARY => "REG_BINARY",
+        REG_DWORD => "REG_DWORD",
+        5 => "REG_DWORD_BIG_ENDIAN",
+        REG_MULTI_SZ => "REG_MULTI_SZ",
+        REG_QWORD => "REG_QWORD",
+        _ => "REG_UNKNOWN",
+    }
+}
+
+fn decode_utf16z(data: &[u8]) -> String {
+    let units: Vec<u16> = data
+        .chunks_exact(2)
+        .map(|c| u16::from_le_bytes([c[0], c[1]]))
+        .collect();
+    String::from_utf16_lossy(&units)
+        .trim_end_matches('\0')
+        .to_string()
+}
+
+pub fn render_data(value_type: u32, data: &[u8]) -> serde_json::Value {
+    match value_type {
+        REG_SZ | REG_EXPAND_SZ => serde_json::Value::String(decode_utf16z(data)),
+        REG_MULTI_SZ => serde_json::json!(decode_utf16z(data)
+            .split('\0')
+            .filter(|s| !s.is_empty())
+            .collect::<Vec<_>>()),
+        REG_DWORD if data.len() >= 4 => {
+            serde_json::json!(u32::from_le_bytes([data[0], data[1], data[2], data[3]]))
+        }
+        REG_QWORD if data.len() >= 8 => {
+            let mut raw = [0u8; 8];
+            raw.copy_from_slice(&data[..8]);
+            serde_json::json!(u64::from_le_bytes(raw))
+        }
+        _ => serde_json::Value::String(
+            data.iter()
+                .map(|b| format!("{:02x}", b))
+                .collect::<Vec<_>>()
+                .join(" "),
+        ),
+    }
+}
+
+pub fn encode_tweak_value(value_type: &str, value: &str) -> Result<(u32, Vec<u8>), String> {
+    match value_type.to_lowercase().as_str() {
+        "dword" => {
+            let v = parse_number(value, false)?;
+            let v = u32::try_from(v).map_err(|_| format!("DWORD value out of range: {}", value))?;
+            Ok((REG_DWORD, v.to_le_bytes().to_vec()))
+        }
+        "qword" => Ok((REG_QWORD, parse_number(value, true)?.to_le_bytes().to_vec())),
+        "string" | "expandstring" => {
+            let mut data = Vec::new();
+            push_utf16(&mut data, value);
+            data.extend_from_slice(&[0, 0]);
+            let vtype = if value_type.eq_ignore_ascii_case("string") {
+                REG_SZ
+            } else {
+                REG_EXPAND_SZ
+            };
+            Ok((vtype, data))
+        }
+        "multistring" => {
+            let mut data = Vec::new();
+            for part in value.split('\n').filter(|p| !p.is_empty()) {
+                push_utf16(&mut data, part);
+                data.extend_from_slice(&[0, 0]);
+            }
+            data.extend_from_slice(&[0, 0]);
+            Ok((REG_MULTI_SZ, data))
+        }
+        "binary" => {
+            let cleaned: String = value.chars().filter(|c| c.is_ascii_hexdigit()).collect();
+            if !cleaned.len().is_multiple_of(2) {
+                return Err(format!("Invalid binary value: {}", value));
+            }
+            let data = (0..cleaned.len())
+                .step_by(2)
+                .map(|i| u8::from_str_radix(&cleaned[i..i + 2], 16))
+                .collect::<Result<Vec<u8>, _>>()
+                .map_err(|_| format!("Invalid binary value: {}", value))?;
+            Ok((REG_BINARY, data))
+        }
+        _ => Err(format!("Unsupported registry type: {}", value_type)),
+    }
+}
+
+// Negative values are stored as their two's complement in the value's own
+// width, so -1 is 0xFFFFFFFF as a DWORD and 0xFFFFFFFFFFFFFFFF as a QWORD.
+fn parse_number(value: &str, qword: bool) -> Result<u64, String> {
+    let trimmed = value.trim();
+    let parsed = match trimmed
+        .strip_prefix("0x")
+        .or_else(|| trimmed.strip_prefix("0X"))
+    {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => trimmed.parse::<u64>().ok().or_else(|| {
+            let v = trimmed.parse::<i64>().ok()?;
+            if qword {
+                Some(v as u64)
+            } else {
+                i32::try_from(v).ok().map(|v| v as u32 as u64)
+            }
+        }),
+    };
+    parsed.ok_or_else(|| format!("Invalid numeric value: {}", value))
+}
+
+impl PolicyEntry {
+    pub fn action(&self) -> &'static str {
+        let lower = self.value_name.to_lowercase();
+        if lower.starts_with("**del.") {
+            "delete_value"
+        } else if lower.starts_with("**delvals") {
+            "delete_all_values"
+        } else if lower.starts_with("**deletevalues") {
+            "delete_values"
+        } else if lower.starts_with("**deletekeys") {
+            "delete_keys"
+        } else if lower.starts_with("**securekey") {
+            "secure_key"
+        } else if lower.starts_with("**soft.") {
+            "soft_set"
+        } else if lower.is_empty() && self.data.is_empty() {
+            "create_key"
+        } else {
+            "set_value"
+        }
+    }
+
+    pub fn view(&self) -> PolicyEntryView {
+        PolicyEntryView {
+            key: self.key.clone(),
+            value_name: self.value_name.clone(),
+            value_type: type_name(self.value_type).to_string(),
+            data: render_data(self.value_type, &self.data),
+            action: self.action().to_string(),
+        }
+    }
+}
+
+impl PolicyFile {
+    pub fn parse(bytes: &[u8]) -> Result<Self, String> {
+        if bytes.is_empty() {
+            return Ok(Self::default());
+        }
+        if bytes.len() < 8 || &bytes[0..4] != PREG_SIGNATURE {
+            return Err("Not a Registry.pol file (missing PReg signature)".to_string());
+        }
+        let version = u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]);
+        if version != PREG_VERSION {
+            return Err(format!("Unsupported Registry.pol version: {}", version));
+        }
+
+        let mut reader = Reader { bytes, pos: 8 };
+        let mut entries = Vec::new();
+        while reader.pos < bytes.len() {
+            reader.expect('[')?;
+            let key = reader.string()?;
+            reader.expect(';')?;
+            let value_name = reader.string()?;
+            reader.expect(';')?;
+            let value_type = reader.u32()?;
+            reader.expect(';')?;
+            let size = reader.u32()? as usize;
+            reader.expect(';')?;
+            let data = reader.data(size)?;
+            reader.expect(']')?;
+            entries.push(PolicyEntry {
+                key,
+                value_name,
+                value_type,
+                data,
+            });
+        }
+        Ok(Self { entries })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(PREG_SIGNATURE);
+        out.extend_from_slice(&PREG_VERSION.to_le_bytes());
+        for entry in &self.entries {
+            push_char(&mut out, '[');
+            push_utf16(&mut out, &entry.key);
+            out.extend_from_slice(&[0, 0]);
+            push_char(&mut out, ';');
+            push_utf16(&mut out, &entry.value_name);
+            out.extend_from_slice(&[0, 0]);
+            push_char(&mut out, ';');
+            out.extend_from_slice(&entry.value_type.to_le_bytes());
+            push_char(&mut out, ';');
+            out.extend_from_slice(&(entry.data.len() as u32).to_le_bytes());
+            push_char(&mut out, ';');
+            out.extend_from_slice(&entry.data);
+            push_char(&mut out, ']');
+        }
+        out
+    }
+
+    pub fn find(&self, key: &str, value_name: &str) -> Option<&PolicyEntry> {
+        self.entries.iter().find(|e| {
+            e.key.eq_ignore_ascii_case(key) && e.value_name.eq_ignore_ascii_case(value_name)
+        })
+    }
+
+    pub fn is_deleted(&self, key: &str, value_name: &str) -> bool {
+        let marker = format!("**del.{}", value_name);
+        self.entries.iter().any(|e| {
+            e.key.eq_ignore_ascii_case(key)
+                && (e.value_name.eq_ignore_ascii_case(&marker)
+                    || e.value_name.to_lowercase().starts_with("**delvals"))
+        })
+    }
+
+    pub fn set(&mut self, entry: PolicyEntry) {
+        let marker = format!("**del.{}", entry.value_name);
+        self.entries.retain(|e| {
+            !(e.key.eq_ignore_ascii_case(&entry.key)
+                && (e.value_name.eq_ignore_ascii_case(&entry.value_name)
+                    || e.value_name.eq_ignore_ascii_case(&marker)))
+        });
+        self.entries.push(entry);
+    }
+}
+
+pub fn split_policy_path(path: &str) -> Option<(PolicyScope, String)> {
+    let (hive, rest) = path.trim().split_once(':')?;
+    let scope = match hive.to_uppercase().as_str() {
+        "HKLM" | "HKEY_LOCAL_MACHINE" => PolicyScope::Machine,
+        "HKCU" | "HKEY_CURRENT_USER" => PolicyScope::User,
+        _ => return None,
+    };
+    let key = rest.trim_matches('\\').to_string();
+    if !key.to_lowercase().starts_with("software\\policies\\") {
+        return None;
+    }
+    Some((scope, key))
+}
+
+pub fn find_conflicts(
+    tweaks: &[TweakRegistryEntry],
+    machine: &PolicyFile,
+    user: &PolicyFile,
+) -> Vec<PolicyConflict> {
+    let mut conflicts = Vec::new();
+    for tweak in tweaks {
+        let (scope, key) = match split_policy_path(&tweak.path) {
+            Some(v) => v,
+            None => continue,
+        };
+        let file = match scope {
+            PolicyScope::Machine => machine,
+            PolicyScope::User => user,
+        };
+        let wanted = match encode_tweak_value(&tweak.value_type, &tweak.value) {
+            Ok((vtype, data)) => (vtype, data),
+            Err(_) => continue,
+        };
+
+        let (status, gpo_value) = match file.find(&key, &tweak.name) {
+            Some(entry) if entry.value_type == wanted.0 && entry.data == wanted.1 => {
+                ("matches", Some(render_data(entry.value_type, &entry.data)))
+            }
+            Some(entry) => ("conflict", Some(render_data(entry.value_type, &entry.data))),
+            None if file.is_deleted(&key, &tweak.name) => ("deleted_by_gpo", None),
+            None => ("not_configured", None),
+        };
+
+        conflicts.push(PolicyConflict {
+            path: tweak.path.clone(),
+            name: tweak.name.clone(),
+            scope,
+            status: status.to_string(),
+            wanted: render_data(wanted.0, &wanted.1),
+            gpo_value,
+        });
+    }
+    conflicts
+}
+
+pub fn bump_gpt_ini(existing: &str, scope: PolicyScope) -> String {
+    let (ext_key, tool_guid) = match scope {
+        PolicyScope::Machine => ("gPCMachineExtensionNames", MACHINE_TOOL_GUID),
+        PolicyScope::User => ("gPCUserExtensionNames", USER_TOOL_GUID),
+    };
+    let registry_ext = format!("[{}{}]", REGISTRY_CSE_GUID, tool_guid);
+
+    let mut lines: Vec<String> = existing
+        .lines()
+        .map(|l| l.trim_end().to_string())
+        .filter(|l| !l.is_empty())
+        .collect();
+    if !lines.iter().any(|l| l.eq_ignore_ascii_case("[General]")) {
+        lines.insert(0, "[General]".to_string());
+    }
+
+    let mut version_seen = false;
+    let mut ext_seen = false;
+    for line in lines.iter_mut() {
+        let (name, value) = match line.split_once('=') {
+            Some((n, v)) => (n.trim().to_string(), v.trim().to_string()),
+            None => continue,
+        };
+        if name.eq_ignore_ascii_case("Version") {
+            let current = value.parse::<u32>().unwrap_or(0);
+            let (user, machine) = (current >> 16, current & 0xFFFF);
+            let next = match scope {
+                PolicyScope::Machine => (user << 16) | ((machine + 1) & 0xFFFF),
+                PolicyScope::User => (((user + 1) & 0xFFFF) << 16) | machine,
+            };
+            *line = format!("Version={}", next);
+            version_seen = true;
+        } else if name.eq_ignore_ascii_case(ext_key) {
+            if !value
+                .to_uppercase()
+                .contains(&format!("[{}", REGISTRY_CSE_GUID))
+            {
+                *line = format!("{}={}{}", ext_key, registry_ext, value);
+            }
+            ext_seen = true;
+        }
+    }
+    if !ext_seen {
+        lines.push(format!("{}={}", ext_key, registry_ext));
+    }
+    if !version_seen {
+        let initial = match scope {
+            PolicyScope::Machine => 1,
+            PolicyScope::User => 1 << 16,
+        };
+        lines.push(format!("Version={}", initial));
+    }
+    let mut out = lines.join("\r\n");
+    out.push_str("\r\n");
+    out
+}
+
+fn group_policy_dir() -> PathBuf {
+    let root = std::env::var("SystemRoot").unwrap_or_else(|_| "C:\\Windows".to_string());
+    PathBuf::from(root).join("System32").join("GroupPolicy")
+}
+
+pub fn policy_file_path(scope: PolicyScope) -> PathBuf {
+    let sub = match scope {
+        PolicyScope::Machine => "Machine",
+        PolicyScope::User => "User",
+    };
+    group_policy_dir().join(sub).join("Registry.pol")
+}
+
+pub fn load(scope: PolicyScope) -> Result<PolicyFile, String> {
+    match std::fs::read(policy_file_path(scope)) {
+        Ok(bytes) => PolicyFile::parse(&bytes),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(PolicyFile::default()),
+        Err(e) => Err(format!("Failed to read Registry.pol: {}", e)),
+    }
+}
+
+pub fn save(scope: PolicyScope, file: &PolicyFile) -> Result<(), String> {
+    let path = policy_file_path(scope);
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| format!("Failed to create policy directory: {}", e))?;
+    }
+    if path.exists() {
+        std::fs::copy(&path, path.with_extension("pol.confutils.bak"))
+            .map_err(|e| format!("Failed to back up Registry.pol: {}", e))?;
+    }
+    let tmp = path.with_extension("pol.tmp");
+    std::fs::write(&tmp, file.to_bytes())
+        .map_err(|e| format!("Failed to write Registry.pol: {}", e))?;
+    std::fs::rename(&tmp, &path).map_err(|e| format!("Failed to replace Registry.pol: {}", e))?;
+
+    let gpt_path = group_policy_dir().join("gpt.ini");
+    let existing = std::fs::read_to_string(&gpt_path).unwrap_or_default();
+    std::fs::write(&gpt_path, bump_gpt_ini(&existing, scope))
+        .map_err(|e| format!("Failed to update gpt.ini: {}", e))
+}
+
+pub fn apply_tweaks(tweaks: &[TweakRegistryEntry]) -> Result<usize, String> {
+    let mut machine: Option<PolicyFile> = None;
+    let mut user: Option<PolicyFile> = None;
+    let mut applied = 0usize;
+
+    for tweak in tweaks {
+        let (scope, key) = split_policy_path(&tweak.path)
+            .ok_or_else(|| format!("Not a policy registry path: {}", tweak.path))?;
+        let (value_type, data) = encode_tweak_value(&tweak.value_type, &tweak.value)?;
+        let slot = match scope {
+            PolicyScope::Machine => &mut machine,
+            PolicyScope::User => &mut user,
+        };
+        if slot.is_none() {
+            *slot = Some(load(scope)?);
+        }
+        if let Some(file) = slot.as_mut() {
+            file.set(PolicyEntry {
+                key,
+                value_name: tweak.name.clone(),
+                value_type,
+                data,
+            });
+            applied += 1;
+        }
+    }
+
+    if let Some(file) = machine {
+        save(PolicyScope::Machine, &file)?;
+    }
+    if let Some(file) = user {
+        save(PolicyScope::User, &file)?;
+    }
+    Ok(applied)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utf16z(text: &str) -> Vec<u8> {
+        let mut out = Vec::new();
+        push_utf16(&mut out, text);
+        out.extend_from_slice(&[0, 0]);
+        out
+    }
+
+    // [Software\Policies\Microsoft\Windows\DataCollection;AllowTelemetry;REG_DWORD;4;0]
+    fn fixture() -> Vec<u8> {
+        let mut out = b"PReg\x01\x00\x00\x00".to_vec();
+        out.extend_from_slice(&[b'[', 0]);
+        out.extend(utf16z(
+            r"Software\Policies\Microsoft\Windows\DataCollection",
+        ));
+        out.extend_from_slice(&[b';', 0]);
+        out.extend(utf16z("AllowTelemetry"));
+        out.extend_from_slice(&[b';', 0]);
+        out.extend_from_slice(&REG_DWORD.to_le_bytes());
+        out.extend_from_slice(&[b';', 0]);
+        out.extend_from_slice(&4u32.to_le_bytes());
+        out.extend_from_slice(&[b';', 0]);
+        out.extend_from_slice(&0u32.to_le_bytes());
+        out.extend_from_slice(&[b']', 0]);
+        out
+    }
+
+    fn entry(key: &str, name: &str, value_type: &str, value: &str) -> PolicyEntry {
+        let (value_type, data) = encode_tweak_value(value_type, value).unwrap();
+        PolicyEntry {
+            key: key.to_string(),
+            value_name: name.to_string(),
+            value_type,
+            data,
+        }
+    }
+
+    #[test]
+    fn parses_and_reserializes_fixture() {
+        let bytes = fixture();
+        let file = PolicyFile::parse(&bytes).unwrap();
+        assert_eq!(file.entries.len(), 1);
+        let view = file.entries[0].view();
+        assert_eq!(
+            view.key,
+            r"Software\Policies\Microsoft\Windows\DataCollection"
+        );
+        assert_eq!(view.value_name, "AllowTelemetry");
+        assert_eq!(view.value_type, "REG_DWORD");
+        assert_eq!(view.data, serde_json::json!(0));
+        assert_eq!(view.action, "set_value");
+        assert_eq!(file.to_bytes(), bytes);
+    }
+
+    #[test]
+    fn round_trips_every_value_type() {
+        let key = r"Software\Policies\ConfUtils\Test";
+        let file = PolicyFile {
+            entries: vec![
+                entry(key, "Dword", "dword", "0x10"),
+                entry(key, "Qword", "qword", "-2"),
+                entry(key, "String", "string", "Grüße"),
+                entry(key, "Expand", "expandstring", r"%SystemRoot%\System32"),
+                entry(key, "Multi", "multistring", "one\ntwo"),
+                entry(key, "Binary", "binary", "de ad be ef"),
+                PolicyEntry {
+                    key: key.to_string(),
+                    value_name: "**del.Old".to_string(),
+                    value_type: REG_SZ,
+                    data: vec![0, 0],
+                },
+            ],
+        };
+        let parsed = PolicyFile::parse(&file.to_bytes()).unwrap();
+        assert_eq!(parsed, file);
+
+        let data: Vec<serde_json::Value> = parsed.entries.iter().map(|e| e.view().data).collect();
+        assert_eq!(
+            data,
+            vec![
+                serde_json::json!(16),
+                serde_json::json!(u64::MAX - 1),
+                serde_json::json!("Grüße"),
+                serde_json::json!(r"%SystemRoot%\System32"),
+                serde_json::json!(["one", "two"]),
+                serde_json::json!("de ad be ef"),
+                serde_json::json!(""),
+            ]
+        );
+        assert_eq!(parsed.entries[6].action(), "delete_value");
+        assert!(parsed.is_deleted(key, "old"));
+    }
+
+    #[test]
+    fn negative_numbers_use_the_value_width() {
+        assert_eq!(
+            encode_tweak_value("dword", "-1").unwrap(),
+            (REG_DWORD, vec![0xff; 4])
+        );
+        assert_eq!(
+            encode_tweak_value("qword", "-1").unwrap(),
+            (REG_QWORD, vec![0xff; 8])
+        );
+        assert_eq!(
+            encode_tweak_value("qword", "-4294967296").unwrap().1,
+            (-4294967296i64 as u64).to_le_bytes().to_vec()
+        );
+        assert!(encode_tweak_value("dword", "-2147483649").is_err());
+        assert!(encode_tweak_value("dword", "4294967296").is_err());
+    }
+
+    #[test]
+    fn rejects_malformed_files() {
+        assert_eq!(PolicyFile::parse(&[]).unwrap(), PolicyFile::default());
+        assert!(PolicyFile::parse(b"REGEDIT4").is_err());
+        assert!(PolicyFile::parse(b"PReg\x02\x00\x00\x00").is_err());
+
+        let mut truncated = fixture();
+        truncated.truncate(truncated.len() - 6);
+        assert!(PolicyFile::parse(&truncated).is_err());
+
+        let mut overrun = fixture();
+        let size_at = overrun.len() - 12;
+        overrun[size_at..size_at + 4].copy_from_slice(&64u32.to_le_bytes());
+        assert!(PolicyFile::parse(&overrun).is_err());
+    }
+
+    #[test]
+    fn set_replaces_value_and_delete_marker() {
+        let key = r"Software\Policies\ConfUtils\Test";
+        let mut file = PolicyFile {
+            entries: vec![
+                entry(key, "Value", "dword", "1"),
+                PolicyEntry {
+                    key: key.to_uppercase(),
+                    value_name: "**del.Value".to_string(),
+                    value_type: REG_SZ,
+                    data: vec![0, 0],
+                },
+            ],
+        };
+        file.set(entry(key, "value", "dword", "2"));
+        assert_eq!(file.entries.len(), 1);
+        assert_eq!(file.entries[0].view().data, serde_json::json!(2));
+    }
+}
@@ -0,0 +1,499 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::net::IpAddr;
+
+use crate::firewall;
+
+const RULES_DATA: &str = include_str!("../data/port_risk_rules.json");
+const MAX_SCORE: f64 = 100.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Protocol {
+    #[serde(rename = "TCP", alias = "tcp")]
+    Tcp,
+    #[serde(rename = "UDP", alias = "udp")]
+    Udp,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+enum RuleProtocol {
+    #[serde(rename = "TCP")]
+    Tcp,
+    #[serde(rename = "UDP")]
+    Udp,
+    Any,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BindScope {
+    Loopback,
+    Lan,
+    All,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Info,
+    Low,
+    Medium,
+    High,
+    Critical,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum RemediationKind {
+    BlockInbound,
+    StopService,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum Remediation {
+    BlockInbound { protocol: Protocol, port: u16 },
+    StopService { service: String },
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct PathModifier {
+    contains: String,
+    add: f64,
+    reason: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct RiskRule {
+    id: String,
+    title: String,
+    description: String,
+    protocol: RuleProtocol,
+    ports: Vec<u16>,
+    score: f64,
+    #[serde(default)]
+    services: Vec<String>,
+    #[serde(default)]
+    remediations: Option<Vec<RemediationKind>>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct ScopeWeights {
+    loopback: f64,
+    lan: f64,
+    all: f64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct RiskRules {
+    default_score: BTreeMap<Protocol, f64>,
+    default_remediations: Vec<RemediationKind>,
+    scope_weights: ScopeWeights,
+    #[serde(default)]
+    path_modifiers: Vec<PathModifier>,
+    rules: Vec<RiskRule>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Endpoint {
+    pub protocol: Protocol,
+    pub local_address: String,
+    pub local_port: u16,
+    #[serde(default)]
+    pub owning_process: u32,
+    #[serde(default)]
+    pub process_name: Option<String>,
+    #[serde(default)]
+    pub process_path: Option<String>,
+    #[serde(default)]
+    pub services: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct EndpointList {
+    #[serde(default)]
+    endpoints: Vec<Endpoint>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Finding {
+    pub protocol: Protocol,
+    pub local_address: String,
+    pub local_port: u16,
+    pub scope: BindScope,
+    pub pid: u32,
+    pub process_name: Option<String>,
+    pub process_path: Option<String>,
+    pub services: Vec<String>,
+    pub rule_id: Option<String>,
+    pub title: String,
+    pub description: Option<String>,
+    pub score: u8,
+    pub severity: Severity,
+    pub reasons: Vec<String>,
+    pub remediations: Vec<Remediation>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ScanReport {
+    pub tcp: usize,
+    pub udp: usize,
+    pub exposed: usize,
+    pub by_severity: BTreeMap<String, usize>,
+    pub findings: Vec<Finding>,
+}
+
+impl Protocol {
+    fn label(self) -> &'static str {
+        match self {
+            Protocol::Tcp => "TCP",
+            Protocol::Udp => "UDP",
+        }
+    }
+
+    fn firewall(self) -> firewall::Protocol {
+        match self {
+            Protocol::Tcp => firewall::Protocol::Tcp,
+            Protocol::Udp => firewall::Protocol::Udp,
+        }
+    }
+}
+
+impl RuleProtocol {
+    fn matches(self, protocol: Protocol) -> bool {
+        match self {
+            RuleProtocol::Tcp => protocol == Protocol::Tcp,
+            RuleProtocol::Udp => protocol == Protocol::Udp,
+            RuleProtocol::Any => true,
+        }
+    }
+}
+
+impl Severity {
+    fn from_score(score: u8) -> Self {
+        match score {
+            80.. => Severity::Critical,
+            60..=79 => Severity::High,
+            35..=59 => Severity::Medium,
+            15..=34 => Severity::Low,
+            _ => Severity::Info,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Severity::Info => "info",
+            Severity::Low => "low",
+            Severity::Medium => "medium",
+            Severity::High => "high",
+            Severity::Critical => "critical",
+        }
+    }
+}
+
+pub fn load_rules() -> Result<RiskRules, String> {
+    serde_json::from_str(RULES_DATA).map_err(|e| format!("Corrupt port risk rules: {}", e))
+}
+
+pub fn scan_script() -> &'static str {
+    r#"
+        [Console]::OutputEncoding = [System.Text.Encoding]::UTF8
+        $procs = @{}
+        Get-CimInstance Win32_Process -ErrorAction SilentlyContinue | ForEach-Object { $procs[[int]$_.ProcessId] = $_ }
+        $services = @{}
+        Get-CimInstance Win32_Service -ErrorAction SilentlyContinue | Where-Object { $_.ProcessId -gt 0 } | ForEach-Object {
+            $id = [int]$_.ProcessId
+            if (-not $services.ContainsKey($id)) { $services[$id] = @() }
+            $services[$id] += $_.Name
+        }
+        function Describe($protocol, $endpoint) {
+            $id = [int]$endpoint.OwningProcess
+            $proc = $procs[$id]
+            [pscustomobject]@{
+                Protocol = $protocol
+                LocalAddress = [string]$endpoint.LocalAddress
+                LocalPort = [int]$endpoint.LocalPort
+                OwningProcess = $id
+                ProcessName = if ($proc) { $proc.Name } elseif ($id -eq 4) { 'System' } else { $null }
+                ProcessPath = if ($proc) { $proc.ExecutablePath } else { $null }
+                Services = @($services[$id] | Where-Object { $_ })
+            }
+        }
+        $endpoints = @()
+        $endpoints += @(Get-NetTCPConnection -State Listen -ErrorAction SilentlyContinue | ForEach-Object { Describe 'TCP' $_ })
+        $endpoints += @(Get-NetUDPEndpoint -ErrorAction SilentlyContinue | ForEach-Object { Describe 'UDP' $_ })
+        [pscustomobject]@{ Endpoints = $endpoints } | ConvertTo-Json -Depth 3 -Compress
+    "#
+}
+
+pub fn parse_endpoints(raw: &str) -> Result<Vec<Endpoint>, String> {
+    let list: EndpointList = serde_json::from_str(raw.trim())
+        .map_err(|e| format!("Failed to parse listening ports: {}", e))?;
+    let mut endpoints = list.endpoints;
+    endpoints.sort_by(|a, b| {
+        (a.protocol, a.local_port, &a.local_address, a.owning_process).cmp(&(
+            b.protocol,
+            b.local_port,
+            &b.local_address,
+            b.owning_process,
+        ))
+    });
+    endpoints.dedup_by(|a, b| {
+        a.protocol == b.protocol
+            && a.local_port == b.local_port
+            && a.local_address == b.local_address
+            && a.owning_process == b.owning_process
+    });
+    Ok(endpoints)
+}
+
+pub fn bind_scope(address: &str) -> BindScope {
+    let address = address.split('%').next().unwrap_or(address);
+    match address.parse::<IpAddr>() {
+        Ok(ip) if ip.is_unspecified() => BindScope::All,
+        Ok(ip) if ip.is_loopback() => BindScope::Loopback,
+        Ok(IpAddr::V6(v6)) if v6.to_ipv4_mapped().is_some_and(|v4| v4.is_loopback()) => {
+            BindScope::Loopback
+        }
+        _ => BindScope::Lan,
+    }
+}
+
+impl RiskRules {
+    fn rule_for(&self, endpoint: &Endpoint) -> Option<&RiskRule> {
+        self.rules.iter().find(|rule| {
+            rule.protocol.matches(endpoint.protocol) && rule.ports.contains(&endpoint.local_port)
+        })
+    }
+
+    fn scope_weight(&self, scope: BindScope) -> f64 {
+        match scope {
+            BindScope::Loopback => self.scope_weights.loopback,
+            BindScope::Lan => self.scope_weights.lan,
+            BindScope::All => self.scope_weights.all,
+        }
+    }
+
+    pub fn assess(&self, endpoint: &Endpoint) -> Finding {
+        let scope = bind_scope(&endpoint.local_address);
+        let rule = self.rule_for(endpoint);
+        let mut reasons = Vec::new();
+
+        let base = match rule {
+            Some(rule) => {
+                reasons.push(format!("Matches rule '{}'", rule.id));
+                rule.score
+            }
+            None => self
+                .default_score
+                .get(&endpoint.protocol)
+                .copied()
+                .unwrap_or(0.0),
+        };
+        let mut score = base * self.scope_weight(scope);
+        reasons.push(match scope {
+            BindScope::Loopback => "Only reachable from this computer".to_string(),
+            BindScope::Lan => format!("Bound to {}", endpoint.local_address),
+            BindScope::All => "Listening on all interfaces".to_string(),
+        });
+        if let Some(path) = &endpoint.process_path {
+            let lower = path.to_lowercase();
+            for modifier in &self.path_modifiers {
+                if lower.contains(&modifier.contains.to_lowercase()) {
+                    score += modifier.add;
+                    reasons.push(modifier.reason.clone());
+                }
+            }
+        }
+        let score = score.clamp(0.0, MAX_SCORE).round() as u8;
+
+        Finding {
+            protocol: endpoint.protocol,
+            local_address: endpoint.local_address.clone(),
+            local_port: endpoint.local_port,
+            scope,
+            pid: endpoint.owning_process,
+            process_name: endpoint.process_name.clone(),
+            process_path: endpoint.process_path.clone(),
+            services: endpoint.services.clone(),
+            rule_id: rule.map(|r| r.id.clone()),
+            title: rule.map(|r| r.title.clone()).unwrap_or_else(|| {
+                format!("{} port {}", endpoint.protocol.label(), endpoint.local_port)
+            }),
+            description: rule.map(|r| r.description.clone()),
+            score,
+            severity: Severity::from_score(score),
+            reasons,
+            remediations: self.remediations(endpoint, scope, rule),
+        }
+    }
+
+    fn remediations(
+        &self,
+        endpoint: &Endpoint,
+        scope: BindScope,
+        rule: Option<&RiskRule>,
+    ) -> Vec<Remediation> {
+        let kinds = rule
+            .and_then(|r| r.remediations.as_ref())
+            .unwrap_or(&self.default_remediations);
+        let mut remediations = Vec::new();
+        if kinds.contains(&RemediationKind::BlockInbound) && scope != BindScope::Loopback {
+            remediations.push(Remediation::BlockInbound {
+                protocol: endpoint.protocol,
+                port: endpoint.local_port,
+            });
+        }
+        if kinds.contains(&RemediationKind::StopService) {
+            for service in owning_services(endpoint, rule) {
+                remediations.push(Remediation::StopService { service });
+            }
+        }
+        remediations
+    }
+
+    pub fn scan(&self, endpoints: &[Endpoint]) -> ScanReport {
+        let mut findings: Vec<Finding> = endpoints.iter().map(|e| self.assess(e)).collect();
+        findings.sort_by(|a, b| {
+            b.score
+                .cmp(&a.score)
+                .then(a.local_port.cmp(&b.local_port))
+                .then(a.protocol.cmp(&b.protocol))
+        });
+        let mut by_severity = BTreeMap::new();
+        for finding in &findings {
+            *by_severity
+                .entry(finding.severity.label().to_string())
+                .or_insert(0) += 1;
+        }
+        ScanReport {
+            tcp: findings
+                .iter()
+                .filter(|f| f.protocol == Protocol::Tcp)
+                .count(),
+            udp: findings
+                .iter()
+                .filter(|f| f.protocol == Protocol::Udp)
+                .count(),
+            exposed: findings
+                .iter()
+                .filter(|f| f.scope != BindScope::Loopback)
+                .count(),
+            by_severity,
+            findings,
+        }
+    }
+}
+
+// Services hosted in a shared svchost are narrowed to the ones the rule names;
+// kernel listeners (SMB, http.sys) belong to System and only have the hint.
+fn owning_services(endpoint: &Endpoint, rule: Option<&RiskRule>) -> Vec<String> {
+    let hints = rule.map(|r| r.services.as_slice()).unwrap_or_default();
+    let matching: Vec<String> = endpoint
+        .services
+        .iter()
+        .filter(|s| hints.iter().any(|h| h.eq_ignore_ascii_case(s)))
+        .cloned()
+        .collect();
+    if !matching.is_empty() {
+        matching
+    } else if !endpoint.services.is_empty() {
+        endpoint.services.clone()
+    } else if endpoint.owning_process == 4 {
+        hints.to_vec()
+    } else {
+        Vec::new()
+    }
+}
+
+pub fn block_inbound_spec(protocol: Protocol, port: u16) -> firewall::RuleSpec {
+    firewall::RuleSpec {
+        display_name: format!("ConfUtils Block Inbound {} {}", protocol.label(), port),
+        description: Some(format!(
+            "Blocks inbound {} connections to local port {}",
+            protocol.label(),
+            port
+        )),
+        direction: firewall::Direction::Inbound,
+        action: firewall::RuleAction::Block,
+        program: None,
+        protocol: protocol.firewall(),
+        local_ports: vec![port.to_string()],
+        remote_ports: Vec::new(),
+        remote_addresses: Vec::new(),
+        profiles: Vec::new(),
+        enabled: true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn endpoint(port: u16, pid: u32, services: &[&str]) -> Endpoint {
+        Endpoint {
+            protocol: Protocol::Tcp,
+            local_address: "0.0.0.0".to_string(),
+            local_port: port,
+            owning_process: pid,
+            process_name: Some("svchost".to_string()),
+            process_path: None,
+            services: services.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+
+    fn stops(finding: &Finding) -> Vec<&str> {
+        finding
+            .remediations
+            .iter()
+            .filter_map(|r| match r {
+                Remediation::StopService { service } => Some(service.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn stopping_services_is_opt_in_per_rule() {
+        let rules = load_rules().unwrap();
+
+        let rdp = rules.assess(&endpoint(3389, 1200, &["TermService", "UmRdpService"]));
+        assert_eq!(rdp.rule_id.as_deref(), Some("rdp"));
+        assert!(stops(&rdp).is_empty());
+        assert_eq!(
+            rdp.remediations,
+            vec![Remediation::BlockInbound {
+                protocol: Protocol::Tcp,
+                port: 3389
+            }]
+        );
+
+        let unknown = rules.assess(&endpoint(40000, 1300, &["SomeSvc"]));
+        assert_eq!(unknown.rule_id, None);
+        assert!(stops(&unknown).is_empty());
+
+        let upnp = rules.assess(&endpoint(2869, 4, &[]));
+        assert_eq!(upnp.rule_id.as_deref(), Some("upnp"));
+        assert_eq!(stops(&upnp), vec!["upnphost"]);
+
+        let telnet = rules.assess(&endpoint(23, 1400, &["TlntSvr", "Other"]));
+        assert_eq!(stops(&telnet), vec!["TlntSvr"]);
+    }
+
+    #[test]
+    fn loopback_listeners_are_not_blocked() {
+        let rules = load_rules().unwrap();
+        let mut telnet = endpoint(23, 1400, &["TlntSvr"]);
+        telnet.local_address = "::ffff:127.0.0.1".to_string();
+        let finding = rules.assess(&telnet);
+        assert_eq!(finding.scope, BindScope::Loopback);
+        assert_eq!(
+            finding.remediations,
+            vec![Remediation::StopService {
+                service: "TlntSvr".to_string()
+            }]
+        );
+    }
+}
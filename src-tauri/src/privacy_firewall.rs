@@ -0,0 +1,540 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::net::{IpAddr, SocketAddr};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::allowlist::{self, Suppressed};
+use crate::dns_bench::{self, TYPE_A, TYPE_AAAA};
+use crate::lan_discovery;
+use crate::util::{self, ps_quote};
+
+pub const RULE_NAME: &str = "ConfUtils Telemetry Block";
+const RULE_GROUP: &str = "ConfUtils Privacy";
+const DEFAULT_INTERVAL_HOURS: u64 = 6;
+const SCHEDULER_TICK_SECS: u64 = 900;
+const QUERY_TIMEOUT_MS: u64 = 3000;
+// Used when no usable resolver is configured on the adapters.
+const FALLBACK_RESOLVERS: [&str; 2] = ["1.1.1.1", "9.9.9.9"];
+const RETENTION_SECS: u64 = 14 * 24 * 3600;
+const MAX_ADDRESSES_PER_DOMAIN: usize = 64;
+const MAX_RULE_ADDRESSES: usize = 1000;
+
+lazy_static::lazy_static! {
+    static ref REFRESH_LOCK: tokio::sync::Mutex<()> = tokio::sync::Mutex::new(());
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AddressRecord {
+    pub ip: String,
+    pub first_seen_unix: u64,
+    pub last_seen_unix: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DomainHistory {
+    pub addresses: Vec<AddressRecord>,
+    #[serde(default)]
+    pub last_resolved_unix: Option<u64>,
+    #[serde(default)]
+    pub last_error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PrivacyFirewallState {
+    pub enabled: bool,
+    pub interval_hours: u64,
+    #[serde(default)]
+    pub last_refresh_unix: Option<u64>,
+    #[serde(default)]
+    pub rule_addresses: Vec<String>,
+    #[serde(default)]
+    pub history: BTreeMap<String, DomainHistory>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RefreshReport {
+    pub resolved_domains: usize,
+    pub failed_domains: Vec<String>,
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub addresses: usize,
+    pub rule_updated: bool,
+    pub suppressed: Vec<Suppressed>,
+}
+
+impl Default for PrivacyFirewallState {
+    fn default() -> Self {
+        PrivacyFirewallState {
+            enabled: false,
+            interval_hours: DEFAULT_INTERVAL_HOURS,
+            last_refresh_unix: None,
+            rule_addresses: Vec::new(),
+            history: BTreeMap::new(),
+        }
+    }
+}
+
+pub fn domains() -> Vec<&'static str> {
+    vec![
+        "vortex.data.microsoft.com",
+        "vortex-win.data.microsoft.com",
+        "telemetry.microsoft.com",
+        "settings-win.data.microsoft.com",
+        "watson.telemetry.microsoft.com",
+        "oca.telemetry.microsoft.com",
+        "sqm.telemetry.microsoft.com",
+        "telecommand.telemetry.microsoft.com",
+        "telecommand.telemetry.microsoft.com.nsatc.net",
+        "wes.df.telemetry.microsoft.com",
+    ]
+}
+
+fn state_path() -> PathBuf {
+    util::data_dir().join("privacy_firewall.json")
+}
+
+pub fn load_state() -> PrivacyFirewallState {
+    std::fs::read_to_string(state_path())
+        .ok()
+        .and_then(|raw| serde_json::from_str(&raw).ok())
+        .unwrap_or_default()
+}
+
+fn save_state(state: &PrivacyFirewallState) -> Result<(), String> {
+    let path = state_path();
+    if let Some(parent) = path.parent() {
+        let _ = std::fs::create_dir_all(parent);
+    }
+    let raw = serde_json::to_string_pretty(state)
+        .map_err(|e| format!("Failed to serialize firewall history: {}", e))?;
+    std::fs::write(path, raw).map_err(|e| format!("Failed to save firewall history: {}", e))
+}
+
+// Filtering resolvers answer blocked names with 0.0.0.0 or ::, which must
+// never end up in a block rule.
+fn is_blockable(ip: &IpAddr) -> bool {
+    !(ip.is_unspecified() || ip.is_loopback() || ip.is_multicast())
+}
+
+// The system resolver consults the hosts file, so once the telemetry
+// blocklist is applied it answers 0.0.0.0 for every domain here. The
+// adapters' DNS servers are queried directly instead.
+async fn upstream_servers() -> Vec<SocketAddr> {
+    let configured = match crate::commands::run_powershell_no_rate_limit(
+        lan_discovery::context_script().to_string(),
+    )
+    .await
+    {
+        Ok(raw) => lan_discovery::parse_context(&raw)
+            .map(|context| context.dns_servers())
+            .unwrap_or_default(),
+        Err(_) => Vec::new(),
+    };
+    let servers: Vec<IpAddr> = configured
+        .into_iter()
+        .filter(|ip| !ip.is_unspecified())
+        .collect();
+    let servers = if servers.is_empty() {
+        FALLBACK_RESOLVERS
+            .iter()
+            .filter_map(|s| s.parse().ok())
+            .collect()
+    } else {
+        servers
+    };
+    servers
+        .into_iter()
+        .map(|ip| SocketAddr::new(ip, 53))
+        .collect()
+}
+
+async fn resolve(servers: &[SocketAddr], domain: &str) -> Result<Vec<IpAddr>, String> {
+    let timeout = Duration::from_millis(QUERY_TIMEOUT_MS);
+    let mut last_error = "No DNS servers available".to_string();
+    for server in servers {
+        let (v4, v6) = tokio::join!(
+            dns_bench::query_udp(*server, domain, TYPE_A, timeout),
+            dns_bench::query_udp(*server, domain, TYPE_AAAA, timeout)
+        );
+        let mut ips = BTreeSet::new();
+        let mut answered = false;
+        for result in [v4, v6] {
+            match result {
+                Ok((_, answer)) if dns_bench::is_answer(&answer) => {
+                    answered = true;
+                    ips.extend(answer.addresses.into_iter().filter(is_blockable));
+                }
+                Ok((_, answer)) => {
+                    last_error = format!("{} answered with rcode {}", server, answer.rcode)
+                }
+                Err(e) => last_error = format!("{}: {}", server, e),
+            }
+        }
+        if answered {
+            return Ok(ips.into_iter().collect());
+        }
+    }
+    Err(last_error)
+}
+
+pub fn record(history: &mut DomainHistory, ips: &[IpAddr], now: u64) {
+    for ip in ips {
+        let ip = ip.to_string();
+        match history.addresses.iter_mut().find(|r| r.ip == ip) {
+            Some(existing) => existing.last_seen_unix = now,
+            None => history.addresses.push(AddressRecord {
+                ip,
+                first_seen_unix: now,
+                last_seen_unix: now,
+            }),
+        }
+    }
+    history
+        .addresses
+        .retain(|r| now.saturating_sub(r.last_seen_unix) <= RETENTION_SECS);
+    history
+        .addresses
+        .sort_by_key(|r| std::cmp::Reverse(r.last_seen_unix));
+    history.addresses.truncate(MAX_ADDRESSES_PER_DOMAIN);
+}
+
+// Addresses stay blocked for the retention window after they were last seen,
+// so CDN endpoints that rotate between a handful of IPs remain covered.
+pub fn active_addresses(state: &PrivacyFirewallState, domains: &[String], now: u64) -> Vec<String> {
+    let mut addresses = BTreeSet::new();
+    for domain in domains {
+        if let Some(history) = state.history.get(domain) {
+            addresses.extend(
+                history
+                    .addresses
+                    .iter()
+                    .filter(|r| now.saturating_sub(r.last_seen_unix) <= RETENTION_SECS)
+                    .map(|r| r.ip.clone()),
+            );
+        }
+    }
+    addresses.into_iter().take(MAX_RULE_ADDRESSES).collect()
+}
+
+pub fn diff(old: &[String], new: &[String]) -> (Vec<String>, Vec<String>) {
+    let old: BTreeSet<&String> = old.iter().collect();
+    let new: BTreeSet<&String> = new.iter().collect();
+    (
+        new.difference(&old).map(|s| s.to_string()).collect(),
+        old.difference(&new).map(|s| s.to_string()).collect(),
+    )
+}
+
+pub fn rule_script(addresses: &[String]) -> String {
+    let list = addresses
+        .iter()
+        .map(|a| ps_quote(a))
+        .collect::<Vec<_>>()
+        .join(",");
+    format!(
+        r#"
+        $ruleName = {name}
+        $ips = @({list})
+        $rule = Get-NetFirewallRule -DisplayName $ruleName -ErrorAction SilentlyContinue
+        if ($rule) {{
+            $rule | Set-NetFirewallRule -RemoteAddress $ips -ErrorAction Stop
+        }} else {{
+            New-NetFirewallRule -DisplayName $ruleName -Group {group} -Direction Outbound -Action Block -RemoteAddress $ips -Profile Any -ErrorAction Stop | Out-Null
+        }}
+        "ok"
+    "#,
+        name = ps_quote(RULE_NAME),
+        group = ps_quote(RULE_GROUP),
+        list = list
+    )
+}
+
+pub fn remove_rule_script() -> String {
+    format!(
+        r#"
+        Get-NetFirewallRule -DisplayName {} -ErrorAction SilentlyContinue | Remove-NetFirewallRule -ErrorAction SilentlyContinue
+        "Privacy firewall rules removed"
+    "#,
+        ps_quote(RULE_NAME)
+    )
+}
+
+fn rule_exists_script() -> String {
+    format!(
+        "if (Get-NetFirewallRule -DisplayName {} -ErrorAction SilentlyContinue) {{ 'true' }} else {{ 'false' }}",
+        ps_quote(RULE_NAME)
+    )
+}
+
+pub async fn rule_exists() -> bool {
+    crate::commands::run_powershell_no_rate_limit(rule_exists_script())
+        .await
+        .map(|out| out.trim() == "true")
+        .unwrap_or(false)
+}
+
+fn is_due(state: &PrivacyFirewallState, now: u64) -> bool {
+    state.enabled
+        && state
+            .last_refresh_unix
+            .is_none_or(|last| now.saturating_sub(last) >= state.interval_hours * 3600)
+}
+
+// Re-resolves every domain, merges the answers into the history and pushes
+// the resulting address set to the firewall rule when it changed. `enable`
+// marks the rule as managed so the scheduler keeps it fresh afterwards.
+pub async fn refresh(enable: bool, force: bool) -> Result<RefreshReport, String> {
+    let _guard = REFRESH_LOCK.lock().await;
+    let mut state = load_state();
+    if enable {
+        state.enabled = true;
+    }
+    let now = util::now_unix();
+    let (active_domains, suppressed) = allowlist::load()?.filter(&domains());
+    let mut report = RefreshReport {
+        suppressed,
+        ..Default::default()
+    };
+    let servers = upstream_servers().await;
+
+    for domain in &active_domains {
+        let history = state.history.entry(domain.clone()).or_default();
+        match resolve(&servers, domain).await {
+            Ok(ips) if !ips.is_empty() => {
+                record(history, &ips, now);
+                history.last_resolved_unix = Some(now);
+                history.last_error = None;
+                report.resolved_domains += 1;
+            }
+            Ok(_) => {
+                record(history, &[], now);
+                history.last_error = Some("No public addresses resolved".to_string());
+                report.failed_domains.push(domain.clone());
+            }
+            Err(e) => {
+                record(history, &[], now);
+                history.last_error = Some(e);
+                report.failed_domains.push(domain.clone());
+            }
+        }
+    }
+
+    sync_rule(&mut state, &active_domains, now, force, &mut report).await?;
+    state.last_refresh_unix = Some(now);
+    save_state(&state)?;
+    Ok(report)
+}
+
+// Pushes the recorded addresses of `active_domains` to the firewall rule when
+// they differ from what the rule currently holds.
+async fn sync_rule(
+    state: &mut PrivacyFirewallState,
+    active_domains: &[String],
+    now: u64,
+    force: bool,
+    report: &mut RefreshReport,
+) -> Result<(), String> {
+    let addresses = active_addresses(state, active_domains, now);
+    let (added, removed) = diff(&state.rule_addresses, &addresses);
+    report.addresses = addresses.len();
+    report.added = added;
+    report.removed = removed;
+
+    let changed = !report.added.is_empty() || !report.removed.is_empty();
+    if addresses.is_empty() {
+        // Nothing to block yet; an existing rule with an empty address list
+        // would match everything, so drop it and retry on the next tick.
+        if !state.rule_addresses.is_empty() || force {
+            crate::commands::run_powershell_no_rate_limit(remove_rule_script()).await?;
+            report.rule_updated = true;
+        }
+    } else if changed || force || !rule_exists().await {
+        crate::commands::run_powershell_no_rate_limit(rule_script(&addresses)).await?;
+        report.rule_updated = true;
+    }
+    state.rule_addresses = addresses;
+    Ok(())
+}
+
+// Re-applies the allowlist to the rule from the recorded history without
+// resolving anything.
+pub async fn reapply_allowlist() -> Result<RefreshReport, String> {
+    let _guard = REFRESH_LOCK.lock().await;
+    let mut state = load_state();
+    let (active_domains, suppressed) = allowlist::load()?.filter(&domains());
+    let mut report = RefreshReport {
+        suppressed,
+        ..Default::default()
+    };
+    sync_rule(
+        &mut state,
+        &active_domains,
+        util::now_unix(),
+        false,
+        &mut report,
+    )
+    .await?;
+    save_state(&state)?;
+    Ok(report)
+}
+
+pub async fn disable() -> Result<String, String> {
+    let _guard = REFRESH_LOCK.lock().await;
+    let output = crate::commands::run_powershell_no_rate_limit(remove_rule_script()).await?;
+    let mut state = load_state();
+    state.enabled = false;
+    state.rule_addresses.clear();
+    save_state(&state)?;
+    Ok(output)
+}
+
+pub async fn set_interval(hours: u64) -> Result<PrivacyFirewallState, String> {
+    let _guard = REFRESH_LOCK.lock().await;
+    let mut state = load_state();
+    state.interval_hours = hours.clamp(1, 24 * 7);
+    save_state(&state)?;
+    Ok(state)
+}
+
+pub async fn run_scheduler() {
+    loop {
+        tokio::time::sleep(Duration::from_secs(SCHEDULER_TICK_SECS)).await;
+        if is_due(&load_state(), util::now_unix()) {
+            let _ = refresh(false, false).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    const DAY: u64 = 24 * 3600;
+
+    fn ips(raw: &[&str]) -> Vec<IpAddr> {
+        raw.iter().map(|s| s.parse().unwrap()).collect()
+    }
+
+    fn seen(history: &DomainHistory) -> Vec<(&str, u64, u64)> {
+        history
+            .addresses
+            .iter()
+            .map(|r| (r.ip.as_str(), r.first_seen_unix, r.last_seen_unix))
+            .collect()
+    }
+
+    #[test]
+    fn record_tracks_first_and_last_seen() {
+        let mut history = DomainHistory::default();
+        record(&mut history, &ips(&["20.42.65.92", "20.42.73.29"]), 1000);
+        record(&mut history, &ips(&["20.42.73.29", "2603:1030::1"]), 2000);
+        assert_eq!(
+            seen(&history),
+            vec![
+                ("20.42.73.29", 1000, 2000),
+                ("2603:1030::1", 2000, 2000),
+                ("20.42.65.92", 1000, 1000),
+            ]
+        );
+    }
+
+    #[test]
+    fn record_expires_and_caps_addresses() {
+        let mut history = DomainHistory::default();
+        record(&mut history, &ips(&["20.42.65.92"]), 0);
+        record(&mut history, &[], 15 * DAY);
+        assert!(history.addresses.is_empty());
+
+        let many: Vec<IpAddr> = (0..100u8)
+            .map(|i| IpAddr::V4(Ipv4Addr::new(20, 1, 0, i)))
+            .collect();
+        record(&mut history, &many, DAY);
+        assert_eq!(history.addresses.len(), MAX_ADDRESSES_PER_DOMAIN);
+    }
+
+    #[test]
+    fn active_addresses_only_cover_listed_fresh_domains() {
+        let mut state = PrivacyFirewallState::default();
+        let mut fresh = DomainHistory::default();
+        record(
+            &mut fresh,
+            &ips(&["20.42.65.92", "13.69.109.130"]),
+            10 * DAY,
+        );
+        let mut stale = DomainHistory::default();
+        record(&mut stale, &ips(&["52.168.117.170"]), 0);
+        let mut allowlisted = DomainHistory::default();
+        record(&mut allowlisted, &ips(&["40.77.226.250"]), 10 * DAY);
+        state.history.insert("a.example".to_string(), fresh);
+        state.history.insert("b.example".to_string(), stale);
+        state.history.insert("c.example".to_string(), allowlisted);
+
+        let domains = vec!["a.example".to_string(), "b.example".to_string()];
+        assert_eq!(
+            active_addresses(&state, &domains, 15 * DAY),
+            vec!["13.69.109.130", "20.42.65.92"]
+        );
+    }
+
+    #[test]
+    fn diff_reports_added_and_removed() {
+        let old = vec!["1.1.1.1".to_string(), "2.2.2.2".to_string()];
+        let new = vec!["2.2.2.2".to_string(), "3.3.3.3".to_string()];
+        assert_eq!(
+            diff(&old, &new),
+            (vec!["3.3.3.3".to_string()], vec!["1.1.1.1".to_string()])
+        );
+        assert_eq!(diff(&new, &new), (Vec::new(), Vec::new()));
+    }
+
+    // Answers every query with one sinkholed and one real address, like a
+    // filtering upstream resolver would.
+    fn stub_answer(query: &[u8]) -> Vec<u8> {
+        let qtype = u16::from_be_bytes([query[query.len() - 4], query[query.len() - 3]]);
+        let mut out = query.to_vec();
+        out[2] = 0x81;
+        out[3] = 0x80;
+        out[6..8].copy_from_slice(&2u16.to_be_bytes());
+        let answers: [&[u8]; 2] = if qtype == TYPE_A {
+            [&[0, 0, 0, 0], &[20, 42, 65, 92]]
+        } else {
+            [
+                &[0; 16],
+                &[0x26, 0x03, 0x10, 0x30, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1],
+            ]
+        };
+        for data in answers {
+            out.extend_from_slice(&[0xC0, 12]);
+            out.extend_from_slice(&qtype.to_be_bytes());
+            out.extend_from_slice(&[0, 1, 0, 0, 0, 60]);
+            out.extend_from_slice(&(data.len() as u16).to_be_bytes());
+            out.extend_from_slice(data);
+        }
+        out
+    }
+
+    #[tokio::test]
+    async fn resolve_queries_upstream_and_drops_sinkholed_answers() {
+        let socket = tokio::net::UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let server = socket.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut buf = [0u8; 512];
+            for _ in 0..2 {
+                let (len, peer) = socket.recv_from(&mut buf).await.unwrap();
+                let _ = socket.send_to(&stub_answer(&buf[..len]), peer).await;
+            }
+        });
+        // The unreachable first server must not hide the working one.
+        let dead = tokio::net::UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let dead_addr = dead.local_addr().unwrap();
+        drop(dead);
+
+        let resolved = resolve(&[dead_addr, server], "vortex.data.microsoft.com")
+            .await
+            .unwrap();
+        assert_eq!(resolved, ips(&["20.42.65.92", "2603:1030::1"]));
+        assert!(resolve(&[], "vortex.data.microsoft.com").await.is_err());
+    }
+}
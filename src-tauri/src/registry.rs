@@ -0,0 +1,846 @@
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use winreg::enums::*;
+use winreg::{RegKey, RegValue, HKEY};
+
+use crate::util;
+
+const BACKUP_MAX_ENTRIES: usize = 200;
+const BACKUP_MAX_AGE_SECS: u64 = 30 * 24 * 60 * 60;
+const BACKUP_MAX_TOTAL_BYTES: u64 = 64 * 1024 * 1024;
+const BACKUP_MAX_KEYS: usize = 20_000;
+
+const BROWSABLE_HIVES: [&str; 5] = ["HKLM", "HKCU", "HKCR", "HKU", "HKCC"];
+const BROWSE_DENIED_PREFIXES: [&str; 2] = ["HKLM\\SAM", "HKLM\\SECURITY"];
+const BROWSE_DEFAULT_LIMIT: usize = 200;
+const BROWSE_MAX_LIMIT: usize = 1000;
+const VALUE_PREVIEW_MAX_BYTES: usize = 4096;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BackupValue {
+    pub name: String,
+    pub vtype: u32,
+    pub data: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BackupKey {
+    pub name: String,
+    pub values: Vec<BackupValue>,
+    pub subkeys: Vec<BackupKey>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegistryBackup {
+    pub id: String,
+    pub created_unix: u64,
+    pub operation: String,
+    pub hive: String,
+    pub path: String,
+    pub value_name: Option<String>,
+    pub existed: bool,
+    pub incomplete: bool,
+    pub key: Option<BackupKey>,
+    pub value: Option<BackupValue>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RegistryPage<T> {
+    pub hive: String,
+    pub path: String,
+    pub offset: usize,
+    pub limit: usize,
+    pub total: usize,
+    pub last_write_unix: Option<u64>,
+    pub items: Vec<T>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SubkeyEntry {
+    pub name: String,
+    pub subkey_count: u32,
+    pub value_count: u32,
+    pub last_write_unix: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ValueEntry {
+    pub name: String,
+    pub value_type: String,
+    pub size: usize,
+    pub data: serde_json::Value,
+    pub truncated: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BackupSummary {
+    pub id: String,
+    pub created_unix: u64,
+    pub operation: String,
+    pub hive: String,
+    pub path: String,
+    pub value_name: Option<String>,
+    pub existed: bool,
+    pub incomplete: bool,
+    pub key_count: usize,
+    pub value_count: usize,
+    pub size_bytes: u64,
+}
+
+pub fn parse_hive(hive: &str) -> Result<(HKEY, &'static str), String> {
+    let normalized = hive.trim().trim_end_matches(':').to_uppercase();
+    match normalized.as_str() {
+        "HKLM" | "HKEY_LOCAL_MACHINE" => Ok((HKEY_LOCAL_MACHINE, "HKLM")),
+        "HKCU" | "HKEY_CURRENT_USER" => Ok((HKEY_CURRENT_USER, "HKCU")),
+        "HKCR" | "HKEY_CLASSES_ROOT" => Ok((HKEY_CLASSES_ROOT, "HKCR")),
+        "HKU" | "HKEY_USERS" => Ok((HKEY_USERS, "HKU")),
+        "HKCC" | "HKEY_CURRENT_CONFIG" => Ok((HKEY_CURRENT_CONFIG, "HKCC")),
+        _ => Err(format!("Unknown registry hive: {}", hive)),
+    }
+}
+
+pub fn hive_full_name(hive: &str) -> &'static str {
+    match hive {
+        "HKLM" => "HKEY_LOCAL_MACHINE",
+        "HKCU" => "HKEY_CURRENT_USER",
+        "HKCR" => "HKEY_CLASSES_ROOT",
+        "HKU" => "HKEY_USERS",
+        "HKCC" => "HKEY_CURRENT_CONFIG",
+        _ => "UNKNOWN",
+    }
+}
+
+pub fn normalize_key_path(path: &str) -> String {
+    path.trim().replace('/', "\\").trim_matches('\\').to_string()
+}
+
+pub fn reg_type_from_u32(vtype: u32) -> Result<RegType, String> {
+    Ok(match vtype {
+        0 => REG_NONE,
+        1 => REG_SZ,
+        2 => REG_EXPAND_SZ,
+        3 => REG_BINARY,
+        4 => REG_DWORD,
+        5 => REG_DWORD_BIG_ENDIAN,
+        6 => REG_LINK,
+        7 => REG_MULTI_SZ,
+        8 => REG_RESOURCE_LIST,
+        9 => REG_FULL_RESOURCE_DESCRIPTOR,
+        10 => REG_RESOURCE_REQUIREMENTS_LIST,
+        11 => REG_QWORD,
+        _ => return Err(format!("Unknown registry value type: {}", vtype)),
+    })
+}
+
+pub fn reg_type_name(vtype: &RegType) -> &'static str {
+    match vtype {
+        REG_NONE => "REG_NONE",
+        REG_SZ => "REG_SZ",
+        REG_EXPAND_SZ => "REG_EXPAND_SZ",
+        REG_BINARY => "REG_BINARY",
+        REG_DWORD => "REG_DWORD",
+        REG_DWORD_BIG_ENDIAN => "REG_DWORD_BIG_ENDIAN",
+        REG_LINK => "REG_LINK",
+        REG_MULTI_SZ => "REG_MULTI_SZ",
+        REG_RESOURCE_LIST => "REG_RESOURCE_LIST",
+        REG_FULL_RESOURCE_DESCRIPTOR => "REG_FULL_RESOURCE_DESCRIPTOR",
+        REG_RESOURCE_REQUIREMENTS_LIST => "REG_RESOURCE_REQUIREMENTS_LIST",
+        REG_QWORD => "REG_QWORD",
+    }
+}
+
+pub fn decode_reg_string(bytes: &[u8]) -> String {
+    let wide: Vec<u16> = bytes
+        .chunks_exact(2)
+        .map(|c| u16::from_le_bytes([c[0], c[1]]))
+        .collect();
+    String::from_utf16_lossy(&wide)
+        .trim_end_matches('\0')
+        .to_string()
+}
+
+pub fn render_value(value: &RegValue, max_bytes: usize) -> (serde_json::Value, bool) {
+    let truncated = value.bytes.len() > max_bytes;
+    let bytes = &value.bytes[..value.bytes.len().min(max_bytes)];
+    let rendered = match value.vtype {
+        REG_SZ | REG_EXPAND_SZ | REG_LINK => serde_json::Value::String(decode_reg_string(bytes)),
+        REG_MULTI_SZ => serde_json::Value::Array(
+            decode_reg_string(bytes)
+                .split('\0')
+                .filter(|s| !s.is_empty())
+                .map(|s| serde_json::Value::String(s.to_string()))
+                .collect(),
+        ),
+        REG_DWORD if bytes.len() >= 4 => {
+            serde_json::json!(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        }
+        REG_DWORD_BIG_ENDIAN if bytes.len() >= 4 => {
+            serde_json::json!(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        }
+        REG_QWORD if bytes.len() >= 8 => {
+            let mut raw = [0u8; 8];
+            raw.copy_from_slice(&bytes[..8]);
+            serde_json::json!(u64::from_le_bytes(raw))
+        }
+        _ => serde_json::Value::String(
+            bytes
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect::<Vec<_>>()
+                .join(" "),
+        ),
+    };
+    (rendered, truncated)
+}
+
+fn filetime_to_unix(low: u32, high: u32) -> Option<u64> {
+    let ticks = ((high as u64) << 32) | low as u64;
+    (ticks / 10_000_000).checked_sub(11_644_473_600)
+}
+
+fn key_last_write_unix(key: &RegKey) -> Option<u64> {
+    let info = key.query_info().ok()?;
+    filetime_to_unix(
+        info.last_write_time.dwLowDateTime,
+        info.last_write_time.dwHighDateTime,
+    )
+}
+
+pub fn open_browsable_key(
+    hive: &str,
+    path: &str,
+) -> Result<(RegKey, &'static str, String), String> {
+    let (root, hive_name) = parse_hive(hive)?;
+    if !BROWSABLE_HIVES.contains(&hive_name) {
+        return Err(format!("Registry hive is not readable: {}", hive_name));
+    }
+    let path = normalize_key_path(path);
+    let full = format!("{}\\{}", hive_name, path).to_uppercase();
+    if BROWSE_DENIED_PREFIXES
+        .iter()
+        .any(|p| full == *p || full.starts_with(&format!("{}\\", p)))
+    {
+        return Err("Access to this registry key is not allowed".to_string());
+    }
+    let key = RegKey::predef(root)
+        .open_subkey_with_flags(&path, KEY_READ)
+        .map_err(|e| format!("Failed to open registry key {}\\{}: {}", hive_name, path, e))?;
+    Ok((key, hive_name, path))
+}
+
+fn page_bounds(offset: Option<usize>, limit: Option<usize>) -> (usize, usize) {
+    let limit = limit
+        .unwrap_or(BROWSE_DEFAULT_LIMIT)
+        .clamp(1, BROWSE_MAX_LIMIT);
+    (offset.unwrap_or(0), limit)
+}
+
+pub fn list_subkeys(
+    hive: &str,
+    path: &str,
+    offset: Option<usize>,
+    limit: Option<usize>,
+) -> Result<RegistryPage<SubkeyEntry>, String> {
+    let (key, hive_name, path) = open_browsable_key(hive, path)?;
+    let (offset, limit) = page_bounds(offset, limit);
+    let info = key
+        .query_info()
+        .map_err(|e| format!("Failed to query registry key: {}", e))?;
+
+    let mut items = Vec::new();
+    for name in key.enum_keys().skip(offset).take(limit) {
+        let name = match name {
+            Ok(n) => n,
+            Err(_) => continue,
+        };
+        let entry = match key.open_subkey_with_flags(&name, KEY_READ) {
+            Ok(sub) => match sub.query_info() {
+                Ok(sub_info) => SubkeyEntry {
+                    name,
+                    subkey_count: sub_info.sub_keys,
+                    value_count: sub_info.values,
+                    last_write_unix: filetime_to_unix(
+                        sub_info.last_write_time.dwLowDateTime,
+                        sub_info.last_write_time.dwHighDateTime,
+                    ),
+                },
+                Err(_) => SubkeyEntry {
+                    name,
+                    subkey_count: 0,
+                    value_count: 0,
+                    last_write_unix: None,
+                },
+            },
+            Err(_) => SubkeyEntry {
+                name,
+                subkey_count: 0,
+                value_count: 0,
+                last_write_unix: None,
+            },
+        };
+        items.push(entry);
+    }
+
+    Ok(RegistryPage {
+        hive: hive_name.to_string(),
+        path,
+        offset,
+        limit,
+        total: info.sub_keys as usize,
+        last_write_unix: key_last_write_unix(&key),
+        items,
+    })
+}
+
+pub fn list_values(
+    hive: &str,
+    path: &str,
+    offset: Option<usize>,
+    limit: Option<usize>,
+) -> Result<RegistryPage<ValueEntry>, String> {
+    let (key, hive_name, path) = open_browsable_key(hive, path)?;
+    let (offset, limit) = page_bounds(offset, limit);
+    let info = key
+        .query_info()
+        .map_err(|e| format!("Failed to query registry key: {}", e))?;
+
+    let items = key
+        .enum_values()
+        .skip(offset)
+        .take(limit)
+        .filter_map(|item| item.ok())
+        .map(|(name, value)| {
+            let (data, truncated) = render_value(&value, VALUE_PREVIEW_MAX_BYTES);
+            ValueEntry {
+                name,
+                value_type: reg_type_name(&value.vtype).to_string(),
+                size: value.bytes.len(),
+                data,
+                truncated,
+            }
+        })
+        .collect();
+
+    Ok(RegistryPage {
+        hive: hive_name.to_string(),
+        path,
+        offset,
+        limit,
+        total: info.values as usize,
+        last_write_unix: filetime_to_unix(
+            info.last_write_time.dwLowDateTime,
+            info.last_write_time.dwHighDateTime,
+        ),
+        items,
+    })
+}
+
+fn backup_dir() -> PathBuf {
+    util::data_subdir("registry_backups")
+}
+
+fn validate_backup_id(id: &str) -> Result<&str, String> {
+    let id = id.trim();
+    if id.is_empty()
+        || id.len() > 64
+        || !id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err("Invalid backup id".to_string());
+    }
+    Ok(id)
+}
+
+impl BackupValue {
+    pub fn bytes(&self) -> Option<Vec<u8>> {
+        base64::Engine::decode(&base64::engine::general_purpose::STANDARD, &self.data).ok()
+    }
+
+    fn from_reg(name: String, value: &RegValue) -> Self {
+        Self {
+            name,
+            vtype: value.vtype.clone() as u32,
+            data: base64::Engine::encode(&base64::engine::general_purpose::STANDARD, &value.bytes),
+        }
+    }
+
+    fn to_reg(&self) -> Result<RegValue, String> {
+        let bytes =
+            base64::Engine::decode(&base64::engine::general_purpose::STANDARD, &self.data)
+                .map_err(|e| format!("Corrupt backup value {}: {}", self.name, e))?;
+        Ok(RegValue {
+            bytes,
+            vtype: reg_type_from_u32(self.vtype)?,
+        })
+    }
+}
+
+impl BackupKey {
+    fn counts(&self) -> (usize, usize) {
+        self.subkeys.iter().fold((1, self.values.len()), |acc, sub| {
+            let (k, v) = sub.counts();
+            (acc.0 + k, acc.1 + v)
+        })
+    }
+}
+
+fn capture_key(
+    key: &RegKey,
+    name: &str,
+    visited: &mut usize,
+    incomplete: &mut bool,
+) -> Result<BackupKey, String> {
+    *visited += 1;
+    if *visited > BACKUP_MAX_KEYS {
+        return Err(format!(
+            "Registry subtree is too large to back up (more than {} keys)",
+            BACKUP_MAX_KEYS
+        ));
+    }
+
+    let mut values = Vec::new();
+    for item in key.enum_values() {
+        match item {
+            Ok((value_name, value)) => values.push(BackupValue::from_reg(value_name, &value)),
+            Err(_) => *incomplete = true,
+        }
+    }
+
+    let mut subkeys = Vec::new();
+    for item in key.enum_keys() {
+        let sub_name = match item {
+            Ok(n) => n,
+            Err(_) => {
+                *incomplete = true;
+                continue;
+            }
+        };
+        match key.open_subkey_with_flags(&sub_name, KEY_READ) {
+            Ok(sub) => subkeys.push(capture_key(&sub, &sub_name, visited, incomplete)?),
+            Err(_) => *incomplete = true,
+        }
+    }
+
+    Ok(BackupKey {
+        name: name.to_string(),
+        values,
+        subkeys,
+    })
+}
+
+fn restore_key(parent: &RegKey, path: &str, node: &BackupKey) -> Result<(), String> {
+    let (key, _) = parent
+        .create_subkey(path)
+        .map_err(|e| format!("Failed to create registry key {}: {}", path, e))?;
+    for value in &node.values {
+        key.set_raw_value(&value.name, &value.to_reg()?)
+            .map_err(|e| format!("Failed to restore value {}: {}", value.name, e))?;
+    }
+    for sub in &node.subkeys {
+        restore_key(&key, &sub.name, sub)?;
+    }
+    Ok(())
+}
+
+fn persist_backup(backup: &RegistryBackup) -> Result<(), String> {
+    let raw = serde_json::to_string(backup)
+        .map_err(|e| format!("Failed to serialize registry backup: {}", e))?;
+    let path = backup_dir().join(format!("{}.json", backup.id));
+    std::fs::write(&path, raw).map_err(|e| format!("Failed to write registry backup: {}", e))?;
+    prune_backups();
+    Ok(())
+}
+
+fn load_backup(id: &str) -> Result<RegistryBackup, String> {
+    let id = validate_backup_id(id)?;
+    let raw = std::fs::read_to_string(backup_dir().join(format!("{}.json", id)))
+        .map_err(|_| format!("Registry backup not found: {}", id))?;
+    serde_json::from_str(&raw).map_err(|e| format!("Corrupt registry backup {}: {}", id, e))
+}
+
+fn prune_backups() {
+    let mut files: Vec<(PathBuf, u64, u64)> = match std::fs::read_dir(backup_dir()) {
+        Ok(entries) => entries
+            .filter_map(|e| e.ok())
+            .filter_map(|e| {
+                let path = e.path();
+                if path.extension().and_then(|x| x.to_str()) != Some("json") {
+                    return None;
+                }
+                let meta = e.metadata().ok()?;
+                let modified = meta
+                    .modified()
+                    .ok()
+                    .and_then(|m| m.duration_since(std::time::UNIX_EPOCH).ok())
+                    .map(|d| d.as_secs())
+                    .unwrap_or(0);
+                Some((path, modified, meta.len()))
+            })
+            .collect(),
+        Err(_) => return,
+    };
+    files.sort_by_key(|f| std::cmp::Reverse(f.1));
+
+    let now = util::now_unix();
+    let mut total_bytes = 0u64;
+    for (index, (path, modified, size)) in files.iter().enumerate() {
+        total_bytes += size;
+        let keep = index == 0
+            || (index < BACKUP_MAX_ENTRIES
+                && now.saturating_sub(*modified) <= BACKUP_MAX_AGE_SECS
+                && total_bytes <= BACKUP_MAX_TOTAL_BYTES);
+        if !keep {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+fn new_backup(operation: &str, hive: &str, path: &str, value_name: Option<&str>) -> RegistryBackup {
+    RegistryBackup {
+        id: util::new_id(),
+        created_unix: util::now_unix(),
+        operation: operation.to_string(),
+        hive: hive.to_string(),
+        path: path.to_string(),
+        value_name: value_name.map(|n| n.to_string()),
+        existed: false,
+        incomplete: false,
+        key: None,
+        value: None,
+    }
+}
+
+pub fn backup_key(hive: &str, path: &str) -> Result<Option<String>, String> {
+    let (root, hive_name) = parse_hive(hive)?;
+    let path = normalize_key_path(path);
+    if path.is_empty() {
+        return Err("Refusing to back up or delete a registry hive root".to_string());
+    }
+
+    let key = match RegKey::predef(root).open_subkey_with_flags(&path, KEY_READ) {
+        Ok(k) => k,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(format!("Failed to open registry key for backup: {}", e)),
+    };
+
+    let mut backup = new_backup("delete_key", hive_name, &path, None);
+    let mut visited = 0usize;
+    let leaf = path.rsplit('\\').next().unwrap_or(&path).to_string();
+    backup.key = Some(capture_key(&key, &leaf, &mut visited, &mut backup.incomplete)?);
+    backup.existed = true;
+    persist_backup(&backup)?;
+    Ok(Some(backup.id))
+}
+
+pub fn capture_subtree(hive: &str, path: &str) -> Result<Option<BackupKey>, String> {
+    let (root, _) = parse_hive(hive)?;
+    let path = normalize_key_path(path);
+    let key = match RegKey::predef(root).open_subkey_with_flags(&path, KEY_READ) {
+        Ok(k) => k,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(format!("Failed to open registry key {}: {}", path, e)),
+    };
+    let mut visited = 0usize;
+    let mut incomplete = false;
+    let leaf = path.rsplit('\\').next().unwrap_or(&path).to_string();
+    capture_key(&key, &leaf, &mut visited, &mut incomplete).map(Some)
+}
+
+pub fn restore_subtree(hive: &str, path: &str, node: &BackupKey) -> Result<(), String> {
+    let (root, _) = parse_hive(hive)?;
+    restore_key(&RegKey::predef(root), &normalize_key_path(path), node)
+}
+
+pub fn backup_value(
+    operation: &str,
+    hive: &str,
+    path: &str,
+    name: &str,
+) -> Result<Option<String>, String> {
+    let (root, hive_name) = parse_hive(hive)?;
+    let path = normalize_key_path(path);
+
+    let key = match RegKey::predef(root).open_subkey_with_flags(&path, KEY_READ) {
+        Ok(k) => Some(k),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(e) => return Err(format!("Failed to open registry key for backup: {}", e)),
+    };
+
+    let current = match key.as_ref().map(|k| k.get_raw_value(name)) {
+        Some(Ok(v)) => Some(v),
+        Some(Err(e)) if e.kind() != std::io::ErrorKind::NotFound => {
+            return Err(format!("Failed to read registry value for backup: {}", e))
+        }
+        _ => None,
+    };
+
+    if current.is_none() && operation == "delete_value" {
+        return Ok(None);
+    }
+
+    let mut backup = new_backup(operation, hive_name, &path, Some(name));
+    if let Some(value) = current {
+        backup.existed = true;
+        backup.value = Some(BackupValue::from_reg(name.to_string(), &value));
+    }
+    persist_backup(&backup)?;
+    Ok(Some(backup.id))
+}
+
+pub fn list_backups() -> Result<Vec<BackupSummary>, String> {
+    let entries = std::fs::read_dir(backup_dir())
+        .map_err(|e| format!("Failed to read registry backup store: {}", e))?;
+
+    let mut summaries = Vec::new();
+    for entry in entries.filter_map(|e| e.ok()) {
+        let path = entry.path();
+        if path.extension().and_then(|x| x.to_str()) != Some("json") {
+            continue;
+        }
+        let size_bytes = entry.metadata().map(|m| m.len()).unwrap_or(0);
+        let backup = match std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|raw| serde_json::from_str::<RegistryBackup>(&raw).ok())
+        {
+            Some(b) => b,
+            None => continue,
+        };
+        let (key_count, value_count) = match (&backup.key, &backup.value) {
+            (Some(k), _) => k.counts(),
+            (None, Some(_)) => (0, 1),
+            _ => (0, 0),
+        };
+        summaries.push(BackupSummary {
+            id: backup.id,
+            created_unix: backup.created_unix,
+            operation: backup.operation,
+            hive: backup.hive,
+            path: backup.path,
+            value_name: backup.value_name,
+            existed: backup.existed,
+            incomplete: backup.incomplete,
+            key_count,
+            value_count,
+            size_bytes,
+        });
+    }
+    summaries.sort_by(|a, b| b.created_unix.cmp(&a.created_unix).then(b.id.cmp(&a.id)));
+    Ok(summaries)
+}
+
+pub fn restore_backup(id: &str) -> Result<String, String> {
+    let backup = load_backup(id)?;
+    let (root, _) = parse_hive(&backup.hive)?;
+    let root = RegKey::predef(root);
+
+    if let Some(node) = &backup.key {
+        restore_key(&root, &backup.path, node)?;
+        return Ok(format!("Restored {}\\{}", backup.hive, backup.path));
+    }
+
+    let name = backup
+        .value_name
+        .as_deref()
+        .ok_or_else(|| "Backup does not contain a key or value".to_string())?;
+
+    match &backup.value {
+        Some(value) => {
+            let (key, _) = root
+                .create_subkey(&backup.path)
+                .map_err(|e| format!("Failed to open registry key {}: {}", backup.path, e))?;
+            key.set_raw_value(name, &value.to_reg()?)
+                .map_err(|e| format!("Failed to restore value {}: {}", name, e))?;
+            Ok(format!("Restored {}\\{}\\{}", backup.hive, backup.path, name))
+        }
+        None => {
+            if let Ok(key) = root.open_subkey_with_flags(&backup.path, KEY_SET_VALUE) {
+                match key.delete_value(name) {
+                    Ok(_) => {}
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                    Err(e) => return Err(format!("Failed to remove value {}: {}", name, e)),
+                }
+            }
+            Ok(format!(
+                "Removed {}\\{}\\{} (did not exist before backup)",
+                backup.hive, backup.path, name
+            ))
+        }
+    }
+}
+
+pub fn delete_backup(id: &str) -> Result<(), String> {
+    let id = validate_backup_id(id)?;
+    std::fs::remove_file(backup_dir().join(format!("{}.json", id)))
+        .map_err(|e| format!("Failed to delete registry backup {}: {}", id, e))
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn value(vtype: RegType, bytes: &[u8]) -> RegValue {
+        RegValue {
+            bytes: bytes.to_vec(),
+            vtype,
+        }
+    }
+
+    fn wide(text: &str) -> Vec<u8> {
+        text.encode_utf16().flat_map(|c| c.to_le_bytes()).collect()
+    }
+
+    #[test]
+    fn value_types_round_trip_through_their_codes() {
+        let names = [
+            "REG_NONE",
+            "REG_SZ",
+            "REG_EXPAND_SZ",
+            "REG_BINARY",
+            "REG_DWORD",
+            "REG_DWORD_BIG_ENDIAN",
+            "REG_LINK",
+            "REG_MULTI_SZ",
+            "REG_RESOURCE_LIST",
+            "REG_FULL_RESOURCE_DESCRIPTOR",
+            "REG_RESOURCE_REQUIREMENTS_LIST",
+            "REG_QWORD",
+        ];
+        for (code, name) in names.iter().enumerate() {
+            let vtype = reg_type_from_u32(code as u32).unwrap();
+            assert_eq!(reg_type_name(&vtype), *name);
+            assert_eq!(vtype as u32, code as u32);
+        }
+        assert_eq!(
+            reg_type_from_u32(12).unwrap_err(),
+            "Unknown registry value type: 12"
+        );
+    }
+
+    #[test]
+    fn strings_decode_from_utf16() {
+        assert_eq!(decode_reg_string(&wide("C:\\Windows\0")), "C:\\Windows");
+        assert_eq!(decode_reg_string(&wide("caf\u{e9}\0\0")), "caf\u{e9}");
+        // A trailing odd byte is ignored rather than failing the value.
+        let mut odd = wide("ab");
+        odd.push(0x41);
+        assert_eq!(decode_reg_string(&odd), "ab");
+        assert_eq!(decode_reg_string(&[]), "");
+    }
+
+    #[test]
+    fn values_render_by_type() {
+        let cases = [
+            (value(REG_SZ, &wide("hello\0")), serde_json::json!("hello")),
+            (
+                value(REG_EXPAND_SZ, &wide("%SystemRoot%\\x\0")),
+                serde_json::json!("%SystemRoot%\\x"),
+            ),
+            (
+                value(REG_MULTI_SZ, &wide("one\0two\0\0")),
+                serde_json::json!(["one", "two"]),
+            ),
+            (value(REG_MULTI_SZ, &wide("\0")), serde_json::json!([])),
+            (
+                value(REG_DWORD, &0x1234_5678u32.to_le_bytes()),
+                serde_json::json!(0x1234_5678u32),
+            ),
+            (
+                value(REG_DWORD_BIG_ENDIAN, &[0, 0, 1, 0]),
+                serde_json::json!(256),
+            ),
+            (
+                value(REG_QWORD, &u64::MAX.to_le_bytes()),
+                serde_json::json!(u64::MAX),
+            ),
+            (
+                value(REG_BINARY, &[0x00, 0xab, 0xff]),
+                serde_json::json!("00 ab ff"),
+            ),
+            (value(REG_DWORD, &[1, 2]), serde_json::json!("01 02")),
+            (value(REG_NONE, &[]), serde_json::json!("")),
+        ];
+        for (value, expected) in cases {
+            let (rendered, truncated) = render_value(&value, 1024);
+            assert_eq!(rendered, expected, "{:?}", value.vtype);
+            assert!(!truncated);
+        }
+    }
+
+    #[test]
+    fn render_value_truncates_long_data() {
+        let (rendered, truncated) = render_value(&value(REG_BINARY, &[1, 2, 3, 4, 5]), 3);
+        assert_eq!(rendered, serde_json::json!("01 02 03"));
+        assert!(truncated);
+        let (rendered, truncated) = render_value(&value(REG_SZ, &wide("abcdef")), 4);
+        assert_eq!(rendered, serde_json::json!("ab"));
+        assert!(truncated);
+        let (_, truncated) = render_value(&value(REG_DWORD, &[0; 4]), 4);
+        assert!(!truncated);
+    }
+
+    #[test]
+    fn hives_and_paths_are_normalized() {
+        for (raw, short) in [
+            ("hklm", "HKLM"),
+            ("HKEY_CURRENT_USER", "HKCU"),
+            (" HKCR: ", "HKCR"),
+            ("hkey_users", "HKU"),
+            ("HKCC", "HKCC"),
+        ] {
+            assert_eq!(parse_hive(raw).unwrap().1, short);
+        }
+        assert!(parse_hive("HKXX").is_err());
+        assert_eq!(hive_full_name("HKLM"), "HKEY_LOCAL_MACHINE");
+        assert_eq!(hive_full_name("nope"), "UNKNOWN");
+        assert_eq!(
+            normalize_key_path(" /SOFTWARE/Microsoft\\Windows\\ "),
+            "SOFTWARE\\Microsoft\\Windows"
+        );
+    }
+
+    #[test]
+    fn backup_values_keep_type_and_bytes() {
+        let original = value(REG_QWORD, &42u64.to_le_bytes());
+        let backup = BackupValue::from_reg("Counter".to_string(), &original);
+        assert_eq!(backup.vtype, 11);
+        assert_eq!(backup.bytes().unwrap(), original.bytes);
+        let restored = backup.to_reg().unwrap();
+        assert_eq!(restored.bytes, original.bytes);
+        assert_eq!(reg_type_name(&restored.vtype), "REG_QWORD");
+
+        let corrupt = BackupValue {
+            name: "Bad".to_string(),
+            vtype: 1,
+            data: "not base64!".to_string(),
+        };
+        assert!(corrupt.bytes().is_none());
+        assert!(corrupt.to_reg().is_err());
+        let unknown = BackupValue {
+            vtype: 99,
+            ..backup
+        };
+        assert!(unknown.to_reg().is_err());
+    }
+
+    #[test]
+    fn filetimes_convert_to_unix_seconds() {
+        // 2021-01-01T00:00:00Z
+        let ticks: u64 = (1_609_459_200 + 11_644_473_600) * 10_000_000;
+        assert_eq!(
+            filetime_to_unix(ticks as u32, (ticks >> 32) as u32),
+            Some(1_609_459_200)
+        );
+        assert_eq!(filetime_to_unix(0, 0), None);
+    }
+
+    #[test]
+    fn backup_ids_are_validated() {
+        assert_eq!(
+            validate_backup_id(" 1700000000000-ab12 ").unwrap(),
+            "1700000000000-ab12"
+        );
+        for id in ["", "../x", "a b", "a.json", &"x".repeat(65)] {
+            assert!(validate_backup_id(id).is_err(), "{:?}", id);
+        }
+    }
+}
@@ -0,0 +1,401 @@
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use winreg::enums::*;
+
+use crate::registry;
+
+const SEARCH_DEFAULT_DEPTH: usize = 8;
+const SEARCH_MAX_DEPTH: usize = 32;
+const SEARCH_DEFAULT_RESULTS: usize = 500;
+const SEARCH_MAX_RESULTS: usize = 10_000;
+const SEARCH_DATA_MAX_BYTES: usize = 64 * 1024;
+
+lazy_static::lazy_static! {
+    static ref SEARCH_JOBS: Mutex<HashMap<String, Arc<AtomicBool>>> = Mutex::new(HashMap::new());
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SearchQuery {
+    pub hive: String,
+    pub path: String,
+    pub pattern: String,
+    #[serde(default)]
+    pub regex: bool,
+    #[serde(default)]
+    pub case_sensitive: bool,
+    #[serde(default = "default_true")]
+    pub match_keys: bool,
+    #[serde(default = "default_true")]
+    pub match_value_names: bool,
+    #[serde(default)]
+    pub match_data: bool,
+    pub max_depth: Option<usize>,
+    pub max_results: Option<usize>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchHit {
+    pub hive: String,
+    pub path: String,
+    pub value_name: Option<String>,
+    pub matched: String,
+    pub value_type: Option<String>,
+    pub data: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchSummary {
+    pub hits: usize,
+    pub scanned_keys: usize,
+    pub skipped_keys: usize,
+    pub cancelled: bool,
+    pub truncated: bool,
+}
+
+enum Matcher {
+    Substring {
+        needle: String,
+        case_sensitive: bool,
+    },
+    Pattern(Regex),
+}
+
+impl Matcher {
+    fn new(query: &SearchQuery) -> Result<Self, String> {
+        if query.pattern.is_empty() {
+            return Err("Search pattern cannot be empty".to_string());
+        }
+        if query.regex {
+            let re = RegexBuilder::new(&query.pattern)
+                .case_insensitive(!query.case_sensitive)
+                .size_limit(1 << 20)
+                .build()
+                .map_err(|e| format!("Invalid search pattern: {}", e))?;
+            return Ok(Matcher::Pattern(re));
+        }
+        Ok(Matcher::Substring {
+            needle: if query.case_sensitive {
+                query.pattern.clone()
+            } else {
+                query.pattern.to_lowercase()
+            },
+            case_sensitive: query.case_sensitive,
+        })
+    }
+
+    fn is_match(&self, text: &str) -> bool {
+        match self {
+            Matcher::Substring {
+                needle,
+                case_sensitive: true,
+            } => text.contains(needle.as_str()),
+            Matcher::Substring { needle, .. } => text.to_lowercase().contains(needle.as_str()),
+            Matcher::Pattern(re) => re.is_match(text),
+        }
+    }
+}
+
+fn searchable_text(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::String(s) => s.clone(),
+        serde_json::Value::Array(items) => items
+            .iter()
+            .filter_map(|i| i.as_str())
+            .collect::<Vec<_>>()
+            .join("\n"),
+        serde_json::Value::Number(n) => match n.as_u64() {
+            Some(v) => format!("{} 0x{:x}", v, v),
+            None => n.to_string(),
+        },
+        other => other.to_string(),
+    }
+}
+
+impl SearchSummary {
+    // Returns true once the result limit is reached.
+    fn record_hit(&mut self, max_results: usize) -> bool {
+        self.hits += 1;
+        if self.hits >= max_results {
+            self.truncated = true;
+        }
+        self.truncated
+    }
+}
+
+fn search_limits(query: &SearchQuery) -> (usize, usize) {
+    let max_depth = query
+        .max_depth
+        .unwrap_or(SEARCH_DEFAULT_DEPTH)
+        .min(SEARCH_MAX_DEPTH);
+    let max_results = query
+        .max_results
+        .unwrap_or(SEARCH_DEFAULT_RESULTS)
+        .clamp(1, SEARCH_MAX_RESULTS);
+    (max_depth, max_results)
+}
+
+fn join_path(base: &str, name: &str) -> String {
+    match (base.is_empty(), name.is_empty()) {
+        (true, _) => name.to_string(),
+        (_, true) => base.to_string(),
+        _ => format!("{}\\{}", base, name),
+    }
+}
+
+pub fn register_job(job_id: &str) -> Arc<AtomicBool> {
+    let flag = Arc::new(AtomicBool::new(false));
+    if let Ok(mut jobs) = SEARCH_JOBS.lock() {
+        jobs.insert(job_id.to_string(), flag.clone());
+    }
+    flag
+}
+
+pub fn finish_job(job_id: &str) {
+    if let Ok(mut jobs) = SEARCH_JOBS.lock() {
+        jobs.remove(job_id);
+    }
+}
+
+pub fn cancel_job(job_id: &str) -> bool {
+    match SEARCH_JOBS.lock() {
+        Ok(jobs) => match jobs.get(job_id) {
+            Some(flag) => {
+                flag.store(true, Ordering::SeqCst);
+                true
+            }
+            None => false,
+        },
+        Err(_) => false,
+    }
+}
+
+pub fn search<F>(
+    query: &SearchQuery,
+    cancelled: &AtomicBool,
+    mut on_hit: F,
+) -> Result<SearchSummary, String>
+where
+    F: FnMut(SearchHit),
+{
+    let matcher = Matcher::new(query)?;
+    let (root_key, hive_name, root_path) = registry::open_browsable_key(&query.hive, &query.path)?;
+    let (max_depth, max_results) = search_limits(query);
+
+    let mut summary = SearchSummary {
+        hits: 0,
+        scanned_keys: 0,
+        skipped_keys: 0,
+        cancelled: false,
+        truncated: false,
+    };
+    let mut stack: Vec<(String, usize)> = vec![(String::new(), 0)];
+
+    while let Some((relative, depth)) = stack.pop() {
+        if cancelled.load(Ordering::SeqCst) {
+            summary.cancelled = true;
+            break;
+        }
+        let key = match root_key.open_subkey_with_flags(&relative, KEY_READ) {
+            Ok(k) => k,
+            Err(_) => {
+                summary.skipped_keys += 1;
+                continue;
+            }
+        };
+        let path = join_path(&root_path, &relative);
+        summary.scanned_keys += 1;
+
+        if query.match_value_names || query.match_data {
+            for (value_name, value) in key.enum_values().filter_map(|v| v.ok()) {
+                let name_hit = query.match_value_names && matcher.is_match(&value_name);
+                let (data, _) = registry::render_value(&value, SEARCH_DATA_MAX_BYTES);
+                let data_hit =
+                    !name_hit && query.match_data && matcher.is_match(&searchable_text(&data));
+                if !name_hit && !data_hit {
+                    continue;
+                }
+                on_hit(SearchHit {
+                    hive: hive_name.to_string(),
+                    path: path.clone(),
+                    value_name: Some(value_name),
+                    matched: if name_hit { "value_name" } else { "data" }.to_string(),
+                    value_type: Some(registry::reg_type_name(&value.vtype).to_string()),
+                    data: Some(data),
+                });
+                if summary.record_hit(max_results) {
+                    return Ok(summary);
+                }
+            }
+        }
+
+        if depth >= max_depth {
+            continue;
+        }
+
+        let mut children = Vec::new();
+        for sub_name in key.enum_keys().filter_map(|k| k.ok()) {
+            if query.match_keys && matcher.is_match(&sub_name) {
+                on_hit(SearchHit {
+                    hive: hive_name.to_string(),
+                    path: join_path(&path, &sub_name),
+                    value_name: None,
+                    matched: "key".to_string(),
+                    value_type: None,
+                    data: None,
+                });
+                if summary.record_hit(max_results) {
+                    return Ok(summary);
+                }
+            }
+            children.push((join_path(&relative, &sub_name), depth + 1));
+        }
+        stack.extend(children.into_iter().rev());
+    }
+
+    Ok(summary)
+}
+
+// Roots may overlap (SOFTWARE also reaches SOFTWARE\WOW6432Node at depth 2),
+// so hits are deduplicated case-insensitively, keeping first-seen order.
+pub fn find_leftover_keys(app_name: &str, roots: &[(&str, &str)]) -> Vec<String> {
+    let never_cancelled = AtomicBool::new(false);
+    let mut seen = BTreeSet::new();
+    let mut found = Vec::new();
+    for (hive, path) in roots {
+        let query = SearchQuery {
+            hive: hive.to_string(),
+            path: path.to_string(),
+            pattern: app_name.to_string(),
+            regex: false,
+            case_sensitive: false,
+            match_keys: true,
+            match_value_names: false,
+            match_data: false,
+            max_depth: Some(2),
+            max_results: Some(200),
+        };
+        let _ = search(&query, &never_cancelled, |hit| {
+            let key = format!("{}\\{}", registry::hive_full_name(&hit.hive), hit.path);
+            if seen.insert(key.to_lowercase()) {
+                found.push(key);
+            }
+        });
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn query(pattern: &str) -> SearchQuery {
+        serde_json::from_value(serde_json::json!({
+            "hive": "HKLM",
+            "path": "SOFTWARE",
+            "pattern": pattern,
+        }))
+        .unwrap()
+    }
+
+    fn matcher(pattern: &str, regex: bool, case_sensitive: bool) -> Result<Matcher, String> {
+        Matcher::new(&SearchQuery {
+            regex,
+            case_sensitive,
+            ..query(pattern)
+        })
+    }
+
+    #[test]
+    fn substring_matching_honours_case() {
+        let insensitive = matcher("Defender", false, false).unwrap();
+        assert!(insensitive.is_match("Windows Defender"));
+        assert!(insensitive.is_match("WINDOWS DEFENDER"));
+        assert!(!insensitive.is_match("Defend"));
+
+        let sensitive = matcher("Defender", false, true).unwrap();
+        assert!(sensitive.is_match("Windows Defender"));
+        assert!(!sensitive.is_match("windows defender"));
+
+        // Regex metacharacters are literal in substring mode.
+        let literal = matcher("a.b", false, false).unwrap();
+        assert!(literal.is_match("x a.b y"));
+        assert!(!literal.is_match("axb"));
+    }
+
+    #[test]
+    fn regex_matching_and_errors() {
+        let re = matcher(r"^Run(Once)?$", true, false).unwrap();
+        assert!(re.is_match("run"));
+        assert!(re.is_match("RunOnce"));
+        assert!(!re.is_match("RunServices"));
+        assert!(!matcher(r"^Run$", true, true).unwrap().is_match("run"));
+
+        assert!(matcher("(", true, false)
+            .err()
+            .unwrap()
+            .starts_with("Invalid search pattern"));
+        assert_eq!(
+            matcher("", false, false).err().as_deref(),
+            Some("Search pattern cannot be empty")
+        );
+    }
+
+    #[test]
+    fn data_is_searchable_as_text() {
+        assert_eq!(searchable_text(&serde_json::json!("C:\\x")), "C:\\x");
+        assert_eq!(searchable_text(&serde_json::json!(["a", "b"])), "a\nb");
+        assert_eq!(searchable_text(&serde_json::json!(255)), "255 0xff");
+        let hex = matcher("0x1f4", false, false).unwrap();
+        assert!(hex.is_match(&searchable_text(&serde_json::json!(500))));
+    }
+
+    #[test]
+    fn limits_default_and_clamp() {
+        assert_eq!(
+            search_limits(&query("x")),
+            (SEARCH_DEFAULT_DEPTH, SEARCH_DEFAULT_RESULTS)
+        );
+        let wide = SearchQuery {
+            max_depth: Some(1000),
+            max_results: Some(1_000_000),
+            ..query("x")
+        };
+        assert_eq!(search_limits(&wide), (SEARCH_MAX_DEPTH, SEARCH_MAX_RESULTS));
+        let zero = SearchQuery {
+            max_depth: Some(0),
+            max_results: Some(0),
+            ..query("x")
+        };
+        assert_eq!(search_limits(&zero), (0, 1));
+    }
+
+    #[test]
+    fn hits_stop_at_the_result_limit() {
+        let mut summary = SearchSummary {
+            hits: 0,
+            scanned_keys: 0,
+            skipped_keys: 0,
+            cancelled: false,
+            truncated: false,
+        };
+        assert!(!summary.record_hit(3));
+        assert!(!summary.record_hit(3));
+        assert!(summary.record_hit(3));
+        assert_eq!(summary.hits, 3);
+        assert!(summary.truncated);
+    }
+
+    #[test]
+    fn paths_join_without_stray_separators() {
+        assert_eq!(join_path("", "Run"), "Run");
+        assert_eq!(join_path("SOFTWARE", ""), "SOFTWARE");
+        assert_eq!(join_path("SOFTWARE", "Microsoft"), "SOFTWARE\\Microsoft");
+    }
+}
@@ -0,0 +1,569 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use winreg::enums::*;
+use winreg::RegKey;
+
+use crate::registry;
+use crate::util;
+
+const WATCH_MAX_KEYS: usize = 64;
+const WATCH_VALUE_MAX_BYTES: usize = 4096;
+const AUDIT_MAX_BYTES: u64 = 2 * 1024 * 1024;
+
+lazy_static::lazy_static! {
+    static ref WATCHER: Mutex<Option<WatcherHandle>> = Mutex::new(None);
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WatchedKey {
+    pub hive: String,
+    pub path: String,
+    #[serde(default)]
+    pub value_names: Option<Vec<String>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WatchedValue {
+    pub value_type: String,
+    pub data: serde_json::Value,
+}
+
+pub type KeySnapshot = Option<BTreeMap<String, WatchedValue>>;
+pub type Snapshot = BTreeMap<String, KeySnapshot>;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ValueChange {
+    pub hive: String,
+    pub path: String,
+    pub value_name: Option<String>,
+    pub kind: String,
+    pub old: Option<WatchedValue>,
+    pub new: Option<WatchedValue>,
+    pub detected_unix: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct WatchStatus {
+    pub running: bool,
+    pub keys: Vec<WatchedKey>,
+    pub started_unix: Option<u64>,
+    pub changes_detected: u64,
+}
+
+pub trait RegistrySource {
+    fn read_key(&self, key: &WatchedKey) -> Result<KeySnapshot, String>;
+    fn wait_for_change(&self, keys: &[WatchedKey], timeout: Duration);
+}
+
+struct WatcherHandle {
+    stop: Arc<AtomicBool>,
+    keys: Vec<WatchedKey>,
+    started_unix: u64,
+    changes: Arc<Mutex<u64>>,
+}
+
+fn key_id(key: &WatchedKey) -> String {
+    format!("{}\\{}", key.hive, key.path)
+}
+
+pub fn normalize_keys(keys: Vec<WatchedKey>) -> Result<Vec<WatchedKey>, String> {
+    if keys.is_empty() {
+        return Err("No registry keys to watch".to_string());
+    }
+    if keys.len() > WATCH_MAX_KEYS {
+        return Err(format!(
+            "Too many registry keys to watch (max {})",
+            WATCH_MAX_KEYS
+        ));
+    }
+    let mut normalized: Vec<WatchedKey> = Vec::new();
+    for key in keys {
+        let (_, hive) = registry::parse_hive(&key.hive)?;
+        let entry = WatchedKey {
+            hive: hive.to_string(),
+            path: registry::normalize_key_path(&key.path),
+            value_names: key.value_names,
+        };
+        if !normalized.contains(&entry) {
+            normalized.push(entry);
+        }
+    }
+    Ok(normalized)
+}
+
+pub fn take_snapshot<S: RegistrySource>(source: &S, keys: &[WatchedKey]) -> Snapshot {
+    let mut snapshot = Snapshot::new();
+    for key in keys {
+        let mut values = match source.read_key(key) {
+            Ok(v) => v,
+            Err(_) => continue,
+        };
+        if let (Some(map), Some(names)) = (values.as_mut(), key.value_names.as_ref()) {
+            map.retain(|name, _| names.iter().any(|n| n.eq_ignore_ascii_case(name)));
+        }
+        snapshot.insert(key_id(key), values);
+    }
+    snapshot
+}
+
+fn split_key_id(id: &str) -> (String, String) {
+    match id.split_once('\\') {
+        Some((hive, path)) => (hive.to_string(), path.to_string()),
+        None => (id.to_string(), String::new()),
+    }
+}
+
+pub fn diff_snapshots(old: &Snapshot, new: &Snapshot, detected_unix: u64) -> Vec<ValueChange> {
+    let mut changes = Vec::new();
+    let empty = BTreeMap::new();
+
+    for (id, new_values) in new {
+        let old_values = match old.get(id) {
+            Some(v) => v,
+            None => continue,
+        };
+        let (hive, path) = split_key_id(id);
+        let change = |value_name: Option<&String>,
+                      kind: &str,
+                      old: Option<&WatchedValue>,
+                      new: Option<&WatchedValue>| ValueChange {
+            hive: hive.clone(),
+            path: path.clone(),
+            value_name: value_name.cloned(),
+            kind: kind.to_string(),
+            old: old.cloned(),
+            new: new.cloned(),
+            detected_unix,
+        };
+
+        match (old_values, new_values) {
+            (None, Some(_)) => changes.push(change(None, "key_created", None, None)),
+            (Some(_), None) => changes.push(change(None, "key_deleted", None, None)),
+            _ => {}
+        }
+
+        let old_map = old_values.as_ref().unwrap_or(&empty);
+        let new_map = new_values.as_ref().unwrap_or(&empty);
+        for (name, value) in new_map {
+            match old_map.get(name) {
+                None => changes.push(change(Some(name), "added", None, Some(value))),
+                Some(previous) if previous != value => {
+                    changes.push(change(Some(name), "modified", Some(previous), Some(value)))
+                }
+                _ => {}
+            }
+        }
+        for (name, value) in old_map {
+            if !new_map.contains_key(name) {
+                changes.push(change(Some(name), "removed", Some(value), None));
+            }
+        }
+    }
+    changes
+}
+
+pub fn run_watch_loop<S, F>(
+    source: &S,
+    keys: &[WatchedKey],
+    stop: &AtomicBool,
+    poll_interval: Duration,
+    mut on_change: F,
+) where
+    S: RegistrySource,
+    F: FnMut(ValueChange),
+{
+    let mut previous = take_snapshot(source, keys);
+    while !stop.load(Ordering::SeqCst) {
+        source.wait_for_change(keys, poll_interval);
+        if stop.load(Ordering::SeqCst) {
+            break;
+        }
+        let current = take_snapshot(source, keys);
+        for change in diff_snapshots(&previous, &current, util::now_unix()) {
+            on_change(change);
+        }
+        previous = current;
+    }
+}
+
+pub struct WindowsRegistry;
+
+impl RegistrySource for WindowsRegistry {
+    fn read_key(&self, key: &WatchedKey) -> Result<KeySnapshot, String> {
+        let (root, _) = registry::parse_hive(&key.hive)?;
+        let reg_key = match RegKey::predef(root).open_subkey_with_flags(&key.path, KEY_READ) {
+            Ok(k) => k,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(format!("Failed to open {}: {}", key_id(key), e)),
+        };
+        let values = reg_key
+            .enum_values()
+            .filter_map(|v| v.ok())
+            .map(|(name, value)| {
+                let (data, _) = registry::render_value(&value, WATCH_VALUE_MAX_BYTES);
+                (
+                    name,
+                    WatchedValue {
+                        value_type: registry::reg_type_name(&value.vtype).to_string(),
+                        data,
+                    },
+                )
+            })
+            .collect();
+        Ok(Some(values))
+    }
+
+    #[cfg(windows)]
+    fn wait_for_change(&self, keys: &[WatchedKey], timeout: Duration) {
+        use winapi::shared::minwindef::{FALSE, TRUE};
+        use winapi::um::handleapi::CloseHandle;
+        use winapi::um::synchapi::{CreateEventW, WaitForMultipleObjects};
+        use winapi::um::winnt::{REG_NOTIFY_CHANGE_LAST_SET, REG_NOTIFY_CHANGE_NAME};
+        use winapi::um::winreg::RegNotifyChangeKeyValue;
+
+        let mut open_keys = Vec::new();
+        let mut events = Vec::new();
+        for key in keys {
+            let root = match registry::parse_hive(&key.hive) {
+                Ok((root, _)) => root,
+                Err(_) => continue,
+            };
+            let reg_key = match RegKey::predef(root).open_subkey_with_flags(&key.path, KEY_NOTIFY) {
+                Ok(k) => k,
+                Err(_) => continue,
+            };
+            unsafe {
+                let event = CreateEventW(std::ptr::null_mut(), TRUE, FALSE, std::ptr::null());
+                if event.is_null() {
+                    continue;
+                }
+                let status = RegNotifyChangeKeyValue(
+                    reg_key.raw_handle() as _,
+                    FALSE,
+                    REG_NOTIFY_CHANGE_NAME | REG_NOTIFY_CHANGE_LAST_SET,
+                    event,
+                    TRUE,
+                );
+                if status != 0 {
+                    CloseHandle(event);
+                    continue;
+                }
+                events.push(event);
+            }
+            open_keys.push(reg_key);
+        }
+
+        if events.is_empty() {
+            std::thread::sleep(timeout);
+            return;
+        }
+
+        unsafe {
+            WaitForMultipleObjects(
+                events.len() as u32,
+                events.as_ptr(),
+                FALSE,
+                timeout.as_millis().min(u32::MAX as u128) as u32,
+            );
+            for event in events {
+                CloseHandle(event);
+            }
+        }
+        drop(open_keys);
+    }
+
+    #[cfg(not(windows))]
+    fn wait_for_change(&self, _keys: &[WatchedKey], timeout: Duration) {
+        std::thread::sleep(timeout);
+    }
+}
+
+fn audit_log_path() -> PathBuf {
+    util::data_dir().join("registry_audit.jsonl")
+}
+
+pub fn append_audit_record(change: &ValueChange) {
+    let path = audit_log_path();
+    if std::fs::metadata(&path)
+        .map(|m| m.len() > AUDIT_MAX_BYTES)
+        .unwrap_or(false)
+    {
+        let _ = std::fs::rename(&path, path.with_extension("jsonl.old"));
+    }
+    if let Ok(line) = serde_json::to_string(change) {
+        if let Ok(mut file) = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+        {
+            let _ = writeln!(file, "{}", line);
+        }
+    }
+}
+
+pub fn read_audit_log(limit: usize) -> Vec<ValueChange> {
+    let raw = std::fs::read_to_string(audit_log_path()).unwrap_or_default();
+    let mut records: Vec<ValueChange> = raw
+        .lines()
+        .rev()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .take(limit)
+        .collect();
+    records.reverse();
+    records
+}
+
+pub fn start<F>(keys: Vec<WatchedKey>, poll_interval: Duration, on_change: F) -> Result<(), String>
+where
+    F: Fn(&ValueChange) + Send + 'static,
+{
+    stop();
+    let stop_flag = Arc::new(AtomicBool::new(false));
+    let changes = Arc::new(Mutex::new(0u64));
+    {
+        let mut watcher = WATCHER
+            .lock()
+            .map_err(|_| "Registry watcher state is poisoned".to_string())?;
+        *watcher = Some(WatcherHandle {
+            stop: stop_flag.clone(),
+            keys: keys.clone(),
+            started_unix: util::now_unix(),
+            changes: changes.clone(),
+        });
+    }
+
+    std::thread::spawn(move || {
+        run_watch_loop(
+            &WindowsRegistry,
+            &keys,
+            &stop_flag,
+            poll_interval,
+            |change| {
+                append_audit_record(&change);
+                if let Ok(mut count) = changes.lock() {
+                    *count += 1;
+                }
+                on_change(&change);
+            },
+        );
+    });
+    Ok(())
+}
+
+pub fn stop() -> bool {
+    let handle = WATCHER.lock().ok().and_then(|mut w| w.take());
+    match handle {
+        Some(h) => {
+            h.stop.store(true, Ordering::SeqCst);
+            true
+        }
+        None => false,
+    }
+}
+
+pub fn status() -> WatchStatus {
+    let watcher = match WATCHER.lock() {
+        Ok(w) => w,
+        Err(_) => {
+            return WatchStatus {
+                running: false,
+                keys: Vec::new(),
+                started_unix: None,
+                changes_detected: 0,
+            }
+        }
+    };
+    match watcher.as_ref() {
+        Some(h) => WatchStatus {
+            running: true,
+            keys: h.keys.clone(),
+            started_unix: Some(h.started_unix),
+            changes_detected: h.changes.lock().map(|c| *c).unwrap_or(0),
+        },
+        None => WatchStatus {
+            running: false,
+            keys: Vec::new(),
+            started_unix: None,
+            changes_detected: 0,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    type State = HashMap<String, Result<KeySnapshot, String>>;
+
+    // Replays a scripted sequence of registry states: each wait_for_change
+    // moves to the next one, and the watch loop is stopped after the last.
+    struct SimulatedRegistry {
+        states: Vec<State>,
+        position: Mutex<usize>,
+        stop: Arc<AtomicBool>,
+    }
+
+    impl RegistrySource for SimulatedRegistry {
+        fn read_key(&self, key: &WatchedKey) -> Result<KeySnapshot, String> {
+            let position = *self.position.lock().unwrap();
+            self.states[position]
+                .get(&key_id(key))
+                .cloned()
+                .unwrap_or(Ok(None))
+        }
+
+        fn wait_for_change(&self, _keys: &[WatchedKey], _timeout: Duration) {
+            let mut position = self.position.lock().unwrap();
+            if *position + 1 < self.states.len() {
+                *position += 1;
+            } else {
+                self.stop.store(true, Ordering::SeqCst);
+            }
+        }
+    }
+
+    fn value(data: u32) -> WatchedValue {
+        WatchedValue {
+            value_type: "REG_DWORD".to_string(),
+            data: serde_json::json!(data),
+        }
+    }
+
+    fn key(path: &str, value_names: Option<&[&str]>) -> WatchedKey {
+        WatchedKey {
+            hive: "HKLM".to_string(),
+            path: path.to_string(),
+            value_names: value_names.map(|names| names.iter().map(|n| n.to_string()).collect()),
+        }
+    }
+
+    fn values(pairs: &[(&str, u32)]) -> Result<KeySnapshot, String> {
+        Ok(Some(
+            pairs
+                .iter()
+                .map(|(name, data)| (name.to_string(), value(*data)))
+                .collect(),
+        ))
+    }
+
+    fn state(entries: Vec<(&str, Result<KeySnapshot, String>)>) -> State {
+        entries
+            .into_iter()
+            .map(|(path, values)| (format!("HKLM\\{}", path), values))
+            .collect()
+    }
+
+    fn watch(states: Vec<State>, keys: &[WatchedKey]) -> Vec<(String, Option<String>, String)> {
+        let stop = Arc::new(AtomicBool::new(false));
+        let source = SimulatedRegistry {
+            states,
+            position: Mutex::new(0),
+            stop: stop.clone(),
+        };
+        let mut changes = Vec::new();
+        run_watch_loop(&source, keys, &stop, Duration::ZERO, |change| {
+            changes.push((change.path, change.value_name, change.kind))
+        });
+        changes
+    }
+
+    fn change(path: &str, name: Option<&str>, kind: &str) -> (String, Option<String>, String) {
+        (path.to_string(), name.map(str::to_string), kind.to_string())
+    }
+
+    #[test]
+    fn reports_value_and_key_changes() {
+        let keys = [key(r"SOFTWARE\Policy", None), key(r"SOFTWARE\Later", None)];
+        let states = vec![
+            state(vec![
+                (r"SOFTWARE\Policy", values(&[("A", 1), ("B", 2)])),
+                (r"SOFTWARE\Later", Ok(None)),
+            ]),
+            state(vec![
+                (r"SOFTWARE\Policy", values(&[("A", 1), ("B", 3), ("C", 4)])),
+                (r"SOFTWARE\Later", values(&[("X", 1)])),
+            ]),
+            state(vec![
+                (r"SOFTWARE\Policy", Ok(None)),
+                (r"SOFTWARE\Later", values(&[("X", 1)])),
+            ]),
+        ];
+        assert_eq!(
+            watch(states, &keys),
+            vec![
+                change(r"SOFTWARE\Later", None, "key_created"),
+                change(r"SOFTWARE\Later", Some("X"), "added"),
+                change(r"SOFTWARE\Policy", Some("B"), "modified"),
+                change(r"SOFTWARE\Policy", Some("C"), "added"),
+                change(r"SOFTWARE\Policy", None, "key_deleted"),
+                change(r"SOFTWARE\Policy", Some("A"), "removed"),
+                change(r"SOFTWARE\Policy", Some("B"), "removed"),
+                change(r"SOFTWARE\Policy", Some("C"), "removed"),
+            ]
+        );
+    }
+
+    #[test]
+    fn only_watched_values_are_reported() {
+        let keys = [key(r"SOFTWARE\Policy", Some(&["enabled"]))];
+        let states = vec![
+            state(vec![(
+                r"SOFTWARE\Policy",
+                values(&[("Enabled", 1), ("Noise", 1)]),
+            )]),
+            state(vec![(
+                r"SOFTWARE\Policy",
+                values(&[("Enabled", 0), ("Noise", 2)]),
+            )]),
+        ];
+        assert_eq!(
+            watch(states, &keys),
+            vec![change(r"SOFTWARE\Policy", Some("Enabled"), "modified")]
+        );
+    }
+
+    #[test]
+    fn unreadable_keys_do_not_produce_spurious_changes() {
+        let keys = [key(r"SOFTWARE\Locked", None)];
+        let states = vec![
+            state(vec![(r"SOFTWARE\Locked", values(&[("A", 1)]))]),
+            state(vec![(
+                r"SOFTWARE\Locked",
+                Err("Access is denied".to_string()),
+            )]),
+            state(vec![(r"SOFTWARE\Locked", values(&[("A", 1)]))]),
+            state(vec![(r"SOFTWARE\Locked", values(&[("A", 2)]))]),
+        ];
+        assert_eq!(
+            watch(states, &keys),
+            vec![change(r"SOFTWARE\Locked", Some("A"), "modified")]
+        );
+    }
+
+    #[test]
+    fn normalizes_and_dedupes_keys() {
+        let keys = normalize_keys(vec![
+            WatchedKey {
+                hive: "HKEY_LOCAL_MACHINE".to_string(),
+                path: "/SOFTWARE/Policy/".to_string(),
+                value_names: None,
+            },
+            key(r"SOFTWARE\Policy", None),
+        ])
+        .unwrap();
+        assert_eq!(keys, vec![key(r"SOFTWARE\Policy", None)]);
+        assert!(normalize_keys(Vec::new()).is_err());
+        assert!(normalize_keys(vec![WatchedKey {
+            hive: "HKXX".to_string(),
+            path: "a".to_string(),
+            value_names: None,
+        }])
+        .is_err());
+    }
+}
@@ -0,0 +1,273 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use winreg::enums::*;
+use winreg::RegKey;
+
+use crate::services::{ServiceInfo, StartType};
+
+const BASELINE_DATA: &str = include_str!("../data/service_baselines.json");
+
+#[derive(Debug, Clone, Deserialize)]
+struct BaselineFile {
+    per_user_templates: BTreeMap<String, String>,
+    baselines: Vec<BaselineEntry>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct BaselineEntry {
+    id: String,
+    name: String,
+    min_build: u32,
+    max_build: Option<u32>,
+    #[serde(default)]
+    extends: Option<String>,
+    #[serde(default)]
+    remove: Vec<String>,
+    services: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Baseline {
+    pub id: String,
+    pub name: String,
+    services: HashMap<String, (String, StartType)>,
+    templates: HashMap<String, (String, StartType)>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ServiceDeviation {
+    pub name: String,
+    pub display_name: Option<String>,
+    pub kind: String,
+    pub expected: Option<StartType>,
+    pub actual: Option<StartType>,
+    pub binary_path: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BaselineReport {
+    pub baseline_id: String,
+    pub baseline_name: String,
+    pub build: u32,
+    pub checked: usize,
+    pub deviations: Vec<ServiceDeviation>,
+}
+
+fn parse_start(raw: &str) -> StartType {
+    StartType::parse(&serde_json::Value::String(raw.to_string()), false)
+}
+
+fn index(map: &BTreeMap<String, String>) -> HashMap<String, (String, StartType)> {
+    map.iter()
+        .map(|(name, start)| (name.to_lowercase(), (name.clone(), parse_start(start))))
+        .collect()
+}
+
+fn resolve_services(
+    entries: &[BaselineEntry],
+    entry: &BaselineEntry,
+    depth: usize,
+) -> Result<BTreeMap<String, String>, String> {
+    if depth > 8 {
+        return Err(format!("Baseline {} extends too deeply", entry.id));
+    }
+    let mut services = match &entry.extends {
+        Some(parent_id) => {
+            let parent = entries
+                .iter()
+                .find(|e| &e.id == parent_id)
+                .ok_or_else(|| format!("Unknown parent baseline: {}", parent_id))?;
+            resolve_services(entries, parent, depth + 1)?
+        }
+        None => BTreeMap::new(),
+    };
+    for name in &entry.remove {
+        services.remove(name);
+    }
+    services.extend(entry.services.clone());
+    Ok(services)
+}
+
+pub fn load_baseline(build: u32) -> Result<Baseline, String> {
+    let file: BaselineFile = serde_json::from_str(BASELINE_DATA)
+        .map_err(|e| format!("Corrupt service baseline data: {}", e))?;
+    let entry = file
+        .baselines
+        .iter()
+        .filter(|b| build >= b.min_build && b.max_build.is_none_or(|max| build <= max))
+        .max_by_key(|b| b.min_build)
+        .ok_or_else(|| format!("No service baseline for Windows build {}", build))?;
+    Ok(Baseline {
+        id: entry.id.clone(),
+        name: entry.name.clone(),
+        services: index(&resolve_services(&file.baselines, entry, 0)?),
+        templates: index(&file.per_user_templates),
+    })
+}
+
+pub fn current_build() -> Result<u32, String> {
+    RegKey::predef(HKEY_LOCAL_MACHINE)
+        .open_subkey_with_flags("SOFTWARE\\Microsoft\\Windows NT\\CurrentVersion", KEY_READ)
+        .and_then(|k| k.get_value::<String, _>("CurrentBuildNumber"))
+        .map_err(|e| format!("Failed to read Windows build number: {}", e))?
+        .trim()
+        .parse()
+        .map_err(|e| format!("Invalid Windows build number: {}", e))
+}
+
+// Per-user services are created as "<template>_<luid hex>" for each session.
+fn template_name(name: &str) -> Option<&str> {
+    let (base, suffix) = name.rsplit_once('_')?;
+    (suffix.len() >= 4 && suffix.chars().all(|c| c.is_ascii_hexdigit())).then_some(base)
+}
+
+fn is_system_binary(path: Option<&str>) -> bool {
+    let path = match path {
+        Some(p) => p.trim_start_matches('"').to_lowercase(),
+        None => return false,
+    };
+    path.starts_with("c:\\windows\\")
+        || path.starts_with("%systemroot%")
+        || path.starts_with("\\systemroot\\")
+        || path.starts_with("system32\\")
+}
+
+impl Baseline {
+    pub fn expected(&self, name: &str) -> Option<StartType> {
+        let key = name.to_lowercase();
+        if let Some((_, start)) = self.services.get(&key) {
+            return Some(*start);
+        }
+        if let Some((_, start)) = self.templates.get(&key) {
+            return Some(*start);
+        }
+        template_name(name)
+            .and_then(|base| self.templates.get(&base.to_lowercase()))
+            .map(|(_, start)| *start)
+    }
+
+    pub fn compare(&self, build: u32, current: &[ServiceInfo]) -> BaselineReport {
+        let mut deviations = Vec::new();
+        let mut seen = HashSet::new();
+
+        for service in current {
+            let key = service.name.to_lowercase();
+            seen.insert(key.clone());
+            if let Some(base) = template_name(&service.name) {
+                seen.insert(base.to_lowercase());
+            }
+            let kind = match self.expected(&service.name) {
+                Some(expected) if expected == service.start_type => continue,
+                Some(_) => "start_type_changed",
+                None if is_system_binary(service.binary_path.as_deref()) => "unlisted",
+                None => "third_party",
+            };
+            deviations.push(ServiceDeviation {
+                name: service.name.clone(),
+                display_name: Some(service.display_name.clone()),
+                kind: kind.to_string(),
+                expected: self.expected(&service.name),
+                actual: Some(service.start_type),
+                binary_path: service.binary_path.clone(),
+            });
+        }
+
+        for (key, (name, start)) in &self.services {
+            if !seen.contains(key) {
+                deviations.push(ServiceDeviation {
+                    name: name.clone(),
+                    display_name: None,
+                    kind: "missing".to_string(),
+                    expected: Some(*start),
+                    actual: None,
+                    binary_path: None,
+                });
+            }
+        }
+
+        deviations.sort_by(|a, b| {
+            a.kind
+                .cmp(&b.kind)
+                .then(a.name.to_lowercase().cmp(&b.name.to_lowercase()))
+        });
+        BaselineReport {
+            baseline_id: self.id.clone(),
+            baseline_name: self.name.clone(),
+            build,
+            checked: current.len(),
+            deviations,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::{ServiceStatus, ServiceType};
+
+    fn service(name: &str, start_type: StartType) -> ServiceInfo {
+        ServiceInfo {
+            name: name.to_string(),
+            display_name: name.to_string(),
+            status: ServiceStatus::Stopped,
+            start_type,
+            service_type: ServiceType::OwnProcess,
+            binary_path: Some("C:\\Windows\\system32\\svchost.exe -k LocalService".to_string()),
+            account: None,
+            process_id: None,
+            trigger_start: false,
+        }
+    }
+
+    #[test]
+    fn per_user_services_resolve_through_templates() {
+        let baseline = load_baseline(22631).unwrap();
+        assert_eq!(baseline.id, "win11");
+        for name in [
+            "NPSMSvc",
+            "P9RdrService",
+            "PenService",
+            "webthreatdefusersvc",
+        ] {
+            assert!(
+                !baseline.services.contains_key(&name.to_lowercase()),
+                "{}",
+                name
+            );
+            assert_eq!(
+                baseline.expected(&format!("{}_5a3c1", name)),
+                Some(StartType::Manual)
+            );
+        }
+        assert_eq!(baseline.expected("SgrmBroker"), None);
+        assert_eq!(load_baseline(19045).unwrap().id, "win10");
+        assert!(load_baseline(9600).is_err());
+    }
+
+    #[test]
+    fn compare_does_not_report_template_instances_missing() {
+        let baseline = load_baseline(22631).unwrap();
+        let report = baseline.compare(
+            22631,
+            &[
+                service("PenService_5a3c1", StartType::Manual),
+                service("webthreatdefusersvc_5a3c1", StartType::Disabled),
+            ],
+        );
+        let kinds = |name: &str| {
+            report
+                .deviations
+                .iter()
+                .filter(|d| d.name.eq_ignore_ascii_case(name))
+                .map(|d| d.kind.as_str())
+                .collect::<Vec<_>>()
+        };
+        assert!(kinds("PenService_5a3c1").is_empty());
+        assert!(kinds("PenService").is_empty());
+        assert_eq!(
+            kinds("webthreatdefusersvc_5a3c1"),
+            vec!["start_type_changed"]
+        );
+        assert_eq!(kinds("webthreatdefsvc"), vec!["missing"]);
+    }
+}
@@ -0,0 +1,382 @@
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{BTreeSet, HashMap, VecDeque};
+
+use crate::services;
+
+pub const PROTECTED_SERVICES: &[&str] = &[
+    "AudioEndpointBuilder",
+    "Audiosrv",
+    "BFE",
+    "BrokerInfrastructure",
+    "CoreMessagingRegistrar",
+    "CryptSvc",
+    "DcomLaunch",
+    "Dhcp",
+    "Dnscache",
+    "EventLog",
+    "gpsvc",
+    "LSM",
+    "mpssvc",
+    "nsi",
+    "PlugPlay",
+    "Power",
+    "ProfSvc",
+    "RpcEptMapper",
+    "RpcSs",
+    "SamSs",
+    "Schedule",
+    "SecurityHealthService",
+    "StateRepository",
+    "SystemEventsBroker",
+    "TrustedInstaller",
+    "UserManager",
+    "WinDefend",
+    "Winmgmt",
+    "wscsvc",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operation {
+    Start,
+    Stop,
+    Disable,
+}
+
+impl Operation {
+    pub fn parse(raw: &str) -> Result<Self, String> {
+        match raw.trim().to_lowercase().as_str() {
+            "start" => Ok(Operation::Start),
+            "stop" => Ok(Operation::Stop),
+            "disable" => Ok(Operation::Disable),
+            _ => Err("Invalid operation. Must be start, stop or disable".to_string()),
+        }
+    }
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            Operation::Start => "start",
+            Operation::Stop => "stop",
+            Operation::Disable => "disable",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ImpactReport {
+    pub operation: String,
+    pub services: Vec<String>,
+    pub affected: Vec<String>,
+    pub protected: Vec<String>,
+    pub order: Vec<String>,
+    pub blocked: bool,
+    pub warnings: Vec<String>,
+}
+
+#[derive(Debug, Default)]
+pub struct DependencyGraph {
+    names: HashMap<String, String>,
+    required: HashMap<String, Vec<String>>,
+    dependents: HashMap<String, Vec<String>>,
+}
+
+pub fn is_protected(name: &str) -> bool {
+    PROTECTED_SERVICES
+        .iter()
+        .any(|p| p.eq_ignore_ascii_case(name))
+}
+
+impl DependencyGraph {
+    pub fn from_edges(edges: &[(String, Vec<String>)]) -> Self {
+        let mut graph = DependencyGraph::default();
+        for (name, required) in edges {
+            let key = graph.intern(name);
+            for dep in required {
+                let dep_key = graph.intern(dep);
+                graph
+                    .required
+                    .entry(key.clone())
+                    .or_default()
+                    .push(dep_key.clone());
+                graph
+                    .dependents
+                    .entry(dep_key)
+                    .or_default()
+                    .push(key.clone());
+            }
+        }
+        graph
+    }
+
+    fn intern(&mut self, name: &str) -> String {
+        let key = name.to_lowercase();
+        self.names
+            .entry(key.clone())
+            .or_insert_with(|| name.to_string());
+        key
+    }
+
+    fn display(&self, key: &str) -> String {
+        self.names
+            .get(key)
+            .cloned()
+            .unwrap_or_else(|| key.to_string())
+    }
+
+    fn edges(&self, operation: Operation) -> &HashMap<String, Vec<String>> {
+        match operation {
+            Operation::Start => &self.required,
+            Operation::Stop | Operation::Disable => &self.dependents,
+        }
+    }
+
+    // Stopping or disabling a service affects everything that depends on it;
+    // starting one pulls in everything it requires.
+    fn closure(&self, roots: &[String], operation: Operation) -> BTreeSet<String> {
+        let edges = self.edges(operation);
+        let mut seen = BTreeSet::new();
+        let mut queue: VecDeque<String> = roots.iter().map(|r| r.to_lowercase()).collect();
+        while let Some(key) = queue.pop_front() {
+            if !seen.insert(key.clone()) {
+                continue;
+            }
+            for next in edges.get(&key).into_iter().flatten() {
+                if !seen.contains(next) {
+                    queue.push_back(next.clone());
+                }
+            }
+        }
+        seen
+    }
+
+    pub fn order(&self, names: &[String], operation: Operation) -> Result<Vec<String>, String> {
+        let set: BTreeSet<String> = names.iter().map(|n| n.to_lowercase()).collect();
+        let edges = self.edges(operation);
+        let mut incoming: HashMap<&str, usize> = set.iter().map(|k| (k.as_str(), 0)).collect();
+        for key in &set {
+            for next in edges.get(key).into_iter().flatten() {
+                if set.contains(next) {
+                    *incoming.entry(key.as_str()).or_default() += 1;
+                }
+            }
+        }
+
+        let reverse = match operation {
+            Operation::Start => &self.dependents,
+            Operation::Stop | Operation::Disable => &self.required,
+        };
+        let mut ready: VecDeque<&str> = set
+            .iter()
+            .filter(|k| incoming.get(k.as_str()) == Some(&0))
+            .map(|k| k.as_str())
+            .collect();
+        let mut ordered = Vec::with_capacity(set.len());
+        while let Some(key) = ready.pop_front() {
+            ordered.push(self.display(key));
+            for next in reverse.get(key).into_iter().flatten() {
+                if let Some(count) = incoming.get_mut(next.as_str()) {
+                    *count -= 1;
+                    if *count == 0 {
+                        ready.push_back(next.as_str());
+                    }
+                }
+            }
+        }
+
+        if ordered.len() != set.len() {
+            return Err("Service dependency cycle detected".to_string());
+        }
+        Ok(ordered)
+    }
+
+    pub fn impact(&self, names: &[String], operation: Operation) -> Result<ImpactReport, String> {
+        let requested: BTreeSet<String> = names.iter().map(|n| n.to_lowercase()).collect();
+        let closure = self.closure(names, operation);
+        let affected: Vec<String> = closure
+            .iter()
+            .filter(|k| !requested.contains(*k))
+            .map(|k| self.display(k))
+            .collect();
+        let all: Vec<String> = closure.iter().map(|k| self.display(k)).collect();
+        let order = self.order(&all, operation)?;
+
+        let mut warnings = Vec::new();
+        let protected: Vec<String> = if operation == Operation::Start {
+            Vec::new()
+        } else {
+            all.iter().filter(|n| is_protected(n)).cloned().collect()
+        };
+        if !protected.is_empty() {
+            warnings.push(format!(
+                "Protected services would be affected: {}",
+                protected.join(", ")
+            ));
+        }
+        if !affected.is_empty() {
+            warnings.push(match operation {
+                Operation::Start => {
+                    format!("Required services will also start: {}", affected.join(", "))
+                }
+                Operation::Stop => {
+                    format!("Dependent services will also stop: {}", affected.join(", "))
+                }
+                Operation::Disable => format!(
+                    "Dependent services will fail to start: {}",
+                    affected.join(", ")
+                ),
+            });
+        }
+
+        Ok(ImpactReport {
+            operation: operation.as_str().to_string(),
+            services: names.to_vec(),
+            affected,
+            blocked: !protected.is_empty(),
+            protected,
+            order,
+            warnings,
+        })
+    }
+}
+
+pub fn parse_dependency_edges(raw: &str) -> Result<Vec<(String, Vec<String>)>, String> {
+    Ok(services::json_items(raw)?
+        .iter()
+        .filter_map(|item| {
+            let name = item.get("Name")?.as_str()?.to_string();
+            let required = match item.get("RequiredServices") {
+                Some(Value::Array(items)) => items
+                    .iter()
+                    .filter_map(|v| v.as_str().map(|s| s.to_string()))
+                    .collect(),
+                Some(Value::String(s)) => vec![s.clone()],
+                _ => Vec::new(),
+            };
+            Some((name, required))
+        })
+        .collect())
+}
+
+pub fn dependencies_script() -> &'static str {
+    r#"
+        Get-Service | ForEach-Object {
+            [pscustomobject]@{
+                Name = $_.Name
+                RequiredServices = @($_.ServicesDependedOn | ForEach-Object { $_.Name })
+            }
+        } | ConvertTo-Json -Compress -Depth 3
+    "#
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn graph() -> DependencyGraph {
+        let edge = |name: &str, required: &[&str]| {
+            (
+                name.to_string(),
+                required.iter().map(|r| r.to_string()).collect::<Vec<_>>(),
+            )
+        };
+        DependencyGraph::from_edges(&[
+            edge("LanmanServer", &["SamSs", "Srv2"]),
+            edge("LanmanWorkstation", &["Bowser", "MRxSmb20", "NSI"]),
+            edge("Browser", &["LanmanServer", "LanmanWorkstation"]),
+            edge("MSSQL$SQLEXPRESS", &[]),
+        ])
+    }
+
+    fn names(raw: &[&str]) -> Vec<String> {
+        raw.iter().map(|n| n.to_string()).collect()
+    }
+
+    fn position(order: &[String], name: &str) -> usize {
+        order.iter().position(|n| n == name).unwrap()
+    }
+
+    #[test]
+    fn closure_follows_operation_direction() {
+        let graph = graph();
+        let start = graph.closure(&names(&["lanmanserver"]), Operation::Start);
+        assert_eq!(
+            start.into_iter().collect::<Vec<_>>(),
+            names(&["lanmanserver", "samss", "srv2"])
+        );
+        let stop = graph.closure(&names(&["Srv2"]), Operation::Stop);
+        assert_eq!(
+            stop.into_iter().collect::<Vec<_>>(),
+            names(&["browser", "lanmanserver", "srv2"])
+        );
+        assert_eq!(
+            graph
+                .closure(&names(&["MSSQL$SQLEXPRESS"]), Operation::Disable)
+                .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn start_runs_requirements_first_and_stop_dependents_first() {
+        let graph = graph();
+        let all = names(&["Browser", "LanmanServer", "SamSs", "Srv2", "NSI"]);
+
+        let start = graph.order(&all, Operation::Start).unwrap();
+        assert_eq!(start.len(), all.len());
+        assert!(position(&start, "SamSs") < position(&start, "LanmanServer"));
+        assert!(position(&start, "Srv2") < position(&start, "LanmanServer"));
+        assert!(position(&start, "LanmanServer") < position(&start, "Browser"));
+
+        let stop = graph.order(&all, Operation::Stop).unwrap();
+        assert!(position(&stop, "Browser") < position(&stop, "LanmanServer"));
+        assert!(position(&stop, "LanmanServer") < position(&stop, "SamSs"));
+        assert!(position(&stop, "LanmanServer") < position(&stop, "Srv2"));
+    }
+
+    #[test]
+    fn cycles_are_rejected() {
+        let graph = DependencyGraph::from_edges(&[
+            ("A".to_string(), names(&["B"])),
+            ("B".to_string(), names(&["C"])),
+            ("C".to_string(), names(&["A"])),
+        ]);
+        let all = names(&["A", "B", "C"]);
+        assert!(graph.order(&all, Operation::Start).is_err());
+        assert!(graph.order(&all, Operation::Stop).is_err());
+        assert_eq!(
+            graph.impact(&names(&["A"]), Operation::Stop).unwrap_err(),
+            "Service dependency cycle detected"
+        );
+    }
+
+    #[test]
+    fn impact_summarises_affected_and_protected_services() {
+        let graph = graph();
+
+        let stop = graph.impact(&names(&["Srv2"]), Operation::Stop).unwrap();
+        assert_eq!(stop.operation, "stop");
+        assert_eq!(stop.affected, names(&["Browser", "LanmanServer"]));
+        assert_eq!(stop.order, names(&["Browser", "LanmanServer", "Srv2"]));
+        assert!(!stop.blocked);
+        assert_eq!(
+            stop.warnings,
+            vec!["Dependent services will also stop: Browser, LanmanServer".to_string()]
+        );
+
+        let disable = graph
+            .impact(&names(&["SamSs"]), Operation::Disable)
+            .unwrap();
+        assert!(disable.blocked);
+        assert_eq!(disable.protected, names(&["SamSs"]));
+        assert!(disable.warnings[0].starts_with("Protected services would be affected"));
+        assert!(disable.warnings[1].starts_with("Dependent services will fail to start"));
+
+        let start = graph
+            .impact(&names(&["LanmanServer"]), Operation::Start)
+            .unwrap();
+        assert!(!start.blocked);
+        assert!(start.protected.is_empty());
+        assert_eq!(start.affected, names(&["SamSs", "Srv2"]));
+        assert_eq!(start.order.last().map(String::as_str), Some("LanmanServer"));
+    }
+}
@@ -0,0 +1,430 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use winreg::enums::*;
+use winreg::RegKey;
+
+use crate::util;
+
+const MAX_ACTIONS: usize = 16;
+const MAX_DELAY_MS: u32 = 24 * 60 * 60 * 1000;
+const MAX_COMMAND_LEN: usize = 1024;
+const HEADER_LEN: usize = 20;
+const DEFAULTS_DATA: &str = include_str!("../data/service_recovery_defaults.json");
+
+lazy_static::lazy_static! {
+    static ref ORIGINALS_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FailureActionType {
+    None,
+    Restart,
+    Reboot,
+    RunCommand,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct FailureAction {
+    pub action_type: FailureActionType,
+    pub delay_ms: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct FailureActions {
+    pub reset_period_secs: u32,
+    pub actions: Vec<FailureAction>,
+    #[serde(default)]
+    pub command: Option<String>,
+    #[serde(default)]
+    pub on_non_crash_failures: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct DefaultsFile {
+    services: BTreeMap<String, DefaultEntry>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct DefaultEntry {
+    reset: u32,
+    actions: Vec<String>,
+}
+
+impl FailureActionType {
+    fn from_code(code: u32) -> Self {
+        match code {
+            1 => FailureActionType::Restart,
+            2 => FailureActionType::Reboot,
+            3 => FailureActionType::RunCommand,
+            _ => FailureActionType::None,
+        }
+    }
+
+    fn sc_name(&self) -> &'static str {
+        match self {
+            FailureActionType::None => "\"\"",
+            FailureActionType::Restart => "restart",
+            FailureActionType::Reboot => "reboot",
+            FailureActionType::RunCommand => "run",
+        }
+    }
+}
+
+pub fn service_key_path(service: &str) -> String {
+    format!("SYSTEM\\CurrentControlSet\\Services\\{}", service)
+}
+
+fn dword_at(bytes: &[u8], offset: usize) -> Option<u32> {
+    bytes
+        .get(offset..offset + 4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+// The SCM stores SERVICE_FAILURE_ACTIONS with the pointer fields zeroed:
+// reset period, reboot message, command, action count, actions offset,
+// followed by (type, delay) pairs.
+pub fn parse_failure_blob(bytes: &[u8]) -> Option<(u32, Vec<FailureAction>)> {
+    let reset = dword_at(bytes, 0)?;
+    let count = dword_at(bytes, 12)? as usize;
+    let actions = (0..count.min(MAX_ACTIONS))
+        .map_while(|i| {
+            let offset = HEADER_LEN + i * 8;
+            Some(FailureAction {
+                action_type: FailureActionType::from_code(dword_at(bytes, offset)?),
+                delay_ms: dword_at(bytes, offset + 4)?,
+            })
+        })
+        .collect();
+    Some((reset, actions))
+}
+
+pub fn read_failure_actions(service: &str) -> Result<FailureActions, String> {
+    let key = RegKey::predef(HKEY_LOCAL_MACHINE)
+        .open_subkey_with_flags(service_key_path(service), KEY_READ)
+        .map_err(|_| format!("Service not found: {}", service))?;
+    let (reset_period_secs, actions) = key
+        .get_raw_value("FailureActions")
+        .ok()
+        .and_then(|v| parse_failure_blob(&v.bytes))
+        .unwrap_or((0, Vec::new()));
+    let command = key
+        .get_value::<String, _>("FailureCommand")
+        .ok()
+        .filter(|c| !c.trim().is_empty());
+    let on_non_crash_failures = key
+        .get_value::<u32, _>("FailureActionsOnNonCrashFailures")
+        .map(|v| v != 0)
+        .unwrap_or(false);
+    Ok(FailureActions {
+        reset_period_secs,
+        actions,
+        command,
+        on_non_crash_failures,
+    })
+}
+
+pub fn validate(config: &FailureActions) -> Result<(), String> {
+    if config.actions.len() > MAX_ACTIONS {
+        return Err(format!("Too many failure actions (max {})", MAX_ACTIONS));
+    }
+    if let Some(action) = config.actions.iter().find(|a| a.delay_ms > MAX_DELAY_MS) {
+        return Err(format!(
+            "Failure action delay {} ms exceeds 24 hours",
+            action.delay_ms
+        ));
+    }
+    let needs_command = config
+        .actions
+        .iter()
+        .any(|a| a.action_type == FailureActionType::RunCommand);
+    match config.command.as_deref().map(str::trim) {
+        Some(command) if !command.is_empty() => {
+            if command.len() > MAX_COMMAND_LEN {
+                return Err("Failure command is too long".to_string());
+            }
+            if command
+                .chars()
+                .any(|c| matches!(c, '"' | '`' | '$' | '%') || c.is_control())
+            {
+                return Err("Failure command contains invalid characters".to_string());
+            }
+        }
+        _ if needs_command => {
+            return Err("A command is required for run-program failure actions".to_string())
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+fn originals_path() -> PathBuf {
+    util::data_dir().join("service_recovery.json")
+}
+
+fn load_originals() -> BTreeMap<String, FailureActions> {
+    std::fs::read_to_string(originals_path())
+        .ok()
+        .and_then(|raw| serde_json::from_str(&raw).ok())
+        .unwrap_or_default()
+}
+
+// Only the first settings seen for a service are kept: those are what was
+// configured before this app changed anything.
+fn record_original(
+    originals: &mut BTreeMap<String, FailureActions>,
+    service: &str,
+    current: FailureActions,
+) -> bool {
+    let key = service.to_lowercase();
+    if originals.contains_key(&key) {
+        return false;
+    }
+    originals.insert(key, current);
+    true
+}
+
+pub fn remember_original(service: &str) -> Result<(), String> {
+    let current = read_failure_actions(service)?;
+    let _guard = ORIGINALS_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut originals = load_originals();
+    if !record_original(&mut originals, service, current) {
+        return Ok(());
+    }
+    let raw = serde_json::to_string_pretty(&originals)
+        .map_err(|e| format!("Failed to serialize recovery settings: {}", e))?;
+    std::fs::write(originals_path(), raw)
+        .map_err(|e| format!("Failed to save original recovery settings: {}", e))
+}
+
+fn original_in(
+    originals: &BTreeMap<String, FailureActions>,
+    service: &str,
+) -> Result<FailureActions, String> {
+    originals
+        .get(&service.to_lowercase())
+        .cloned()
+        .ok_or_else(|| {
+            format!(
+                "No original recovery settings were recorded for {}; set them explicitly instead",
+                service
+            )
+        })
+}
+
+pub fn original(service: &str) -> Result<FailureActions, String> {
+    let _guard = ORIGINALS_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    original_in(&load_originals(), service)
+}
+
+fn parse_default_action(raw: &str) -> Result<FailureAction, String> {
+    let (kind, delay) = raw
+        .split_once('/')
+        .ok_or_else(|| format!("Invalid default failure action: {}", raw))?;
+    let action_type = match kind {
+        "none" => FailureActionType::None,
+        "restart" => FailureActionType::Restart,
+        "reboot" => FailureActionType::Reboot,
+        _ => return Err(format!("Invalid default failure action: {}", raw)),
+    };
+    let delay_ms = delay
+        .parse()
+        .map_err(|_| format!("Invalid default failure action: {}", raw))?;
+    Ok(FailureAction {
+        action_type,
+        delay_ms,
+    })
+}
+
+fn load_defaults() -> Result<HashMap<String, FailureActions>, String> {
+    let file: DefaultsFile = serde_json::from_str(DEFAULTS_DATA)
+        .map_err(|e| format!("Corrupt recovery defaults data: {}", e))?;
+    file.services
+        .iter()
+        .map(|(name, entry)| {
+            let actions = entry
+                .actions
+                .iter()
+                .map(|a| parse_default_action(a))
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok((
+                name.to_lowercase(),
+                FailureActions {
+                    reset_period_secs: entry.reset,
+                    actions,
+                    command: None,
+                    on_non_crash_failures: false,
+                },
+            ))
+        })
+        .collect()
+}
+
+// Services Windows ships without recovery actions are not listed in the
+// defaults data, so built-in services missing from it reset to no actions.
+pub fn windows_default(service: &str, built_in: bool) -> Result<FailureActions, String> {
+    match load_defaults()?.remove(&service.to_lowercase()) {
+        Some(defaults) => Ok(defaults),
+        None if built_in => Ok(FailureActions {
+            reset_period_secs: 0,
+            actions: Vec::new(),
+            command: None,
+            on_non_crash_failures: false,
+        }),
+        None => Err(format!(
+            "{} is not a Windows service; no default recovery settings are known",
+            service
+        )),
+    }
+}
+
+pub fn apply_script(service: &str, config: &FailureActions) -> String {
+    let actions = if config.actions.is_empty() {
+        "\"\"/0".to_string()
+    } else {
+        config
+            .actions
+            .iter()
+            .map(|a| format!("{}/{}", a.action_type.sc_name(), a.delay_ms))
+            .collect::<Vec<_>>()
+            .join("/")
+    };
+    let command = config
+        .command
+        .as_deref()
+        .map(str::trim)
+        .filter(|c| !c.is_empty())
+        .unwrap_or("");
+    format!(
+        r#"
+        $out = & sc.exe --% failure "{service}" reset= {reset} actions= {actions} command= "{command}"
+        if ($LASTEXITCODE -ne 0) {{ throw ($out | Out-String).Trim() }}
+        $out = & sc.exe failureflag "{service}" {flag} 2>&1
+        if ($LASTEXITCODE -ne 0) {{ throw ($out | Out-String).Trim() }}
+        "Failure actions updated for {service}"
+        "#,
+        service = service,
+        reset = config.reset_period_secs,
+        actions = actions,
+        command = command,
+        flag = if config.on_non_crash_failures { 1 } else { 0 }
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn restart_twice() -> FailureActions {
+        FailureActions {
+            reset_period_secs: 86_400,
+            actions: vec![
+                FailureAction {
+                    action_type: FailureActionType::Restart,
+                    delay_ms: 60_000,
+                },
+                FailureAction {
+                    action_type: FailureActionType::Restart,
+                    delay_ms: 60_000,
+                },
+            ],
+            command: None,
+            on_non_crash_failures: false,
+        }
+    }
+
+    fn blob(reset: u32, actions: &[(u32, u32)]) -> Vec<u8> {
+        let mut out = Vec::new();
+        for dword in [reset, 0, 0, actions.len() as u32, 0] {
+            out.extend_from_slice(&dword.to_le_bytes());
+        }
+        for (kind, delay) in actions {
+            out.extend_from_slice(&kind.to_le_bytes());
+            out.extend_from_slice(&delay.to_le_bytes());
+        }
+        out
+    }
+
+    #[test]
+    fn parse_failure_blob_reads_actions() {
+        let (reset, actions) =
+            parse_failure_blob(&blob(86_400, &[(1, 60_000), (1, 60_000)])).unwrap();
+        assert_eq!(reset, 86_400);
+        assert_eq!(actions, restart_twice().actions);
+        // A count larger than the data stops at the last complete pair.
+        let mut short = blob(0, &[(2, 1000), (3, 0)]);
+        short.truncate(HEADER_LEN + 12);
+        assert_eq!(parse_failure_blob(&short).unwrap().1.len(), 1);
+        assert!(parse_failure_blob(&[0; 8]).is_none());
+    }
+
+    #[test]
+    fn only_the_first_settings_are_recorded() {
+        let mut originals = BTreeMap::new();
+        let none = FailureActions {
+            reset_period_secs: 0,
+            actions: Vec::new(),
+            command: None,
+            on_non_crash_failures: false,
+        };
+        assert!(record_original(&mut originals, "Spooler", none.clone()));
+        assert!(!record_original(&mut originals, "spooler", restart_twice()));
+        assert!(record_original(&mut originals, "wuauserv", restart_twice()));
+
+        assert_eq!(original_in(&originals, "SPOOLER").unwrap(), none);
+        assert_eq!(
+            original_in(&originals, "wuauserv").unwrap(),
+            restart_twice()
+        );
+        assert!(original_in(&originals, "BITS")
+            .unwrap_err()
+            .starts_with("No original recovery settings"));
+    }
+
+    #[test]
+    fn never_touched_service_resets_to_windows_default() {
+        let originals = BTreeMap::new();
+        assert!(original_in(&originals, "Spooler").is_err());
+
+        let spooler = windows_default("spooler", true).unwrap();
+        assert_eq!(spooler.reset_period_secs, 86_400);
+        assert_eq!(&spooler.actions[..2], &restart_twice().actions[..]);
+        assert_eq!(spooler.actions[2].action_type, FailureActionType::None);
+        assert_eq!(
+            windows_default("RpcSs", true).unwrap().actions[0].action_type,
+            FailureActionType::Reboot
+        );
+
+        let fax = windows_default("Fax", true).unwrap();
+        assert!(fax.actions.is_empty());
+        assert_eq!(fax.reset_period_secs, 0);
+        assert!(windows_default("VendorUpdater", false).is_err());
+    }
+
+    #[test]
+    fn windows_defaults_are_valid() {
+        let defaults = load_defaults().unwrap();
+        assert!(defaults.len() > 20);
+        for config in defaults.values() {
+            validate(config).unwrap();
+        }
+        assert!(parse_default_action("run/0").is_err());
+        assert!(parse_default_action("restart").is_err());
+    }
+
+    #[test]
+    fn apply_script_renders_sc_arguments() {
+        let script = apply_script("Spooler", &restart_twice());
+        assert!(script.contains(
+            "failure \"Spooler\" reset= 86400 actions= restart/60000/restart/60000 command= \"\""
+        ));
+        assert!(script.contains("failureflag \"Spooler\" 0"));
+        let cleared = FailureActions {
+            actions: Vec::new(),
+            ..restart_twice()
+        };
+        assert!(apply_script("Spooler", &cleared).contains("actions= \"\"/0"));
+    }
+}
@@ -0,0 +1,398 @@
+use serde::{Deserialize, Serialize};
+use winreg::enums::HKEY_LOCAL_MACHINE;
+use winreg::RegKey;
+
+use crate::registry::{self, BackupKey};
+
+const KNOWN_SUBTYPES: &[(&str, &str)] = &[
+    (
+        "4f27f2de-14e2-430b-a549-7cd48cbc8245",
+        "first_ip_address_arrival",
+    ),
+    (
+        "cc4ba62a-162e-4648-847a-b6bdf993e335",
+        "last_ip_address_removal",
+    ),
+    ("1ce20aba-9851-4421-9430-1ddeb766e809", "domain_join"),
+    ("ddaf516e-58c2-4866-9574-c3b615d42ea1", "domain_leave"),
+    ("b7569e07-8421-4ee0-ad10-86915afdad09", "firewall_port_open"),
+    (
+        "a144ed38-8e12-4de4-9d96-e64740b1a524",
+        "firewall_port_close",
+    ),
+    (
+        "659fcae6-5bdb-4da9-b1ff-ca2a178d46e0",
+        "machine_policy_present",
+    ),
+    (
+        "54fb46c8-f089-464c-b1fd-59d1b62c3b50",
+        "user_policy_present",
+    ),
+    ("1f81d131-3fac-4537-9e0c-7e7b0c2f4b55", "named_pipe_event"),
+    (
+        "bc90d167-9470-4139-a9ba-be0bbbf5b74d",
+        "rpc_interface_event",
+    ),
+];
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ServiceTrigger {
+    pub index: String,
+    pub trigger_type: String,
+    pub action: String,
+    pub subtype: String,
+    pub subtype_name: Option<String>,
+    pub data: Vec<String>,
+}
+
+pub fn trigger_key_path(service: &str) -> String {
+    format!(
+        "SYSTEM\\CurrentControlSet\\Services\\{}\\TriggerInfo",
+        service
+    )
+}
+
+fn trigger_type_name(code: u32) -> &'static str {
+    match code {
+        1 => "device_interface_arrival",
+        2 => "ip_address_availability",
+        3 => "domain_join",
+        4 => "firewall_port_event",
+        5 => "group_policy",
+        6 => "network_endpoint",
+        7 => "custom_system_state_change",
+        20 => "custom",
+        _ => "unknown",
+    }
+}
+
+fn format_guid(bytes: &[u8]) -> String {
+    if bytes.len() < 16 {
+        return String::new();
+    }
+    format!(
+        "{:08x}-{:04x}-{:04x}-{}-{}",
+        u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+        u16::from_le_bytes([bytes[4], bytes[5]]),
+        u16::from_le_bytes([bytes[6], bytes[7]]),
+        bytes[8..10]
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect::<String>(),
+        bytes[10..16]
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect::<String>()
+    )
+}
+
+fn value_bytes(node: &BackupKey, name: &str) -> Option<Vec<u8>> {
+    node.values
+        .iter()
+        .find(|v| v.name.eq_ignore_ascii_case(name))
+        .and_then(|v| v.bytes())
+}
+
+fn value_dword(node: &BackupKey, name: &str) -> Option<u32> {
+    let bytes = value_bytes(node, name)?;
+    (bytes.len() >= 4).then(|| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+fn decode_data_item(data_type: u32, bytes: &[u8]) -> String {
+    match data_type {
+        2 => registry::decode_reg_string(bytes)
+            .split('\0')
+            .filter(|s| !s.is_empty())
+            .collect::<Vec<_>>()
+            .join("; "),
+        3 if !bytes.is_empty() => format!("level {}", bytes[0]),
+        4 | 5 if bytes.len() >= 8 => {
+            let mut raw = [0u8; 8];
+            raw.copy_from_slice(&bytes[..8]);
+            format!(
+                "keyword {} 0x{:x}",
+                if data_type == 4 { "any" } else { "all" },
+                u64::from_le_bytes(raw)
+            )
+        }
+        _ => bytes.iter().map(|b| format!("{:02x}", b)).collect(),
+    }
+}
+
+fn decode_trigger(node: &BackupKey) -> ServiceTrigger {
+    let subtype = value_bytes(node, "Guid")
+        .map(|b| format_guid(&b))
+        .unwrap_or_default();
+    let subtype_name = KNOWN_SUBTYPES
+        .iter()
+        .find(|(guid, _)| guid.eq_ignore_ascii_case(&subtype))
+        .map(|(_, name)| name.to_string());
+    let data = (0..)
+        .map_while(|i| {
+            let bytes = value_bytes(node, &format!("Data{}", i))?;
+            let data_type = value_dword(node, &format!("DataType{}", i)).unwrap_or(1);
+            Some(decode_data_item(data_type, &bytes))
+        })
+        .collect();
+    ServiceTrigger {
+        index: node.name.clone(),
+        trigger_type: trigger_type_name(value_dword(node, "Type").unwrap_or(0)).to_string(),
+        action: match value_dword(node, "Action") {
+            Some(1) => "start",
+            Some(2) => "stop",
+            _ => "unknown",
+        }
+        .to_string(),
+        subtype,
+        subtype_name,
+        data,
+    }
+}
+
+pub fn decode_triggers(root: &BackupKey) -> Vec<ServiceTrigger> {
+    root.subkeys.iter().map(decode_trigger).collect()
+}
+
+pub fn capture_triggers(service: &str) -> Result<Option<BackupKey>, String> {
+    Ok(
+        registry::capture_subtree("HKLM", &trigger_key_path(service))?
+            .filter(|node| !node.subkeys.is_empty()),
+    )
+}
+
+pub fn read_triggers(service: &str) -> Vec<ServiceTrigger> {
+    capture_triggers(service)
+        .ok()
+        .flatten()
+        .map(|node| decode_triggers(&node))
+        .unwrap_or_default()
+}
+
+pub fn has_triggers(service: &str) -> bool {
+    capture_triggers(service).ok().flatten().is_some()
+}
+
+pub fn restore_triggers(service: &str, node: &BackupKey) -> Result<(), String> {
+    registry::restore_subtree("HKLM", &trigger_key_path(service), node)
+}
+
+pub fn replace_triggers(service: &str, node: Option<&BackupKey>) -> Result<(), String> {
+    let path = trigger_key_path(service);
+    match RegKey::predef(HKEY_LOCAL_MACHINE).delete_subkey_all(&path) {
+        Ok(_) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => return Err(format!("Failed to clear triggers for {}: {}", service, e)),
+    }
+    match node {
+        Some(node) => restore_triggers(service, node),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base64::Engine;
+
+    fn value(name: &str, vtype: u32, bytes: &[u8]) -> registry::BackupValue {
+        registry::BackupValue {
+            name: name.to_string(),
+            vtype,
+            data: base64::engine::general_purpose::STANDARD.encode(bytes),
+        }
+    }
+
+    fn dword(name: &str, v: u32) -> registry::BackupValue {
+        value(name, 4, &v.to_le_bytes())
+    }
+
+    fn wide(text: &str) -> Vec<u8> {
+        text.encode_utf16().flat_map(|c| c.to_le_bytes()).collect()
+    }
+
+    fn guid_bytes(guid: &str) -> Vec<u8> {
+        let hex: String = guid.chars().filter(|c| *c != '-').collect();
+        let raw: Vec<u8> = (0..16)
+            .map(|i| u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).unwrap())
+            .collect();
+        let mut out = Vec::new();
+        out.extend(raw[0..4].iter().rev());
+        out.extend(raw[4..6].iter().rev());
+        out.extend(raw[6..8].iter().rev());
+        out.extend(&raw[8..16]);
+        out
+    }
+
+    fn trigger(
+        index: &str,
+        kind: u32,
+        action: u32,
+        guid: &str,
+        data: &[(u32, Vec<u8>)],
+    ) -> BackupKey {
+        let mut values = vec![
+            dword("Type", kind),
+            dword("Action", action),
+            value("Guid", 3, &guid_bytes(guid)),
+        ];
+        for (i, (data_type, bytes)) in data.iter().enumerate() {
+            values.push(value(&format!("Data{}", i), 3, bytes));
+            values.push(dword(&format!("DataType{}", i), *data_type));
+        }
+        BackupKey {
+            name: index.to_string(),
+            values,
+            subkeys: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn guids_are_formatted_in_registry_byte_order() {
+        for guid in [
+            "4f27f2de-14e2-430b-a549-7cd48cbc8245",
+            "a5dcbf10-6530-11d2-901f-00c04fb951ed",
+        ] {
+            assert_eq!(format_guid(&guid_bytes(guid)), guid);
+        }
+        assert_eq!(format_guid(&[0u8; 8]), "");
+    }
+
+    #[test]
+    fn data_items_decode_by_type() {
+        let cases: Vec<(u32, Vec<u8>, &str)> = vec![
+            (1, vec![0xde, 0xad, 0x01], "dead01"),
+            (2, wide("3389;TCP;System;\0"), "3389;TCP;System;"),
+            (
+                2,
+                wide("USB\\VID_1234\0USB\\VID_5678\0\0"),
+                "USB\\VID_1234; USB\\VID_5678",
+            ),
+            (3, vec![4], "level 4"),
+            (
+                4,
+                0x8000_0000_0000_0010u64.to_le_bytes().to_vec(),
+                "keyword any 0x8000000000000010",
+            ),
+            (5, 0x3u64.to_le_bytes().to_vec(), "keyword all 0x3"),
+            (3, Vec::new(), ""),
+            (4, vec![1, 2], "0102"),
+            (99, vec![0xff], "ff"),
+        ];
+        for (data_type, bytes, expected) in cases {
+            assert_eq!(
+                decode_data_item(data_type, &bytes),
+                expected,
+                "type {}",
+                data_type
+            );
+        }
+    }
+
+    #[test]
+    fn triggers_decode_type_subtype_and_data() {
+        let usb = "a5dcbf10-6530-11d2-901f-00c04fb951ed";
+        let etw = "e46eead8-0c54-4489-9898-8fa79d059e0e";
+        let cases: Vec<(BackupKey, &str, &str, Option<&str>, Vec<&str>)> = vec![
+            (
+                trigger("0", 1, 1, usb, &[(2, wide("USB\\VID_1234&PID_5678\0\0"))]),
+                "device_interface_arrival",
+                "start",
+                None,
+                vec!["USB\\VID_1234&PID_5678"],
+            ),
+            (
+                trigger("1", 2, 1, "4f27f2de-14e2-430b-a549-7cd48cbc8245", &[]),
+                "ip_address_availability",
+                "start",
+                Some("first_ip_address_arrival"),
+                vec![],
+            ),
+            (
+                trigger("2", 2, 2, "cc4ba62a-162e-4648-847a-b6bdf993e335", &[]),
+                "ip_address_availability",
+                "stop",
+                Some("last_ip_address_removal"),
+                vec![],
+            ),
+            (
+                trigger("3", 3, 1, "1ce20aba-9851-4421-9430-1ddeb766e809", &[]),
+                "domain_join",
+                "start",
+                Some("domain_join"),
+                vec![],
+            ),
+            (
+                trigger(
+                    "4",
+                    4,
+                    1,
+                    "b7569e07-8421-4ee0-ad10-86915afdad09",
+                    &[(2, wide("5353;UDP;System;\0"))],
+                ),
+                "firewall_port_event",
+                "start",
+                Some("firewall_port_open"),
+                vec!["5353;UDP;System;"],
+            ),
+            (
+                trigger("5", 5, 1, "659fcae6-5bdb-4da9-b1ff-ca2a178d46e0", &[]),
+                "group_policy",
+                "start",
+                Some("machine_policy_present"),
+                vec![],
+            ),
+            (
+                trigger(
+                    "6",
+                    20,
+                    1,
+                    etw,
+                    &[(3, vec![4]), (4, 0x10u64.to_le_bytes().to_vec())],
+                ),
+                "custom",
+                "start",
+                None,
+                vec!["level 4", "keyword any 0x10"],
+            ),
+            (
+                trigger("7", 42, 9, "00000000-0000-0000-0000-000000000001", &[]),
+                "unknown",
+                "unknown",
+                None,
+                vec![],
+            ),
+        ];
+        for (node, kind, action, name, data) in cases {
+            let decoded = decode_trigger(&node);
+            assert_eq!(decoded.trigger_type, kind, "trigger {}", node.name);
+            assert_eq!(decoded.action, action, "trigger {}", node.name);
+            assert_eq!(
+                decoded.subtype_name.as_deref(),
+                name,
+                "trigger {}",
+                node.name
+            );
+            assert_eq!(decoded.data, data, "trigger {}", node.name);
+        }
+    }
+
+    #[test]
+    fn triggers_without_values_decode_as_unknown() {
+        let root = BackupKey {
+            name: "TriggerInfo".to_string(),
+            values: Vec::new(),
+            subkeys: vec![BackupKey {
+                name: "0".to_string(),
+                values: Vec::new(),
+                subkeys: Vec::new(),
+            }],
+        };
+        let decoded = decode_triggers(&root);
+        assert_eq!(decoded.len(), 1);
+        assert_eq!(decoded[0].trigger_type, "unknown");
+        assert_eq!(decoded[0].subtype, "");
+        assert!(decoded[0].subtype_name.is_none());
+        assert!(decoded[0].data.is_empty());
+    }
+}
@@ -0,0 +1,934 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use crate::registry::BackupKey;
+use crate::service_recovery::FailureActions;
+use crate::service_triggers::{self, ServiceTrigger};
+use crate::util;
+
+const SNAPSHOT_MAX_AUTOMATIC: usize = 20;
+
+pub const MANUAL_CANDIDATES: &[&str] = &[
+    "ALG",                // Application Layer Gateway Service
+    "AJRouter",           // AllJoyn Router Service
+    "AppVClient",         // Microsoft App-V Client
+    "tzautoupdate",       // Auto Time Zone Updater
+    "bthserv",            // Bluetooth Support Service (if not using Bluetooth)
+    "dmwappushservice",   // Device Management Wireless Application Protocol
+    "MapsBroker",         // Downloaded Maps Manager
+    "lfsvc",              // Geolocation Service
+    "SharedAccess",       // Internet Connection Sharing (if not using ICS)
+    "lltdsvc",            // Link-Layer Topology Discovery Mapper
+    "wlpasvc",            // Local Profile Assistant Service
+    "NetTcpPortSharing",  // Net.Tcp Port Sharing Service
+    "CscService",         // Offline Files
+    "PhoneSvc",           // Phone Service
+    "PcaSvc",             // Program Compatibility Assistant Service
+    "QWAVE",              // Quality Windows Audio Video Experience
+    "RmSvc",              // Radio Management Service
+    "SensorDataService",  // Sensor Data Service
+    "SensrSvc",           // Sensor Monitoring Service
+    "SensorService",      // Sensor Service
+    "ShellHWDetection",   // Shell Hardware Detection
+    "SCardSvr",           // Smart Card
+    "ScDeviceEnum",       // Smart Card Device Enumeration Service
+    "SSDPSRV",            // SSDP Discovery
+    "WiaRpc",             // Still Image Acquisition Events
+    "OneSyncSvc",         // Sync Host Service
+    "TabletInputService", // Touch Keyboard and Handwriting Panel Service
+    "upnphost",           // UPnP Device Host
+    "WalletService",      // WalletService
+    "FrameServer",        // Windows Camera Frame Server
+    "stisvc",             // Windows Image Acquisition (WIA)
+    "wisvc",              // Windows Insider Service
+    "icssvc",             // Windows Mobile Hotspot Service
+    "WpnService",         // Windows Push Notifications System Service
+    "WSearch",            // Windows Search (if not using search frequently)
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ServiceStatus {
+    Stopped,
+    StartPending,
+    StopPending,
+    Running,
+    ContinuePending,
+    PausePending,
+    Paused,
+    Unknown,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StartType {
+    Boot,
+    System,
+    Automatic,
+    AutomaticDelayed,
+    Manual,
+    Disabled,
+    Unknown,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ServiceType {
+    KernelDriver,
+    FileSystemDriver,
+    OwnProcess,
+    SharedProcess,
+    UserOwnProcess,
+    UserSharedProcess,
+    Unknown,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ServiceRef {
+    pub name: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ServiceInfo {
+    pub name: String,
+    pub display_name: String,
+    pub status: ServiceStatus,
+    pub start_type: StartType,
+    pub service_type: ServiceType,
+    pub binary_path: Option<String>,
+    pub account: Option<String>,
+    pub process_id: Option<u32>,
+    #[serde(default)]
+    pub trigger_start: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ServiceDetails {
+    #[serde(flatten)]
+    pub info: ServiceInfo,
+    pub service_name: String,
+    pub description: String,
+    pub required_services: Vec<ServiceRef>,
+    pub dependent_services: Vec<ServiceRef>,
+    #[serde(default)]
+    pub triggers: Vec<ServiceTrigger>,
+    #[serde(default)]
+    pub failure_actions: Option<FailureActions>,
+}
+
+fn normalize_token(raw: &str) -> String {
+    raw.chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .collect::<String>()
+        .to_lowercase()
+}
+
+fn field<'a>(obj: &'a Value, names: &[&str]) -> Option<&'a Value> {
+    names
+        .iter()
+        .filter_map(|n| obj.get(*n))
+        .find(|v| !v.is_null())
+}
+
+fn field_str(obj: &Value, names: &[&str]) -> Option<String> {
+    match field(obj, names)? {
+        Value::String(s) if !s.trim().is_empty() => Some(s.trim().to_string()),
+        Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    }
+}
+
+fn field_bool(obj: &Value, names: &[&str]) -> bool {
+    match field(obj, names) {
+        Some(Value::Bool(b)) => *b,
+        Some(Value::Number(n)) => n.as_i64().unwrap_or(0) != 0,
+        Some(Value::String(s)) => matches!(s.trim().to_lowercase().as_str(), "true" | "1"),
+        _ => false,
+    }
+}
+
+impl ServiceStatus {
+    pub fn parse(raw: &Value) -> Self {
+        if let Some(code) = raw.as_i64() {
+            return match code {
+                1 => ServiceStatus::Stopped,
+                2 => ServiceStatus::StartPending,
+                3 => ServiceStatus::StopPending,
+                4 => ServiceStatus::Running,
+                5 => ServiceStatus::ContinuePending,
+                6 => ServiceStatus::PausePending,
+                7 => ServiceStatus::Paused,
+                _ => ServiceStatus::Unknown,
+            };
+        }
+        match normalize_token(raw.as_str().unwrap_or("")).as_str() {
+            "stopped" => ServiceStatus::Stopped,
+            "startpending" => ServiceStatus::StartPending,
+            "stoppending" => ServiceStatus::StopPending,
+            "running" => ServiceStatus::Running,
+            "continuepending" => ServiceStatus::ContinuePending,
+            "pausepending" => ServiceStatus::PausePending,
+            "paused" => ServiceStatus::Paused,
+            _ => ServiceStatus::Unknown,
+        }
+    }
+}
+
+impl StartType {
+    pub fn parse(raw: &Value, delayed: bool) -> Self {
+        let base = if let Some(code) = raw.as_i64() {
+            match code {
+                0 => StartType::Boot,
+                1 => StartType::System,
+                2 => StartType::Automatic,
+                3 => StartType::Manual,
+                4 => StartType::Disabled,
+                _ => StartType::Unknown,
+            }
+        } else {
+            match normalize_token(raw.as_str().unwrap_or("")).as_str() {
+                "boot" => StartType::Boot,
+                "system" => StartType::System,
+                "auto" | "automatic" => StartType::Automatic,
+                "autodelayed" | "automaticdelayed" | "automaticdelayedstart" | "delayedauto" => {
+                    StartType::AutomaticDelayed
+                }
+                "manual" | "demand" => StartType::Manual,
+                "disabled" => StartType::Disabled,
+                _ => StartType::Unknown,
+            }
+        };
+        if base == StartType::Automatic && delayed {
+            StartType::AutomaticDelayed
+        } else {
+            base
+        }
+    }
+}
+
+impl ServiceType {
+    pub fn parse(raw: &Value) -> Self {
+        if let Some(code) = raw.as_i64() {
+            let code = code as u32;
+            return if code & 0x50 == 0x50 {
+                ServiceType::UserOwnProcess
+            } else if code & 0x60 == 0x60 {
+                ServiceType::UserSharedProcess
+            } else if code & 0x10 != 0 {
+                ServiceType::OwnProcess
+            } else if code & 0x20 != 0 {
+                ServiceType::SharedProcess
+            } else if code & 0x2 != 0 {
+                ServiceType::FileSystemDriver
+            } else if code & 0x1 != 0 {
+                ServiceType::KernelDriver
+            } else {
+                ServiceType::Unknown
+            };
+        }
+        let token = normalize_token(raw.as_str().unwrap_or(""));
+        if token.contains("user") && token.contains("own") {
+            ServiceType::UserOwnProcess
+        } else if token.contains("user") && token.contains("share") {
+            ServiceType::UserSharedProcess
+        } else if token.contains("ownprocess") {
+            ServiceType::OwnProcess
+        } else if token.contains("shareprocess") || token.contains("sharedprocess") {
+            ServiceType::SharedProcess
+        } else if token.contains("filesystemdriver") {
+            ServiceType::FileSystemDriver
+        } else if token.contains("kerneldriver") {
+            ServiceType::KernelDriver
+        } else {
+            ServiceType::Unknown
+        }
+    }
+}
+
+pub fn json_items(raw: &str) -> Result<Vec<Value>, String> {
+    let trimmed = raw.trim();
+    if trimmed.is_empty() || trimmed == "null" {
+        return Ok(Vec::new());
+    }
+    match serde_json::from_str::<Value>(trimmed)
+        .map_err(|e| format!("Failed to parse service data: {}", e))?
+    {
+        Value::Array(items) => Ok(items),
+        Value::Null => Ok(Vec::new()),
+        single => Ok(vec![single]),
+    }
+}
+
+fn parse_refs(value: Option<&Value>) -> Vec<ServiceRef> {
+    let items = match value {
+        Some(Value::Array(items)) => items.clone(),
+        Some(Value::Null) | None => Vec::new(),
+        Some(single) => vec![single.clone()],
+    };
+    items
+        .iter()
+        .filter_map(|item| match item {
+            Value::String(s) => Some(s.clone()),
+            Value::Object(_) => field_str(item, &["Name", "name", "ServiceName"]),
+            _ => None,
+        })
+        .filter(|name| !name.is_empty())
+        .map(|name| ServiceRef { name })
+        .collect()
+}
+
+pub fn parse_service_info(obj: &Value) -> Option<ServiceInfo> {
+    let name = field_str(obj, &["Name", "ServiceName"])?;
+    let delayed = field_bool(obj, &["DelayedAutoStart", "Delayed"]);
+    Some(ServiceInfo {
+        display_name: field_str(obj, &["DisplayName"]).unwrap_or_else(|| name.clone()),
+        status: field(obj, &["State", "Status"])
+            .map(ServiceStatus::parse)
+            .unwrap_or(ServiceStatus::Unknown),
+        start_type: field(obj, &["StartMode", "StartType"])
+            .map(|v| StartType::parse(v, delayed))
+            .unwrap_or(StartType::Unknown),
+        service_type: field(obj, &["ServiceType"])
+            .map(ServiceType::parse)
+            .unwrap_or(ServiceType::Unknown),
+        binary_path: field_str(obj, &["PathName", "BinaryPathName", "BinaryPath"]),
+        account: field_str(obj, &["StartName", "Account", "UserName"]),
+        process_id: field(obj, &["ProcessId", "ProcessID", "Pid"])
+            .and_then(|v| v.as_u64())
+            .filter(|pid| *pid != 0)
+            .map(|pid| pid as u32),
+        trigger_start: false,
+        name,
+    })
+}
+
+pub fn parse_service_list(raw: &str) -> Result<Vec<ServiceInfo>, String> {
+    let mut services: Vec<ServiceInfo> = json_items(raw)?
+        .iter()
+        .filter_map(parse_service_info)
+        .collect();
+    services.sort_by_key(|s| s.name.to_lowercase());
+    Ok(services)
+}
+
+pub fn parse_service_details(raw: &str) -> Result<ServiceDetails, String> {
+    let obj = json_items(raw)?
+        .into_iter()
+        .next()
+        .ok_or_else(|| "Service not found".to_string())?;
+    if let Some(error) = field_str(&obj, &["Error"]) {
+        return Err(error);
+    }
+    let info = parse_service_info(&obj).ok_or_else(|| "Service not found".to_string())?;
+    Ok(ServiceDetails {
+        service_name: info.name.clone(),
+        description: field_str(&obj, &["Description"]).unwrap_or_default(),
+        required_services: parse_refs(field(&obj, &["RequiredServices", "ServicesDependedOn"])),
+        dependent_services: parse_refs(field(&obj, &["DependentServices"])),
+        triggers: Vec::new(),
+        failure_actions: None,
+        info,
+    })
+}
+
+pub fn list_services_script() -> &'static str {
+    r#"
+        try {
+            Get-CimInstance Win32_Service -ErrorAction Stop |
+                Select-Object Name, DisplayName, State, StartMode, DelayedAutoStart, ServiceType, PathName, StartName, ProcessId |
+                ConvertTo-Json -Compress
+        } catch {
+            Get-Service | Select-Object Name, DisplayName, Status, StartType, ServiceType | ConvertTo-Json -Compress
+        }
+    "#
+}
+
+pub fn service_details_script(name: &str) -> String {
+    format!(
+        r#"
+        try {{
+            $svc = Get-Service -Name "{name}" -ErrorAction Stop
+            $wmi = Get-CimInstance Win32_Service -Filter "Name='{name}'" -ErrorAction SilentlyContinue
+            [pscustomobject]@{{
+                Name = $svc.Name
+                DisplayName = $svc.DisplayName
+                Status = $svc.Status.ToString()
+                StartType = if ($wmi) {{ $wmi.StartMode }} else {{ $svc.StartType.ToString() }}
+                DelayedAutoStart = if ($wmi) {{ [bool]$wmi.DelayedAutoStart }} else {{ $false }}
+                ServiceType = if ($wmi) {{ $wmi.ServiceType }} else {{ $svc.ServiceType.ToString() }}
+                PathName = if ($wmi) {{ $wmi.PathName }} else {{ $null }}
+                StartName = if ($wmi) {{ $wmi.StartName }} else {{ $null }}
+                ProcessId = if ($wmi) {{ [int]$wmi.ProcessId }} else {{ 0 }}
+                Description = if ($wmi) {{ $wmi.Description }} else {{ "" }}
+                RequiredServices = @($svc.ServicesDependedOn | ForEach-Object {{ $_.Name }})
+                DependentServices = @($svc.DependentServices | ForEach-Object {{ $_.Name }})
+            }} | ConvertTo-Json -Compress -Depth 3
+        }} catch {{
+            @{{Error = "Service not found: {name}"}} | ConvertTo-Json -Compress
+        }}
+        "#,
+        name = name
+    )
+}
+
+impl StartType {
+    pub fn label(&self) -> &'static str {
+        match self {
+            StartType::Boot => "Boot",
+            StartType::System => "System",
+            StartType::Automatic => "Automatic",
+            StartType::AutomaticDelayed => "AutomaticDelayed",
+            StartType::Manual => "Manual",
+            StartType::Disabled => "Disabled",
+            StartType::Unknown => "Unknown",
+        }
+    }
+
+    pub fn sc_value(&self) -> Option<&'static str> {
+        match self {
+            StartType::Automatic => Some("auto"),
+            StartType::AutomaticDelayed => Some("delayed-auto"),
+            StartType::Manual => Some("demand"),
+            StartType::Disabled => Some("disabled"),
+            StartType::Boot | StartType::System | StartType::Unknown => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ServiceConfig {
+    pub name: String,
+    pub display_name: String,
+    pub start_type: StartType,
+    pub delayed: bool,
+    pub status: ServiceStatus,
+    #[serde(default)]
+    pub triggers: Option<BackupKey>,
+    // False when the trigger subtree could not be read, so `triggers: None`
+    // must not be taken to mean "no triggers" on restore.
+    #[serde(default)]
+    pub triggers_captured: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServiceSnapshot {
+    pub id: String,
+    pub name: String,
+    pub created_unix: u64,
+    pub automatic: bool,
+    pub services: Vec<ServiceConfig>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SnapshotSummary {
+    pub id: String,
+    pub name: String,
+    pub created_unix: u64,
+    pub automatic: bool,
+    pub service_count: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ServiceDiff {
+    pub name: String,
+    pub display_name: String,
+    pub snapshot_start_type: StartType,
+    pub current_start_type: Option<StartType>,
+    pub snapshot_status: ServiceStatus,
+    pub current_status: Option<ServiceStatus>,
+    pub start_type_changed: bool,
+    pub status_changed: bool,
+    pub triggers_changed: bool,
+    pub snapshot_triggers: Vec<ServiceTrigger>,
+    pub current_triggers: Vec<ServiceTrigger>,
+    pub missing: bool,
+}
+
+impl From<&ServiceInfo> for ServiceConfig {
+    fn from(info: &ServiceInfo) -> Self {
+        Self {
+            name: info.name.clone(),
+            display_name: info.display_name.clone(),
+            start_type: info.start_type,
+            delayed: info.start_type == StartType::AutomaticDelayed,
+            status: info.status,
+            triggers: None,
+            triggers_captured: false,
+        }
+    }
+}
+
+fn snapshot_dir() -> PathBuf {
+    util::data_subdir("service_snapshots")
+}
+
+fn validate_snapshot_id(id: &str) -> Result<&str, String> {
+    let id = id.trim();
+    if id.is_empty()
+        || id.len() > 64
+        || !id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err("Invalid snapshot id".to_string());
+    }
+    Ok(id)
+}
+
+pub fn build_snapshot(
+    name: &str,
+    automatic: bool,
+    current: &[ServiceInfo],
+    only: Option<&[String]>,
+    capture_triggers: impl Fn(&str) -> Result<Option<BackupKey>, String>,
+) -> ServiceSnapshot {
+    let services = current
+        .iter()
+        .filter(|s| match only {
+            Some(names) => names.iter().any(|n| n.eq_ignore_ascii_case(&s.name)),
+            None => true,
+        })
+        .map(|s| {
+            let captured = capture_triggers(&s.name);
+            ServiceConfig {
+                triggers_captured: captured.is_ok(),
+                triggers: captured.ok().flatten(),
+                ..ServiceConfig::from(s)
+            }
+        })
+        .collect();
+    ServiceSnapshot {
+        id: util::new_id(),
+        name: if name.trim().is_empty() {
+            "Service snapshot".to_string()
+        } else {
+            name.trim().chars().take(128).collect()
+        },
+        created_unix: util::now_unix(),
+        automatic,
+        services,
+    }
+}
+
+// Builds a snapshot with the trigger subtrees read off the async runtime.
+pub async fn capture_snapshot(
+    name: String,
+    automatic: bool,
+    current: Vec<ServiceInfo>,
+    only: Option<Vec<String>>,
+) -> Result<ServiceSnapshot, String> {
+    tokio::task::spawn_blocking(move || {
+        build_snapshot(
+            &name,
+            automatic,
+            &current,
+            only.as_deref(),
+            service_triggers::capture_triggers,
+        )
+    })
+    .await
+    .map_err(|e| format!("Service snapshot task failed: {}", e))
+}
+
+pub fn save_snapshot(snapshot: &ServiceSnapshot) -> Result<(), String> {
+    let raw = serde_json::to_string_pretty(snapshot)
+        .map_err(|e| format!("Failed to serialize service snapshot: {}", e))?;
+    std::fs::write(snapshot_dir().join(format!("{}.json", snapshot.id)), raw)
+        .map_err(|e| format!("Failed to write service snapshot: {}", e))?;
+    prune_automatic_snapshots();
+    Ok(())
+}
+
+pub fn load_snapshot(id: &str) -> Result<ServiceSnapshot, String> {
+    let id = validate_snapshot_id(id)?;
+    let raw = std::fs::read_to_string(snapshot_dir().join(format!("{}.json", id)))
+        .map_err(|_| format!("Service snapshot not found: {}", id))?;
+    serde_json::from_str(&raw).map_err(|e| format!("Corrupt service snapshot {}: {}", id, e))
+}
+
+fn read_all_snapshots() -> Vec<ServiceSnapshot> {
+    let mut snapshots: Vec<ServiceSnapshot> = match std::fs::read_dir(snapshot_dir()) {
+        Ok(entries) => entries
+            .filter_map(|e| e.ok())
+            .filter(|e| e.path().extension().and_then(|x| x.to_str()) == Some("json"))
+            .filter_map(|e| std::fs::read_to_string(e.path()).ok())
+            .filter_map(|raw| serde_json::from_str(&raw).ok())
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    snapshots.sort_by_key(|s| std::cmp::Reverse(s.created_unix));
+    snapshots
+}
+
+pub fn list_snapshots() -> Vec<SnapshotSummary> {
+    read_all_snapshots()
+        .into_iter()
+        .map(|s| SnapshotSummary {
+            service_count: s.services.len(),
+            id: s.id,
+            name: s.name,
+            created_unix: s.created_unix,
+            automatic: s.automatic,
+        })
+        .collect()
+}
+
+pub fn delete_snapshot(id: &str) -> Result<(), String> {
+    let id = validate_snapshot_id(id)?;
+    std::fs::remove_file(snapshot_dir().join(format!("{}.json", id)))
+        .map_err(|_| format!("Service snapshot not found: {}", id))
+}
+
+fn prune_automatic_snapshots() {
+    for snapshot in read_all_snapshots()
+        .into_iter()
+        .filter(|s| s.automatic)
+        .skip(SNAPSHOT_MAX_AUTOMATIC)
+    {
+        let _ = std::fs::remove_file(snapshot_dir().join(format!("{}.json", snapshot.id)));
+    }
+}
+
+pub fn diff_snapshot(snapshot: &ServiceSnapshot, current: &[ServiceInfo]) -> Vec<ServiceDiff> {
+    let live: HashMap<String, &ServiceInfo> =
+        current.iter().map(|s| (s.name.to_lowercase(), s)).collect();
+    snapshot
+        .services
+        .iter()
+        .filter_map(|saved| {
+            let now = live.get(&saved.name.to_lowercase());
+            let start_type_changed = now.is_some_and(|s| s.start_type != saved.start_type);
+            let status_changed = now.is_some_and(|s| s.status != saved.status);
+            let snapshot_triggers = saved
+                .triggers
+                .as_ref()
+                .map(service_triggers::decode_triggers)
+                .unwrap_or_default();
+            let current_triggers = if saved.triggers_captured && now.is_some() {
+                service_triggers::read_triggers(&saved.name)
+            } else {
+                Vec::new()
+            };
+            let triggers_changed =
+                saved.triggers_captured && now.is_some() && snapshot_triggers != current_triggers;
+            if now.is_some() && !start_type_changed && !status_changed && !triggers_changed {
+                return None;
+            }
+            Some(ServiceDiff {
+                name: saved.name.clone(),
+                display_name: saved.display_name.clone(),
+                snapshot_start_type: saved.start_type,
+                current_start_type: now.map(|s| s.start_type),
+                snapshot_status: saved.status,
+                current_status: now.map(|s| s.status),
+                start_type_changed,
+                status_changed,
+                triggers_changed,
+                snapshot_triggers,
+                current_triggers,
+                missing: now.is_none(),
+            })
+        })
+        .collect()
+}
+
+pub fn restore_script(changes: &[(String, Option<&'static str>, Option<ServiceStatus>)]) -> String {
+    let items = changes
+        .iter()
+        .map(|(name, start, status)| {
+            let state = match status {
+                Some(ServiceStatus::Running) => "running",
+                Some(ServiceStatus::Stopped) => "stopped",
+                _ => "",
+            };
+            util::ps_quote(&format!("{}|{}|{}", name, start.unwrap_or(""), state))
+        })
+        .collect::<Vec<_>>()
+        .join(",");
+    format!(
+        r#"
+        $items = @({})
+        $restored = 0
+        $failed = @()
+        foreach ($item in $items) {{
+            $parts = $item.Split('|')
+            $name = $parts[0]
+            try {{
+                if ($parts[1]) {{
+                    $out = & sc.exe config "$name" start= $parts[1] 2>&1
+                    if ($LASTEXITCODE -ne 0) {{ throw ($out | Out-String).Trim() }}
+                }}
+                if ($parts[2] -eq 'running') {{ Start-Service -Name $name -ErrorAction Stop }}
+                if ($parts[2] -eq 'stopped') {{ Stop-Service -Name $name -ErrorAction Stop }}
+                $restored++
+            }} catch {{
+                $failed += @{{Name = $name; Error = $_.Exception.Message}}
+            }}
+        }}
+        @{{restored = $restored; failed = @($failed)}} | ConvertTo-Json -Compress -Depth 3
+        "#,
+        items
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn service(name: &str, start_type: StartType, status: ServiceStatus) -> ServiceInfo {
+        ServiceInfo {
+            name: name.to_string(),
+            display_name: name.to_string(),
+            status,
+            start_type,
+            service_type: ServiceType::OwnProcess,
+            binary_path: None,
+            account: None,
+            process_id: None,
+            trigger_start: false,
+        }
+    }
+
+    fn trigger_node() -> BackupKey {
+        BackupKey {
+            name: "TriggerInfo".to_string(),
+            values: Vec::new(),
+            subkeys: vec![BackupKey {
+                name: "0".to_string(),
+                values: Vec::new(),
+                subkeys: Vec::new(),
+            }],
+        }
+    }
+
+    #[test]
+    fn snapshot_records_trigger_capture_per_service() {
+        let current = vec![
+            service("Triggered", StartType::Manual, ServiceStatus::Stopped),
+            service("Locked", StartType::Manual, ServiceStatus::Stopped),
+            service("Plain", StartType::Automatic, ServiceStatus::Running),
+        ];
+        let snapshot = build_snapshot("", true, &current, None, |name| match name {
+            "Triggered" => Ok(Some(trigger_node())),
+            "Locked" => Err("Access is denied".to_string()),
+            _ => Ok(None),
+        });
+        assert_eq!(snapshot.name, "Service snapshot");
+        let flags: Vec<(&str, bool, bool)> = snapshot
+            .services
+            .iter()
+            .map(|s| (s.name.as_str(), s.triggers_captured, s.triggers.is_some()))
+            .collect();
+        assert_eq!(
+            flags,
+            vec![
+                ("Triggered", true, true),
+                ("Locked", false, false),
+                ("Plain", true, false),
+            ]
+        );
+    }
+
+    #[test]
+    fn snapshot_filters_services_case_insensitively() {
+        let current = vec![
+            service("WSearch", StartType::Automatic, ServiceStatus::Running),
+            service("Spooler", StartType::Automatic, ServiceStatus::Running),
+        ];
+        let only = vec!["wsearch".to_string()];
+        let snapshot = build_snapshot("Mine", false, &current, Some(&only), |_| Ok(None));
+        assert_eq!(snapshot.services.len(), 1);
+        assert_eq!(snapshot.services[0].name, "WSearch");
+    }
+
+    #[test]
+    fn uncaptured_triggers_are_not_diffed() {
+        let saved = vec![service("Locked", StartType::Manual, ServiceStatus::Stopped)];
+        let snapshot = build_snapshot("", false, &saved, None, |_| {
+            Err("Access is denied".to_string())
+        });
+        let current = vec![service(
+            "Locked",
+            StartType::Disabled,
+            ServiceStatus::Stopped,
+        )];
+        let diff = diff_snapshot(&snapshot, &current);
+        assert_eq!(diff.len(), 1);
+        assert!(diff[0].start_type_changed);
+        assert!(!diff[0].triggers_changed);
+    }
+
+    #[test]
+    fn legacy_snapshots_load_without_trigger_capture() {
+        let raw = r#"{
+            "id": "1700000000000-00ab",
+            "name": "Old",
+            "created_unix": 1700000000,
+            "automatic": false,
+            "triggers_captured": true,
+            "services": [{
+                "name": "WSearch",
+                "display_name": "Windows Search",
+                "start_type": "AutomaticDelayed",
+                "delayed": true,
+                "status": "Running"
+            }]
+        }"#;
+        let snapshot: ServiceSnapshot = serde_json::from_str(raw).unwrap();
+        assert!(!snapshot.services[0].triggers_captured);
+        assert!(snapshot.services[0].triggers.is_none());
+    }
+
+    #[test]
+    fn status_normalizes_codes_and_names() {
+        let cases = [
+            (json!(1), ServiceStatus::Stopped),
+            (json!(4), ServiceStatus::Running),
+            (json!(7), ServiceStatus::Paused),
+            (json!(42), ServiceStatus::Unknown),
+            (json!("Running"), ServiceStatus::Running),
+            (json!("Stop Pending"), ServiceStatus::StopPending),
+            (json!("START_PENDING"), ServiceStatus::StartPending),
+            (json!("continuepending"), ServiceStatus::ContinuePending),
+            (json!(""), ServiceStatus::Unknown),
+            (json!(null), ServiceStatus::Unknown),
+        ];
+        for (raw, expected) in cases {
+            assert_eq!(ServiceStatus::parse(&raw), expected, "{}", raw);
+        }
+    }
+
+    #[test]
+    fn start_type_normalizes_codes_names_and_delay() {
+        let cases = [
+            (json!(0), false, StartType::Boot),
+            (json!(1), false, StartType::System),
+            (json!(2), false, StartType::Automatic),
+            (json!(2), true, StartType::AutomaticDelayed),
+            (json!(3), true, StartType::Manual),
+            (json!(4), false, StartType::Disabled),
+            (json!(9), false, StartType::Unknown),
+            (json!("Auto"), false, StartType::Automatic),
+            (json!("Auto"), true, StartType::AutomaticDelayed),
+            (
+                json!("Automatic (Delayed Start)"),
+                false,
+                StartType::AutomaticDelayed,
+            ),
+            (json!("delayed-auto"), false, StartType::AutomaticDelayed),
+            (json!("Demand"), false, StartType::Manual),
+            (json!("Manual"), true, StartType::Manual),
+            (json!("Disabled"), false, StartType::Disabled),
+            (json!("Boot"), false, StartType::Boot),
+            (json!("whatever"), false, StartType::Unknown),
+        ];
+        for (raw, delayed, expected) in cases {
+            assert_eq!(
+                StartType::parse(&raw, delayed),
+                expected,
+                "{} {}",
+                raw,
+                delayed
+            );
+        }
+        assert_eq!(StartType::AutomaticDelayed.sc_value(), Some("delayed-auto"));
+        assert_eq!(StartType::System.sc_value(), None);
+    }
+
+    #[test]
+    fn service_type_normalizes_flags_and_names() {
+        let cases = [
+            (json!(0x10), ServiceType::OwnProcess),
+            (json!(0x20), ServiceType::SharedProcess),
+            (json!(0x1), ServiceType::KernelDriver),
+            (json!(0x2), ServiceType::FileSystemDriver),
+            (json!(0x50), ServiceType::UserOwnProcess),
+            (json!(0xE0), ServiceType::UserSharedProcess),
+            (json!("Own Process"), ServiceType::OwnProcess),
+            (json!("Win32ShareProcess"), ServiceType::SharedProcess),
+            (json!("Kernel Driver"), ServiceType::KernelDriver),
+            (json!("240"), ServiceType::Unknown),
+        ];
+        for (raw, expected) in cases {
+            assert_eq!(ServiceType::parse(&raw), expected, "{}", raw);
+        }
+    }
+
+    // Get-CimInstance Win32_Service output.
+    const CIM_LIST: &str = r#"[
+        {"Name":"WSearch","DisplayName":"Windows Search","State":"Running","StartMode":"Auto","DelayedAutoStart":true,"ServiceType":"Own Process","PathName":"C:\\Windows\\system32\\SearchIndexer.exe /Embedding","StartName":"LocalSystem","ProcessId":4321},
+        {"Name":"ALG","DisplayName":"Application Layer Gateway Service","State":"Stopped","StartMode":"Manual","DelayedAutoStart":false,"ServiceType":"Own Process","PathName":"C:\\Windows\\System32\\alg.exe","StartName":"NT AUTHORITY\\LocalService","ProcessId":0},
+        {"Name":"","DisplayName":"Broken"}
+    ]"#;
+
+    // Get-Service fallback output, where enums serialize as numbers.
+    const GET_SERVICE_LIST: &str = r#"{"Name":"Spooler","DisplayName":"Print Spooler","Status":4,"StartType":2,"ServiceType":272}"#;
+
+    #[test]
+    fn parses_cim_service_list() {
+        let services = parse_service_list(CIM_LIST).unwrap();
+        let names: Vec<&str> = services.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, vec!["ALG", "WSearch"]);
+
+        let search = &services[1];
+        assert_eq!(search.status, ServiceStatus::Running);
+        assert_eq!(search.start_type, StartType::AutomaticDelayed);
+        assert_eq!(search.service_type, ServiceType::OwnProcess);
+        assert_eq!(search.process_id, Some(4321));
+        assert_eq!(search.account.as_deref(), Some("LocalSystem"));
+
+        let alg = &services[0];
+        assert_eq!(alg.status, ServiceStatus::Stopped);
+        assert_eq!(alg.start_type, StartType::Manual);
+        assert_eq!(alg.process_id, None);
+    }
+
+    #[test]
+    fn parses_get_service_fallback() {
+        let services = parse_service_list(GET_SERVICE_LIST).unwrap();
+        assert_eq!(services.len(), 1);
+        assert_eq!(services[0].status, ServiceStatus::Running);
+        assert_eq!(services[0].start_type, StartType::Automatic);
+        assert_eq!(services[0].service_type, ServiceType::OwnProcess);
+        assert!(services[0].binary_path.is_none());
+        assert!(parse_service_list("").unwrap().is_empty());
+        assert!(parse_service_list("null").unwrap().is_empty());
+        assert!(parse_service_list("{oops").is_err());
+    }
+
+    #[test]
+    fn parses_service_details() {
+        let raw = r#"{"Name":"WSearch","DisplayName":"Windows Search","Status":"Running","StartType":"Auto","DelayedAutoStart":true,"ServiceType":"Own Process","ProcessId":0,"Description":"Indexing","RequiredServices":"RPCSS","DependentServices":[{"Name":"WMPNetworkSvc"},null]}"#;
+        let details = parse_service_details(raw).unwrap();
+        assert_eq!(details.service_name, "WSearch");
+        assert_eq!(details.info.start_type, StartType::AutomaticDelayed);
+        assert_eq!(details.description, "Indexing");
+        assert_eq!(
+            details.required_services,
+            vec![ServiceRef {
+                name: "RPCSS".to_string()
+            }]
+        );
+        assert_eq!(details.dependent_services.len(), 1);
+        assert_eq!(
+            parse_service_details(r#"{"Error":"Service not found: Nope"}"#).unwrap_err(),
+            "Service not found: Nope"
+        );
+    }
+
+    #[test]
+    fn restore_script_quotes_items() {
+        let script = restore_script(&[
+            ("Spooler".to_string(), Some("demand"), None),
+            (
+                "O'Brien Svc".to_string(),
+                None,
+                Some(ServiceStatus::Running),
+            ),
+        ]);
+        assert!(script.contains("$items = @('Spooler|demand|','O''Brien Svc||running')"));
+    }
+}
@@ -0,0 +1,42 @@
+use std::path::PathBuf;
+
+pub fn now_unix() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+pub fn now_unix_millis() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+// Snapshot, backup and job ids: creation time first so they sort
+// chronologically, plus a random suffix for ids made in the same millisecond.
+pub fn new_id() -> String {
+    format!("{}-{:04x}", now_unix_millis(), rand::random::<u16>())
+}
+
+// %APPDATA%\ConfUtils, or ProgramData when running without a user profile.
+pub fn data_dir() -> PathBuf {
+    let base = std::env::var("APPDATA")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| PathBuf::from("C:\\ProgramData"));
+    let dir = base.join("ConfUtils");
+    let _ = std::fs::create_dir_all(&dir);
+    dir
+}
+
+pub fn data_subdir(name: &str) -> PathBuf {
+    let dir = data_dir().join(name);
+    let _ = std::fs::create_dir_all(&dir);
+    dir
+}
+
+// Single-quoted PowerShell literal; embedded quotes are doubled.
+pub fn ps_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "''"))
+}
@@ -21,6 +21,8 @@ interface ConnectedDevice {
   mac_address: String;
   device_type: String;
   hostname: String;
+  vendor?: string | null;
+  randomized_mac?: boolean;
 }
 
 interface ActiveConnection {
@@ -350,7 +352,7 @@ export const WifiPasswordsModal: React.FC = () => {
                 </div>
                 <div className="wifi-details">
                   <div className="wifi-ssid">
-                    {device.hostname}
+                    {device.hostname !== 'Unknown' ? device.hostname : device.vendor || device.hostname}
                     <span className="connection-badge">{device.device_type}</span>
                   </div>
                   <div className="wifi-auth">{t('network_ip_short_label')} {device.ip_address}</div>
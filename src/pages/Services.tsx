@@ -396,6 +396,7 @@ const Services: React.FC = () => {
                       className="startup-select"
                     >
                       <option value="Automatic">Automatic</option>
+                      <option value="AutomaticDelayed">Automatic (Delayed Start)</option>
                       <option value="Manual">Manual</option>
                       <option value="Disabled">Disabled</option>
                     </select>